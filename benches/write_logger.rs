@@ -0,0 +1,69 @@
+//! Measures the time a `WriteLogger` takes per record, writing into a `Vec<u8>`.
+//!
+//! Run with `cargo bench --bench write_logger`. To compare a change, save a baseline
+//! before it with `-- --save-baseline before`, and compare after it with `-- --baseline before`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use log::{Level, Log, Record};
+use sp_log2::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+fn bench(c: &mut Criterion, name: &str, config: Config) {
+    c.bench_function(name, |b| {
+        // A logger per sample, so the output does not grow across all of them
+        b.iter_custom(|iters| {
+            let logger = WriteLogger::new(LevelFilter::Info, config.clone(), Vec::new());
+            let start = Instant::now();
+            for i in 0..iters {
+                logger.log(
+                    &Record::builder()
+                        .level(Level::Info)
+                        .target("bench::write_logger")
+                        .file(Some("benches/write_logger.rs"))
+                        .line(Some(42))
+                        .args(format_args!("request {} handled in {}ms", i, i % 100))
+                        .build(),
+                );
+            }
+            let elapsed = start.elapsed();
+            black_box(logger.into_inner());
+            elapsed
+        })
+    });
+}
+
+fn write_logger(c: &mut Criterion) {
+    bench(c, "default", Config::default());
+    bench(
+        c,
+        "minimal",
+        ConfigBuilder::new()
+            .set_format(FormatFlags::LEVEL | FormatFlags::TARGET)
+            .build(),
+    );
+    bench(
+        c,
+        "full",
+        ConfigBuilder::new()
+            .set_format(
+                FormatFlags::LEVEL
+                    | FormatFlags::TIME
+                    | FormatFlags::THREAD
+                    | FormatFlags::TARGET
+                    | FormatFlags::FILE_LOCATION
+                    | FormatFlags::MODULE,
+            )
+            .build(),
+    );
+    bench(
+        c,
+        "formatter",
+        ConfigBuilder::new()
+            .set_formatter(Some("[time] [level] [target]: [message]"))
+            .build(),
+    );
+}
+
+criterion_group!(benches, write_logger);
+criterion_main!(benches);
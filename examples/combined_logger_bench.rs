@@ -0,0 +1,60 @@
+//! Measures the time a `CombinedLogger` with two loggers takes per record, with and
+//! without sharing the rendered fields between them.
+//!
+//! Run with `cargo run --release --example combined_logger_bench`.
+
+use log::{Level, Log, Record};
+use sp_log2::*;
+use std::time::Instant;
+
+const RECORDS: u32 = 200_000;
+
+fn config() -> Config {
+    ConfigBuilder::new()
+        .set_format(
+            FormatFlags::LEVEL
+                | FormatFlags::TIME
+                | FormatFlags::THREAD
+                | FormatFlags::TARGET
+                | FormatFlags::FILE_LOCATION,
+        )
+        .set_time_format_rfc3339_with_precision(SecondsFormat::Micros)
+        .build()
+}
+
+fn bench(name: &str, shared: bool) {
+    let loggers: Vec<Box<dyn SharedLogger>> = vec![
+        WriteLogger::new(LevelFilter::Trace, config(), std::io::sink()),
+        WriteLogger::new(LevelFilter::Trace, config(), std::io::sink()),
+    ];
+    let logger = if shared {
+        CombinedLogger::new_shared(loggers)
+    } else {
+        CombinedLogger::new(loggers)
+    };
+
+    let start = Instant::now();
+    for i in 0..RECORDS {
+        logger.log(
+            &Record::builder()
+                .level(Level::Trace)
+                .target("bench::combined_logger")
+                .file(Some("examples/combined_logger_bench.rs"))
+                .line(Some(42))
+                .args(format_args!("request {} handled in {}ms", i, i % 100))
+                .build(),
+        );
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<10} {:>8.0} ns/record",
+        name,
+        elapsed.as_nanos() as f64 / f64::from(RECORDS)
+    );
+}
+
+fn main() {
+    bench("separate", false);
+    bench("shared", true);
+}
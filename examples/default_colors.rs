@@ -4,7 +4,11 @@ use sp_log2::*;
 fn main() {
     let mut config_builder = ConfigBuilder::new();
     config_builder.set_format(
-        Format::LevelFlag | Format::Time | Format::Thread | Format::Target | Format::FileLocation,
+        FormatFlags::LEVEL
+            | FormatFlags::TIME
+            | FormatFlags::THREAD
+            | FormatFlags::TARGET
+            | FormatFlags::FILE_LOCATION,
     );
     // config_builder.set_formatter(Some(
     //     "{time:#89dceb} {level} ({thread}) {target:rgb(137, 180, 250):bold}: {message} [{file:#eba0ac}]\n",
@@ -0,0 +1,19 @@
+use log::*;
+use sp_log2::*;
+
+fn main() {
+    let config = ConfigBuilder::new()
+        .set_format(FormatFlags::LEVEL | FormatFlags::TARGET)
+        .set_inline_tags(true)
+        .build();
+    TermLogger::init(
+        LevelFilter::Trace,
+        config,
+        TerminalMode::Stdout,
+        ColorChoice::Auto,
+    )
+    .unwrap();
+    info!("I can write <b>bold</b> text or use tags to <red>color it</>");
+    warn!("<on-yellow><black>Backgrounds</> work too, and <dim>so does dimmed text</>");
+    debug!("Unknown tags like <this> stay as they are");
+}
@@ -0,0 +1,220 @@
+//! Lines describing a logging session, written at the start and the end of a log.
+
+use crate::loggers::logging::{hostname, render_record};
+use crate::{Config, LoggerStats};
+use log::{Level, Record};
+use std::io::{Error, Write};
+use std::thread;
+
+/// The target of the records of the banner and the summary.
+const TARGET: &str = "sp_log2::session";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BannerItem {
+    App { name: String, version: String },
+    Os,
+    Args,
+    ProcessId,
+    Hostname,
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Lines written at the start of every log, e.g. for support bundles, see
+/// [`ConfigBuilder::set_banner`](crate::ConfigBuilder::set_banner).
+///
+/// Each item is written as a record at `Info` in the layout of the `Config`, on its own
+/// line and in the order the items were added. The level and the filters of the logger
+/// do not apply, so the banner is always complete.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// let banner = Banner::new()
+///     .app(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+///     .os()
+///     .args()
+///     .text("build: nightly")
+///     .with_summary();
+/// let config = ConfigBuilder::new().set_banner(banner).build();
+/// ```
+///
+/// A `FileLogger` writes the banner again at the start of every new file:
+/// ```
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// let path = std::env::temp_dir().join(format!("sp_log2-banner-{}.log", std::process::id()));
+/// let config = ConfigBuilder::new()
+///     .set_formatter(Some("[level] [message]"))
+///     .set_banner(Banner::new().app("my_app", "1.2.0"))
+///     .build();
+/// let logger = FileLogger::new(LevelFilter::Info, config, &path, Some(40));
+/// for i in 0..3 {
+///     logger.log(&Record::builder().level(Level::Info).args(format_args!("request {}", i)).build());
+/// }
+/// logger.flush();
+///
+/// let backup = std::fs::read_to_string(path.with_extension("log.bak")).unwrap();
+/// let current = std::fs::read_to_string(&path).unwrap();
+/// assert_eq!(backup, "[INFO] my_app 1.2.0\n[INFO] request 0\n[INFO] request 1\n");
+/// assert_eq!(current, "[INFO] my_app 1.2.0\n[INFO] request 2\n");
+/// # let _ = std::fs::remove_file(&path);
+/// # let _ = std::fs::remove_file(path.with_extension("log.bak"));
+/// ```
+pub struct Banner {
+    items: Vec<BannerItem>,
+    summary: bool,
+}
+
+impl Banner {
+    /// Creates an empty banner.
+    pub fn new() -> Banner {
+        Banner::default()
+    }
+
+    /// Adds the name and the version of the application, e.g. from `env!("CARGO_PKG_NAME")`
+    /// and `env!("CARGO_PKG_VERSION")`, written as `my_app 1.2.0`.
+    pub fn app(mut self, name: impl Into<String>, version: impl Into<String>) -> Banner {
+        self.items.push(BannerItem::App {
+            name: name.into(),
+            version: version.into(),
+        });
+        self
+    }
+
+    /// Adds the operating system and the architecture, written as `os: linux x86_64`.
+    pub fn os(mut self) -> Banner {
+        self.items.push(BannerItem::Os);
+        self
+    }
+
+    /// Adds the command line arguments, written as `args: my_app --verbose`. Arguments
+    /// containing spaces or quotes are quoted.
+    pub fn args(mut self) -> Banner {
+        self.items.push(BannerItem::Args);
+        self
+    }
+
+    /// Adds the process id, written as `pid: 4242`.
+    pub fn process_id(mut self) -> Banner {
+        self.items.push(BannerItem::ProcessId);
+        self
+    }
+
+    /// Adds the hostname, written as `hostname: myhost`.
+    pub fn hostname(mut self) -> Banner {
+        self.items.push(BannerItem::Hostname);
+        self
+    }
+
+    /// Adds a line of custom text.
+    pub fn text(mut self, text: impl Into<String>) -> Banner {
+        self.items.push(BannerItem::Text(text.into()));
+        self
+    }
+
+    /// Makes the [`FlushGuard`](crate::FlushGuard) of the logger write a summary line when
+    /// dropped, with the exit status and the number of errors and warnings, e.g.
+    /// `session ended: status=ok errors=0 warnings=2`.
+    ///
+    /// The status is `panicked` if the guard is dropped while the thread panics.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// # use std::sync::{Arc, Mutex};
+    /// let output = Arc::new(Mutex::new(Vec::new()));
+    /// let config = ConfigBuilder::new()
+    ///     .set_formatter(Some("[level] [message]"))
+    ///     .set_banner(Banner::new().with_summary())
+    ///     .build();
+    /// let guard = CombinedLogger::init_with_guard(vec![
+    ///     WriteLogger::new_shared(LevelFilter::Info, config, output.clone()),
+    /// ])
+    /// .unwrap();
+    ///
+    /// log::warn!("disk almost full");
+    /// log::error!("disk full");
+    /// log::warn!("dropping records");
+    /// drop(guard);
+    ///
+    /// let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(
+    ///     output.lines().last(),
+    ///     Some("[INFO] session ended: status=ok errors=1 warnings=2")
+    /// );
+    /// ```
+    pub fn with_summary(mut self) -> Banner {
+        self.summary = true;
+        self
+    }
+
+    /// Returns the lines of the banner.
+    fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.items.iter().map(|item| match item {
+            BannerItem::App { name, version } => format!("{} {}", name, version),
+            BannerItem::Os => format!("os: {} {}", std::env::consts::OS, std::env::consts::ARCH),
+            BannerItem::Args => {
+                let args: Vec<String> = std::env::args_os()
+                    .map(|arg| {
+                        let arg = arg.to_string_lossy();
+                        if arg.is_empty() || arg.contains([' ', '\t', '"', '\'']) {
+                            format!("{:?}", arg)
+                        } else {
+                            arg.into_owned()
+                        }
+                    })
+                    .collect();
+                format!("args: {}", args.join(" "))
+            }
+            BannerItem::ProcessId => format!("pid: {}", std::process::id()),
+            BannerItem::Hostname => format!("hostname: {}", hostname()),
+            BannerItem::Text(text) => text.clone(),
+        })
+    }
+}
+
+/// Writes the banner of `config`, if any, to `out`.
+pub(crate) fn write_banner<W: Write>(config: &Config, out: &mut W) -> Result<(), Error> {
+    if let Some(banner) = &config.banner {
+        for line in banner.lines() {
+            write_line(config, out, &line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the summary of the session to `out`, if the banner of `config` asks for one.
+pub(crate) fn write_summary<W: Write>(
+    config: &Config,
+    stats: &LoggerStats,
+    out: &mut W,
+) -> Result<(), Error> {
+    if !config.banner.as_ref().is_some_and(|banner| banner.summary) {
+        return Ok(());
+    }
+    let status = if thread::panicking() {
+        "panicked"
+    } else {
+        "ok"
+    };
+    let line = format!(
+        "session ended: status={} errors={} warnings={}",
+        status,
+        stats.written(Level::Error),
+        stats.written(Level::Warn)
+    );
+    write_line(config, out, &line)
+}
+
+fn write_line<W: Write>(config: &Config, out: &mut W, line: &str) -> Result<(), Error> {
+    render_record(
+        config,
+        &Record::builder()
+            .level(Level::Info)
+            .target(TARGET)
+            .args(format_args!("{}", line))
+            .build(),
+        out,
+    )
+}
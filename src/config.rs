@@ -1,8 +1,61 @@
+use crate::banner::Banner;
+use crate::json::JsonFieldNames;
+use crate::loggers::logging::{apply_style, check_formatter, hostname, PLACEHOLDERS};
+#[cfg(feature = "regex")]
+use crate::RegexError;
+use chrono::{DateTime, SecondsFormat};
 use log::Level;
 use log::LevelFilter;
+#[cfg(feature = "regex")]
+use regex::Regex;
 
 use std::borrow::Cow;
-use termcolor2::Color;
+use std::fmt;
+use std::io;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
+use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termcolor2::{Color, ColorSpec};
+
+/// Error returned for a color string that cannot be parsed.
+///
+/// See [`ConfigBuilder::set_level_color_str`].
+pub use termcolor2::ParseColorError as ColorParseError;
+
+/// Called when a logger fails to write or flush a record, see [`ConfigBuilder::set_error_handler`].
+///
+/// The arguments are the name of the failing logger, e.g. `"FileLogger"`, and the error.
+pub type ErrorHandler = Arc<dyn Fn(&str, &io::Error) + Send + Sync>;
+
+/// The [`ErrorHandler`] of a `Config`, which cannot derive `Debug` itself.
+#[derive(Clone)]
+pub(crate) struct ConfigErrorHandler(pub(crate) ErrorHandler);
+
+impl fmt::Debug for ConfigErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ErrorHandler")
+    }
+}
+
+/// Returns the time of a record, see [`ConfigBuilder::set_clock`].
+pub type Clock = Arc<dyn Fn() -> DateTime<UtcOffset> + Send + Sync>;
+
+/// The [`Clock`] of a `Config`, which cannot derive `Debug` itself.
+#[derive(Clone)]
+pub(crate) struct ConfigClock(pub(crate) Clock);
+
+impl fmt::Debug for ConfigClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+/// A fixed offset from UTC, used for the time of the log records.
+///
+/// See [`ConfigBuilder::set_time_offset`].
+pub use chrono::FixedOffset as UtcOffset;
 
 #[derive(Debug, Clone, Copy)]
 /// Defines how padding should be applied to the logging level in the log output.
@@ -17,6 +70,212 @@ pub enum LevelPadding {
     Off,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Defines the letter case of the logging level in the log output.
+pub enum LevelCase {
+    /// All letters uppercase, e.g. `WARN`.
+    Upper,
+
+    /// All letters lowercase, e.g. `warn`.
+    Lower,
+
+    /// The first letter uppercase and the others lowercase, e.g. `Warn`.
+    Capitalized,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Defines how long messages are wrapped by the `TermLogger`.
+pub enum WrapMode {
+    /// Messages are not wrapped.
+    #[default]
+    Off,
+
+    /// Messages are wrapped at the width of the terminal.
+    Terminal,
+
+    /// Messages are wrapped at the given width, in chars.
+    Width(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Defines how the lines of messages with line breaks are written.
+pub enum MultilineMode {
+    /// The lines after the first start at the beginning of the line.
+    #[default]
+    AsIs,
+
+    /// The lines after the first are indented to the column the message starts at.
+    Indent,
+
+    /// The lines after the first are indented by the given number of spaces.
+    IndentBy(usize),
+
+    /// Every line is written with the fields in front of it, like a record of its own.
+    PrefixEach,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Defines how the module path of the target is written.
+pub enum TargetStyle {
+    /// The whole target, e.g. `app::services::ingest::pipeline`.
+    #[default]
+    Full,
+
+    /// Only the last segment of the target, e.g. `pipeline`.
+    LastSegment,
+
+    /// The given number of last segments in full and the first letter of the others,
+    /// e.g. `a::s::ingest::pipeline` for 2.
+    Abbreviated(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// Defines how the file path of the location is written.
+pub enum LocationStyle {
+    /// The path as it was recorded, e.g. `src/main.rs` or an absolute path for dependencies.
+    #[default]
+    Full,
+
+    /// Only the file name, e.g. `main.rs`.
+    FileName,
+
+    /// The path without the given directory, e.g. the workspace root. Paths outside of it
+    /// are written in full.
+    StripPrefix(String),
+}
+
+/// The style of the logging level on the terminal.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// let config = ConfigBuilder::new()
+///     .set_level_style(Level::Error, LevelStyle::new().fg(Color::White).bg(Color::Red).bold())
+///     .set_level_style(Level::Trace, LevelStyle::new().dimmed())
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelStyle {
+    pub(crate) fg: Option<Color>,
+    pub(crate) bg: Option<Color>,
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    pub(crate) dimmed: bool,
+    pub(crate) underline: bool,
+}
+
+impl LevelStyle {
+    /// Creates a style without colors and effects.
+    pub fn new() -> LevelStyle {
+        LevelStyle::default()
+    }
+
+    /// Sets the foreground color.
+    pub fn fg(mut self, color: Color) -> LevelStyle {
+        self.fg = Some(color);
+        self
+    }
+
+    /// Sets the background color.
+    pub fn bg(mut self, color: Color) -> LevelStyle {
+        self.bg = Some(color);
+        self
+    }
+
+    /// Makes the text bold.
+    pub fn bold(mut self) -> LevelStyle {
+        self.bold = true;
+        self
+    }
+
+    /// Makes the text italic.
+    pub fn italic(mut self) -> LevelStyle {
+        self.italic = true;
+        self
+    }
+
+    /// Makes the text dimmed.
+    pub fn dimmed(mut self) -> LevelStyle {
+        self.dimmed = true;
+        self
+    }
+
+    /// Underlines the text.
+    pub fn underline(mut self) -> LevelStyle {
+        self.underline = true;
+        self
+    }
+
+    pub(crate) fn color_spec(&self) -> ColorSpec {
+        let mut spec = ColorSpec::new();
+        spec.set_fg(self.fg.clone())
+            .set_bg(self.bg.clone())
+            .set_bold(self.bold)
+            .set_italic(self.italic)
+            .set_dimmed(self.dimmed)
+            .set_underline(self.underline);
+        spec
+    }
+}
+
+impl FromStr for LevelStyle {
+    type Err = ColorParseError;
+
+    /// Parses colon separated styles as in a placeholder of the formatter, e.g.
+    /// `ansi(208):bgred:bold`. The first foreground and background color apply.
+    ///
+    /// ```
+    /// # use sp_log2::*;
+    /// let styles = [
+    ///     ("ansi(208)", LevelStyle::new().fg(Color::Ansi256(208))),
+    ///     ("@208", LevelStyle::new().fg(Color::Ansi256(208))),
+    ///     ("bgansi(17)", LevelStyle::new().bg(Color::Ansi256(17))),
+    ///     ("bg@17", LevelStyle::new().bg(Color::Ansi256(17))),
+    ///     ("ansi(0):bgansi(255)", LevelStyle::new().fg(Color::Ansi256(0)).bg(Color::Ansi256(255))),
+    ///     ("ansi(208):bold", LevelStyle::new().fg(Color::Ansi256(208)).bold()),
+    ///     ("red:blue:italic:underline", LevelStyle::new().fg(Color::Red).italic().underline()),
+    ///     ("white:bgred:dim", LevelStyle::new().fg(Color::White).bg(Color::Red).dimmed()),
+    ///     ("bg#1e1e2e", LevelStyle::new().bg(Color::Hex("#1E1E2E"))),
+    ///     ("bgrgb(30 30 46)", LevelStyle::new().bg(Color::Rgb(30, 30, 46))),
+    ///     ("bg:red", LevelStyle::new().bg(Color::Red)),
+    ///     ("bg:#1e1e2e:white", LevelStyle::new().fg(Color::White).bg(Color::Hex("#1E1E2E"))),
+    ///     ("#ffffff:bg#d20f39:bold", LevelStyle::new().fg(Color::Hex("#FFFFFF")).bg(Color::Hex("#D20F39")).bold()),
+    /// ];
+    /// for (style, expected) in styles {
+    ///     assert_eq!(style.parse::<LevelStyle>(), Ok(expected), "{}", style);
+    /// }
+    ///
+    /// for style in ["ansi(256)", "@300", "bgansi(999)", "ansi()", "ansi(red)", "@", "blink", "bg", "bg:bold"] {
+    ///     assert!(style.parse::<LevelStyle>().is_err(), "{}", style);
+    /// }
+    /// ```
+    fn from_str(styles: &str) -> Result<LevelStyle, ColorParseError> {
+        let mut level_style = LevelStyle::new();
+        let mut styles = styles.split(':');
+        while let Some(style) = styles.next() {
+            match style.to_ascii_lowercase().as_str() {
+                "bg" => {
+                    let (color, _) = apply_style(styles.next().unwrap_or_default())?;
+                    level_style.bg = level_style.bg.or(Some(color));
+                }
+                "bold" => level_style.bold = true,
+                "italic" => level_style.italic = true,
+                "dim" => level_style.dimmed = true,
+                "underline" => level_style.underline = true,
+                _ => {
+                    let (color, is_fg) = apply_style(style)?;
+                    if is_fg {
+                        level_style.fg = level_style.fg.or(Some(color));
+                    } else {
+                        level_style.bg = level_style.bg.or(Some(color));
+                    }
+                }
+            }
+        }
+        Ok(level_style)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 /// Defines how padding should be applied to the thread information in the log output.
 pub enum ThreadPadding {
@@ -26,6 +285,10 @@ pub enum ThreadPadding {
     /// Pad the thread information with spaces to the right, with a specified width.
     Right(usize),
 
+    /// Pad the thread information with spaces to the right, and truncate it with an
+    /// ellipsis if it is longer than the specified width.
+    Exact(usize),
+
     /// No padding applied to the thread information.
     Off,
 }
@@ -37,10 +300,48 @@ pub enum TargetPadding {
     Left(usize),
     /// Add spaces on the right side, up to usize many
     Right(usize),
+    /// Add spaces on the right side, up to usize many, and truncate longer targets with an ellipsis
+    Exact(usize),
     /// Do not pad the thread id/name
     Off,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A field of the default layout, see [`set_field_order`](ConfigBuilder::set_field_order).
+pub enum LogField {
+    /// The time
+    Time,
+    /// The time since the `Config` was built, see
+    /// [`set_uptime_precision`](ConfigBuilder::set_uptime_precision)
+    Uptime,
+    /// The hostname, see [`set_hostname`](ConfigBuilder::set_hostname)
+    Hostname,
+    /// The level in brackets, e.g. `[INFO]`
+    Level,
+    /// The thread and process id in parentheses, e.g. `(main)`
+    Thread,
+    /// The target followed by a colon, e.g. `myapp::db:`
+    Target,
+    /// The message, preceded by the context values if those are enabled
+    Message,
+    /// The file location in brackets, e.g. `[src/main.rs:10]`
+    Location,
+    /// The module path
+    Module,
+}
+
+/// The order of the fields in the default layout, unless set otherwise.
+const DEFAULT_FIELD_ORDER: [LogField; 8] = [
+    LogField::Time,
+    LogField::Uptime,
+    LogField::Hostname,
+    LogField::Level,
+    LogField::Thread,
+    LogField::Target,
+    LogField::Message,
+    LogField::Location,
+];
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 /// Mode for logging the thread name or id or both.
 pub enum ThreadLogMode {
@@ -50,36 +351,382 @@ pub enum ThreadLogMode {
     Names,
     /// If this thread is named, log the name. Otherwise, log the thread id.
     Both,
+    /// Log the thread name and id, joined with the separator set with
+    /// [`set_thread_separator`](ConfigBuilder::set_thread_separator). Unnamed threads log the id only.
+    NameAndId,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// What the target filters are matched against.
+pub enum FilterMatch {
+    /// The target of the record.
+    #[default]
+    TargetOnly,
+
+    /// The module path of the record. Records without one match no filter.
+    ModuleOnly,
+
+    /// The target or the module path of the record, if either matches.
+    Either,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// How a target filter entry is matched against a record's target.
+///
+/// # Examples
+///
+/// ```
+/// # use sp_log2::FilterPattern;
+/// assert!(FilterPattern::Prefix("hyper".into()).matches("hyperlocal"));
+/// assert!(!FilterPattern::Exact("hyper".into()).matches("hyperlocal"));
+/// assert!(FilterPattern::Glob("hyper::*".into()).matches("hyper::client"));
+/// assert!(!FilterPattern::Glob("hyper::*".into()).matches("hyperlocal::client"));
+/// assert!(FilterPattern::Glob("tokio::*::driver".into()).matches("tokio::io::driver"));
+/// assert!(!FilterPattern::Glob("tokio::*::driver".into()).matches("tokio::net::io::driver"));
+/// assert!(FilterPattern::Glob("tokio::**::driver".into()).matches("tokio::net::io::driver"));
+/// ```
+pub enum FilterPattern {
+    /// The target has to be equal to the pattern
+    Exact(Cow<'static, str>),
+    /// The target has to start with the pattern
+    Prefix(Cow<'static, str>),
+    /// The whole target has to match the glob pattern.
+    ///
+    /// `*` matches any characters within a single path segment, `**` matches any
+    /// characters including `::` separators and `?` matches exactly one character.
+    Glob(Cow<'static, str>),
+}
+
+impl FilterPattern {
+    /// Returns true if `target` matches this pattern.
+    pub fn matches(&self, target: &str) -> bool {
+        match self {
+            FilterPattern::Exact(pattern) => target == pattern,
+            FilterPattern::Prefix(pattern) => target.starts_with(&**pattern),
+            FilterPattern::Glob(pattern) => glob_matches(pattern, target),
+        }
+    }
+}
+
+fn glob_matches(pattern: &str, target: &str) -> bool {
+    let boundaries = || {
+        target
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(target.len()))
+    };
+
+    if let Some(rest) = pattern.strip_prefix("**") {
+        return boundaries().any(|i| glob_matches(rest, &target[i..]));
+    }
+
+    if let Some(rest) = pattern.strip_prefix('*') {
+        return boundaries()
+            .take_while(|i| !target[..*i].contains(':'))
+            .any(|i| glob_matches(rest, &target[i..]));
+    }
+
+    let mut pattern_chars = pattern.chars();
+    match pattern_chars.next() {
+        None => target.is_empty(),
+        Some(p) => {
+            let mut target_chars = target.chars();
+            match target_chars.next() {
+                Some(t) if p == '?' || p == t => {
+                    glob_matches(pattern_chars.as_str(), target_chars.as_str())
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+/// Sample rate for targets starting with `prefix`.
+#[derive(Debug)]
+pub(crate) struct SampleRate {
+    pub(crate) prefix: Cow<'static, str>,
+    pub(crate) rate: usize,
+    pub(crate) counter: AtomicUsize,
+}
+
+impl Clone for SampleRate {
+    /// Clones start counting from zero, so loggers sharing a `Config` sample independently.
+    fn clone(&self) -> Self {
+        SampleRate {
+            prefix: self.prefix.clone(),
+            rate: self.rate,
+            counter: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum TimeFormat {
     Rfc2822,
     Rfc3339,
-    Custom(&'static str),
+    Rfc3339Opts(SecondsFormat, bool),
+    UnixSeconds,
+    UnixMillis,
+    Custom(Cow<'static, str>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+/// The parts of a record shown in the log format, combined with `|`.
+///
+/// `Default` is the layout of `Config::default()`: level, time, thread and target.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// let format = FormatFlags::TIME | FormatFlags::LEVEL | FormatFlags::TARGET;
+/// assert!(format.contains(FormatFlags::TIME | FormatFlags::TARGET));
+/// assert!(!format.contains(FormatFlags::THREAD));
+/// assert_eq!(format!("{:?}", format), "FormatFlags(TIME | LEVEL | TARGET)");
+/// assert_eq!(format!("{:?}", FormatFlags::TIME & FormatFlags::TARGET), "FormatFlags(empty)");
+///
+/// // The deprecated integer flags still convert
+/// # #[allow(deprecated)]
+/// let old = FormatFlags::from(Format::Time | Format::Target);
+/// assert_eq!(old, FormatFlags::TIME | FormatFlags::TARGET);
+/// ```
+pub struct FormatFlags(u16);
+
+impl FormatFlags {
+    /// Flag to include the time in the log format.
+    pub const TIME: FormatFlags = FormatFlags(1);
+
+    /// Flag to include the log level (e.g., INFO, ERROR) in the log format.
+    pub const LEVEL: FormatFlags = FormatFlags(2);
+
+    /// Flag to include the thread information in the log format.
+    pub const THREAD: FormatFlags = FormatFlags(4);
+
+    /// Flag to include the file location (e.g., file name, line number) in the log format.
+    pub const FILE_LOCATION: FormatFlags = FormatFlags(8);
+
+    /// Flag to include the target (e.g., module or crate) in the log format.
+    pub const TARGET: FormatFlags = FormatFlags(16);
+
+    /// Flag to include the module name in the log format.
+    pub const MODULE: FormatFlags = FormatFlags(32);
+
+    /// Flag to include the process id in the log format.
+    ///
+    /// In the default layout it is shown next to the thread as `(pid:tid)`.
+    ///
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new().set_format(FormatFlags::PROCESS_ID | FormatFlags::THREAD).build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// let expected = format!(" ({}:", std::process::id());
+    /// assert!(logger.take_records()[0].formatted.starts_with(&expected));
+    ///
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::PROCESS_ID)
+    ///         .set_formatter(Some("[pid] [message]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, format!("{} hi", std::process::id()));
+    /// ```
+    pub const PROCESS_ID: FormatFlags = FormatFlags(64);
+
+    /// Flag to include the hostname in the log format.
+    ///
+    /// In the default layout it is shown after the time.
+    pub const HOSTNAME: FormatFlags = FormatFlags(128);
+
+    /// Flag to include the time since the `Config` was created in the log format,
+    /// e.g. `   0.042s`.
+    ///
+    /// In the default layout it is shown after the time.
+    pub const UPTIME: FormatFlags = FormatFlags(256);
+
+    /// Flag to include the values of the thread's [`context`](crate::context) in the log format.
+    ///
+    /// In the default layout they are shown in braces in front of the message, e.g.
+    /// `{request_id=42 user=alice}`.
+    ///
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new().set_format(FormatFlags::LEVEL | FormatFlags::CONTEXT).build(),
+    /// );
+    /// let _request = context::scoped("request_id", 42);
+    /// let _user = context::scoped("user", "alice");
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, " [INFO] {request_id=42 user=alice} hi");
+    /// ```
+    pub const CONTEXT: FormatFlags = FormatFlags(512);
+
+    const NAMES: [(&'static str, FormatFlags); 10] = [
+        ("TIME", FormatFlags::TIME),
+        ("LEVEL", FormatFlags::LEVEL),
+        ("THREAD", FormatFlags::THREAD),
+        ("FILE_LOCATION", FormatFlags::FILE_LOCATION),
+        ("TARGET", FormatFlags::TARGET),
+        ("MODULE", FormatFlags::MODULE),
+        ("PROCESS_ID", FormatFlags::PROCESS_ID),
+        ("HOSTNAME", FormatFlags::HOSTNAME),
+        ("UPTIME", FormatFlags::UPTIME),
+        ("CONTEXT", FormatFlags::CONTEXT),
+    ];
+
+    /// Returns the flags with nothing set.
+    pub const fn empty() -> FormatFlags {
+        FormatFlags(0)
+    }
+
+    /// Returns the flags as an integer.
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Returns true if no flag is set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if every flag of `other` is set.
+    pub const fn contains(self, other: FormatFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the flags of `bits`, as returned by [`FormatFlags::bits`].
+    pub(crate) const fn from_bits(bits: u16) -> FormatFlags {
+        FormatFlags(bits)
+    }
+}
+
+impl Default for FormatFlags {
+    fn default() -> FormatFlags {
+        FormatFlags::LEVEL | FormatFlags::TIME | FormatFlags::THREAD | FormatFlags::TARGET
+    }
+}
+
+impl fmt::Debug for FormatFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FormatFlags(")?;
+        if self.is_empty() {
+            f.write_str("empty")?;
+        }
+        let mut rest = self.0;
+        for (name, flag) in FormatFlags::NAMES {
+            if self.contains(flag) {
+                if rest != self.0 {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                rest &= !flag.0;
+            }
+        }
+        if rest != 0 {
+            if rest != self.0 {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{:#x}", rest)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl BitOr for FormatFlags {
+    type Output = FormatFlags;
+
+    fn bitor(self, rhs: FormatFlags) -> FormatFlags {
+        FormatFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for FormatFlags {
+    fn bitor_assign(&mut self, rhs: FormatFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for FormatFlags {
+    type Output = FormatFlags;
+
+    fn bitand(self, rhs: FormatFlags) -> FormatFlags {
+        FormatFlags(self.0 & rhs.0)
+    }
 }
 
+impl BitAndAssign for FormatFlags {
+    fn bitand_assign(&mut self, rhs: FormatFlags) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl From<u8> for FormatFlags {
+    /// Converts the integer flags of [`Format`], as taken by `set_format` before.
+    fn from(bits: u8) -> FormatFlags {
+        FormatFlags(bits.into())
+    }
+}
+
+/// The flags of the log format as integers, kept for compatibility.
+///
+/// Use [`FormatFlags`] instead. As integers, a mistake like `Format::Time & Format::Target`
+/// compiles and silently disables every flag.
 #[allow(non_upper_case_globals, non_snake_case)]
 pub mod Format {
+    use super::FormatFlags;
+
     /// Flag to include the time in the log format.
-    pub const Time: u8 = 1;
+    #[deprecated(note = "use `FormatFlags::TIME` instead")]
+    pub const Time: u8 = FormatFlags::TIME.bits() as u8;
 
     /// Flag to include the log level (e.g., INFO, ERROR) in the log format.
-    pub const LevelFlag: u8 = 2;
+    #[deprecated(note = "use `FormatFlags::LEVEL` instead")]
+    pub const LevelFlag: u8 = FormatFlags::LEVEL.bits() as u8;
 
     /// Flag to include the thread information in the log format.
-    pub const Thread: u8 = 4;
+    #[deprecated(note = "use `FormatFlags::THREAD` instead")]
+    pub const Thread: u8 = FormatFlags::THREAD.bits() as u8;
 
     /// Flag to include the file location (e.g., file name, line number) in the log format.
-    pub const FileLocation: u8 = 8;
+    #[deprecated(note = "use `FormatFlags::FILE_LOCATION` instead")]
+    pub const FileLocation: u8 = FormatFlags::FILE_LOCATION.bits() as u8;
 
     /// Flag to include the target (e.g., module or crate) in the log format.
-    pub const Target: u8 = 16;
+    #[deprecated(note = "use `FormatFlags::TARGET` instead")]
+    pub const Target: u8 = FormatFlags::TARGET.bits() as u8;
 
     /// Flag to include the module name in the log format.
-    pub const Module: u8 = 32;
+    #[deprecated(note = "use `FormatFlags::MODULE` instead")]
+    pub const Module: u8 = FormatFlags::MODULE.bits() as u8;
+
+    /// Flag to include the process id in the log format.
+    #[deprecated(note = "use `FormatFlags::PROCESS_ID` instead")]
+    pub const ProcessId: u8 = FormatFlags::PROCESS_ID.bits() as u8;
+
+    /// Flag to include the hostname in the log format.
+    #[deprecated(note = "use `FormatFlags::HOSTNAME` instead")]
+    pub const Hostname: u8 = FormatFlags::HOSTNAME.bits() as u8;
+
+    /// Flag to include the time since the `Config` was created in the log format.
+    ///
+    /// It does not fit in a `u8`, so unlike the older flags it is a [`FormatFlags`].
+    #[deprecated(note = "use `FormatFlags::UPTIME` instead")]
+    pub const Uptime: FormatFlags = FormatFlags::UPTIME;
+
+    /// Flag to include the values of the thread's context in the log format.
+    ///
+    /// It does not fit in a `u8`, so unlike the older flags it is a [`FormatFlags`].
+    #[deprecated(note = "use `FormatFlags::CONTEXT` instead")]
+    pub const Context: FormatFlags = FormatFlags::CONTEXT;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// UTF-8 end of line character sequences
 pub enum LineEnding {
     /// Line feed
@@ -112,27 +759,392 @@ pub enum LineEnding {
 /// Construct using [`Default`](Config::default) or using [`ConfigBuilder`]
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub(crate) format: u8,
+    pub(crate) format: FormatFlags,
+    pub(crate) field_order: Cow<'static, [LogField]>,
     pub(crate) level_padding: LevelPadding,
     pub(crate) thread_log_mode: ThreadLogMode,
     pub(crate) thread_padding: ThreadPadding,
+    pub(crate) thread_separator: Cow<'static, str>,
+    pub(crate) level_delimiters: (Cow<'static, str>, Cow<'static, str>),
+    pub(crate) thread_delimiters: (Cow<'static, str>, Cow<'static, str>),
+    pub(crate) target_suffix: Cow<'static, str>,
+    pub(crate) field_separator: Cow<'static, str>,
     pub(crate) target_padding: TargetPadding,
-    pub(crate) min_level: LevelFilter,
-    pub(crate) max_level: LevelFilter,
+    pub(crate) most_severe: LevelFilter,
+    pub(crate) least_severe: LevelFilter,
     pub(crate) time_format: TimeFormat,
-    pub(crate) filter_allow: Cow<'static, [Cow<'static, str>]>,
-    pub(crate) filter_ignore: Cow<'static, [Cow<'static, str>]>,
-    pub(crate) level_color: [Option<Color>; 6],
+    pub(crate) time_offset: Option<UtcOffset>,
+    pub(crate) time_include_offset: bool,
+    pub(crate) clock: Option<ConfigClock>,
+    pub(crate) filter_allow: Cow<'static, [FilterPattern]>,
+    pub(crate) filter_ignore: Cow<'static, [FilterPattern]>,
+    pub(crate) filter_allow_message: Vec<String>,
+    pub(crate) filter_ignore_message: Vec<String>,
+    #[cfg(feature = "regex")]
+    pub(crate) filter_allow_message_regex: Vec<Regex>,
+    #[cfg(feature = "regex")]
+    pub(crate) filter_ignore_message_regex: Vec<Regex>,
+    pub(crate) level_directives: Vec<(Cow<'static, str>, LevelFilter)>,
+    /// Levels of allow list entries added with a level, longest prefix first
+    pub(crate) filter_allow_levels: Vec<(Cow<'static, str>, LevelFilter)>,
+    pub(crate) sample_rates: Vec<SampleRate>,
+    pub(crate) repeat_suppression: bool,
+    pub(crate) repeat_window: Duration,
+    pub(crate) nonblocking: bool,
+    pub(crate) banner: Option<Banner>,
+    pub(crate) error_handler: Option<ConfigErrorHandler>,
+    #[cfg(feature = "regex")]
+    pub(crate) filter_allow_regex: Vec<Regex>,
+    #[cfg(feature = "regex")]
+    pub(crate) filter_ignore_regex: Vec<Regex>,
+    pub(crate) level_style: [LevelStyle; 6],
+    pub(crate) level_texts: [Option<String>; 5],
+    pub(crate) level_case: Option<LevelCase>,
+    pub(crate) level_padding_width: Option<usize>,
+    pub(crate) message_column: Option<usize>,
+    pub(crate) wrap: WrapMode,
+    pub(crate) inline_tags: bool,
+    pub(crate) multiline: MultilineMode,
+    pub(crate) escape_newlines: bool,
+    pub(crate) json_output: bool,
+    pub(crate) json_field_names: JsonFieldNames,
+    pub(crate) location_style: LocationStyle,
+    pub(crate) target_style: TargetStyle,
+    pub(crate) filter_match: FilterMatch,
+    pub(crate) level_icons: [Option<String>; 5],
+    pub(crate) use_level_icons: bool,
     pub(crate) enable_colors: bool,
+    pub(crate) respect_color_env: bool,
+    pub(crate) colorize_line: bool,
+    pub(crate) write_log_enable_colors: bool,
     pub(crate) line_ending: String,
     pub(crate) formatter: Option<String>,
+    pub(crate) level_formatters: [Option<String>; 5],
+    pub(crate) strict_formatter: bool,
+    pub(crate) hostname: Option<String>,
+    pub(crate) custom_placeholders: Vec<(String, String)>,
+    pub(crate) created: Instant,
+    pub(crate) uptime_precision: usize,
+}
+
+/// Error returned by [`ConfigBuilder::try_build`] for a configuration that cannot work as intended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The most severe level of the range is less severe than the least severe one,
+    /// so no record lies within the level range
+    LevelRange {
+        /// The most severe level that is logged
+        most_severe: LevelFilter,
+        /// The least severe level that is logged
+        least_severe: LevelFilter,
+    },
+    /// A `[` in the formatter has no closing `]`
+    UnclosedPlaceholder {
+        /// Byte offset of the `[` in the formatter
+        position: usize,
+    },
+    /// The formatter contains a placeholder that is not known
+    UnknownPlaceholder {
+        /// The name of the placeholder
+        key: String,
+        /// Byte offset of the name in the formatter
+        position: usize,
+    },
+    /// A placeholder of the formatter has a style that is not known
+    UnknownStyle {
+        /// The style, e.g. `blod`
+        style: String,
+        /// Byte offset of the style in the formatter
+        position: usize,
+    },
+    /// A field of a `ConfigSpec` has a value that cannot be parsed
+    InvalidValue {
+        /// The name of the field, e.g. `max_level`
+        field: String,
+        /// The value of the field
+        value: String,
+        /// Why the value is invalid, e.g. the values that are expected
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::LevelRange {
+                most_severe,
+                least_severe,
+            } => write!(
+                f,
+                "level range: most severe level {} is less severe than least severe level {}",
+                most_severe, least_severe
+            ),
+            ConfigError::UnclosedPlaceholder { position } => {
+                write!(f, "formatter: unclosed '[' at byte {}", position)
+            }
+            ConfigError::UnknownPlaceholder { key, position } => {
+                write!(
+                    f,
+                    "formatter: unknown placeholder '{}' at byte {}, expected one of: {}",
+                    key,
+                    position,
+                    PLACEHOLDERS.join(", ")
+                )
+            }
+            ConfigError::UnknownStyle { style, position } => {
+                write!(
+                    f,
+                    "formatter: unknown style '{}' at byte {}",
+                    style, position
+                )
+            }
+            ConfigError::InvalidValue {
+                field,
+                value,
+                reason,
+            } => write!(f, "{}: invalid value '{}', {}", field, value, reason),
+        }
+    }
 }
 
+impl std::error::Error for ConfigError {}
+
 impl Config {
     /// Create a new default `ConfigBuilder`
     pub fn builder() -> ConfigBuilder {
         ConfigBuilder::new()
     }
+
+    /// A terse `Config` for development, showing the level and the message only.
+    ///
+    /// The level keeps its color on the terminal.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(LevelFilter::Info, Config::minimal());
+    /// logger.log(
+    ///     &Record::builder()
+    ///         .level(Level::Warn)
+    ///         .target("app::db")
+    ///         .file(Some("src/db.rs"))
+    ///         .line(Some(42))
+    ///         .args(format_args!("slow query"))
+    ///         .build(),
+    /// );
+    /// assert_eq!(logger.take_records()[0].formatted, "[WARN] slow query");
+    /// ```
+    pub fn minimal() -> Config {
+        ConfigBuilder::new()
+            .set_format(FormatFlags::LEVEL)
+            .set_formatter(Some("[level] [message]"))
+            .build()
+    }
+
+    /// A `Config` for debugging, showing every part of a record: the time in RFC 3339
+    /// with milliseconds, the uptime, the hostname, the level, the process and thread
+    /// ids, the target, the module, the context and the file location.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(LevelFilter::Info, Config::verbose());
+    /// logger.log(
+    ///     &Record::builder()
+    ///         .level(Level::Warn)
+    ///         .target("app::db")
+    ///         .module_path(Some("app::db"))
+    ///         .file(Some("src/db.rs"))
+    ///         .line(Some(42))
+    ///         .args(format_args!("slow query"))
+    ///         .build(),
+    /// );
+    ///
+    /// // e.g. 2024-05-01T14:03:07.123+02:00    0.000s myhost [WARN] (4242:1) app::db: slow query [src/db.rs:42]
+    /// let line = logger.take_records().remove(0).formatted;
+    /// assert!(chrono::DateTime::parse_from_rfc3339(&line[..29]).is_ok(), "{:?}", line);
+    /// let rest = line[29..].trim_start();
+    /// assert!(rest.split(' ').next().unwrap().ends_with('s'), "{:?}", line);
+    /// assert!(
+    ///     rest.ends_with(&format!(
+    ///         " [WARN] ({}:1) app::db: slow query [src/db.rs:42]",
+    ///         std::process::id()
+    ///     )),
+    ///     "{:?}",
+    ///     line
+    /// );
+    /// ```
+    pub fn verbose() -> Config {
+        ConfigBuilder::new()
+            .set_format(
+                FormatFlags::TIME
+                    | FormatFlags::LEVEL
+                    | FormatFlags::THREAD
+                    | FormatFlags::FILE_LOCATION
+                    | FormatFlags::TARGET
+                    | FormatFlags::MODULE
+                    | FormatFlags::PROCESS_ID
+                    | FormatFlags::HOSTNAME
+                    | FormatFlags::UPTIME
+                    | FormatFlags::CONTEXT,
+            )
+            .set_time_format_rfc3339_with_precision(SecondsFormat::Millis)
+            .build()
+    }
+
+    /// A `Config` for log files: full timestamps in RFC 3339 with milliseconds, no
+    /// colors, and line breaks in messages escaped, so every record is one line.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(LevelFilter::Info, Config::for_files());
+    /// logger.log(
+    ///     &Record::builder()
+    ///         .level(Level::Warn)
+    ///         .target("app::db")
+    ///         .args(format_args!("slow query\nSELECT 1"))
+    ///         .build(),
+    /// );
+    ///
+    /// // e.g. 2024-05-01T14:03:07.123+02:00 [WARN] (1) app::db: slow query\nSELECT 1
+    /// let line = logger.take_records().remove(0).formatted;
+    /// assert!(chrono::DateTime::parse_from_rfc3339(&line[..29]).is_ok(), "{:?}", line);
+    /// assert_eq!(&line[29..], " [WARN] (1) app::db: slow query\\nSELECT 1");
+    /// ```
+    pub fn for_files() -> Config {
+        ConfigBuilder::new()
+            .set_time_format_rfc3339_with_precision(SecondsFormat::Millis)
+            .set_enable_colors(false)
+            .set_write_log_enable_colors(false)
+            .set_escape_newlines(true)
+            .build()
+    }
+
+    /// Checks the formatter and the formatters of the levels for unclosed brackets,
+    /// unknown placeholders and unknown styles.
+    ///
+    /// Succeeds if no formatter is set. [`ConfigBuilder::try_build`] calls this as well.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// fn check(formatter: &str) -> Result<(), ConfigError> {
+    ///     ConfigBuilder::new().set_formatter(Some(formatter)).build().validate_formatter()
+    /// }
+    ///
+    /// let keys = [
+    ///     "time", "thread", "target", "level", "file", "filename", "filepath", "line", "pid",
+    ///     "hostname", "uptime", "delta", "module", "message", "context", "context.request_id",
+    ///     "icon", "thread_id", "thread_name",
+    /// ];
+    /// for key in keys {
+    ///     assert!(check(&format!("[{}]", key)).is_ok());
+    ///     assert!(check(&format!("[[{}:bold:red:bgblue:<10]]", key)).is_ok());
+    /// }
+    /// assert!(check("[level:nb]").is_ok());
+    ///
+    /// assert!(matches!(check("[mesage]"), Err(ConfigError::UnknownPlaceholder { .. })));
+    /// assert!(matches!(check("[Level]"), Err(ConfigError::UnknownPlaceholder { .. })));
+    /// assert!(check("[time] [trget]")
+    ///     .unwrap_err()
+    ///     .to_string()
+    ///     .starts_with("formatter: unknown placeholder 'trget' at byte 8, expected one of: time, thread, target"));
+    /// assert_eq!(
+    ///     check("[level:blod]"),
+    ///     Err(ConfigError::UnknownStyle { style: "blod".to_string(), position: 7 })
+    /// );
+    /// for style in ["bg#1e1e2e", "bgrgb(30 30 46)", "bgansi(17)", "bg:red", "#ffffff:bg#d20f39:bold"] {
+    ///     assert!(check(&format!("[level:{}]", style)).is_ok());
+    /// }
+    /// assert_eq!(
+    ///     check("[level:bg:bold]"),
+    ///     Err(ConfigError::UnknownStyle { style: "bg".to_string(), position: 7 })
+    /// );
+    /// assert!(matches!(check("[message"), Err(ConfigError::UnclosedPlaceholder { position: 0 })));
+    /// ```
+    pub fn validate_formatter(&self) -> Result<(), ConfigError> {
+        self.level_formatters
+            .iter()
+            .chain(Some(&self.formatter))
+            .flatten()
+            .try_for_each(|formatter| check_formatter(formatter, &self.custom_placeholders))
+    }
+
+    /// Returns the text written for `level`, in the configured case.
+    pub(crate) fn level_text(&self, level: Level) -> Cow<'_, str> {
+        let text = self.level_texts[level as usize - 1]
+            .as_deref()
+            .unwrap_or(level.as_str());
+        match self.level_case {
+            None => Cow::Borrowed(text),
+            Some(LevelCase::Upper) => Cow::Owned(text.to_uppercase()),
+            Some(LevelCase::Lower) => Cow::Owned(text.to_lowercase()),
+            Some(LevelCase::Capitalized) => {
+                let mut chars = text.chars();
+                Cow::Owned(match chars.next() {
+                    Some(first) => first
+                        .to_uppercase()
+                        .chain(chars.as_str().to_lowercase().chars())
+                        .collect(),
+                    None => String::new(),
+                })
+            }
+        }
+    }
+
+    /// Returns the length of the longest level text, which the level is padded to.
+    pub(crate) fn level_width(&self) -> usize {
+        Level::iter()
+            .map(|level| self.level_text(level).chars().count())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns the icon of `level`, or an empty string if it has none.
+    pub(crate) fn level_icon(&self, level: Level) -> &str {
+        self.level_icons[level as usize - 1]
+            .as_deref()
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of chars of the longest level icon, which the icon is padded to.
+    pub(crate) fn level_icon_width(&self) -> usize {
+        Level::iter()
+            .map(|level| self.level_icon(level).chars().count())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns the formatter of records of `level`, if any.
+    pub(crate) fn formatter_for(&self, level: Level) -> Option<&str> {
+        self.level_formatters[level as usize - 1]
+            .as_deref()
+            .or(self.formatter.as_deref())
+    }
+
+    /// Returns the fields of the default layout in front of and after the message.
+    pub(crate) fn fields_around_message(&self) -> (&[LogField], &[LogField]) {
+        match self
+            .field_order
+            .iter()
+            .position(|&f| f == LogField::Message)
+        {
+            Some(message) => (
+                &self.field_order[..message],
+                &self.field_order[message + 1..],
+            ),
+            None => (&self.field_order, &[]),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -172,119 +1184,2011 @@ impl ConfigBuilder {
 
     /// Sets the logging format.
     ///
-    /// The `format` value is an unsigned 8-bit integer that determines the format of the log entries.
-    pub fn set_format(&mut self, format: u8) -> &mut ConfigBuilder {
-        self.0.format = format;
-        self
-    }
-
-    /// Sets the custom formatter for the logs.
+    /// The `format` value is a combination of the [`FormatFlags`] that determines the format of the log entries.
+    /// The deprecated integer flags of [`Format`] are accepted as well.
     ///
-    /// The `formatter` is an optional string representing the format to be used. If `None`, the default format is applied.
-    pub fn set_formatter(&mut self, formatter: Option<&str>) -> &mut ConfigBuilder {
-        self.0.formatter = formatter.map(|s| s.to_string());
-        self
-    }
-
-    /// Sets the minimum log level filter.
+    /// # Usage
     ///
-    /// The `level` value specifies the minimum level of logs to be displayed. Logs with a level lower than this will be ignored.
-    pub fn set_min_level(&mut self, level: LevelFilter) -> &mut ConfigBuilder {
-        self.0.min_level = level;
-        self
-    }
-
-    /// Sets the maximum log level filter.
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let log = |builder: &mut ConfigBuilder| {
+    ///     let logger = TestLogger::new(LevelFilter::Info, builder.set_formatter(None).build());
+    ///     logger.log(&Record::builder().level(Level::Info).target("app").args(format_args!("hi")).build());
+    ///     logger.take_records().remove(0).formatted
+    /// };
     ///
-    /// The `level` value specifies the maximum level of logs to be displayed. Logs with a level higher than this will be ignored.
-    pub fn set_max_level(&mut self, level: LevelFilter) -> &mut ConfigBuilder {
-        self.0.max_level = level;
+    /// // Code written against the integer flags keeps working
+    /// # #[allow(deprecated)]
+    /// let flags: u8 = Format::LevelFlag | Format::Target;
+    /// let expected = log(ConfigBuilder::new().set_format(FormatFlags::LEVEL | FormatFlags::TARGET));
+    /// assert_eq!(log(ConfigBuilder::new().set_format(flags)), expected);
+    /// assert_eq!(log(ConfigBuilder::new().set_format(2 | 16)), expected);
+    /// ```
+    pub fn set_format(&mut self, format: impl Into<FormatFlags>) -> &mut ConfigBuilder {
+        self.0.format = format.into();
         self
     }
 
-    /// Enables or disables the use of colors in the logs.
+    /// Sets the order of the fields in the default layout (default is time, uptime,
+    /// hostname, level, thread, target, message, location).
+    ///
+    /// Each field keeps its decoration, e.g. the brackets around the level. Fields that
+    /// are not listed are not written, except the message, which is written last if it is
+    /// not listed. The format flags still decide which fields are written at all, e.g.
+    /// `LogField::Module` needs `FormatFlags::MODULE`. Fields listed more than once are
+    /// written at their first position.
+    ///
+    /// The formatter, if set, takes precedence over the field order.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL | FormatFlags::TIME | FormatFlags::TARGET)
+    ///         .set_time_format_custom("12:00")
+    ///         .set_field_order(&[LogField::Level, LogField::Time, LogField::Message, LogField::Target])
+    ///         .build(),
+    /// );
+    /// let record = Record::builder()
+    ///     .level(Level::Info)
+    ///     .target("myapp::db")
+    ///     .args(format_args!("connected"))
+    ///     .build();
+    /// logger.log(&record);
+    /// assert_eq!(logger.take_records()[0].formatted, " [INFO] 12:00 connected myapp::db:");
+    ///
+    /// // Unlisted fields are left out, the message goes last
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL | FormatFlags::TIME | FormatFlags::TARGET | FormatFlags::MODULE)
+    ///         .set_field_order(&[LogField::Target, LogField::Module, LogField::Level])
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder()
+    ///     .level(Level::Warn)
+    ///     .target("myapp")
+    ///     .module_path(Some("myapp::db"))
+    ///     .args(format_args!("slow query"))
+    ///     .build());
+    /// assert_eq!(logger.take_records()[0].formatted, " myapp: myapp::db [WARN] slow query");
+    ///
+    /// // The hostname and the uptime are fields of their own, written without the time
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL | FormatFlags::HOSTNAME)
+    ///         .set_hostname("web-01")
+    ///         .set_field_order(&[LogField::Level, LogField::Hostname, LogField::Time])
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, " [INFO] web-01 hi");
+    /// ```
+    pub fn set_field_order(&mut self, order: &[LogField]) -> &mut ConfigBuilder {
+        let mut fields: Vec<LogField> = Vec::with_capacity(order.len());
+        for &field in order {
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+        self.0.field_order = Cow::Owned(fields);
+        self
+    }
+
+    /// Sets the strings around the level in the default layout (default is `[` and `]`).
+    ///
+    /// Empty strings write the level bare.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL | FormatFlags::PROCESS_ID | FormatFlags::TARGET)
+    ///         .set_level_delimiters("", " |")
+    ///         .set_thread_delimiters("<", ">")
+    ///         .set_target_suffix("")
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).target("myapp").args(format_args!("ready")).build());
+    /// assert_eq!(
+    ///     logger.take_records()[0].formatted,
+    ///     format!(" INFO | <{}> myapp ready", std::process::id())
+    /// );
+    /// ```
+    pub fn set_level_delimiters(&mut self, prefix: &str, suffix: &str) -> &mut ConfigBuilder {
+        self.0.level_delimiters = (
+            Cow::Owned(prefix.to_string()),
+            Cow::Owned(suffix.to_string()),
+        );
+        self
+    }
+
+    /// Sets the strings around the thread and process id in the default layout
+    /// (default is `(` and `)`).
+    ///
+    /// Empty strings write them bare.
+    pub fn set_thread_delimiters(&mut self, prefix: &str, suffix: &str) -> &mut ConfigBuilder {
+        self.0.thread_delimiters = (
+            Cow::Owned(prefix.to_string()),
+            Cow::Owned(suffix.to_string()),
+        );
+        self
+    }
+
+    /// Sets the string after the target in the default layout (default is `:`).
+    pub fn set_target_suffix(&mut self, suffix: &str) -> &mut ConfigBuilder {
+        self.0.target_suffix = Cow::Owned(suffix.to_string());
+        self
+    }
+
+    /// Sets the string between the fields of the default layout (default is a space).
+    ///
+    /// It is also written in front of every field but a leading time, and in front of the
+    /// message unless a [message column](ConfigBuilder::set_message_column) is set.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::TIME | FormatFlags::LEVEL | FormatFlags::TARGET)
+    ///         .set_time_format_custom("12:00")
+    ///         .set_level_delimiters("", "")
+    ///         .set_target_suffix("")
+    ///         .set_field_separator(" | ")
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Warn).target("myapp").args(format_args!("low disk space")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, "12:00 | WARN | myapp | low disk space");
+    /// ```
+    pub fn set_field_separator(&mut self, separator: &str) -> &mut ConfigBuilder {
+        self.0.field_separator = Cow::Owned(separator.to_string());
+        self
+    }
+
+    /// Sets the custom formatter for the logs.
+    ///
+    /// The `formatter` is an optional string representing the format to be used. If `None`, the default format is applied.
+    ///
+    /// A placeholder can be aligned and truncated like in `std::fmt`, e.g. `[target:<25]` pads
+    /// the target to 25 chars, `[level:>5]` right aligns the level and `[target:.20]` cuts the
+    /// target to 20 chars. Targets, modules and files keep their end when cut, other fields
+    /// their beginning. A placeholder width replaces the padding set with e.g.
+    /// [`set_target_padding`](ConfigBuilder::set_target_padding).
+    ///
+    /// On the terminal a placeholder can be styled with `bold`, `italic`, `dim`, `underline`,
+    /// `strikethrough` and colors, e.g. `[level:#ffffff:bg#d20f39:bold]`. A color is a name
+    /// like `red`, `#rrggbb` or `#rgb`, `rgb(r g b)`, or an ANSI 256 color as `ansi(n)`, `@n`
+    /// or `n`. It is the foreground color, unless it has a `bg` prefix, e.g. `bgred`,
+    /// `bg#1e1e2e`, `bgrgb(30 30 46)` or `bgansi(17)`, or follows a separate `bg`, e.g.
+    /// `[level:bg:red]`. The first foreground and background color of a placeholder apply.
+    /// `levelcolor` styles any placeholder like the level of the record, see
+    /// [`set_level_color`](ConfigBuilder::set_level_color), e.g. `[message:levelcolor]` or
+    /// `[target:levelcolor:dim]`, and is added to the other styles of the placeholder.
+    ///
+    /// `[[key]]` wraps the value in brackets. Literal brackets are written as `\[` and `\]`,
+    /// a literal backslash as `\\`, and a `[` that is never closed is written as it is.
+    ///
+    /// `[file]` is the source location as `path/to/file.rs:12`, while `[filepath]`, `[filename]`
+    /// and `[line]` are its parts, e.g. `path/to/file.rs`, `file.rs` and `12`. Like `[file]`
+    /// they need `FormatFlags::FILE_LOCATION`, and are empty if the record has no location.
+    /// `[pid]` is the process id, which needs `FormatFlags::PROCESS_ID`, `[hostname]` the
+    /// hostname, which needs `FormatFlags::HOSTNAME`, and `[uptime]` the time since the `Config`
+    /// was created, which needs `FormatFlags::UPTIME`. `[delta]` is the time since the previous
+    /// record written by the logger, e.g. `+12.4ms`, and `+0ms` for its first record.
+    /// `[context]` are the values of the thread's [`context`](crate::context) as `key=value`
+    /// pairs and `[context.key]` the value of one key, which need `FormatFlags::CONTEXT`.
+    /// `[icon]` is the icon of the level, see [`set_level_icon`](ConfigBuilder::set_level_icon).
+    /// `[thread_name]` and `[thread_id]` are the name and id of the thread, whatever the
+    /// [`ThreadLogMode`], which need `FormatFlags::THREAD`. `[thread_name]` is empty for unnamed threads.
+    ///
+    /// `[?key:body]` writes `body` only if the field of `key` is not empty, e.g.
+    /// `[?thread: ([thread])]` leaves out the parentheses for records without a thread field.
+    /// The body can contain text and placeholders, but no further groups.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "no-color"))]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io::{self, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let render = |level: Level, enable_colors: bool| {
+    ///     let out = Shared::default();
+    ///     let config = ConfigBuilder::new()
+    ///         .set_formatter(Some("[message:levelcolor]"))
+    ///         .set_level_color(Level::Error, Some(Color::Red))
+    ///         .set_level_color(Level::Info, Some(Color::Green))
+    ///         .set_enable_colors(enable_colors)
+    ///         .build();
+    ///     let logger = TermLogger::with_streams(
+    ///         LevelFilter::Info,
+    ///         config,
+    ///         Box::new(termcolor2::Ansi::new(out.clone())),
+    ///         Box::new(termcolor2::Ansi::new(out.clone())),
+    ///     );
+    ///     logger.log(&Record::builder().level(level).args(format_args!("hi")).build());
+    ///     logger.flush();
+    ///     let written = out.0.lock().unwrap().clone();
+    ///     String::from_utf8(written).unwrap()
+    /// };
+    ///
+    /// assert_eq!(render(Level::Error, true), "\x1b[0m\x1b[31mhi\x1b[0m");
+    /// assert_eq!(render(Level::Info, true), "\x1b[0m\x1b[32mhi\x1b[0m");
+    /// assert_eq!(render(Level::Error, false), "hi");
+    ///
+    /// // Writers other than the terminal are not colored by the level
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_formatter(Some("[message:levelcolor]"))
+    ///         .set_write_log_enable_colors(true)
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Error).args(format_args!("hi")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, "hi");
+    /// # }
+    /// ```
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_target_padding(TargetPadding::Left(30))
+    ///         .set_formatter(Some("[level:<5]|[target:<12.10]|[message:^7]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).target("my_app::ünïcode").args(format_args!("hi")).build());
+    ///
+    /// assert_eq!(logger.take_records()[0].formatted, "[INFO ]|p::ünïcode  |  hi   ");
+    /// ```
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// fn render(formatter: &str) -> String {
+    ///     let logger = TestLogger::new(
+    ///         LevelFilter::Info,
+    ///         ConfigBuilder::new().set_formatter(Some(formatter)).build(),
+    ///     );
+    ///     logger.log(&Record::builder().level(Level::Info).target("app").args(format_args!("hi")).build());
+    ///     logger.take_records().remove(0).formatted
+    /// }
+    ///
+    /// assert_eq!(render("\\[myapp\\] [level] [message]"), "[myapp] [INFO] hi");
+    /// assert_eq!(render("[[target]] [level][message]"), "[app] [INFO]hi");
+    /// assert_eq!(render("\\[[target]\\]\\\\[message]"), "[app]\\hi");
+    /// assert_eq!(render("[target] ["), "app [");
+    ///
+    /// // Non-ASCII text around and inside placeholders
+    /// assert_eq!(render("› [level] [target] ✦"), "› [INFO] app ✦");
+    /// assert_eq!(render("日志 [[target]] 🚀[level]🚀"), "日志 [app] 🚀[INFO]🚀");
+    /// assert_eq!(render("[nïveau] ✦ [target:>5]"), "nïveau ✦   app");
+    ///
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::FILE_LOCATION)
+    ///         .set_formatter(Some("[filename]:[line] ([filepath]) [message]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).file(Some("src/db/pool.rs")).line(Some(42)).args(format_args!("hi")).build());
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// let records = logger.take_records();
+    /// assert_eq!(records[0].formatted, "pool.rs:42 (src/db/pool.rs) hi");
+    /// assert_eq!(records[1].formatted, ": () hi");
+    ///
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new().set_formatter(Some("[delta] [message]")).build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("first")).build());
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("second")).build());
+    /// let records = logger.take_records();
+    /// assert_eq!(records[0].formatted, "+0ms first");
+    /// assert!(records[1].formatted.starts_with('+'));
+    /// assert!(records[1].formatted.ends_with("ms second"));
+    /// assert_ne!(records[1].formatted, "+0.0ms second");
+    ///
+    /// // Groups vanish if their field is empty, e.g. as `FormatFlags::MODULE` is disabled by default
+    /// assert_eq!(render("[level][?module: ([module])] [message]"), "[INFO] hi");
+    ///
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL | FormatFlags::THREAD)
+    ///         .set_thread_mode(ThreadLogMode::Names)
+    ///         .set_formatter(Some("[level][?thread: ([thread])] [message]"))
+    ///         .build(),
+    /// );
+    /// std::thread::Builder::new()
+    ///     .name("worker".to_string())
+    ///     .spawn(move || {
+    ///         logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    ///         assert_eq!(logger.take_records()[0].formatted, "[INFO] (worker) hi");
+    ///     })
+    ///     .unwrap()
+    ///     .join()
+    ///     .unwrap();
+    /// ```
+    pub fn set_formatter(&mut self, formatter: Option<&str>) -> &mut ConfigBuilder {
+        self.0.formatter = formatter.map(|s| s.to_string());
+        self
+    }
+
+    /// Sets the formatter of records of `level`, used instead of the one set with
+    /// [`set_formatter`](ConfigBuilder::set_formatter) for this level.
+    ///
+    /// Levels without a formatter of their own use the global formatter, or the
+    /// default layout if there is none. The same placeholders and styles are supported.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL | FormatFlags::FILE_LOCATION)
+    ///         .set_formatter(Some("[level] [message]"))
+    ///         .set_level_formatter(Level::Error, "[level:white:bgred] [filename]:[line]: [message]")
+    ///         .try_build()
+    ///         .unwrap(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("ready")).build());
+    /// logger.log(
+    ///     &Record::builder()
+    ///         .level(Level::Error)
+    ///         .file(Some("src/db.rs"))
+    ///         .line(Some(42))
+    ///         .args(format_args!("connection lost"))
+    ///         .build(),
+    /// );
+    ///
+    /// let records = logger.take_records();
+    /// assert_eq!(records[0].formatted, "[INFO] ready");
+    /// assert_eq!(records[1].formatted, "[ERROR] db.rs:42: connection lost");
+    /// ```
+    pub fn set_level_formatter(&mut self, level: Level, formatter: &str) -> &mut ConfigBuilder {
+        self.0.level_formatters[level as usize - 1] = Some(formatter.to_string());
+        self
+    }
+
+    /// Makes unknown placeholders of the formatter visible (default is false).
+    ///
+    /// By default an unknown placeholder like `[mesage]` is written as its plain text,
+    /// `mesage`. In strict mode it is written as `<unknown:mesage>` instead.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_formatter(Some("[level] [mesage]"))
+    ///         .set_strict_formatter(true)
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    ///
+    /// assert_eq!(logger.take_records()[0].formatted, "[INFO] <unknown:mesage>");
+    /// ```
+    pub fn set_strict_formatter(&mut self, strict: bool) -> &mut ConfigBuilder {
+        self.0.strict_formatter = strict;
+        self
+    }
+
+    /// Sets the hostname shown with `FormatFlags::HOSTNAME` and the `[hostname]` placeholder.
+    ///
+    /// By default the hostname of the machine is looked up when the `Config` is built.
+    /// Setting it is useful e.g. in containers, whose hostname is often a random id.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::HOSTNAME | FormatFlags::LEVEL)
+    ///         .set_hostname("web-01")
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, " web-01 [INFO] hi");
+    ///
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::HOSTNAME)
+    ///         .set_hostname("web-01")
+    ///         .set_formatter(Some("[hostname]: [message]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, "web-01: hi");
+    /// ```
+    pub fn set_hostname(&mut self, hostname: &str) -> &mut ConfigBuilder {
+        self.0.hostname = Some(hostname.to_string());
+        self
+    }
+
+    /// Adds a placeholder `[key]` to the formatter that is replaced with `value` on every line,
+    /// e.g. for the version or the environment of the application.
+    ///
+    /// Custom placeholders accept the same styles as the built in ones. Adding the same key
+    /// again replaces its value. The built in placeholders take precedence, so a custom
+    /// placeholder named e.g. `level` is never shown.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let config = ConfigBuilder::new()
+    ///     .set_custom_placeholder("version", env!("CARGO_PKG_VERSION").to_string())
+    ///     .set_custom_placeholder("env", "staging".to_string())
+    ///     .set_custom_placeholder("level", "ignored".to_string())
+    ///     .set_formatter(Some("[level] [env:green:bold] v[version:<8]| [message]"))
+    ///     .try_build()
+    ///     .unwrap();
+    ///
+    /// let logger = TestLogger::new(LevelFilter::Info, config.clone());
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// assert_eq!(
+    ///     logger.take_records()[0].formatted,
+    ///     format!("[INFO] staging v{:<8}| hi", env!("CARGO_PKG_VERSION"))
+    /// );
+    ///
+    /// // Styles are applied on the terminal
+    /// let logger = TermLogger::new(LevelFilter::Info, config, TerminalMode::Stderr, ColorChoice::Always);
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// ```
+    pub fn set_custom_placeholder(&mut self, key: &str, value: String) -> &mut ConfigBuilder {
+        let placeholders = &mut self.0.custom_placeholders;
+        match placeholders.iter_mut().find(|(name, _)| name == key) {
+            Some((_, current)) => *current = value,
+            None => placeholders.push((key.to_string(), value)),
+        }
+        self
+    }
+
+    /// Sets the number of decimal places of the uptime shown with `FormatFlags::UPTIME` and the
+    /// `[uptime]` placeholder, at most 9 (default is 3, i.e. milliseconds).
+    ///
+    /// The uptime is right aligned to 4 digits before the decimal point, so it stays in
+    /// one column for the first few hours.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::UPTIME | FormatFlags::TIME)
+    ///         .set_uptime_precision(1)
+    ///         .set_formatter(Some("[[uptime]] [message]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("starting")).build());
+    /// let formatted = logger.take_records().remove(0).formatted;
+    /// assert!(formatted.starts_with("[   0."));
+    /// assert!(formatted.ends_with("s] starting"));
+    /// assert_eq!(formatted.len(), "[   0.0s] starting".len());
+    /// ```
+    pub fn set_uptime_precision(&mut self, precision: usize) -> &mut ConfigBuilder {
+        self.0.uptime_precision = precision.min(9);
+        self
+    }
+
+    /// Sets the range of levels that are logged, from the most severe to the least severe one,
+    /// both inclusive (default is `Error` to `Trace`, i.e. all levels).
+    ///
+    /// This applies in addition to the level of the logger. For example
+    /// `set_level_range(LevelFilter::Warn, LevelFilter::Info)` logs Warn and Info records,
+    /// but no Error records, e.g. to send those to a different logger.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Trace,
+    ///     ConfigBuilder::new()
+    ///         .set_level_range(LevelFilter::Warn, LevelFilter::Info)
+    ///         .build(),
+    /// );
+    /// for level in [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace] {
+    ///     logger.log(&Record::builder().level(level).args(format_args!("hello")).build());
+    /// }
+    ///
+    /// let levels: Vec<Level> = logger.take_records().iter().map(|r| r.level).collect();
+    /// assert_eq!(levels, [Level::Warn, Level::Info]);
+    /// ```
+    pub fn set_level_range(
+        &mut self,
+        most_severe: LevelFilter,
+        least_severe: LevelFilter,
+    ) -> &mut ConfigBuilder {
+        self.0.most_severe = most_severe;
+        self.0.least_severe = least_severe;
+        self
+    }
+
+    /// Sets the least severe level that is logged.
+    ///
+    /// Despite the name, `set_min_level(LevelFilter::Info)` logs Info and every more severe level.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # #![allow(deprecated)]
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Trace,
+    ///     ConfigBuilder::new().set_min_level(LevelFilter::Info).build(),
+    /// );
+    /// for level in [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace] {
+    ///     logger.log(&Record::builder().level(level).args(format_args!("hello")).build());
+    /// }
+    ///
+    /// let levels: Vec<Level> = logger.take_records().iter().map(|r| r.level).collect();
+    /// assert_eq!(levels, [Level::Error, Level::Warn, Level::Info]);
+    /// ```
+    #[deprecated(note = "use `set_level_range` instead, this sets its least severe level")]
+    pub fn set_min_level(&mut self, level: LevelFilter) -> &mut ConfigBuilder {
+        self.0.least_severe = level;
+        self
+    }
+
+    /// Sets the most severe level that is logged.
+    ///
+    /// Despite the name, `set_max_level(LevelFilter::Warn)` logs Warn and every less severe level.
+    #[deprecated(note = "use `set_level_range` instead, this sets its most severe level")]
+    pub fn set_max_level(&mut self, level: LevelFilter) -> &mut ConfigBuilder {
+        self.0.most_severe = level;
+        self
+    }
+
+    /// Enables or disables the use of colors in the logs.
     ///
     /// The `enable` flag determines whether colors should be used in the log output.
+    /// With the `no-color` feature, colors are never used, and no escape sequence is
+    /// written whatever the config asks for.
+    ///
+    /// ```
+    /// # #[cfg(feature = "no-color")]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io::{self, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Buffer {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let out = Buffer::default();
+    /// let config = ConfigBuilder::new()
+    ///     .set_enable_colors(true)
+    ///     .set_write_log_enable_colors(true)
+    ///     .set_colorize_line(true)
+    ///     .set_inline_tags(true)
+    ///     .build();
+    /// let term = TermLogger::with_streams(
+    ///     LevelFilter::Info,
+    ///     config.clone(),
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    /// );
+    /// let formatted = ConfigBuilder::from(config).set_formatter(Some("[level:red:bold] [message]")).build();
+    /// let write = WriteLogger::new(LevelFilter::Info, formatted, out.clone());
+    ///
+    /// let record = Record::builder().level(Level::Error).args(format_args!("<red>failed</red>")).build();
+    /// term.log(&record);
+    /// term.flush();
+    /// write.log(&record);
+    /// write.flush();
+    ///
+    /// let written = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    /// assert!(written.contains("failed"));
+    /// assert!(!written.contains('\x1b'), "{:?}", written);
+    /// # }
+    /// ```
     pub fn set_enable_colors(&mut self, enable: bool) -> &mut ConfigBuilder {
         self.0.enable_colors = enable;
         self
     }
 
+    /// Colors the whole line in the color of the level on the terminal, instead of only the
+    /// level (default is false).
+    ///
+    /// The styles of the formatter's placeholders still apply to their fields. Has no
+    /// effect if colors are disabled.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// let config = ConfigBuilder::new().set_colorize_line(true).build();
+    /// let _ = TermLogger::new(LevelFilter::Info, config, TerminalMode::Mixed, ColorChoice::Auto);
+    /// ```
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "no-color"))]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone)]
+    /// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+    /// # impl io::Write for Buffer {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let log = |builder: &mut ConfigBuilder, ansi: bool| {
+    ///     let out = Buffer(Arc::new(Mutex::new(Vec::new())));
+    ///     let config = builder
+    ///         .set_colorize_line(true)
+    ///         .set_level_color(Level::Warn, Some(Color::Yellow))
+    ///         .build();
+    ///     // An ANSI writer, or one that drops colors like a stream with `ColorChoice::Never`
+    ///     let stream = || -> Box<dyn WriteColor + Send> {
+    ///         if ansi {
+    ///             Box::new(termcolor2::Ansi::new(out.clone()))
+    ///         } else {
+    ///             Box::new(termcolor2::NoColor::new(out.clone()))
+    ///         }
+    ///     };
+    ///     let logger = TermLogger::with_streams(LevelFilter::Info, config, stream(), stream());
+    ///     logger.log(&Record::builder().level(Level::Warn).target("app").args(format_args!("slow")).build());
+    ///     let bytes = out.0.lock().unwrap().clone();
+    ///     String::from_utf8(bytes).unwrap()
+    /// };
+    ///
+    /// // The style of a placeholder overrides the line color for its field only
+    /// let out = log(ConfigBuilder::new().set_formatter(Some("[level] [target:blue] [message]")), true);
+    /// assert!(out.contains("\x1b[33m[WARN]"), "{:?}", out);
+    /// assert!(out.contains("\x1b[34mapp\x1b[0m"), "{:?}", out);
+    /// assert!(out.contains("\x1b[33mslow"), "{:?}", out);
+    ///
+    /// let mut builder = ConfigBuilder::new();
+    /// builder.set_format(FormatFlags::LEVEL | FormatFlags::TARGET);
+    /// assert_eq!(log(&mut builder, true), "\x1b[0m\x1b[33m [WARN] app: slow\n\x1b[0m");
+    ///
+    /// // No colors at all if they are disabled, or the stream does not support them
+    /// assert_eq!(log(&mut builder, false), " [WARN] app: slow\n");
+    /// assert_eq!(log(builder.set_enable_colors(false), true), " [WARN] app: slow\n");
+    /// # }
+    /// ```
+    pub fn set_colorize_line(&mut self, colorize: bool) -> &mut ConfigBuilder {
+        self.0.colorize_line = colorize;
+        self
+    }
+
+    /// Writes the styles of the formatter's placeholders as ANSI escape sequences for
+    /// loggers that do not write to a terminal, e.g. the `WriteLogger` and `FileLogger`
+    /// (default is false).
+    ///
+    /// This is useful for output that is shown with a pager like `less -R` or `cat` later.
+    /// The default layout stays uncolored.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "no-color"))]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_formatter(Some("[level:red:bold] [message]"))
+    ///         .set_write_log_enable_colors(true)
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hi")).build());
+    /// assert_eq!(
+    ///     logger.take_records()[0].formatted,
+    ///     "\x1b[0m\x1b[1m\x1b[31m[INFO]\x1b[0m hi"
+    /// );
+    /// # }
+    /// ```
+    pub fn set_write_log_enable_colors(&mut self, enable: bool) -> &mut ConfigBuilder {
+        self.0.write_log_enable_colors = enable;
+        self
+    }
+
+    /// Makes a `TermLogger` created with `ColorChoice::Auto` follow the `NO_COLOR`,
+    /// `CLICOLOR` and `CLICOLOR_FORCE` environment variables (default is true).
+    ///
+    /// See [`TermLogger::resolve_color_choice`](crate::TermLogger::resolve_color_choice).
+    pub fn set_respect_color_env(&mut self, respect: bool) -> &mut ConfigBuilder {
+        self.0.respect_color_env = respect;
+        self
+    }
+
     /// Sets the padding for the target field in the log output.
     ///
     /// The `padding` value determines how the target field should be padded.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let log = |padding: TargetPadding, target: &str| {
+    ///     let logger = TestLogger::new(
+    ///         LevelFilter::Info,
+    ///         ConfigBuilder::new().set_formatter(Some("[target]|")).set_target_padding(padding).build(),
+    ///     );
+    ///     logger.log(&Record::builder().level(Level::Info).target(target).args(format_args!("")).build());
+    ///     logger.take_records().remove(0).formatted
+    /// };
+    ///
+    /// assert_eq!(log(TargetPadding::Right(8), "tokio-runtime-worker"), "tokio-runtime-worker|");
+    /// assert_eq!(log(TargetPadding::Exact(8), "db"), "db      |");
+    /// assert_eq!(log(TargetPadding::Exact(8), "database"), "database|");
+    /// assert_eq!(log(TargetPadding::Exact(8), "tokio-runtime-worker"), "tokio-r…|");
+    /// assert_eq!(log(TargetPadding::Exact(4), "größe"), "grö…|");
+    /// assert_eq!(log(TargetPadding::Exact(5), "größe"), "größe|");
+    /// assert_eq!(log(TargetPadding::Exact(3), "日本語のログ"), "日本…|");
+    /// ```
     pub fn set_target_padding(&mut self, padding: TargetPadding) -> &mut ConfigBuilder {
         self.0.target_padding = padding;
         self
     }
 
+    /// Sets how the target is written, in the default layout and the `[target]` placeholder
+    /// (default is `Full`).
+    ///
+    /// The target is shortened before it is padded with [`TargetPadding`]. Filters still
+    /// match the whole target.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let log = |style: TargetStyle, padding: TargetPadding| {
+    ///     let logger = TestLogger::new(
+    ///         LevelFilter::Info,
+    ///         ConfigBuilder::new()
+    ///             .set_formatter(Some("[target]|"))
+    ///             .set_target_style(style)
+    ///             .set_target_padding(padding)
+    ///             .add_filter_allow_str("platform::services")
+    ///             .build(),
+    ///     );
+    ///     let target = "platform::services::ingestion::pipeline::stage3";
+    ///     logger.log(&Record::builder().level(Level::Info).target(target).args(format_args!("")).build());
+    ///     logger.take_records().remove(0).formatted
+    /// };
+    ///
+    /// assert_eq!(
+    ///     log(TargetStyle::Full, TargetPadding::Off),
+    ///     "platform::services::ingestion::pipeline::stage3|"
+    /// );
+    /// assert_eq!(log(TargetStyle::LastSegment, TargetPadding::Off), "stage3|");
+    /// assert_eq!(log(TargetStyle::Abbreviated(1), TargetPadding::Off), "p::s::i::p::stage3|");
+    /// assert_eq!(log(TargetStyle::Abbreviated(2), TargetPadding::Off), "p::s::i::pipeline::stage3|");
+    /// assert_eq!(log(TargetStyle::Abbreviated(9), TargetPadding::Off), log(TargetStyle::Full, TargetPadding::Off));
+    /// assert_eq!(log(TargetStyle::LastSegment, TargetPadding::Left(8)), "  stage3|");
+    /// ```
+    pub fn set_target_style(&mut self, style: TargetStyle) -> &mut ConfigBuilder {
+        self.0.target_style = style;
+        self
+    }
+
+    /// Sets how the file path of the location is written, in the default layout and the
+    /// `[file]` placeholder (default is `Full`). Backslashes are written as slashes.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let log = |style: LocationStyle, file: &str| {
+    ///     let logger = TestLogger::new(
+    ///         LevelFilter::Info,
+    ///         ConfigBuilder::new()
+    ///             .set_formatter(Some("[file]"))
+    ///             .set_format(FormatFlags::FILE_LOCATION)
+    ///             .set_location_style(style)
+    ///             .build(),
+    ///     );
+    ///     logger.log(&Record::builder().level(Level::Info).file(Some(file)).line(Some(7)).args(format_args!("")).build());
+    ///     logger.take_records().remove(0).formatted
+    /// };
+    /// let path = "/home/me/app/src/db/pool.rs";
+    ///
+    /// assert_eq!(log(LocationStyle::Full, path), "/home/me/app/src/db/pool.rs:7");
+    /// assert_eq!(log(LocationStyle::FileName, path), "pool.rs:7");
+    /// assert_eq!(log(LocationStyle::FileName, "C:\\app\\src\\main.rs"), "main.rs:7");
+    ///
+    /// let strip = |prefix: &str| LocationStyle::StripPrefix(prefix.to_string());
+    /// assert_eq!(log(strip("/home/me/app"), path), "src/db/pool.rs:7");
+    /// assert_eq!(log(strip("/home/me/app/"), path), "src/db/pool.rs:7");
+    /// assert_eq!(log(strip("C:\\app"), "C:\\app\\src\\main.rs"), "src/main.rs:7");
+    /// // Paths outside of the directory are left alone
+    /// assert_eq!(log(strip("/home/me/ap"), path), "/home/me/app/src/db/pool.rs:7");
+    /// assert_eq!(log(strip("/opt"), path), "/home/me/app/src/db/pool.rs:7");
+    /// ```
+    pub fn set_location_style(&mut self, style: LocationStyle) -> &mut ConfigBuilder {
+        self.0.location_style = style;
+        self
+    }
+
     /// Sets the padding for the log level field.
     ///
     /// The `padding` value determines how the level field should be padded when logging. Default is `Off`.
+    /// The level is padded to the length of the longest level text, see
+    /// [`set_level_text`](ConfigBuilder::set_level_text), unless a width is set with
+    /// [`set_level_padding_width`](ConfigBuilder::set_level_padding_width).
     pub fn set_level_padding(&mut self, padding: LevelPadding) -> &mut ConfigBuilder {
         self.0.level_padding = padding;
         self
     }
 
-    /// Sets the padding for the thread field in the log output.
+    /// Sets the width the level is padded to with [`LevelPadding`], in chars.
+    ///
+    /// By default the level is padded to the length of the longest level text. Longer
+    /// texts are not cut.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL)
+    ///         .set_level_padding(LevelPadding::Left)
+    ///         .set_level_padding_width(7)
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("ready")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, " [   INFO] ready");
+    /// ```
+    pub fn set_level_padding_width(&mut self, width: usize) -> &mut ConfigBuilder {
+        self.0.level_padding_width = Some(width);
+        self
+    }
+
+    /// Starts the message of the default layout at `column`, counted in chars from the
+    /// start of the line, by padding the time, level, thread, target and context in front
+    /// of it with spaces. A longer prefix is followed by a single space, as without a column.
+    ///
+    /// Has no effect on the layout of a formatter.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let log = |config: Config| {
+    ///     let logger = TestLogger::new(LevelFilter::Info, config);
+    ///     for target in ["db", "http::server", "http::server::router"] {
+    ///         logger.log(&Record::builder().level(Level::Info).target(target).args(format_args!("ready")).build());
+    ///     }
+    ///     logger.take_records().into_iter().map(|record| record.formatted).collect::<Vec<_>>()
+    /// };
+    /// let mut builder = ConfigBuilder::new();
+    /// builder.set_format(FormatFlags::LEVEL | FormatFlags::TARGET);
+    ///
+    /// assert_eq!(log(builder.build()), [
+    ///     " [INFO] db: ready",
+    ///     " [INFO] http::server: ready",
+    ///     " [INFO] http::server::router: ready",
+    /// ]);
+    /// assert_eq!(log(builder.set_message_column(24).build()), [
+    ///     " [INFO] db:             ready",
+    ///     " [INFO] http::server:   ready",
+    ///     " [INFO] http::server::router: ready",
+    /// ]);
+    /// ```
+    pub fn set_message_column(&mut self, column: usize) -> &mut ConfigBuilder {
+        self.0.message_column = Some(column);
+        self
+    }
+
+    /// Sets how the `TermLogger` wraps long messages of the default layout (default is `Off`).
+    ///
+    /// Messages are wrapped between words, and the wrapped lines are indented to the
+    /// column the message starts at. Words longer than the line are not split, and line
+    /// breaks in the message are kept as they are. ANSI escape sequences in the message
+    /// do not count towards the width.
+    ///
+    /// Wrapping is disabled if stdout or stderr is not a terminal. The streams of
+    /// [`TermLogger::with_streams`](crate::TermLogger::with_streams) are only wrapped at a
+    /// fixed [`WrapMode::Width`]. Wrapping does not affect other loggers or the layout of a
+    /// formatter.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// let config = ConfigBuilder::new().set_wrap(WrapMode::Terminal).build();
+    /// let _ = TermLogger::new(LevelFilter::Info, config, TerminalMode::Mixed, ColorChoice::Auto);
+    /// ```
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone)]
+    /// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+    /// # impl io::Write for Buffer {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let out = Buffer(Arc::new(Mutex::new(Vec::new())));
+    /// let config = ConfigBuilder::new()
+    ///     .set_format(FormatFlags::LEVEL)
+    ///     .set_enable_colors(false)
+    ///     .set_wrap(WrapMode::Width(24))
+    ///     .build();
+    /// let logger = TermLogger::with_streams(
+    ///     LevelFilter::Info,
+    ///     config,
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    ///     Box::new(termcolor2::Ansi::new(io::sink())),
+    /// );
+    /// let message = "one two \x1b[1mthree\x1b[0m four an_overlong_word_that_does_not_fit five";
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+    ///
+    /// let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    /// // The escape sequences around "three" take no room on the first line
+    /// assert_eq!(
+    ///     out,
+    ///     concat!(
+    ///         " [INFO] one two \x1b[1mthree\x1b[0m\n",
+    ///         "        four\n",
+    ///         "        an_overlong_word_that_does_not_fit\n",
+    ///         "        five\n",
+    ///     )
+    /// );
+    /// ```
+    pub fn set_wrap(&mut self, wrap: WrapMode) -> &mut ConfigBuilder {
+        self.0.wrap = wrap;
+        self
+    }
+
+    /// Interprets inline tags in messages, like `<b>bold</b>` or `<red>red</>` (default is false).
+    ///
+    /// The tags are `<b>`, `<i>`, `<u>` and `<dim>`, the color names `<black>`, `<red>`,
+    /// `<green>`, `<yellow>`, `<blue>`, `<magenta>`, `<cyan>` and `<white>`, and `<on-red>`
+    /// and so on for the background. `</>` or a closing tag like `</b>` ends all of them.
+    /// Unknown tags are kept as they are.
+    ///
+    /// The tags are written as colors wherever the styles of the formatter are, that is on
+    /// a terminal with colors, and for other loggers with
+    /// [`set_write_log_enable_colors`](ConfigBuilder::set_write_log_enable_colors).
+    /// Otherwise they are removed.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "no-color"))]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let log = |config: Config, message: &str| {
+    ///     let logger = TestLogger::new(LevelFilter::Info, config);
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+    ///     logger.take_records().remove(0).formatted
+    /// };
+    /// let mut builder = ConfigBuilder::new();
+    /// builder.set_formatter(Some("[message]")).set_inline_tags(true);
+    ///
+    /// let plain = builder.build();
+    /// assert_eq!(log(plain.clone(), "<b>bold</b> and <red>red</>"), "bold and red");
+    /// assert_eq!(log(plain.clone(), "<on-blue><i>both</> <dim>done"), "both done");
+    /// assert_eq!(log(plain.clone(), "a <tag> <b, 1 < 2 </x> <purple></"), "a <tag> <b, 1 < 2 </x> <purple></");
+    /// assert_eq!(log(plain, "<<u>>"), "<>");
+    ///
+    /// let colored = builder.set_write_log_enable_colors(true).build();
+    /// assert_eq!(
+    ///     log(colored.clone(), "<b>bold</b> and <red>red</>"),
+    ///     "\x1b[0m\x1b[1mbold\x1b[0m and \x1b[0m\x1b[31mred\x1b[0m"
+    /// );
+    /// assert_eq!(
+    ///     log(colored, "<b><on-green>go</>"),
+    ///     "\x1b[0m\x1b[1m\x1b[0m\x1b[1m\x1b[42mgo\x1b[0m"
+    /// );
+    /// # }
+    /// ```
+    pub fn set_inline_tags(&mut self, inline_tags: bool) -> &mut ConfigBuilder {
+        self.0.inline_tags = inline_tags;
+        self
+    }
+
+    /// Sets how the lines of messages with line breaks are written (default is `AsIs`).
+    ///
+    /// The lines are split at `\n` and `\r\n`, and written with the line ending of the
+    /// config between them, except with `AsIs`, which leaves the message as it is.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let log = |config: Config, message: &str| {
+    ///     let logger = TestLogger::new(LevelFilter::Info, config);
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+    ///     logger.take_records().remove(0).formatted
+    /// };
+    /// let mut builder = ConfigBuilder::new();
+    /// builder.set_format(FormatFlags::LEVEL);
+    ///
+    /// let message = "failed:\n  disk full\n  retrying";
+    /// assert_eq!(log(builder.build(), message), " [INFO] failed:\n  disk full\n  retrying");
+    /// assert_eq!(
+    ///     log(builder.set_multiline(MultilineMode::Indent).build(), message),
+    ///     " [INFO] failed:\n          disk full\n          retrying"
+    /// );
+    /// assert_eq!(
+    ///     log(builder.set_multiline(MultilineMode::IndentBy(4)).build(), message),
+    ///     " [INFO] failed:\n      disk full\n      retrying"
+    /// );
+    ///
+    /// // The formatter's fields in front of the message count as well
+    /// builder.set_formatter(Some("[level] | [message]"));
+    /// assert_eq!(
+    ///     log(builder.set_multiline(MultilineMode::Indent).build(), "a\r\nb"),
+    ///     "[INFO] | a\n         b"
+    /// );
+    /// assert_eq!(
+    ///     log(builder.set_multiline(MultilineMode::PrefixEach).build(), "a\r\nb"),
+    ///     "[INFO] | a\n[INFO] | b"
+    /// );
+    ///
+    /// // The line ending of the config separates the lines
+    /// builder.set_line_ending(LineEnding::Crlf);
+    /// assert_eq!(
+    ///     log(builder.set_multiline(MultilineMode::IndentBy(2)).build(), "a\nb"),
+    ///     "[INFO] | a\r\n  b"
+    /// );
+    /// ```
+    pub fn set_multiline(&mut self, multiline: MultilineMode) -> &mut ConfigBuilder {
+        self.0.multiline = multiline;
+        self
+    }
+
+    /// Escapes line breaks in messages as `\n` and `\r`, and other control chars except
+    /// tabs like `\u{1b}`, so every record is written on a single line (default is false).
+    ///
+    /// This is useful for log shippers that read a record per line. It applies to all
+    /// loggers, and takes precedence over [`set_multiline`](ConfigBuilder::set_multiline).
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let path = std::env::temp_dir().join(format!("sp_log2-escape-{}.log", std::process::id()));
+    /// let config = ConfigBuilder::new()
+    ///     .set_format(FormatFlags::LEVEL)
+    ///     .set_escape_newlines(true)
+    ///     .build();
+    /// let logger = FileLogger::new(LevelFilter::Info, config, path.to_str().unwrap(), None);
+    /// logger.log(&Record::builder().level(Level::Error).args(format_args!("failed:\n  disk full\r\n  retrying")).build());
+    /// logger.flush();
+    ///
+    /// let contents = std::fs::read_to_string(&path).unwrap();
+    /// assert_eq!(contents.lines().count(), 1);
+    /// assert_eq!(contents, " [ERROR] failed:\\n  disk full\\r\\n  retrying\n");
+    /// # let _ = std::fs::remove_file(&path);
+    /// ```
+    ///
+    /// Other control chars and the Unicode line and paragraph separators are written as
+    /// Rust escapes, tabs are kept:
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let config = ConfigBuilder::new()
+    ///     .set_formatter(Some("[level] [message]"))
+    ///     .set_escape_newlines(true)
+    ///     .build();
+    /// let logger = TestLogger::new(LevelFilter::Info, config);
+    /// let message = "a\nb\rc\td\x1b[1me\x07f\u{2028}g";
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+    ///
+    /// assert_eq!(
+    ///     logger.take_records()[0].formatted,
+    ///     "[INFO] a\\nb\\rc\td\\u{1b}[1me\\u{7}f\\u{2028}g"
+    /// );
+    /// ```
+    pub fn set_escape_newlines(&mut self, escape: bool) -> &mut ConfigBuilder {
+        self.0.escape_newlines = escape;
+        self
+    }
+
+    /// Writes every record as a JSON object on a single line, instead of the layout or
+    /// the formatter (default is false).
+    ///
+    /// The level, its syslog severity and the message are always written. The time,
+    /// thread, target, module and file location are written if enabled with
+    /// [`set_format`](ConfigBuilder::set_format), and the [context](crate::context) as an
+    /// object if `FormatFlags::CONTEXT` is enabled. With the `kv` feature, the key-values
+    /// of the record are written as an object as well. Fields are written in a fixed
+    /// order and are named by [`set_json_field_names`](ConfigBuilder::set_json_field_names).
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL | FormatFlags::TARGET | FormatFlags::CONTEXT)
+    ///         .set_json_output(true)
+    ///         .build(),
+    /// );
+    ///
+    /// context::insert("request_id", 7);
+    /// logger.log(&Record::builder().level(Level::Warn).target("app::db").args(format_args!("slow \"users\" query:\n\t3s")).build());
+    /// # context::clear();
+    /// assert_eq!(
+    ///     logger.take_records()[0].formatted,
+    ///     r#"{"level":"WARN","severity":4,"target":"app::db","message":"slow \"users\" query:\n\t3s","extra":{"request_id":"7"}}"#
+    /// );
+    /// ```
+    pub fn set_json_output(&mut self, json: bool) -> &mut ConfigBuilder {
+        self.0.json_output = json;
+        self
+    }
+
+    /// Sets the names of the fields of records written as JSON, see
+    /// [`set_json_output`](ConfigBuilder::set_json_output).
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL)
+    ///         .set_json_output(true)
+    ///         .set_json_field_names(JsonFieldNames {
+    ///             level: "lvl".to_string(),
+    ///             message: "msg".to_string(),
+    ///             ..JsonFieldNames::default()
+    ///         })
+    ///         .build(),
+    /// );
+    ///
+    /// logger.log(&Record::builder().level(Level::Error).args(format_args!("disk full")).build());
+    /// assert_eq!(
+    ///     logger.take_records()[0].formatted,
+    ///     r#"{"lvl":"ERROR","severity":3,"msg":"disk full"}"#
+    /// );
+    /// ```
+    pub fn set_json_field_names(&mut self, names: JsonFieldNames) -> &mut ConfigBuilder {
+        self.0.json_field_names = names;
+        self
+    }
+
+    /// Sets the banner the `FileLogger` and the `WriteLogger` write at the start of their
+    /// output, and the `FileLogger` again at the start of every new file after rotating
+    /// or reopening it (default is none).
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let config = ConfigBuilder::new()
+    ///     .set_formatter(Some("[level] [message]"))
+    ///     .set_banner(Banner::new().app("my_app", "1.2.0").os())
+    ///     .build();
+    /// let logger = WriteLogger::new(LevelFilter::Warn, config, Vec::new());
+    /// logger.log(&Record::builder().level(Level::Warn).args(format_args!("low disk space")).build());
+    ///
+    /// let output = String::from_utf8(logger.into_inner()).unwrap();
+    /// let os = format!("os: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+    /// assert_eq!(
+    ///     output.lines().collect::<Vec<_>>(),
+    ///     ["[INFO] my_app 1.2.0", &format!("[INFO] {}", os), "[WARN] low disk space"]
+    /// );
+    /// ```
+    pub fn set_banner(&mut self, banner: Banner) -> &mut ConfigBuilder {
+        self.0.banner = Some(banner);
+        self
+    }
+
+    /// Sets the text written for `level`, instead of its name like `WARN`.
+    ///
+    /// The text is used in the default layout and the `[level]` placeholder. With
+    /// [`LevelPadding`], all levels are padded to the length of the longest text.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL)
+    ///         .set_level_text(Level::Warn, "WARNING")
+    ///         .set_level_text(Level::Error, "CRITICAL")
+    ///         .set_level_padding(LevelPadding::Right)
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Warn).args(format_args!("disk full")).build());
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("ready")).build());
+    ///
+    /// let records = logger.take_records();
+    /// assert_eq!(records[0].formatted, " [WARNING ] disk full");
+    /// assert_eq!(records[1].formatted, " [INFO    ] ready");
+    ///
+    /// let mut builder = ConfigBuilder::new();
+    /// for (level, text) in [
+    ///     (Level::Error, "E"),
+    ///     (Level::Warn, "W"),
+    ///     (Level::Info, "I"),
+    ///     (Level::Debug, "D"),
+    ///     (Level::Trace, "T"),
+    /// ] {
+    ///     builder.set_level_text(level, text);
+    /// }
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     builder
+    ///         .set_level_padding(LevelPadding::Left)
+    ///         .set_formatter(Some("[level]|[message]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("ready")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, "[I]|ready");
+    /// ```
+    pub fn set_level_text(&mut self, level: Level, text: &str) -> &mut ConfigBuilder {
+        self.0.level_texts[level as usize - 1] = Some(text.to_string());
+        self
+    }
+
+    /// Sets the letter case of the level, applied to the level names as well as to the
+    /// texts set with [`set_level_text`](ConfigBuilder::set_level_text).
+    ///
+    /// By default the level is written as it is, i.e. the names are uppercase and the
+    /// texts unchanged.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let levels = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+    /// let expected = [
+    ///     (LevelCase::Upper, ["ERROR", "WARN ", "INFO ", "DEBUG", "TRACE"]),
+    ///     (LevelCase::Lower, ["error", "warn ", "info ", "debug", "trace"]),
+    ///     (LevelCase::Capitalized, ["Error", "Warn ", "Info ", "Debug", "Trace"]),
+    /// ];
+    /// for (case, texts) in expected {
+    ///     let logger = TestLogger::new(
+    ///         LevelFilter::Trace,
+    ///         ConfigBuilder::new()
+    ///             .set_level_case(case)
+    ///             .set_level_padding(LevelPadding::Right)
+    ///             .set_formatter(Some("[level]|[message]"))
+    ///             .build(),
+    ///     );
+    ///     for level in levels {
+    ///         logger.log(&Record::builder().level(level).args(format_args!("hi")).build());
+    ///     }
+    ///     for (record, text) in logger.take_records().iter().zip(texts) {
+    ///         assert_eq!(record.formatted, format!("[{}]|hi", text));
+    ///     }
+    /// }
+    ///
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL)
+    ///         .set_level_text(Level::Warn, "WARNING")
+    ///         .set_level_case(LevelCase::Capitalized)
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Warn).args(format_args!("hi")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, " [Warning] hi");
+    /// ```
+    pub fn set_level_case(&mut self, case: LevelCase) -> &mut ConfigBuilder {
+        self.0.level_case = Some(case);
+        self
+    }
+
+    /// Sets the icon of `level`, e.g. a unicode symbol, shown with the `[icon]` placeholder.
+    ///
+    /// Levels have no icon by default. With [`LevelPadding`], icons are padded to the
+    /// number of chars of the longest one. The color of the level, see
+    /// [`set_level_color`](ConfigBuilder::set_level_color), applies to the icon as well.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_level_icon(Level::Error, "✖")
+    ///         .set_level_icon(Level::Warn, "⚠")
+    ///         .set_level_icon(Level::Info, "ℹ")
+    ///         .set_formatter(Some("[icon] [message]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Warn).args(format_args!("disk almost full")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, "⚠ disk almost full");
+    /// ```
+    pub fn set_level_icon(&mut self, level: Level, icon: &str) -> &mut ConfigBuilder {
+        self.0.level_icons[level as usize - 1] = Some(icon.to_string());
+        self
+    }
+
+    /// Shows the icon of the level instead of its text in the default layout (default is false).
+    ///
+    /// Levels without an icon, see [`set_level_icon`](ConfigBuilder::set_level_icon),
+    /// keep their text.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::LEVEL)
+    ///         .set_level_icon(Level::Error, "✖")
+    ///         .set_level_icon(Level::Warn, "⚠️")
+    ///         .set_level_padding(LevelPadding::Right)
+    ///         .set_use_level_icons(true)
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Error).args(format_args!("failed")).build());
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("ready")).build());
+    ///
+    /// let records = logger.take_records();
+    /// // "⚠️" is two chars, a symbol and a variation selector
+    /// assert_eq!(records[0].formatted, " ✖  failed");
+    /// assert_eq!(records[1].formatted, " [INFO ] ready");
+    /// ```
+    pub fn set_use_level_icons(&mut self, use_icons: bool) -> &mut ConfigBuilder {
+        self.0.use_level_icons = use_icons;
+        self
+    }
+
+    /// Sets the padding for the thread field in the log output.
+    ///
+    /// The `padding` value determines how the thread field should be padded.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::Arc;
+    /// let log = |padding: ThreadPadding, name: &str| {
+    ///     let logger: Arc<TestLogger> = TestLogger::new(
+    ///         LevelFilter::Info,
+    ///         ConfigBuilder::new()
+    ///             .set_format(FormatFlags::THREAD)
+    ///             .set_thread_mode(ThreadLogMode::Names)
+    ///             .set_thread_padding(padding)
+    ///             .set_formatter(Some("[thread]|"))
+    ///             .build(),
+    ///     )
+    ///     .into();
+    ///     let thread_logger = logger.clone();
+    ///     std::thread::Builder::new()
+    ///         .name(name.to_string())
+    ///         .spawn(move || {
+    ///             thread_logger.log(&Record::builder().level(Level::Info).args(format_args!("")).build());
+    ///         })
+    ///         .unwrap()
+    ///         .join()
+    ///         .unwrap();
+    ///     logger.take_records().remove(0).formatted
+    /// };
+    ///
+    /// assert_eq!(log(ThreadPadding::Left(8), "main-io"), " main-io|");
+    /// assert_eq!(log(ThreadPadding::Exact(8), "main-io"), "main-io |");
+    /// assert_eq!(log(ThreadPadding::Exact(8), "worker-3"), "worker-3|");
+    /// assert_eq!(log(ThreadPadding::Exact(8), "tokio-runtime-worker"), "tokio-r…|");
+    /// assert_eq!(log(ThreadPadding::Exact(4), "stößel"), "stö…|");
+    /// ```
+    pub fn set_thread_padding(&mut self, padding: ThreadPadding) -> &mut ConfigBuilder {
+        self.0.thread_padding = padding;
+        self
+    }
+
+    /// Sets the mode for logging thread information.
+    ///
+    /// The `mode` value determines how the thread field is logged.
+    pub fn set_thread_mode(&mut self, mode: ThreadLogMode) -> &mut ConfigBuilder {
+        self.0.thread_log_mode = mode;
+        self
+    }
+
+    /// Sets the separator between the thread name and id in [`ThreadLogMode::NameAndId`].
+    /// Default is `/`.
+    ///
+    /// The name and id are also available on their own, as the `[thread_name]` and
+    /// `[thread_id]` placeholders of the [formatter](ConfigBuilder::set_formatter).
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::Arc;
+    /// let logger: Arc<TestLogger> = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_format(FormatFlags::THREAD)
+    ///         .set_thread_mode(ThreadLogMode::NameAndId)
+    ///         .set_thread_separator(" #")
+    ///         .set_thread_padding(ThreadPadding::Right(12))
+    ///         .set_formatter(Some("[thread]|[thread_name]|[thread_id] [message]"))
+    ///         .build(),
+    /// )
+    /// .into();
+    ///
+    /// let log = |logger: Arc<TestLogger>, name: Option<&str>| {
+    ///     let builder = match name {
+    ///         Some(name) => std::thread::Builder::new().name(name.to_string()),
+    ///         None => std::thread::Builder::new(),
+    ///     };
+    ///     builder
+    ///         .spawn(move || {
+    ///             logger.log(&Record::builder().level(Level::Info).args(format_args!("waiting")).build());
+    ///             format!("{:?}", std::thread::current().id())
+    ///         })
+    ///         .unwrap()
+    ///         .join()
+    ///         .unwrap()
+    /// };
+    /// let named = log(logger.clone(), Some("worker-3"));
+    /// let unnamed = log(logger.clone(), None);
+    ///
+    /// let id = |debug: String| debug.trim_start_matches("ThreadId(").trim_end_matches(')').to_string();
+    /// let (named, unnamed) = (id(named), id(unnamed));
+    /// let records = logger.take_records();
+    /// assert_eq!(
+    ///     records[0].formatted,
+    ///     format!("{:<12}|worker-3    |{:<12} waiting", format!("worker-3 #{}", named), named)
+    /// );
+    /// assert_eq!(records[1].formatted, format!("{:<12}||{:<12} waiting", unnamed, unnamed));
+    /// ```
+    pub fn set_thread_separator(&mut self, separator: &str) -> &mut ConfigBuilder {
+        self.0.thread_separator = Cow::Owned(separator.to_string());
+        self
+    }
+
+    /// Sets the color used for logging the log level.
+    ///
+    /// If `color` is `None`, the default foreground color is used.
+    /// This is useful when customizing the log output appearance based on log levels.
+    /// Only the foreground color of the level style is changed, see
+    /// [`set_level_style`](ConfigBuilder::set_level_style).
+    pub fn set_level_color(&mut self, level: Level, color: Option<Color>) -> &mut ConfigBuilder {
+        self.0.level_style[level as usize].fg = color;
+        self
+    }
+
+    /// Sets the color used for logging the log level from a string, e.g. `red`, `#89dceb`,
+    /// `rgb(137 180 250)`, or `ansi(137)`, `@137` or `137` for an ANSI 256 color.
+    ///
+    /// The strings are the same as the colors of the formatter's placeholders, so a `bg`
+    /// prefix, e.g. `bgred`, sets the background color instead. Fails if `color` is not
+    /// a valid color.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// fn formatter_accepts(color: &str) -> bool {
+    ///     let formatter = format!("[level:{}] [message]", color);
+    ///     ConfigBuilder::new().set_formatter(Some(&formatter)).build().validate_formatter().is_ok()
+    /// }
+    ///
+    /// let valid = [
+    ///     "red",
+    ///     "Magenta",
+    ///     "#89dceb",
+    ///     "#fff",
+    ///     "rgb(137 180 250)",
+    ///     "rgb(137,180,250)",
+    ///     "rgb(50% 0 100%)",
+    ///     "137",
+    ///     "0x89",
+    ///     "ansi(137)",
+    ///     "@137",
+    ///     "bgansi(17)",
+    ///     "bgred",
+    ///     "bg#89dceb",
+    /// ];
+    /// for color in valid {
+    ///     assert!(ConfigBuilder::new().set_level_color_str(Level::Error, color).is_ok(), "{}", color);
+    ///     assert!(formatter_accepts(color), "{}", color);
+    /// }
+    ///
+    /// let invalid = [
+    ///     "redd",
+    ///     "#89dce",
+    ///     "#ggg",
+    ///     "rgb(137 180)",
+    ///     "rgb(137 180 256)",
+    ///     "256",
+    ///     "ansi(256)",
+    ///     "@",
+    ///     "bg",
+    /// ];
+    /// for color in invalid {
+    ///     assert!(ConfigBuilder::new().set_level_color_str(Level::Error, color).is_err(), "{}", color);
+    ///     assert!(!formatter_accepts(color), "{}", color);
+    /// }
+    /// ```
+    pub fn set_level_color_str(
+        &mut self,
+        level: Level,
+        color: &str,
+    ) -> Result<&mut ConfigBuilder, ColorParseError> {
+        let (color, is_fg) = apply_style(color)?;
+        let style = &mut self.0.level_style[level as usize];
+        if is_fg {
+            style.fg = Some(color);
+        } else {
+            style.bg = Some(color);
+        }
+        Ok(self)
+    }
+
+    /// Sets the style used for logging the log level on the terminal, i.e. its colors
+    /// and effects like bold.
+    ///
+    /// The style applies to the level in the default layout and to the `[level]` and
+    /// `[icon]` placeholders of the formatter, where the styles of the placeholder
+    /// take precedence.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// let config = ConfigBuilder::new()
+    ///     .set_level_style(Level::Error, LevelStyle::new().fg(Color::White).bg(Color::Red).bold())
+    ///     .set_level_style(Level::Trace, LevelStyle::new().dimmed())
+    ///     .build();
+    /// let _ = TermLogger::new(LevelFilter::Trace, config, TerminalMode::Mixed, ColorChoice::Auto);
+    /// ```
+    ///
+    /// The escape sequences written for the default layout and the `[level]` placeholder:
+    /// ```rust
+    /// # #[cfg(not(feature = "no-color"))]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone)]
+    /// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+    /// # impl io::Write for Buffer {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let log = |builder: &mut ConfigBuilder| {
+    ///     let out = Buffer(Arc::new(Mutex::new(Vec::new())));
+    ///     let config = builder
+    ///         .set_level_style(Level::Error, LevelStyle::new().fg(Color::White).bg(Color::Red).bold())
+    ///         .set_level_style(Level::Trace, LevelStyle::new().dimmed())
+    ///         .build();
+    ///     let logger = TermLogger::with_streams(
+    ///         LevelFilter::Trace,
+    ///         config,
+    ///         Box::new(termcolor2::Ansi::new(out.clone())),
+    ///         Box::new(termcolor2::Ansi::new(out.clone())),
+    ///     );
+    ///     logger.log(&Record::builder().level(Level::Error).args(format_args!("failed")).build());
+    ///     logger.log(&Record::builder().level(Level::Trace).args(format_args!("details")).build());
+    ///     let bytes = out.0.lock().unwrap().clone();
+    ///     String::from_utf8(bytes).unwrap()
+    /// };
+    ///
+    /// // Bold, white on red for errors, and dimmed for trace records
+    /// let error = "\x1b[0m\x1b[1m\x1b[37m\x1b[41m[ERROR]\x1b[0m";
+    /// let trace = "\x1b[0m\x1b[2m[TRACE]\x1b[0m";
+    /// for out in [
+    ///     log(ConfigBuilder::new().set_format(FormatFlags::LEVEL)),
+    ///     log(ConfigBuilder::new().set_formatter(Some("[level] [message]"))),
+    /// ] {
+    ///     assert!(out.contains(&format!("{} failed", error)), "{:?}", out);
+    ///     assert!(out.contains(&format!("{} details", trace)), "{:?}", out);
+    /// }
+    /// # }
+    /// ```
+    pub fn set_level_style(&mut self, level: Level, style: LevelStyle) -> &mut ConfigBuilder {
+        self.0.level_style[level as usize] = style;
+        self
+    }
+
+    /// Sets the time format to a custom representation.
+    ///
+    /// *Note*: The default time format is `%H:%M:%S`.
+    ///
+    /// The syntax for the format can be found in the
+    /// [`strftime` crate book](https://docs.rs/chrono/latest/chrono/format/strftime/index.html).
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// use sp_log2::ConfigBuilder;
+    /// let config = ConfigBuilder::new()
+    ///     .set_time_format_custom("%Y-%m-%d %H:%M:%S")
+    ///     .build();
+    /// ```
+    ///
+    /// The format is copied into the `Config`, so configs can be rebuilt as often as needed,
+    /// e.g. when reloading settings, without using up memory:
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// for _ in 0..1_000 {
+    ///     let format = String::from("%Y");
+    ///     let logger = TestLogger::new(
+    ///         LevelFilter::Info,
+    ///         ConfigBuilder::new()
+    ///             .set_time_format_custom(&format)
+    ///             .set_formatter(Some("[time]"))
+    ///             .build(),
+    ///     );
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///     assert_eq!(logger.take_records()[0].formatted.trim_end().len(), 4);
+    /// }
+    /// ```
+    pub fn set_time_format_custom(&mut self, time_format: &str) -> &mut ConfigBuilder {
+        self.0.time_format = TimeFormat::Custom(Cow::Owned(time_format.to_string()));
+        self
+    }
+
+    /// Sets the time format to a custom representation given as a static string,
+    /// which is borrowed instead of copied.
+    ///
+    /// See [`ConfigBuilder::set_time_format_custom`] for the syntax.
+    pub fn set_time_format_custom_str(&mut self, time_format: &'static str) -> &mut ConfigBuilder {
+        self.0.time_format = TimeFormat::Custom(Cow::Borrowed(time_format));
+        self
+    }
+
+    /// Sets the time format to RFC 2822.
+    ///
+    /// This format is typically used for email headers and specifies a standard date-time representation.
+    pub fn set_time_format_rfc2822(&mut self) -> &mut ConfigBuilder {
+        self.0.time_format = TimeFormat::Rfc2822;
+        self
+    }
+
+    /// Sets the time format to RFC 3339.
+    ///
+    /// This format is a common representation for timestamps in log entries.
+    pub fn set_time_format_rfc3339(&mut self) -> &mut ConfigBuilder {
+        self.0.time_format = TimeFormat::Rfc3339;
+        self
+    }
+
+    /// Sets the time format to RFC 3339 with a fixed number of digits for the fraction
+    /// of the second, so timestamps have the same width on every line.
+    ///
+    /// `SecondsFormat::AutoSi` picks the shortest of seconds, millis, micros and nanos
+    /// that shows the time exactly, like [`ConfigBuilder::set_time_format_rfc3339`].
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let time = |precision: SecondsFormat| {
+    ///     let logger = TestLogger::new(
+    ///         LevelFilter::Info,
+    ///         ConfigBuilder::new()
+    ///             .set_time_format_rfc3339_with_precision(precision)
+    ///             .set_time_offset(UtcOffset::east_opt(2 * 3600).unwrap())
+    ///             .set_formatter(Some("[time]"))
+    ///             .build(),
+    ///     );
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///     logger.take_records().remove(0).formatted
+    /// };
+    ///
+    /// // e.g. 2024-05-01T14:03:07+02:00
+    /// let secs = time(SecondsFormat::Secs);
+    /// assert_eq!(secs.len(), 25);
+    /// assert_eq!(&secs[10..11], "T");
+    /// assert!(secs.ends_with("+02:00"));
+    ///
+    /// // e.g. 2024-05-01T14:03:07.123+02:00
+    /// let millis = time(SecondsFormat::Millis);
+    /// assert_eq!(millis.len(), 29);
+    /// assert_eq!(&millis[19..20], ".");
+    /// assert!(millis[20..23].bytes().all(|b| b.is_ascii_digit()));
+    ///
+    /// // e.g. 2024-05-01T14:03:07.123456+02:00
+    /// let micros = time(SecondsFormat::Micros);
+    /// assert_eq!(micros.len(), 32);
+    /// assert!(micros[20..26].bytes().all(|b| b.is_ascii_digit()));
+    /// ```
+    pub fn set_time_format_rfc3339_with_precision(
+        &mut self,
+        precision: SecondsFormat,
+    ) -> &mut ConfigBuilder {
+        self.set_time_format_rfc3339_opts(precision, false)
+    }
+
+    /// Same as [`ConfigBuilder::set_time_format_rfc3339_with_precision`], but if `use_z`
+    /// is true, a zero offset is written as `Z` instead of `+00:00`.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_rfc3339_opts(SecondsFormat::Millis, true)
+    ///         .set_time_offset_utc()
+    ///         .set_formatter(Some("[time]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///
+    /// // e.g. 2024-05-01T12:03:07.123Z
+    /// let time = logger.take_records().remove(0).formatted;
+    /// assert_eq!(time.len(), 24);
+    /// assert!(time.ends_with('Z'));
+    /// ```
+    pub fn set_time_format_rfc3339_opts(
+        &mut self,
+        precision: SecondsFormat,
+        use_z: bool,
+    ) -> &mut ConfigBuilder {
+        self.0.time_format = TimeFormat::Rfc3339Opts(precision, use_z);
+        self
+    }
+
+    /// Sets the time format to the seconds since the Unix epoch, e.g. `1700000000`.
+    ///
+    /// Epoch times do not depend on the time zone, so the offset settings have no effect.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_unix()
+    ///         .set_formatter(Some("[time]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///
+    /// let seconds: i64 = logger.take_records()[0].formatted.trim_end().parse().unwrap();
+    /// assert!(seconds > 1_600_000_000);
+    /// ```
+    pub fn set_time_format_unix(&mut self) -> &mut ConfigBuilder {
+        self.0.time_format = TimeFormat::UnixSeconds;
+        self
+    }
+
+    /// Sets the time format to the milliseconds since the Unix epoch, e.g. `1700000000123`.
     ///
-    /// The `padding` value determines how the thread field should be padded.
-    pub fn set_thread_padding(&mut self, padding: ThreadPadding) -> &mut ConfigBuilder {
-        self.0.thread_padding = padding;
+    /// Epoch times do not depend on the time zone, so the offset settings have no effect.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_unix_millis()
+    ///         .set_formatter(Some("[time]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///
+    /// let millis: i64 = logger.take_records()[0].formatted.trim_end().parse().unwrap();
+    /// assert!(millis > 1_600_000_000_000);
+    /// ```
+    pub fn set_time_format_unix_millis(&mut self) -> &mut ConfigBuilder {
+        self.0.time_format = TimeFormat::UnixMillis;
         self
     }
 
-    /// Sets the mode for logging thread information.
+    /// Renders the time in UTC instead of the local time zone.
     ///
-    /// The `mode` value determines how the thread field is logged.
-    pub fn set_thread_mode(&mut self, mode: ThreadLogMode) -> &mut ConfigBuilder {
-        self.0.thread_log_mode = mode;
+    /// RFC 2822 and RFC 3339 times carry the matching `+0000` or `+00:00` suffix.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::Arc;
+    /// let fixed = DateTime::parse_from_rfc3339("2024-05-01T14:03:07+02:00").unwrap();
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_rfc3339()
+    ///         .set_time_offset_utc()
+    ///         .set_clock(Arc::new(move || fixed))
+    ///         .set_formatter(Some("[time]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///
+    /// assert_eq!(logger.take_records()[0].formatted, "2024-05-01T12:03:07+00:00");
+    /// ```
+    pub fn set_time_offset_utc(&mut self) -> &mut ConfigBuilder {
+        self.0.time_offset = Some(UtcOffset::east_opt(0).unwrap());
         self
     }
 
-    /// Sets the color used for logging the log level.
+    /// Renders the time at a fixed offset from UTC instead of the local time zone.
     ///
-    /// If `color` is `None`, the default foreground color is used.
-    /// This is useful when customizing the log output appearance based on log levels.
-    pub fn set_level_color(&mut self, level: Level, color: Option<Color>) -> &mut ConfigBuilder {
-        self.0.level_color[level as usize] = color;
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::Arc;
+    /// let fixed = DateTime::parse_from_rfc3339("2024-05-01T12:03:07Z").unwrap();
+    /// let india = UtcOffset::east_opt(5 * 3600 + 30 * 60).unwrap();
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_rfc2822()
+    ///         .set_time_offset(india)
+    ///         .set_clock(Arc::new(move || fixed))
+    ///         .set_formatter(Some("[time]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///
+    /// assert_eq!(logger.take_records()[0].formatted, "Wed, 1 May 2024 17:33:07 +0530");
+    /// ```
+    pub fn set_time_offset(&mut self, offset: UtcOffset) -> &mut ConfigBuilder {
+        self.0.time_offset = Some(offset);
         self
     }
 
-    /// Sets the time format to a custom representation.
-    ///
-    /// *Note*: The default time format is `%H:%M:%S`.
+    /// Renders the time in the local time zone again. This is the default.
+    pub fn set_time_offset_to_local(&mut self) -> &mut ConfigBuilder {
+        self.0.time_offset = None;
+        self
+    }
+
+    /// Appends the UTC offset, e.g. `+02:00`, to the time (default is false).
     ///
-    /// The syntax for the format can be found in the
-    /// [`strftime` crate book](https://docs.rs/chrono/latest/chrono/format/strftime/index.html).
+    /// This works with any custom format, where `%Z` is not reliable for the local time zone.
+    /// RFC 2822 and RFC 3339 times already contain the offset and are left unchanged, as are
+    /// epoch times.
     ///
     /// # Usage
     ///
     /// ```rust
-    /// use sp_log2::ConfigBuilder;
-    /// let config = ConfigBuilder::new()
-    ///     .set_time_format_custom("%Y-%m-%d %H:%M:%S")
-    ///     .build();
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::Arc;
+    /// let fixed = DateTime::parse_from_rfc3339("2024-05-01T12:03:07Z").unwrap();
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_custom("%H:%M:%S")
+    ///         .set_time_offset(UtcOffset::west_opt(3 * 3600).unwrap())
+    ///         .set_time_include_offset(true)
+    ///         .set_clock(Arc::new(move || fixed))
+    ///         .set_formatter(Some("[time]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    /// assert_eq!(logger.take_records()[0].formatted, "09:03:07-03:00");
+    ///
+    /// // No second suffix for RFC 3339
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_rfc3339()
+    ///         .set_time_offset_utc()
+    ///         .set_time_include_offset(true)
+    ///         .set_formatter(Some("[time]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    /// let time = logger.take_records().remove(0).formatted;
+    /// assert!(time.trim_end().ends_with("+00:00"));
+    /// assert_eq!(time.matches("+00:00").count(), 1);
     /// ```
-    pub fn set_time_format_custom(&mut self, time_format: &str) -> &mut ConfigBuilder {
-        self.0.time_format =
-            TimeFormat::Custom(Box::leak(time_format.to_string().into_boxed_str()));
+    pub fn set_time_include_offset(&mut self, include: bool) -> &mut ConfigBuilder {
+        self.0.time_include_offset = include;
         self
     }
 
-    /// Sets the time format to RFC 2822.
+    /// Takes the time of the records from `clock` instead of the system clock, e.g. a
+    /// fixed time to compare the rendered lines of a test exactly.
     ///
-    /// This format is typically used for email headers and specifies a standard date-time representation.
-    pub fn set_time_format_rfc2822(&mut self) -> &mut ConfigBuilder {
-        self.0.time_format = TimeFormat::Rfc2822;
+    /// The offset of the returned time is used unless [`ConfigBuilder::set_time_offset`]
+    /// or [`ConfigBuilder::set_time_offset_utc`] sets another one. Loggers writing on
+    /// another thread, like the `AsyncLogger`, call the clock on that thread. The uptime
+    /// and `[delta]` still use the system clock.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::Arc;
+    /// let fixed = DateTime::parse_from_rfc3339("2024-05-01T14:03:07.123+02:00").unwrap();
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_time_format_custom("%Y-%m-%d %H:%M:%S%.3f")
+    ///         .set_clock(Arc::new(move || fixed))
+    ///         .set_formatter(Some("[time] [level] [message]"))
+    ///         .build(),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    ///
+    /// assert_eq!(logger.take_records()[0].formatted, "2024-05-01 14:03:07.123 [INFO] hello");
+    /// ```
+    pub fn set_clock(&mut self, clock: Clock) -> &mut ConfigBuilder {
+        self.0.clock = Some(ConfigClock(clock));
         self
     }
 
-    /// Sets the time format to RFC 3339.
-    ///
-    /// This format is a common representation for timestamps in log entries.
-    pub fn set_time_format_rfc3339(&mut self) -> &mut ConfigBuilder {
-        self.0.time_format = TimeFormat::Rfc3339;
+    /// Takes the time of the records from the system clock again. This is the default.
+    pub fn set_clock_to_system(&mut self) -> &mut ConfigBuilder {
+        self.0.clock = None;
         self
     }
 
@@ -294,7 +3198,7 @@ impl ConfigBuilder {
     /// For example, `add_filter_allow_str("tokio::uds")` would allow only logging from the `tokio` crates `uds` module.
     pub fn add_filter_allow_str(&mut self, filter_allow: &'static str) -> &mut ConfigBuilder {
         let mut list = Vec::from(&*self.0.filter_allow);
-        list.push(Cow::Borrowed(filter_allow));
+        list.push(FilterPattern::Prefix(Cow::Borrowed(filter_allow)));
         self.0.filter_allow = Cow::Owned(list);
         self
     }
@@ -305,16 +3209,126 @@ impl ConfigBuilder {
     /// For example, `add_filter_allow(format!("{}::{}","tokio", "uds"))` will allow logs only from the `tokio::uds` target.
     pub fn add_filter_allow(&mut self, filter_allow: String) -> &mut ConfigBuilder {
         let mut list = Vec::from(&*self.0.filter_allow);
-        list.push(Cow::Owned(filter_allow));
+        list.push(FilterPattern::Prefix(Cow::Owned(filter_allow)));
+        self.0.filter_allow = Cow::Owned(list);
+        self
+    }
+
+    /// Adds an allowed target filter using the given matching mode.
+    ///
+    /// For example, `add_filter_allow_matching(FilterPattern::Exact("myapp".into()))` allows
+    /// records from the `myapp` target, but not from its submodules.
+    pub fn add_filter_allow_matching(&mut self, filter_allow: FilterPattern) -> &mut ConfigBuilder {
+        let mut list = Vec::from(&*self.0.filter_allow);
+        list.push(filter_allow);
         self.0.filter_allow = Cow::Owned(list);
         self
     }
 
+    /// Adds an allowed target filter with its own level, used in place of the logger's
+    /// level for targets starting with `filter_allow`.
+    ///
+    /// The most specific (longest) matching entry wins, so an entry can raise the verbosity
+    /// of a module above the logger's level, or lower it. Targets allowed by the other
+    /// `add_filter_allow` methods keep the logger's level. Entries take part in the same
+    /// lookup as [level directives](ConfigBuilder::add_level_directive), and win over a
+    /// directive for the same prefix.
+    ///
+    /// The most verbose level of the entries is included in `log::set_max_level` when the
+    /// logger is installed, so the `log` macros pass these records on to the logger. The
+    /// logger then drops the records of other targets at that level, after the macros
+    /// already built them.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use std::sync::{Arc, Mutex};
+    /// let output = Arc::new(Mutex::new(Vec::new()));
+    /// let config = ConfigBuilder::new()
+    ///     .set_formatter(Some("[level] [target] [message]"))
+    ///     .add_filter_allow_str("myapp")
+    ///     .add_filter_allow_with_level("myapp::db", LevelFilter::Trace)
+    ///     .build();
+    /// let _ = CombinedLogger::init(vec![WriteLogger::new_shared(LevelFilter::Info, config, output.clone())]);
+    /// assert_eq!(log::max_level(), LevelFilter::Trace);
+    ///
+    /// log::trace!(target: "myapp::db", "BEGIN");
+    /// log::debug!(target: "myapp::http", "GET /");
+    /// log::info!(target: "myapp::http", "listening");
+    /// log::info!(target: "hyper", "connected");
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(output.lock().unwrap().clone()).unwrap(),
+    ///     "[TRACE] myapp::db BEGIN\n[INFO] myapp::http listening\n"
+    /// );
+    /// ```
+    pub fn add_filter_allow_with_level(
+        &mut self,
+        filter_allow: &str,
+        level: LevelFilter,
+    ) -> &mut ConfigBuilder {
+        let mut list = Vec::from(&*self.0.filter_allow);
+        list.push(FilterPattern::Prefix(Cow::Owned(filter_allow.to_string())));
+        self.0.filter_allow = Cow::Owned(list);
+
+        let levels = &mut self.0.filter_allow_levels;
+        levels.retain(|(prefix, _)| prefix != filter_allow);
+        levels.push((Cow::Owned(filter_allow.to_string()), level));
+        // Keep the longest prefixes first, so the first match is the most specific one
+        levels.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    /// Adds an allowed target filter matching the target against a regular expression.
+    ///
+    /// Records pass the allow list if their target matches any of the prefix or regex allow filters.
+    /// The pattern is compiled immediately, an invalid pattern is returned as an error.
+    ///
+    /// Requires the `regex` feature. The syntax is that of the
+    /// [`regex`](https://docs.rs/regex) crate, which matches in time linear to the length
+    /// of the target.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_formatter(Some("[target]: [message]"))
+    ///         .add_filter_allow_regex("^myapp(::|$)")
+    ///         .unwrap()
+    ///         .add_filter_ignore_regex("^myapp::http::access$")
+    ///         .unwrap()
+    ///         .build(),
+    /// );
+    /// for target in ["myapp", "myapp::db", "myapp::http::access", "myapplication", "hyper"] {
+    ///     logger.log(&Record::builder().level(Level::Info).target(target).args(format_args!("hi")).build());
+    /// }
+    /// let lines: Vec<String> = logger.take_records().into_iter().map(|r| r.formatted).collect();
+    /// assert_eq!(lines, ["myapp: hi", "myapp::db: hi"]);
+    ///
+    /// assert!(ConfigBuilder::new().add_filter_allow_regex("myapp(").is_err());
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn add_filter_allow_regex(
+        &mut self,
+        filter_allow: &str,
+    ) -> Result<&mut ConfigBuilder, RegexError> {
+        self.0.filter_allow_regex.push(Regex::new(filter_allow)?);
+        Ok(self)
+    }
+
     /// Clears all allowed target filters.
     ///
     /// This removes any previously set filters and allows logs from all targets.
     pub fn clear_filter_allow(&mut self) -> &mut ConfigBuilder {
         self.0.filter_allow = Cow::Borrowed(&[]);
+        self.0.filter_allow_levels.clear();
+        #[cfg(feature = "regex")]
+        self.0.filter_allow_regex.clear();
         self
     }
 
@@ -322,9 +3336,39 @@ impl ConfigBuilder {
     /// If any are specified, records from targets matching one of these entries will be ignored
     ///
     /// For example, `add_filter_ignore_str("tokio::uds")` would deny logging from the `tokio` crates `uds` module.
+    ///
+    /// Ignored targets are also reported as disabled by `Log::enabled`, so code guarded by
+    /// `log_enabled!` does not compute values that would be discarded.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// use log::{debug, log_enabled};
+    ///
+    /// WriteLogger::init(
+    ///     LevelFilter::Debug,
+    ///     ConfigBuilder::new().add_filter_ignore_str("myapp::cache").build(),
+    ///     std::io::sink(),
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut computed = Vec::new();
+    /// let mut expensive = |target: &'static str| {
+    ///     computed.push(target);
+    ///     "cache statistics"
+    /// };
+    /// if log_enabled!(target: "myapp::cache", Level::Debug) {
+    ///     debug!(target: "myapp::cache", "{}", expensive("myapp::cache"));
+    /// }
+    /// if log_enabled!(target: "myapp::db", Level::Debug) {
+    ///     debug!(target: "myapp::db", "{}", expensive("myapp::db"));
+    /// }
+    /// assert_eq!(computed, ["myapp::db"]);
+    /// ```
     pub fn add_filter_ignore_str(&mut self, filter_ignore: &'static str) -> &mut ConfigBuilder {
         let mut list = Vec::from(&*self.0.filter_ignore);
-        list.push(Cow::Borrowed(filter_ignore));
+        list.push(FilterPattern::Prefix(Cow::Borrowed(filter_ignore)));
         self.0.filter_ignore = Cow::Owned(list);
         self
     }
@@ -335,25 +3379,602 @@ impl ConfigBuilder {
     /// For example, `add_filter_ignore(format!("{}::{}","tokio", "uds"))` would deny logging from the `tokio` crates `uds` module.
     pub fn add_filter_ignore(&mut self, filter_ignore: String) -> &mut ConfigBuilder {
         let mut list = Vec::from(&*self.0.filter_ignore);
-        list.push(Cow::Owned(filter_ignore));
+        list.push(FilterPattern::Prefix(Cow::Owned(filter_ignore)));
+        self.0.filter_ignore = Cow::Owned(list);
+        self
+    }
+
+    /// Adds a denied target filter using the given matching mode.
+    ///
+    /// For example, `add_filter_ignore_matching(FilterPattern::Glob("hyper::**".into()))` ignores
+    /// the `hyper` crate's modules without also ignoring a crate called `hyperlocal`.
+    pub fn add_filter_ignore_matching(
+        &mut self,
+        filter_ignore: FilterPattern,
+    ) -> &mut ConfigBuilder {
+        let mut list = Vec::from(&*self.0.filter_ignore);
+        list.push(filter_ignore);
         self.0.filter_ignore = Cow::Owned(list);
         self
     }
 
+    /// Adds a denied target filter matching the target against a regular expression.
+    ///
+    /// Records whose target matches the pattern anywhere are ignored.
+    /// The pattern is compiled immediately, an invalid pattern is returned as an error.
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn add_filter_ignore_regex(
+        &mut self,
+        filter_ignore: &str,
+    ) -> Result<&mut ConfigBuilder, RegexError> {
+        self.0.filter_ignore_regex.push(Regex::new(filter_ignore)?);
+        Ok(self)
+    }
+
     /// Clears all denied target filters.
     ///
     /// This removes any previously set filters and does not filter out any targets.
     pub fn clear_filter_ignore(&mut self) -> &mut ConfigBuilder {
         self.0.filter_ignore = Cow::Borrowed(&[]);
+        #[cfg(feature = "regex")]
+        self.0.filter_ignore_regex.clear();
+        self
+    }
+
+    /// Sets what the allowed and denied target filters are matched against (default is
+    /// `TargetOnly`).
+    ///
+    /// Libraries sometimes log with targets of their own, like `sql_query`, which the
+    /// module path filters can still catch.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let logged = |filter_match: FilterMatch, builder: &mut ConfigBuilder| {
+    ///     let logger = TestLogger::new(LevelFilter::Info, builder.set_filter_match(filter_match).build());
+    ///     logger.log(
+    ///         &Record::builder()
+    ///             .level(Level::Info)
+    ///             .target("sql_query")
+    ///             .module_path(Some("sqlx::query"))
+    ///             .args(format_args!("SELECT 1"))
+    ///             .build(),
+    ///     );
+    ///     !logger.take_records().is_empty()
+    /// };
+    ///
+    /// let mut ignore = ConfigBuilder::new();
+    /// ignore.add_filter_ignore_str("sqlx");
+    /// assert!(logged(FilterMatch::TargetOnly, &mut ignore));
+    /// assert!(!logged(FilterMatch::ModuleOnly, &mut ignore));
+    /// assert!(!logged(FilterMatch::Either, &mut ignore));
+    ///
+    /// let mut allow = ConfigBuilder::new();
+    /// allow.add_filter_allow_str("sqlx");
+    /// assert!(!logged(FilterMatch::TargetOnly, &mut allow));
+    /// assert!(logged(FilterMatch::ModuleOnly, &mut allow));
+    /// assert!(logged(FilterMatch::Either, &mut allow));
+    ///
+    /// let mut allow_target = ConfigBuilder::new();
+    /// allow_target.add_filter_allow_str("sql_query");
+    /// assert!(logged(FilterMatch::TargetOnly, &mut allow_target));
+    /// assert!(!logged(FilterMatch::ModuleOnly, &mut allow_target));
+    /// assert!(logged(FilterMatch::Either, &mut allow_target));
+    /// ```
+    pub fn set_filter_match(&mut self, filter_match: FilterMatch) -> &mut ConfigBuilder {
+        self.0.filter_match = filter_match;
+        self
+    }
+
+    /// Add allowed message filters.
+    /// If any are specified, only records whose message contains one of these entries will be printed
+    ///
+    /// Message filters are checked after the target filters. The message is only rendered
+    /// for this check if any message filter is set.
+    pub fn add_filter_allow_message(&mut self, filter_allow: &str) -> &mut ConfigBuilder {
+        self.0.filter_allow_message.push(filter_allow.to_string());
+        self
+    }
+
+    /// Adds an allowed message filter matching the message against a regular expression.
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn add_filter_allow_message_regex(
+        &mut self,
+        filter_allow: &str,
+    ) -> Result<&mut ConfigBuilder, RegexError> {
+        self.0
+            .filter_allow_message_regex
+            .push(Regex::new(filter_allow)?);
+        Ok(self)
+    }
+
+    /// Clears all allowed message filters.
+    pub fn clear_filter_allow_message(&mut self) -> &mut ConfigBuilder {
+        self.0.filter_allow_message.clear();
+        #[cfg(feature = "regex")]
+        self.0.filter_allow_message_regex.clear();
+        self
+    }
+
+    /// Add denied message filters.
+    /// If any are specified, records whose message contains one of these entries will be ignored
+    ///
+    /// For example, `add_filter_ignore_message("connection pool idle timeout")` drops that line
+    /// while keeping other records from the same target.
+    pub fn add_filter_ignore_message(&mut self, filter_ignore: &str) -> &mut ConfigBuilder {
+        self.0.filter_ignore_message.push(filter_ignore.to_string());
+        self
+    }
+
+    /// Adds a denied message filter matching the message against a regular expression.
+    ///
+    /// Requires the `regex` feature. Matching takes time linear to the length of the
+    /// message, whatever the pattern, so long or hostile messages cannot stall the logger.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::time::{Duration, Instant};
+    /// let logger = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new()
+    ///         .set_formatter(Some("[message]"))
+    ///         .add_filter_ignore_message_regex("x.*y")
+    ///         .unwrap()
+    ///         .add_filter_ignore_message_regex("(a*)*b")
+    ///         .unwrap()
+    ///         .build(),
+    /// );
+    ///
+    /// let start = Instant::now();
+    /// let long = format!("x{}y", "-".repeat(200_000));
+    /// let hostile = "a".repeat(10_000);
+    /// for message in [&long, &hostile, &"a".repeat(30), &format!("{}b", hostile)] {
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+    /// }
+    /// assert!(start.elapsed() < Duration::from_secs(5));
+    ///
+    /// let lines: Vec<String> = logger.take_records().into_iter().map(|r| r.formatted).collect();
+    /// assert_eq!(lines, [hostile, "a".repeat(30)]);
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn add_filter_ignore_message_regex(
+        &mut self,
+        filter_ignore: &str,
+    ) -> Result<&mut ConfigBuilder, RegexError> {
+        self.0
+            .filter_ignore_message_regex
+            .push(Regex::new(filter_ignore)?);
+        Ok(self)
+    }
+
+    /// Clears all denied message filters.
+    pub fn clear_filter_ignore_message(&mut self) -> &mut ConfigBuilder {
+        self.0.filter_ignore_message.clear();
+        #[cfg(feature = "regex")]
+        self.0.filter_ignore_message_regex.clear();
+        self
+    }
+
+    /// Sets the level for records whose target starts with `target_prefix`.
+    ///
+    /// The most specific (longest) matching directive is used in place of the logger's level,
+    /// so directives can both lower and raise the verbosity for parts of the program.
+    /// Targets without a matching directive use the logger's level.
+    /// The target filters are still applied to records that pass their directive.
+    ///
+    /// Adding a directive for a prefix that already has one replaces its level.
+    ///
+    /// The prefix is compared as a plain string, so a directive for `a` also applies to the
+    /// target `ab`, unless `ab` has a directive of its own.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// use sp_log2::{ConfigBuilder, LevelFilter};
+    /// let config = ConfigBuilder::new()
+    ///     .add_level_directive("myapp", LevelFilter::Debug)
+    ///     .add_level_directive("hyper", LevelFilter::Warn)
+    ///     .add_level_directive("sqlx", LevelFilter::Warn)
+    ///     .build();
+    /// ```
+    ///
+    /// The longest matching prefix wins, regardless of the order the directives were added in:
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let warn = ("a", LevelFilter::Warn);
+    /// let trace = ("a::b", LevelFilter::Trace);
+    /// for directives in [[warn, trace], [trace, warn]] {
+    ///     let mut builder = ConfigBuilder::new();
+    ///     builder.set_formatter(Some("[target] [level]"));
+    ///     for (prefix, level) in directives {
+    ///         builder.add_level_directive(prefix, level);
+    ///     }
+    ///     let logger = TestLogger::new(LevelFilter::Info, builder.build());
+    ///
+    ///     for target in ["a::b::c", "a::x", "ab", "b"] {
+    ///         for level in [Level::Warn, Level::Info, Level::Trace] {
+    ///             logger.log(&Record::builder().level(level).target(target).args(format_args!("")).build());
+    ///         }
+    ///     }
+    ///
+    ///     let lines: Vec<String> = logger.take_records().into_iter().map(|r| r.formatted).collect();
+    ///     assert_eq!(
+    ///         lines,
+    ///         [
+    ///             "a::b::c [WARN]",
+    ///             "a::b::c [INFO]",
+    ///             "a::b::c [TRACE]",
+    ///             "a::x [WARN]",
+    ///             "ab [WARN]",
+    ///             "b [WARN]",
+    ///             "b [INFO]",
+    ///         ]
+    ///     );
+    /// }
+    /// ```
+    pub fn add_level_directive(
+        &mut self,
+        target_prefix: &str,
+        level: LevelFilter,
+    ) -> &mut ConfigBuilder {
+        let directives = &mut self.0.level_directives;
+        directives.retain(|(prefix, _)| prefix != target_prefix);
+        directives.push((Cow::Owned(target_prefix.to_string()), level));
+        // Keep the longest prefixes first, so the first match is the most specific one
+        directives.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    /// Parses a `RUST_LOG`-style directive string and adds the contained level directives.
+    ///
+    /// The string is a comma separated list of segments, each being one of
+    /// - `level`: the global level, e.g. `info`
+    /// - `target=level`: a level directive for targets starting with `target`
+    /// - `target`: enables every level for targets starting with `target`
+    ///
+    /// Levels are case insensitive and include `off`. Malformed segments are skipped with a
+    /// warning on stderr. Returns the global level, if one was given. If several were given,
+    /// the last one wins.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// use sp_log2::{ConfigBuilder, LevelFilter};
+    /// let mut builder = ConfigBuilder::new();
+    /// assert_eq!(builder.parse_env_directives("info,myapp=debug,hyper=warn"), Some(LevelFilter::Info));
+    /// assert_eq!(builder.parse_env_directives("myapp=trace"), None);
+    /// assert_eq!(builder.parse_env_directives("WARN"), Some(LevelFilter::Warn));
+    /// assert_eq!(builder.parse_env_directives("off,myapp"), Some(LevelFilter::Off));
+    /// assert_eq!(builder.parse_env_directives(" debug , ,myapp=nope"), Some(LevelFilter::Debug));
+    /// assert_eq!(builder.parse_env_directives(""), None);
+    /// let config = builder.build();
+    /// ```
+    pub fn parse_env_directives(&mut self, directives: &str) -> Option<LevelFilter> {
+        let mut global = None;
+
+        for segment in directives.split(',').map(str::trim) {
+            if segment.is_empty() {
+                continue;
+            }
+
+            let mut parts = segment.splitn(2, '=').map(str::trim);
+            let (target, level) = match (parts.next(), parts.next()) {
+                (Some(name), None) => match name.parse::<LevelFilter>() {
+                    Ok(level) => {
+                        global = Some(level);
+                        continue;
+                    }
+                    Err(_) => (name, Ok(LevelFilter::Trace)),
+                },
+                (Some(target), Some(level)) => (target, level.parse::<LevelFilter>()),
+                _ => continue,
+            };
+
+            let valid_target =
+                !target.is_empty() && !target.contains(|c: char| c.is_whitespace() || c == '/');
+            match level {
+                Ok(level) if valid_target => {
+                    self.add_level_directive(target, level);
+                }
+                _ => eprintln!("sp_log2: ignoring malformed log directive `{}`", segment),
+            }
+        }
+
+        global
+    }
+
+    /// Removes all level directives.
+    pub fn clear_level_directives(&mut self) -> &mut ConfigBuilder {
+        self.0.level_directives.clear();
+        self
+    }
+
+    /// Logs only one in `rate` records from targets starting with `target_prefix`.
+    ///
+    /// The most specific (longest) matching prefix is used. Records at `Warn` and above are
+    /// never dropped by sampling. Every logger counts records separately, also when they
+    /// were built from the same `Config`.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// use sp_log2::ConfigBuilder;
+    /// // Log 1 in 100 records of the render loop
+    /// let config = ConfigBuilder::new()
+    ///     .add_sample_rate("myapp::render", 100)
+    ///     .build();
+    /// ```
+    pub fn add_sample_rate(&mut self, target_prefix: &str, rate: usize) -> &mut ConfigBuilder {
+        let sample_rates = &mut self.0.sample_rates;
+        sample_rates.retain(|sample| sample.prefix != target_prefix);
+        sample_rates.push(SampleRate {
+            prefix: Cow::Owned(target_prefix.to_string()),
+            rate: rate.max(1),
+            counter: AtomicUsize::new(0),
+        });
+        // Keep the longest prefixes first, so the first match is the most specific one
+        sample_rates.sort_by_key(|sample| std::cmp::Reverse(sample.prefix.len()));
+        self
+    }
+
+    /// Removes all sample rates.
+    pub fn clear_sample_rates(&mut self) -> &mut ConfigBuilder {
+        self.0.sample_rates.clear();
+        self
+    }
+
+    /// Enables or disables the suppression of repeated messages.
+    ///
+    /// When enabled, a record with the same level, target and message as the previously
+    /// written one is counted instead of written. A single `last message repeated N times`
+    /// line is written once a different record arrives, the repeat window expired, or the
+    /// logger is flushed. Default is disabled.
+    pub fn set_repeat_suppression(&mut self, enable: bool) -> &mut ConfigBuilder {
+        self.0.repeat_suppression = enable;
+        self
+    }
+
+    /// Sets how long repeats of a message are folded into one summary line. Default is 30 seconds.
+    pub fn set_repeat_suppression_window(&mut self, window: Duration) -> &mut ConfigBuilder {
+        self.0.repeat_window = window;
+        self
+    }
+
+    /// Makes the `TermLogger` and the `FileLogger` not wait for the lock of their output
+    /// if the current thread already holds it (default is false). Other threads still
+    /// wait for the lock as usual.
+    ///
+    /// If a thread logs while it holds the lock, e.g. from a `Drop` impl, an output hook
+    /// or a signal handler that interrupted it while it was writing a record, the record
+    /// is formatted into a fixed-size queue of 64 records instead, without colors and cut
+    /// to 256 bytes. The next record that gets the lock, or `flush`, writes the queued
+    /// records first.
+    /// Records that find the queue full are counted as dropped by
+    /// [`LoggerStats`](crate::LoggerStats).
+    ///
+    /// The order of queued records relative to the others is best-effort: a queued
+    /// record is written after the record that held the lock, and may come after
+    /// records of other threads that got the lock in between. Formatting the record
+    /// still allocates, so this prevents deadlocks on the logger's lock, but does not
+    /// make logging async-signal-safe.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::Log;
+    /// # use std::io::{self, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let out = Shared::default();
+    /// let config = ConfigBuilder::new()
+    ///     .set_format(FormatFlags::LEVEL)
+    ///     .set_enable_colors(false)
+    ///     .set_nonblocking(true)
+    ///     .build();
+    /// let mut logger = TermLogger::with_streams(
+    ///     LevelFilter::Info,
+    ///     config,
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    /// );
+    /// let stats = logger.stats().unwrap();
+    /// logger.set_output_hook(Box::new(|write| {
+    ///     // Logs while the logger holds its lock, which would otherwise deadlock
+    ///     log::warn!("interrupted");
+    ///     write()
+    /// }));
+    /// log::set_boxed_logger(logger).unwrap();
+    /// log::set_max_level(LevelFilter::Info);
+    ///
+    /// log::info!("first");
+    /// log::info!("second");
+    /// log::logger().flush();
+    ///
+    /// let written = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    /// let lines: Vec<_> = written.lines().map(str::trim).collect();
+    /// assert_eq!(lines, ["[INFO] first", "[WARN] interrupted", "[INFO] second", "[WARN] interrupted"]);
+    /// assert_eq!(stats.written(Level::Warn), 2);
+    /// ```
+    ///
+    /// A record of another thread waits for the lock and is written in full:
+    /// ```rust
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io::{self, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// # #[derive(Clone, Default)]
+    /// # struct Shared(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Shared {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let out = Shared::default();
+    /// let config = ConfigBuilder::new()
+    ///     .set_format(FormatFlags::empty())
+    ///     .set_enable_colors(false)
+    ///     .set_nonblocking(true)
+    ///     .build();
+    /// let mut logger = TermLogger::with_streams(
+    ///     LevelFilter::Info,
+    ///     config,
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    /// );
+    /// logger.set_output_hook(Box::new(|write| {
+    ///     thread::sleep(Duration::from_millis(100));
+    ///     write()
+    /// }));
+    /// let logger = Arc::new(logger);
+    ///
+    /// let slow = thread::spawn({
+    ///     let logger = logger.clone();
+    ///     move || logger.log(&Record::builder().level(Level::Info).args(format_args!("slow")).build())
+    /// });
+    /// thread::sleep(Duration::from_millis(30));
+    /// let long = "x".repeat(300);
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", long)).build());
+    /// slow.join().unwrap();
+    ///
+    /// let written = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    /// let lines: Vec<_> = written.lines().map(str::trim).collect();
+    /// assert_eq!(lines, ["slow", long.as_str()]);
+    /// ```
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> &mut ConfigBuilder {
+        self.0.nonblocking = nonblocking;
+        self
+    }
+
+    /// Sets the handler called when the logger fails to write or flush a record.
+    ///
+    /// Errors are reported at most once per second and logger, further errors within that
+    /// second are dropped, so a full disk does not flood the handler. Without a handler the
+    /// error is written to stderr as a single line.
+    ///
+    /// Inside a `CombinedLogger` every logger uses the handler of its own config, and the
+    /// handler is told which logger failed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io::{self, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// struct FullDisk;
+    ///
+    /// impl Write for FullDisk {
+    ///     fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+    ///         Err(io::Error::new(io::ErrorKind::StorageFull, "no space left on device"))
+    ///     }
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let errors = Arc::new(Mutex::new(Vec::new()));
+    /// let handler_errors = errors.clone();
+    /// let config = ConfigBuilder::new()
+    ///     .set_error_handler(Arc::new(move |logger: &str, error: &io::Error| {
+    ///         handler_errors.lock().unwrap().push(format!("{}: {}", logger, error));
+    ///     }))
+    ///     .build();
+    /// let logger = CombinedLogger::new(vec![
+    ///     SimpleLogger::new(LevelFilter::Off, Config::default()),
+    ///     WriteLogger::new(LevelFilter::Info, config, FullDisk),
+    /// ]);
+    ///
+    /// for _ in 0..1000 {
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("lost")).build());
+    /// }
+    /// assert_eq!(*errors.lock().unwrap(), ["WriteLogger: no space left on device"]);
+    /// ```
+    pub fn set_error_handler(&mut self, handler: ErrorHandler) -> &mut ConfigBuilder {
+        self.0.error_handler = Some(ConfigErrorHandler(handler));
         self
     }
 
     /// Builds and returns the final `Config` instance.
     ///
     /// This applies all the configurations set in the builder and returns the complete `Config`.
+    /// The configuration is not validated, e.g. unknown placeholders in the formatter are
+    /// written as they are. Use [`ConfigBuilder::try_build`] to catch such mistakes.
     pub fn build(&mut self) -> Config {
+        if self.0.format.contains(FormatFlags::HOSTNAME) && self.0.hostname.is_none() {
+            self.0.hostname = Some(hostname().to_string());
+        }
         self.0.clone()
     }
+
+    /// Validates the configuration, then builds and returns the final `Config` instance.
+    ///
+    /// Fails if the level range is empty, or if [`Config::validate_formatter`] fails.
+    /// Regex filters are already checked when they are added.
+    ///
+    /// # Usage
+    ///
+    /// ```rust
+    /// # use sp_log2::*;
+    /// assert!(ConfigBuilder::new().set_formatter(Some("[time] [level] [message]")).try_build().is_ok());
+    ///
+    /// let err = ConfigBuilder::new()
+    ///     .set_formatter(Some("[time] [level] [message"))
+    ///     .try_build()
+    ///     .unwrap_err();
+    /// assert_eq!(err.to_string(), "formatter: unclosed '[' at byte 15");
+    ///
+    /// let err = ConfigBuilder::new()
+    ///     .set_formatter(Some("[time] [lvl]"))
+    ///     .try_build()
+    ///     .unwrap_err();
+    /// assert_eq!(err, ConfigError::UnknownPlaceholder { key: "lvl".to_string(), position: 8 });
+    ///
+    /// // Positions are byte offsets
+    /// let err = ConfigBuilder::new()
+    ///     .set_formatter(Some("✦ [nïveau]"))
+    ///     .try_build()
+    ///     .unwrap_err();
+    /// assert!(matches!(err, ConfigError::UnknownPlaceholder { position: 5, .. }));
+    ///
+    /// let err = ConfigBuilder::new()
+    ///     .set_level_range(LevelFilter::Info, LevelFilter::Warn)
+    ///     .try_build()
+    ///     .unwrap_err();
+    /// assert!(matches!(err, ConfigError::LevelRange { .. }));
+    /// ```
+    pub fn try_build(&mut self) -> Result<Config, ConfigError> {
+        if self.0.most_severe > self.0.least_severe {
+            return Err(ConfigError::LevelRange {
+                most_severe: self.0.most_severe,
+                least_severe: self.0.least_severe,
+            });
+        }
+        self.0.validate_formatter()?;
+        Ok(self.build())
+    }
+}
+
+impl From<Config> for ConfigBuilder {
+    /// Creates a `ConfigBuilder` starting from an existing `Config`.
+    fn from(config: Config) -> Self {
+        ConfigBuilder(config)
+    }
 }
 
 impl Default for ConfigBuilder {
@@ -365,25 +3986,76 @@ impl Default for ConfigBuilder {
 impl Default for Config {
     fn default() -> Config {
         Config {
-            format: Format::LevelFlag | Format::Time | Format::Thread | Format::Target,
+            format: FormatFlags::default(),
+            field_order: Cow::Borrowed(&DEFAULT_FIELD_ORDER),
             level_padding: LevelPadding::Off,
             thread_log_mode: ThreadLogMode::IDs,
             thread_padding: ThreadPadding::Off,
+            thread_separator: Cow::Borrowed("/"),
+            level_delimiters: (Cow::Borrowed("["), Cow::Borrowed("]")),
+            thread_delimiters: (Cow::Borrowed("("), Cow::Borrowed(")")),
+            target_suffix: Cow::Borrowed(":"),
+            field_separator: Cow::Borrowed(" "),
             target_padding: TargetPadding::Off,
-            time_format: TimeFormat::Custom("%H:%M:%S"),
+            time_format: TimeFormat::Custom(Cow::Borrowed("%H:%M:%S")),
+            time_offset: None,
+            time_include_offset: false,
+            clock: None,
             filter_allow: Cow::Borrowed(&[]),
             filter_ignore: Cow::Borrowed(&[]),
+            filter_allow_message: Vec::new(),
+            filter_ignore_message: Vec::new(),
+            #[cfg(feature = "regex")]
+            filter_allow_message_regex: Vec::new(),
+            #[cfg(feature = "regex")]
+            filter_ignore_message_regex: Vec::new(),
+            level_directives: Vec::new(),
+            filter_allow_levels: Vec::new(),
+            sample_rates: Vec::new(),
+            repeat_suppression: false,
+            banner: None,
+            repeat_window: Duration::from_secs(30),
+            nonblocking: false,
+            error_handler: None,
+            #[cfg(feature = "regex")]
+            filter_allow_regex: Vec::new(),
+            #[cfg(feature = "regex")]
+            filter_ignore_regex: Vec::new(),
             enable_colors: true,
-            max_level: LevelFilter::Error,
-            min_level: LevelFilter::Trace,
+            respect_color_env: true,
+            colorize_line: false,
+            write_log_enable_colors: false,
+            most_severe: LevelFilter::Error,
+            least_severe: LevelFilter::Trace,
             formatter: None,
-            level_color: [
-                None,                // Default foreground
-                Some(Color::Red),    // Error
-                Some(Color::Yellow), // Warn
-                Some(Color::Blue),   // Info
-                Some(Color::Cyan),   // Debug
-                Some(Color::White),  // Trace
+            level_texts: Default::default(),
+            level_case: None,
+            level_padding_width: None,
+            message_column: None,
+            wrap: WrapMode::Off,
+            inline_tags: false,
+            multiline: MultilineMode::AsIs,
+            escape_newlines: false,
+            json_output: false,
+            json_field_names: JsonFieldNames::default(),
+            location_style: LocationStyle::Full,
+            target_style: TargetStyle::Full,
+            filter_match: FilterMatch::TargetOnly,
+            level_icons: Default::default(),
+            use_level_icons: false,
+            level_formatters: Default::default(),
+            strict_formatter: false,
+            hostname: None,
+            custom_placeholders: Vec::new(),
+            created: Instant::now(),
+            uptime_precision: 3,
+            level_style: [
+                LevelStyle::new(),                   // Default foreground
+                LevelStyle::new().fg(Color::Red),    // Error
+                LevelStyle::new().fg(Color::Yellow), // Warn
+                LevelStyle::new().fg(Color::Blue),   // Info
+                LevelStyle::new().fg(Color::Cyan),   // Debug
+                LevelStyle::new().fg(Color::White),  // Trace
             ],
 
             line_ending: String::from("\u{000A}"),
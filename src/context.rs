@@ -0,0 +1,148 @@
+//! Thread-local context values shown on every record logged from the thread.
+//!
+//! Values are shown with the `[context]` placeholder as `key=value` pairs, or one at a
+//! time with `[context.key]`, if `FormatFlags::CONTEXT` is enabled. The default layout shows
+//! them in braces in front of the message.
+//!
+//! The values are taken on the thread that logs the record, also for loggers like the
+//! `AsyncLogger` that write records on another thread.
+//!
+//! ```
+//! # use sp_log2::*;
+//! # use log::{Log, Record};
+//! let test_logger = TestLogger::new(
+//!     LevelFilter::Info,
+//!     ConfigBuilder::new().set_format(FormatFlags::CONTEXT).set_formatter(Some("[context] [message]")).build(),
+//! );
+//! let captured = test_logger.captured();
+//! let (logger, guard) = AsyncLogger::new(test_logger, 16, OverflowPolicy::Block).unwrap();
+//!
+//! context::insert("user", "alice");
+//! logger.log(&Record::builder().level(Level::Info).args(format_args!("login")).build());
+//! context::clear();
+//! drop(guard);
+//!
+//! assert_eq!(captured.take_records()[0].formatted, "user=alice login");
+//! ```
+//!
+//! # Examples
+//! ```
+//! # use sp_log2::*;
+//! # use log::{Log, Record};
+//! let logger = TestLogger::new(
+//!     LevelFilter::Info,
+//!     ConfigBuilder::new()
+//!         .set_format(FormatFlags::LEVEL | FormatFlags::CONTEXT)
+//!         .set_formatter(Some("[level] [?context.request_id:<[context.request_id]> ][message]"))
+//!         .build(),
+//! );
+//!
+//! {
+//!     let _request = context::scoped("request_id", 42);
+//!     logger.log(&Record::builder().level(Level::Info).args(format_args!("handling")).build());
+//! }
+//! logger.log(&Record::builder().level(Level::Info).args(format_args!("idle")).build());
+//!
+//! let records = logger.take_records();
+//! assert_eq!(records[0].formatted, "[INFO] <42> handling");
+//! assert_eq!(records[1].formatted, "[INFO] idle");
+//! ```
+
+use std::cell::RefCell;
+use std::fmt::Display;
+
+thread_local! {
+    static CONTEXT: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Sets the context value of `key` on the current thread, replacing the previous one.
+///
+/// Keys should not contain `:`, which separates the styles of a placeholder.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// context::insert("user", "alice");
+/// assert_eq!(context::get("user").as_deref(), Some("alice"));
+/// # context::clear();
+/// ```
+pub fn insert<V: Display>(key: &str, value: V) {
+    let value = value.to_string();
+    CONTEXT.with(|c| {
+        let mut context = c.borrow_mut();
+        match context.iter_mut().find(|(name, _)| name == key) {
+            Some((_, current)) => *current = value,
+            None => context.push((key.to_string(), value)),
+        }
+    });
+}
+
+/// Removes the context value of `key` from the current thread, returning it.
+pub fn remove(key: &str) -> Option<String> {
+    CONTEXT.with(|c| {
+        let mut context = c.borrow_mut();
+        let index = context.iter().position(|(name, _)| name == key)?;
+        Some(context.remove(index).1)
+    })
+}
+
+/// Returns the context value of `key` on the current thread.
+pub fn get(key: &str) -> Option<String> {
+    CONTEXT.with(|c| {
+        c.borrow()
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.clone())
+    })
+}
+
+/// Removes all context values from the current thread.
+pub fn clear() {
+    CONTEXT.with(|c| c.borrow_mut().clear());
+}
+
+/// Sets the context value of `key` on the current thread until the returned guard is dropped,
+/// which restores the previous value.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// let _outer = context::scoped("step", "load");
+/// {
+///     let _inner = context::scoped("step", "parse");
+///     assert_eq!(context::get("step").as_deref(), Some("parse"));
+/// }
+/// assert_eq!(context::get("step").as_deref(), Some("load"));
+/// ```
+#[must_use = "the value is removed again when the guard is dropped"]
+pub fn scoped<V: Display>(key: &str, value: V) -> ContextGuard {
+    let previous = get(key);
+    insert(key, value);
+    ContextGuard {
+        key: key.to_string(),
+        previous,
+    }
+}
+
+/// Restores the previous context value of a key when dropped, see [`scoped`].
+#[derive(Debug)]
+pub struct ContextGuard {
+    key: String,
+    previous: Option<String>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(previous) => insert(&self.key, previous),
+            None => {
+                remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Returns a copy of the context values of the current thread.
+pub(crate) fn snapshot() -> Vec<(String, String)> {
+    CONTEXT.with(|c| c.borrow().clone())
+}
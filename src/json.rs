@@ -0,0 +1,246 @@
+//! Records written as JSON objects, one per line.
+
+use crate::loggers::logging::{current_context, write_thread, write_time};
+use crate::{Config, FormatFlags};
+use log::{Level, Record};
+use std::fmt::Write as _;
+use std::io::{Error, Write};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The names of the fields of records written as JSON, see
+/// [`ConfigBuilder::set_json_field_names`](crate::ConfigBuilder::set_json_field_names).
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// let names = JsonFieldNames {
+///     time: "ts".to_string(),
+///     message: "msg".to_string(),
+///     ..JsonFieldNames::default()
+/// };
+/// ```
+///
+/// With the `kv` feature, booleans and numbers among the key-values are written as
+/// they are, and every other value as a string:
+/// ```
+/// # #[cfg(feature = "kv")]
+/// # {
+/// # use sp_log2::*;
+/// # use log::{kv::Value, Log, Record};
+/// let logger = TestLogger::new(
+///     LevelFilter::Info,
+///     ConfigBuilder::new()
+///         .set_format(FormatFlags::LEVEL)
+///         .set_json_output(true)
+///         .set_json_field_names(JsonFieldNames {
+///             kv: "fields".to_string(),
+///             ..JsonFieldNames::default()
+///         })
+///         .build(),
+/// );
+/// let kvs = [
+///     ("user", Value::from("alice")),
+///     ("attempt", Value::from(3)),
+///     ("admin", Value::from(false)),
+/// ];
+/// logger.log(&Record::builder().level(Level::Info).args(format_args!("login")).key_values(&kvs).build());
+/// assert_eq!(
+///     logger.take_records()[0].formatted,
+///     r#"{"level":"INFO","severity":6,"message":"login","fields":{"user":"alice","attempt":3,"admin":false}}"#
+/// );
+/// # }
+/// ```
+pub struct JsonFieldNames {
+    /// The time of the record, if `FormatFlags::TIME` is enabled (default `time`)
+    pub time: String,
+    /// The level as a string, e.g. `"WARN"` (default `level`)
+    pub level: String,
+    /// The level as a numeric syslog severity, e.g. `4` for `Warn` (default `severity`)
+    pub severity: String,
+    /// The thread, if `FormatFlags::THREAD` is enabled (default `thread`)
+    pub thread: String,
+    /// The target, if `FormatFlags::TARGET` is enabled (default `target`)
+    pub target: String,
+    /// The module path, if `FormatFlags::MODULE` is enabled (default `module`)
+    pub module: String,
+    /// The file, if `FormatFlags::FILE_LOCATION` is enabled (default `file`)
+    pub file: String,
+    /// The line, if `FormatFlags::FILE_LOCATION` is enabled (default `line`)
+    pub line: String,
+    /// The message (default `message`)
+    pub message: String,
+    /// The key-values of the record, with the `kv` feature (default `kv`)
+    pub kv: String,
+    /// The thread-local [context](crate::context), if `FormatFlags::CONTEXT` is enabled
+    /// (default `extra`)
+    pub extra: String,
+}
+
+impl Default for JsonFieldNames {
+    fn default() -> JsonFieldNames {
+        JsonFieldNames {
+            time: "time".to_string(),
+            level: "level".to_string(),
+            severity: "severity".to_string(),
+            thread: "thread".to_string(),
+            target: "target".to_string(),
+            module: "module".to_string(),
+            file: "file".to_string(),
+            line: "line".to_string(),
+            message: "message".to_string(),
+            kv: "kv".to_string(),
+            extra: "extra".to_string(),
+        }
+    }
+}
+
+/// Returns the syslog severity of `level`.
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Writes `record` as a JSON object on its own line.
+///
+/// The fields are always written in the order of [`JsonFieldNames`], and the
+/// key-values and the context in the order they were given.
+pub(crate) fn write_json<W: Write + ?Sized>(
+    config: &Config,
+    record: &Record<'_>,
+    out: &mut W,
+) -> Result<(), Error> {
+    let names = &config.json_field_names;
+    let mut json = String::with_capacity(256);
+    json.push('{');
+
+    if config.format.contains(FormatFlags::TIME) {
+        let time = write_time(config)?;
+        push_field(&mut json, &names.time, |json| push_string(json, &time));
+    }
+    push_field(&mut json, &names.level, |json| {
+        push_string(json, record.level().as_str())
+    });
+    push_field(&mut json, &names.severity, |json| {
+        let _ = write!(json, "{}", severity(record.level()));
+    });
+    if config.format.contains(FormatFlags::THREAD) {
+        let thread = write_thread(config)?;
+        push_field(&mut json, &names.thread, |json| {
+            push_string(json, thread.trim())
+        });
+    }
+    if config.format.contains(FormatFlags::TARGET) {
+        push_field(&mut json, &names.target, |json| {
+            push_string(json, record.target())
+        });
+    }
+    if config.format.contains(FormatFlags::MODULE) {
+        if let Some(module) = record.module_path() {
+            push_field(&mut json, &names.module, |json| push_string(json, module));
+        }
+    }
+    if config.format.contains(FormatFlags::FILE_LOCATION) {
+        if let Some(file) = record.file() {
+            push_field(&mut json, &names.file, |json| push_string(json, file));
+        }
+        if let Some(line) = record.line() {
+            push_field(&mut json, &names.line, |json| {
+                let _ = write!(json, "{}", line);
+            });
+        }
+    }
+    push_field(&mut json, &names.message, |json| {
+        push_string(json, &record.args().to_string())
+    });
+    #[cfg(feature = "kv")]
+    push_key_values(&mut json, &names.kv, record);
+    let context = current_context(config);
+    if !context.is_empty() {
+        push_field(&mut json, &names.extra, |json| {
+            json.push('{');
+            for (key, value) in &context {
+                push_field(json, key, |json| push_string(json, value));
+            }
+            json.push('}');
+        });
+    }
+
+    json.push('}');
+    json.push_str(&config.line_ending);
+    out.write_all(json.as_bytes())
+}
+
+/// Appends `"key":` and the value written by `value` to the object being written.
+fn push_field(json: &mut String, key: &str, value: impl FnOnce(&mut String)) {
+    // Every value ends with a quote, a digit or a brace, so only an empty object ends with `{`
+    if !json.ends_with('{') {
+        json.push(',');
+    }
+    push_string(json, key);
+    json.push(':');
+    value(json);
+}
+
+/// Appends `value` as a JSON string, escaping quotes, backslashes and control characters.
+fn push_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Appends the key-values of `record` as an object, if it has any.
+///
+/// Booleans and numbers are written as they are, every other value as a string.
+#[cfg(feature = "kv")]
+fn push_key_values(json: &mut String, name: &str, record: &Record<'_>) {
+    use log::kv::{Error, Key, Value, VisitSource};
+
+    struct Visitor<'a>(&'a mut String);
+
+    impl<'kvs> VisitSource<'kvs> for Visitor<'_> {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+            push_field(self.0, key.as_str(), |json| push_value(json, &value));
+            Ok(())
+        }
+    }
+
+    fn push_value(json: &mut String, value: &Value<'_>) {
+        if let Some(value) = value.to_bool() {
+            let _ = write!(json, "{}", value);
+        } else if let Some(value) = value.to_i64() {
+            let _ = write!(json, "{}", value);
+        } else if let Some(value) = value.to_u64() {
+            let _ = write!(json, "{}", value);
+        } else if let Some(value) = value.to_f64().filter(|value| value.is_finite()) {
+            let _ = write!(json, "{}", value);
+        } else {
+            push_string(json, &value.to_string());
+        }
+    }
+
+    let source = record.key_values();
+    if source.count() == 0 {
+        return;
+    }
+    push_field(json, name, |json| {
+        json.push('{');
+        let _ = source.visit(&mut Visitor(json));
+        json.push('}');
+    });
+}
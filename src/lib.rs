@@ -1,17 +1,143 @@
+mod banner;
 mod config;
+pub mod context;
+mod json;
 mod loggers;
+mod panic;
+mod spec;
+mod suspend;
 
+pub use self::banner::Banner;
 pub use self::config::{
-    Config, ConfigBuilder, Format, LevelPadding, TargetPadding, ThreadLogMode, ThreadPadding,
+    Clock, ColorParseError, Config, ConfigBuilder, ConfigError, ErrorHandler, FilterMatch,
+    FilterPattern, Format, FormatFlags, LevelCase, LevelPadding, LevelStyle, LineEnding,
+    LocationStyle, LogField, MultilineMode, TargetPadding, TargetStyle, ThreadLogMode,
+    ThreadPadding, UtcOffset, WrapMode,
 };
+pub use self::json::JsonFieldNames;
 
-pub use self::loggers::{CombinedLogger, FileLogger, SimpleLogger, WriteLogger};
-pub use self::loggers::{TermLogger, TerminalMode};
-pub use termcolor2::{Color, ColorChoice};
+#[cfg(feature = "android")]
+pub use self::loggers::AndroidLogger;
+#[cfg(all(windows, feature = "eventlog"))]
+pub use self::loggers::EventLogLogger;
+#[cfg(all(target_os = "linux", feature = "journald"))]
+pub use self::loggers::JournaldLogger;
+pub use self::loggers::{format_record, PreparedRecord};
+pub use self::loggers::{
+    AsyncLogger, ConfigHandle, LevelHandle, LoggerStats, OverflowPolicy, WorkerGuard,
+};
+pub use self::loggers::{CapturedRecord, CapturedRecords, TestLogger};
+pub use self::loggers::{ChainError, ChainReport, LoggerChain, LoggerFactory};
+pub use self::loggers::{ChannelLogger, LogMessage};
+pub use self::loggers::{
+    CombinedHandle, CombinedLogger, FileLogger, FileLoggerOptions, FlushGuard, FlushPolicy,
+    LoggerId, OutputStream, RotateHook, SharedWriter, SimpleLogger, WriteLogger,
+};
+#[cfg(feature = "net")]
+pub use self::loggers::{ConnectionState, NetLogger, NetLoggerOptions, NetStatus};
+#[cfg(all(unix, feature = "syslog"))]
+pub use self::loggers::{Facility, SyslogLogger, SyslogOptions};
+pub use self::loggers::{OutputHook, TermLogger, TerminalMode};
+pub use self::loggers::{RingBuffer, RingBufferCapacity, RingBufferLogger};
+pub use self::panic::install_panic_hook;
+pub use self::spec::ConfigSpec;
+pub use self::suspend::{suspend, suspend_below, SuspendGuard};
+pub use chrono::{DateTime, SecondsFormat};
+#[cfg(feature = "regex")]
+pub use regex::Error as RegexError;
+pub use termcolor2::{Color, ColorChoice, WriteColor};
 
 pub use log::{Level, LevelFilter};
 
-use log::Log;
+use log::{Log, SetLoggerError};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Error returned by the `init` functions of loggers that need to acquire a resource,
+/// e.g. open a file, before they can be installed.
+#[derive(Debug)]
+pub enum InitError {
+    /// Another logger was already installed globally
+    SetLogger(SetLoggerError),
+    /// The logger could not be constructed
+    Io(io::Error),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::SetLogger(err) => write!(f, "failed to set logger: {}", err),
+            InitError::Io(err) => write!(f, "failed to create logger: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for InitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InitError::SetLogger(err) => Some(err),
+            InitError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<SetLoggerError> for InitError {
+    fn from(err: SetLoggerError) -> Self {
+        InitError::SetLogger(err)
+    }
+}
+
+impl From<io::Error> for InitError {
+    fn from(err: io::Error) -> Self {
+        InitError::Io(err)
+    }
+}
+
+/// Error returned by [`SharedLogger::try_flush`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FlushError {
+    /// The flush did not finish within the timeout. For the `AsyncLogger`, it goes on in
+    /// the background.
+    TimedOut,
+    /// Writing out the buffered records failed
+    Io(io::Error),
+    /// Some loggers of a `CombinedLogger` failed to flush, in the order they were added
+    Loggers(Vec<(LoggerId, FlushError)>),
+}
+
+impl fmt::Display for FlushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlushError::TimedOut => write!(f, "flushing the logger timed out"),
+            FlushError::Io(err) => write!(f, "failed to flush the logger: {}", err),
+            FlushError::Loggers(errors) => {
+                write!(f, "failed to flush {} logger(s)", errors.len())?;
+                for (id, err) in errors {
+                    write!(f, "; {:?}: {}", id, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for FlushError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FlushError::Io(err) => Some(err),
+            FlushError::TimedOut | FlushError::Loggers(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for FlushError {
+    fn from(err: io::Error) -> Self {
+        FlushError::Io(err)
+    }
+}
 
 pub trait SharedLogger: Log {
     /// Returns the set Level for this Logger
@@ -30,7 +156,8 @@ pub trait SharedLogger: Log {
 
     /// Inspect the config of a running Logger
     ///
-    /// An Option is returned, because some Logger may not contain a Config
+    /// An Option is returned, because some Logger may not contain a Config.
+    /// The returned config is a snapshot, later changes through a `ConfigHandle` are not reflected.
     ///
     /// # Examples
     ///
@@ -42,7 +169,177 @@ pub trait SharedLogger: Log {
     /// println!("{:?}", logger.config());
     /// # }
     /// ```
-    fn config(&self) -> Option<&Config>;
+    fn config(&self) -> Option<Arc<Config>>;
+
+    /// Returns a handle to replace the config of this Logger at runtime
+    ///
+    /// An Option is returned, because some Logger may not contain a Config
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let logger = SimpleLogger::new(LevelFilter::Info, Config::default());
+    /// if let Some(handle) = logger.config_handle() {
+    ///     handle.modify(|builder| {
+    ///         builder.set_formatter(Some("[time] [level] [message]"));
+    ///     });
+    /// }
+    /// # }
+    /// ```
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        None
+    }
+
+    /// Returns a handle to change the level of this Logger at runtime
+    ///
+    /// An Option is returned, because some Logger may not support runtime level changes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let logger = SimpleLogger::new(LevelFilter::Info, Config::default());
+    /// if let Some(handle) = logger.level_handle() {
+    ///     handle.set_level(LevelFilter::Warn);
+    /// }
+    /// # }
+    /// ```
+    fn level_handle(&self) -> Option<LevelHandle> {
+        None
+    }
+
+    /// Returns the counts of the records this Logger wrote, skipped and dropped
+    ///
+    /// An Option is returned, because some Logger may not count its records.
+    /// See [`LoggerStats`] for what is counted. A `CombinedLogger` returns `None`, the
+    /// statistics of its inner loggers are available from `CombinedHandle::stats`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let logger = SimpleLogger::new(LevelFilter::Info, Config::default());
+    /// if let Some(stats) = logger.stats() {
+    ///     assert_eq!(stats.written(Level::Error), 0);
+    /// }
+    /// # }
+    /// ```
+    fn stats(&self) -> Option<LoggerStats> {
+        None
+    }
+
+    /// Writes the summary of the session, if the banner of the `Config` asks for one, see
+    /// [`Banner::with_summary`]. Called by the [`FlushGuard`] when it is dropped.
+    ///
+    /// The `FileLogger` and the `WriteLogger` write it, and the `CombinedLogger` passes
+    /// it on to its loggers. The default does nothing.
+    fn write_summary(&self) {}
+
+    /// Logs a record whose time, file location and message a shared `CombinedLogger`
+    /// already rendered for all of its loggers, see [`CombinedLogger::new_shared`].
+    ///
+    /// The loggers of this library that format records on the calling thread take the
+    /// fields from the [`PreparedRecord`]. The default ignores them and calls `log`, so a
+    /// logger that formats with [`format_record`] can override it to call `log` inside
+    /// [`PreparedRecord::with_fields`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Metadata, Record};
+    /// # use std::sync::{Arc, Mutex};
+    /// struct SinkLogger {
+    ///     config: Config,
+    ///     lines: Arc<Mutex<Vec<String>>>,
+    /// }
+    /// impl Log for SinkLogger {
+    ///     fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+    ///     fn log(&self, record: &Record<'_>) {
+    ///         let mut line = Vec::new();
+    ///         sp_log2::format_record(&self.config, record, &mut line).unwrap();
+    ///         self.lines.lock().unwrap().push(String::from_utf8(line).unwrap());
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    /// impl SharedLogger for SinkLogger {
+    ///     fn level(&self) -> LevelFilter { LevelFilter::Info }
+    ///     fn config(&self) -> Option<Arc<Config>> { Some(Arc::new(self.config.clone())) }
+    ///     fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+    ///         record.with_fields(|| self.log(record.record()))
+    ///     }
+    ///     fn as_log(self: Box<Self>) -> Box<dyn Log> { self }
+    /// }
+    ///
+    /// let config = ConfigBuilder::new().set_formatter(Some("[time] [message]")).set_time_format_custom("%T%.9f").build();
+    /// let lines = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = SinkLogger { config: config.clone(), lines: lines.clone() };
+    /// let test_logger = TestLogger::new(LevelFilter::Info, config);
+    /// let captured = test_logger.captured();
+    ///
+    /// let combined_logger = CombinedLogger::new_shared(vec![Box::new(sink), test_logger]);
+    /// combined_logger.log(&Record::builder().level(Level::Info).args(format_args!("started")).build());
+    ///
+    /// // Both loggers got the time taken once for the record
+    /// let line = lines.lock().unwrap().remove(0);
+    /// assert_eq!(line.trim_end(), captured.take_records()[0].formatted);
+    /// ```
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        self.log(record.record());
+    }
+
+    /// Flushes the logger like `Log::flush`, but waits at most `timeout` for it, so e.g.
+    /// a writer stalled on a dead pipe cannot hang the shutdown of the program.
+    ///
+    /// The `FileLogger` waits at most `timeout` for its file, returning
+    /// [`FlushError::TimedOut`] without flushing anything if another thread holds it for
+    /// longer. The `AsyncLogger` returns [`FlushError::TimedOut`] once `timeout` has
+    /// passed, while its worker goes on flushing in the background. The `CombinedLogger` flushes its loggers in the order they were added,
+    /// with whatever is left of `timeout`, and returns the errors of all of them. The
+    /// default calls `flush` and waits for it however long it takes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io::{self, Write};
+    /// # use std::time::Duration;
+    /// // A writer that never finishes flushing, like a pipe nobody reads
+    /// struct Stalled;
+    /// impl Write for Stalled {
+    ///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { Ok(buf.len()) }
+    ///     fn flush(&mut self) -> io::Result<()> { loop { std::thread::park(); } }
+    /// }
+    ///
+    /// let stalled = WriteLogger::new(LevelFilter::Info, Config::default(), Stalled);
+    /// let (stalled, guard) = AsyncLogger::new(stalled, 16, OverflowPolicy::Block).unwrap();
+    /// let logger = CombinedLogger::new(vec![SimpleLogger::new(LevelFilter::Info, Config::default()), stalled]);
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("shutting down")).build());
+    ///
+    /// match logger.try_flush(Duration::from_millis(100)) {
+    ///     Err(FlushError::Loggers(errors)) => {
+    ///         assert_eq!(errors.len(), 1);
+    ///         assert!(matches!(errors[0].1, FlushError::TimedOut));
+    ///     }
+    ///     result => panic!("expected a timeout, got {:?}", result),
+    /// }
+    /// // Dropping the guard would wait for the stalled worker
+    /// std::mem::forget(guard);
+    /// ```
+    fn try_flush(&self, timeout: Duration) -> Result<(), FlushError> {
+        let _ = timeout;
+        self.flush();
+        Ok(())
+    }
 
     /// Returns the logger as a Log trait object
     fn as_log(self: Box<Self>) -> Box<dyn Log>;
@@ -0,0 +1,202 @@
+//! Module providing the AndroidLogger Implementation
+
+use super::logging::{
+    level_enabled, level_in_range, mark_written, metadata_enabled, should_skip, write_location,
+    write_module, LoggerState,
+};
+use crate::config::FormatFlags;
+use crate::{Config, ConfigHandle, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// The longest tag logcat accepts on older Android versions
+const MAX_TAG_LEN: usize = 23;
+
+#[cfg(target_os = "android")]
+mod ffi {
+    use std::os::raw::{c_char, c_int};
+
+    pub const ANDROID_LOG_VERBOSE: c_int = 2;
+    pub const ANDROID_LOG_DEBUG: c_int = 3;
+    pub const ANDROID_LOG_INFO: c_int = 4;
+    pub const ANDROID_LOG_WARN: c_int = 5;
+    pub const ANDROID_LOG_ERROR: c_int = 6;
+
+    #[link(name = "log")]
+    extern "C" {
+        pub fn __android_log_write(
+            priority: c_int,
+            tag: *const c_char,
+            text: *const c_char,
+        ) -> c_int;
+    }
+}
+
+/// The AndroidLogger struct. Provides a Logger implementation that writes to the Android log (logcat)
+///
+/// The record target is used as the tag, truncated to 23 bytes, and the level is mapped
+/// to the corresponding Android priority. The message body contains the module path
+/// and the file location if `FormatFlags::MODULE` and `FormatFlags::FILE_LOCATION` are enabled;
+/// time, level and thread are left out, as logcat records those itself.
+///
+/// On other platforms this is a stand-in that writes `tag: message` lines to stderr,
+/// so the same registration code compiles and runs everywhere.
+pub struct AndroidLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    state: Mutex<LoggerState>,
+}
+
+impl AndroidLogger {
+    /// init function. Globally initializes the AndroidLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level` and `Config` as arguments.
+    /// Fails if another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _ = AndroidLogger::init(LevelFilter::Info, Config::default());
+    /// # }
+    /// ```
+    pub fn init(log_level: LevelFilter, config: Config) -> Result<LevelHandle, SetLoggerError> {
+        let logger = AndroidLogger::new(log_level, config);
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Takes the desired `Level` and `Config` as arguments.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let android_logger = AndroidLogger::new(LevelFilter::Info, Config::default());
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn new(log_level: LevelFilter, config: Config) -> Box<AndroidLogger> {
+        let level = LevelHandle::new(log_level, Some(&config));
+        Box::new(AndroidLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            state: Mutex::new(LoggerState::default()),
+        })
+    }
+
+    fn write(&self, config: &Config, record: &Record<'_>) -> io::Result<()> {
+        if should_skip(config, record) || !level_in_range(config, record.level()) {
+            return Ok(());
+        }
+        mark_written();
+
+        let mut message = String::new();
+        if config.format.contains(FormatFlags::MODULE) {
+            message.push_str(&write_module(record)?);
+            message.push_str(": ");
+        }
+        message.push_str(&record.args().to_string());
+        if config.format.contains(FormatFlags::FILE_LOCATION) {
+            message.push_str(&format!(" [{}]", write_location(record, config)?));
+        }
+
+        write_android(record.level(), tag(record.target()), &message)
+    }
+}
+
+/// Truncates `target` to the maximum tag length, on a char boundary.
+fn tag(target: &str) -> &str {
+    let mut end = target.len().min(MAX_TAG_LEN);
+    while !target.is_char_boundary(end) {
+        end -= 1;
+    }
+    &target[..end]
+}
+
+#[cfg(target_os = "android")]
+fn write_android(level: Level, tag: &str, message: &str) -> io::Result<()> {
+    use std::ffi::CString;
+
+    let priority = match level {
+        Level::Error => ffi::ANDROID_LOG_ERROR,
+        Level::Warn => ffi::ANDROID_LOG_WARN,
+        Level::Info => ffi::ANDROID_LOG_INFO,
+        Level::Debug => ffi::ANDROID_LOG_DEBUG,
+        Level::Trace => ffi::ANDROID_LOG_VERBOSE,
+    };
+    // Interior nul bytes would cut the strings short
+    let tag = CString::new(tag.replace('\0', "")).unwrap_or_default();
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+
+    // SAFETY: both pointers are valid nul terminated strings for the duration of the call
+    unsafe {
+        ffi::__android_log_write(priority, tag.as_ptr(), message.as_ptr());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "android"))]
+fn write_android(_level: Level, tag: &str, message: &str) -> io::Result<()> {
+    use std::io::Write;
+
+    writeln!(io::stderr().lock(), "{}: {}", tag, message)
+}
+
+impl Log for AndroidLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut state = self.state.lock().unwrap();
+            let result = state.log(&config, record, |record| self.write(&config, record));
+            state.report(&config, "AndroidLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let config = self.config.get();
+        let mut state = self.state.lock().unwrap();
+        let result = state.flush(|record| self.write(&config, record));
+        state.report(&config, "AndroidLogger", result);
+    }
+}
+
+impl SharedLogger for AndroidLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.state.lock().unwrap().stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,539 @@
+//! Module providing the AsyncLogger Implementation
+
+use super::logging::{metadata_enabled, with_origin, RecordOrigin};
+use crate::{Config, ConfigHandle, FlushError, InitError, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// What the [`AsyncLogger`] does when its channel is full.
+///
+/// # Examples
+/// The wrapped logger below stalls while the test holds its output, so the channel fills up:
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # use std::io;
+/// # use std::sync::{mpsc, Arc, Mutex};
+/// # use std::thread;
+/// # use std::time::Duration;
+/// #[derive(Clone, Default)]
+/// struct Output(Arc<Mutex<Vec<u8>>>);
+///
+/// impl io::Write for Output {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0.lock().unwrap().write(buf)
+///     }
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let run = |policy: OverflowPolicy, records: usize| {
+///     let output = Output::default();
+///     let config = ConfigBuilder::new().set_formatter(Some("[level] [message]")).build();
+///     let wrapped = WriteLogger::new(LevelFilter::Info, config, output.clone());
+///     let (logger, guard) = AsyncLogger::new(wrapped, 2, policy).unwrap();
+///
+///     let held = output.0.lock().unwrap();
+///     let (done, finished) = mpsc::channel();
+///     let logging = thread::spawn(move || {
+///         for i in 0..records {
+///             logger.log(&Record::builder().level(Level::Info).args(format_args!("record {}", i)).build());
+///         }
+///         done.send(()).unwrap();
+///         logger
+///     });
+///     let blocked = finished.recv_timeout(Duration::from_millis(200)).is_err();
+///     drop(held);
+///
+///     let logger = logging.join().unwrap();
+///     let dropped = logger.dropped();
+///     drop(guard);
+///     let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+///     let lines: Vec<String> = output.lines().map(str::to_string).collect();
+///     (blocked, dropped, lines)
+/// };
+/// let records = |count: usize| -> Vec<String> { (0..count).map(|i| format!("[INFO] record {}", i)).collect() };
+///
+/// // Block waits for the worker, and nothing is lost
+/// let (blocked, dropped, lines) = run(OverflowPolicy::Block, 10);
+/// assert!(blocked);
+/// assert_eq!(dropped, 0);
+/// assert_eq!(lines, records(10));
+///
+/// // Unbounded never waits, and nothing is lost
+/// let (blocked, dropped, lines) = run(OverflowPolicy::Unbounded, 100);
+/// assert!(!blocked);
+/// assert_eq!(dropped, 0);
+/// assert_eq!(lines, records(100));
+///
+/// // Drop never waits, and keeps what fits into the channel and the worker, here at most 3
+/// let (blocked, dropped, mut lines) = run(OverflowPolicy::Drop, 10);
+/// assert!(!blocked);
+/// assert!(dropped >= 7);
+/// let warning = format!("[WARN] dropped {} messages", dropped);
+/// let position = lines.iter().position(|line| *line == warning).expect("no dropped line");
+/// lines.remove(position);
+/// assert_eq!(lines, records(10 - dropped));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Block the logging thread until the worker catches up
+    #[default]
+    Block,
+    /// Drop the record and count it. The worker periodically logs how many records were dropped.
+    Drop,
+    /// Ignore the capacity and let the queue grow without bound
+    Unbounded,
+}
+
+/// A record with all borrowed data copied, so it can be sent to the worker thread.
+struct OwnedRecord {
+    level: Level,
+    target: String,
+    args: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    origin: RecordOrigin,
+    #[cfg(feature = "kv")]
+    key_values: kv::OwnedKeyValues,
+}
+
+impl OwnedRecord {
+    fn new(record: &Record<'_>) -> OwnedRecord {
+        OwnedRecord {
+            level: record.level(),
+            target: record.target().to_string(),
+            args: record.args().to_string(),
+            module_path: record.module_path().map(str::to_string),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+            origin: RecordOrigin::capture(),
+            #[cfg(feature = "kv")]
+            key_values: kv::OwnedKeyValues::new(record),
+        }
+    }
+
+    fn log(self, logger: &dyn SharedLogger) {
+        with_origin(self.origin, || {
+            let mut builder = Record::builder();
+            builder
+                .level(self.level)
+                .target(&self.target)
+                .module_path(self.module_path.as_deref())
+                .file(self.file.as_deref())
+                .line(self.line);
+            #[cfg(feature = "kv")]
+            builder.key_values(&self.key_values);
+            logger.log(&builder.args(format_args!("{}", self.args)).build())
+        });
+    }
+}
+
+#[cfg(feature = "kv")]
+mod kv {
+    use log::kv::{Error, Key, Source, ToValue, Value, VisitSource};
+    use log::Record;
+
+    /// A key-value with the value copied, keeping booleans and numbers apart from strings.
+    enum OwnedValue {
+        Bool(bool),
+        I64(i64),
+        U64(u64),
+        F64(f64),
+        Str(String),
+    }
+
+    impl ToValue for OwnedValue {
+        fn to_value(&self) -> Value<'_> {
+            match self {
+                OwnedValue::Bool(value) => Value::from(*value),
+                OwnedValue::I64(value) => Value::from(*value),
+                OwnedValue::U64(value) => Value::from(*value),
+                OwnedValue::F64(value) => Value::from(*value),
+                OwnedValue::Str(value) => Value::from(value.as_str()),
+            }
+        }
+    }
+
+    /// The key-values of a record, copied so they can be sent to the worker thread.
+    pub(super) struct OwnedKeyValues(Vec<(String, OwnedValue)>);
+
+    impl OwnedKeyValues {
+        pub(super) fn new(record: &Record<'_>) -> OwnedKeyValues {
+            struct Visitor<'a>(&'a mut Vec<(String, OwnedValue)>);
+
+            impl<'kvs> VisitSource<'kvs> for Visitor<'_> {
+                fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+                    let value = if let Some(value) = value.to_bool() {
+                        OwnedValue::Bool(value)
+                    } else if let Some(value) = value.to_i64() {
+                        OwnedValue::I64(value)
+                    } else if let Some(value) = value.to_u64() {
+                        OwnedValue::U64(value)
+                    } else if let Some(value) = value.to_f64() {
+                        OwnedValue::F64(value)
+                    } else {
+                        OwnedValue::Str(value.to_string())
+                    };
+                    self.0.push((key.as_str().to_string(), value));
+                    Ok(())
+                }
+            }
+
+            let mut key_values = Vec::new();
+            let _ = record.key_values().visit(&mut Visitor(&mut key_values));
+            OwnedKeyValues(key_values)
+        }
+    }
+
+    impl Source for OwnedKeyValues {
+        fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), Error> {
+            for (key, value) in &self.0 {
+                visitor.visit_pair(Key::from_str(key), value.to_value())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+enum Message {
+    Record(Box<OwnedRecord>),
+    Flush(Sender<()>),
+    Shutdown,
+}
+
+enum MessageSender {
+    Bounded(SyncSender<Message>),
+    Unbounded(Sender<Message>),
+}
+
+/// Guard returned when creating an [`AsyncLogger`].
+///
+/// Dropping it processes all queued records, flushes the wrapped logger and joins the
+/// worker thread. Keep it alive until the end of `main`, records logged after it was
+/// dropped are discarded.
+#[must_use = "dropping the guard stops the logging worker"]
+pub struct WorkerGuard {
+    sender: MessageSender,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        let _ = match &self.sender {
+            MessageSender::Bounded(sender) => sender.send(Message::Shutdown),
+            MessageSender::Unbounded(sender) => sender.send(Message::Shutdown),
+        };
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The AsyncLogger struct. Wraps another logger and performs its I/O on a dedicated thread.
+///
+/// Records are copied into a channel by `log()`, so the calling thread never waits on
+/// the wrapped logger's output. The time and thread fields are captured when the record
+/// is logged, not when it is written.
+pub struct AsyncLogger {
+    level: LevelHandle,
+    config: Option<ConfigHandle>,
+    sender: MessageSender,
+    policy: OverflowPolicy,
+    dropped: Arc<AtomicUsize>,
+    stats: Option<LoggerStats>,
+}
+
+impl AsyncLogger {
+    /// init function. Globally initializes the AsyncLogger as the one and only used log facility.
+    ///
+    /// Takes the logger to wrap, the channel capacity and the `OverflowPolicy` as arguments.
+    /// The wrapped logger's `LevelHandle`, if any, keeps controlling the level.
+    /// Fails like [`AsyncLogger::new`], or if another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _guard = AsyncLogger::init(
+    ///     SimpleLogger::new(LevelFilter::Info, Config::default()),
+    ///     1024,
+    ///     OverflowPolicy::Drop,
+    /// );
+    /// # }
+    /// ```
+    pub fn init(
+        logger: Box<dyn SharedLogger>,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Result<WorkerGuard, InitError> {
+        let (logger, guard) = AsyncLogger::new(logger, capacity, policy)?;
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(guard)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Takes the logger to wrap, the channel capacity and the `OverflowPolicy` as arguments.
+    /// Returns the logger together with the `WorkerGuard` that has to be kept alive.
+    /// Fails if the worker thread cannot be spawned, or with `ErrorKind::InvalidInput` if
+    /// `capacity` is 0 and the policy is not `OverflowPolicy::Unbounded`, as a channel
+    /// without capacity would make every record wait for the worker.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let (async_logger, _guard) = AsyncLogger::new(
+    ///     TermLogger::new(LevelFilter::Info, Config::default(), TerminalMode::Mixed, ColorChoice::Auto),
+    ///     1024,
+    ///     OverflowPolicy::Block,
+    /// )
+    /// .expect("failed to spawn the logging thread");
+    ///
+    /// let err = AsyncLogger::new(SimpleLogger::new(LevelFilter::Info, Config::default()), 0, OverflowPolicy::Drop)
+    ///     .err()
+    ///     .unwrap();
+    /// assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    /// assert!(AsyncLogger::new(SimpleLogger::new(LevelFilter::Info, Config::default()), 0, OverflowPolicy::Unbounded).is_ok());
+    /// # }
+    /// ```
+    ///
+    /// With the `kv` feature, the key-values of a record are passed on to the wrapped logger:
+    /// ```
+    /// # #[cfg(feature = "kv")]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{kv::Value, Log, Record};
+    /// let wrapped = TestLogger::new(
+    ///     LevelFilter::Info,
+    ///     ConfigBuilder::new().set_format(FormatFlags::LEVEL).set_json_output(true).build(),
+    /// );
+    /// let captured = wrapped.captured();
+    /// let (logger, guard) = AsyncLogger::new(wrapped, 16, OverflowPolicy::Block).unwrap();
+    ///
+    /// let kvs = [("user", Value::from("alice")), ("attempt", Value::from(3)), ("admin", Value::from(false))];
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("login")).key_values(&kvs).build());
+    /// drop(guard);
+    ///
+    /// assert!(captured.take_records()[0]
+    ///     .formatted
+    ///     .ends_with(r#""kv":{"user":"alice","attempt":3,"admin":false}}"#));
+    /// # }
+    /// ```
+    pub fn new(
+        logger: Box<dyn SharedLogger>,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> io::Result<(Box<AsyncLogger>, WorkerGuard)> {
+        let level = logger
+            .level_handle()
+            .unwrap_or_else(|| LevelHandle::new(logger.level(), logger.config().as_deref()));
+        let config = logger.config_handle().or_else(|| {
+            logger
+                .config()
+                .map(|config| ConfigHandle::new(Config::clone(&config), level.clone()))
+        });
+        if capacity == 0 && policy != OverflowPolicy::Unbounded {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the channel capacity of an AsyncLogger must be at least 1",
+            ));
+        }
+        let stats = logger.stats();
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let (sender, guard_sender, receiver) = match policy {
+            OverflowPolicy::Unbounded => {
+                let (sender, receiver) = mpsc::channel();
+                (
+                    MessageSender::Unbounded(sender.clone()),
+                    MessageSender::Unbounded(sender),
+                    receiver,
+                )
+            }
+            OverflowPolicy::Block | OverflowPolicy::Drop => {
+                let (sender, receiver) = mpsc::sync_channel(capacity);
+                (
+                    MessageSender::Bounded(sender.clone()),
+                    MessageSender::Bounded(sender),
+                    receiver,
+                )
+            }
+        };
+
+        let worker_dropped = dropped.clone();
+        let worker = thread::Builder::new()
+            .name("sp_log2-async".to_string())
+            .spawn(move || run_worker(logger, receiver, worker_dropped))?;
+
+        Ok((
+            Box::new(AsyncLogger {
+                level,
+                config,
+                sender,
+                policy,
+                dropped,
+                stats,
+            }),
+            WorkerGuard {
+                sender: guard_sender,
+                worker: Some(worker),
+            },
+        ))
+    }
+
+    /// Returns the number of records dropped so far because the channel was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, message: Message) {
+        match &self.sender {
+            MessageSender::Unbounded(sender) => {
+                let _ = sender.send(message);
+            }
+            MessageSender::Bounded(sender) => match self.policy {
+                OverflowPolicy::Drop => {
+                    if let Err(TrySendError::Full(_)) = sender.try_send(message) {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                _ => {
+                    let _ = sender.send(message);
+                }
+            },
+        }
+    }
+}
+
+fn run_worker(
+    logger: Box<dyn SharedLogger>,
+    receiver: Receiver<Message>,
+    dropped: Arc<AtomicUsize>,
+) {
+    let mut reported = 0;
+
+    for message in receiver {
+        let total = dropped.load(Ordering::Relaxed);
+        if total > reported {
+            logger.log(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("sp_log2")
+                    .args(format_args!("dropped {} messages", total - reported))
+                    .build(),
+            );
+            reported = total;
+        }
+
+        match message {
+            Message::Record(record) => record.log(&*logger),
+            Message::Flush(done) => {
+                logger.flush();
+                let _ = done.send(());
+            }
+            Message::Shutdown => {
+                logger.flush();
+                break;
+            }
+        }
+    }
+}
+
+impl Log for AsyncLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        match &self.config {
+            Some(config) => metadata_enabled(&config.get(), metadata, self.level.get_level()),
+            None => metadata.level() <= self.level.get_level(),
+        }
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            self.send(Message::Record(Box::new(OwnedRecord::new(record))));
+        }
+    }
+
+    fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        let message = Message::Flush(done);
+        let _ = match &self.sender {
+            MessageSender::Bounded(sender) => sender.send(message),
+            MessageSender::Unbounded(sender) => sender.send(message),
+        };
+        let _ = wait.recv();
+    }
+}
+
+impl SharedLogger for AsyncLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        self.config.as_ref().map(ConfigHandle::get)
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        self.config.clone()
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    /// Returns the statistics of the wrapped logger. Records dropped because the channel
+    /// was full never reach it, they are counted by `dropped` instead.
+    fn stats(&self) -> Option<LoggerStats> {
+        self.stats.clone()
+    }
+
+    /// Waits at most `timeout` for the queued records to be written and the wrapped
+    /// logger to be flushed. Records queued after the call are not waited for.
+    fn try_flush(&self, timeout: Duration) -> Result<(), FlushError> {
+        let deadline = Instant::now() + timeout;
+        let (done, wait) = mpsc::channel();
+        let mut message = Message::Flush(done);
+        match &self.sender {
+            MessageSender::Unbounded(sender) => {
+                let _ = sender.send(message);
+            }
+            // A full channel cannot take the flush request before the worker made room
+            MessageSender::Bounded(sender) => loop {
+                match sender.try_send(message) {
+                    Ok(()) | Err(TrySendError::Disconnected(_)) => break,
+                    Err(TrySendError::Full(returned)) if Instant::now() < deadline => {
+                        message = returned;
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                    Err(TrySendError::Full(_)) => return Err(FlushError::TimedOut),
+                }
+            },
+        }
+        match wait.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            // The worker is gone once its guard was dropped, nothing is left to flush
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => Ok(()),
+            Err(RecvTimeoutError::Timeout) => Err(FlushError::TimedOut),
+        }
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
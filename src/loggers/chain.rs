@@ -0,0 +1,183 @@
+use crate::{CombinedLogger, FlushGuard, InitError, SharedLogger};
+use log::SetLoggerError;
+use std::fmt;
+
+/// Constructs one of the loggers of a [`LoggerChain`].
+pub type LoggerFactory = Box<dyn FnOnce() -> Result<Box<dyn SharedLogger>, InitError>>;
+
+#[derive(Default)]
+/// Tries to construct several loggers in order, and uses the first one that succeeds,
+/// e.g. a file logger if the log directory is writable, else the platform logger, else
+/// stderr.
+///
+/// Every logger is given a name, so the app can tell which one was chosen and why the
+/// ones before it failed, see [`ChainReport`].
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// # use std::io;
+/// # use std::sync::{Arc, Mutex};
+/// let output = Arc::new(Mutex::new(Vec::new()));
+/// let stderr = output.clone();
+/// let config = ConfigBuilder::new().set_formatter(Some("[level] [message]")).build();
+///
+/// let (guard, report) = LoggerChain::new()
+///     .then("file", || {
+///         // e.g. FileLogger::try_new(...) on a read-only log directory
+///         Err(io::Error::new(io::ErrorKind::PermissionDenied, "read-only directory").into())
+///     })
+///     .then("stderr", move || Ok(WriteLogger::new_shared(LevelFilter::Info, config, stderr)))
+///     .init_with_guard()
+///     .unwrap();
+///
+/// assert_eq!(report.chosen(), "stderr");
+/// assert_eq!(report.errors().len(), 1);
+/// for (name, err) in report.errors() {
+///     log::warn!("falling back from the {} logger: {}", name, err);
+/// }
+/// drop(guard);
+///
+/// let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+/// assert_eq!(
+///     output,
+///     "[WARN] falling back from the file logger: failed to create logger: read-only directory\n"
+/// );
+/// ```
+///
+/// If no logger can be constructed, all the errors are returned:
+/// ```
+/// # use sp_log2::*;
+/// # use std::io;
+/// let result = LoggerChain::new()
+///     .then("file", || Err(io::Error::from(io::ErrorKind::PermissionDenied).into()))
+///     .then("syslog", || Err(io::Error::from(io::ErrorKind::NotFound).into()))
+///     .build();
+///
+/// match result {
+///     Err(ChainError::Exhausted(errors)) => {
+///         let names: Vec<&str> = errors.iter().map(|(name, _)| name.as_str()).collect();
+///         assert_eq!(names, ["file", "syslog"]);
+///     }
+///     _ => panic!("expected every logger to fail"),
+/// }
+/// ```
+pub struct LoggerChain {
+    factories: Vec<(String, LoggerFactory)>,
+}
+
+impl LoggerChain {
+    /// Creates an empty chain.
+    pub fn new() -> LoggerChain {
+        LoggerChain::default()
+    }
+
+    /// Adds `factory` under `name`, to be tried if all the loggers added before it fail.
+    pub fn then<F>(mut self, name: impl Into<String>, factory: F) -> LoggerChain
+    where
+        F: FnOnce() -> Result<Box<dyn SharedLogger>, InitError> + 'static,
+    {
+        self.factories.push((name.into(), Box::new(factory)));
+        self
+    }
+
+    /// Constructs the loggers in order, and returns the first one that succeeds, without
+    /// installing it.
+    ///
+    /// The loggers after it are not constructed. Fails with [`ChainError::Exhausted`] if
+    /// none of the loggers could be constructed.
+    pub fn build(self) -> Result<(Box<dyn SharedLogger>, ChainReport), ChainError> {
+        let mut errors = Vec::new();
+        for (name, factory) in self.factories {
+            match factory() {
+                Ok(logger) => {
+                    let report = ChainReport {
+                        chosen: name,
+                        errors,
+                    };
+                    return Ok((logger, report));
+                }
+                Err(err) => errors.push((name, err)),
+            }
+        }
+        Err(ChainError::Exhausted(errors))
+    }
+
+    /// Same as [`LoggerChain::build`], but installs the chosen logger globally, and returns
+    /// a [`FlushGuard`] that flushes it when dropped.
+    ///
+    /// The logger is installed like with [`CombinedLogger::init_with_guard`], so its level
+    /// directives and `LevelHandle` are taken into account.
+    pub fn init_with_guard(self) -> Result<(FlushGuard, ChainReport), ChainError> {
+        let (logger, report) = self.build()?;
+        let guard = CombinedLogger::init_with_guard(vec![logger])?;
+        Ok((guard, report))
+    }
+}
+
+impl fmt::Debug for LoggerChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.factories.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+/// Which logger of a [`LoggerChain`] was chosen, and why the ones before it failed.
+pub struct ChainReport {
+    chosen: String,
+    errors: Vec<(String, InitError)>,
+}
+
+impl ChainReport {
+    /// Returns the name of the logger that was chosen.
+    pub fn chosen(&self) -> &str {
+        &self.chosen
+    }
+
+    /// Returns the names and errors of the loggers tried before the chosen one, in order.
+    pub fn errors(&self) -> &[(String, InitError)] {
+        &self.errors
+    }
+}
+
+/// Error returned by [`LoggerChain::build`] and [`LoggerChain::init_with_guard`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ChainError {
+    /// None of the loggers could be constructed, with the names and errors in order
+    Exhausted(Vec<(String, InitError)>),
+    /// Another logger was already installed globally
+    SetLogger(SetLoggerError),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Exhausted(errors) => {
+                write!(f, "no logger could be created")?;
+                for (name, err) in errors {
+                    write!(f, "; {}: {}", name, err)?;
+                }
+                Ok(())
+            }
+            ChainError::SetLogger(err) => write!(f, "failed to set logger: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChainError::Exhausted(_) => None,
+            ChainError::SetLogger(err) => Some(err),
+        }
+    }
+}
+
+impl From<SetLoggerError> for ChainError {
+    fn from(err: SetLoggerError) -> Self {
+        ChainError::SetLogger(err)
+    }
+}
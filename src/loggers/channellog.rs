@@ -0,0 +1,254 @@
+//! Module providing the ChannelLogger Implementation
+
+use super::logging::{
+    format_record, level_enabled, metadata_enabled, with_origin, write_time, LoggerState,
+    PreparedRecord, RecordOrigin,
+};
+use crate::{Config, ConfigHandle, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+
+/// A formatted record sent by a `ChannelLogger`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMessage {
+    pub level: Level,
+    pub target: String,
+    /// The time the record was logged, in the configured time format
+    pub timestamp: String,
+    /// The message of the record, without any formatting applied
+    pub message: String,
+    /// The record as rendered by the `Config`, without the trailing line ending
+    pub formatted: String,
+}
+
+enum ChannelSender {
+    Bounded(SyncSender<LogMessage>),
+    Unbounded(Sender<LogMessage>),
+}
+
+/// The ChannelLogger struct. Provides a Logger implementation that sends formatted records over a channel
+///
+/// Meant for applications displaying their own log, e.g. a log pane in a GUI, which
+/// read the `Receiver` on their own schedule. Sending never blocks or panics: records
+/// are dropped and counted if a bounded channel is full or the receiver is gone.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # fn main() {
+/// let (logger, receiver) = ChannelLogger::new(LevelFilter::Info, Config::default());
+/// logger.log(&Record::builder().level(Level::Warn).target("app").args(format_args!("low memory")).build());
+///
+/// let message = receiver.try_recv().unwrap();
+/// assert_eq!(message.level, Level::Warn);
+/// assert_eq!(message.message, "low memory");
+/// assert!(message.formatted.ends_with("low memory"));
+/// # }
+/// ```
+pub struct ChannelLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    sender: ChannelSender,
+    state: Mutex<LoggerState>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl ChannelLogger {
+    /// init function. Globally initializes the ChannelLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level` and `Config` as arguments and returns the `Receiver` of an
+    /// unbounded channel. Fails if another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// if let Ok(receiver) = ChannelLogger::init(LevelFilter::Info, Config::default()) {
+    ///     log::info!("ready");
+    ///     for message in receiver.try_iter() {
+    ///         println!("{}", message.formatted);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn init(
+        log_level: LevelFilter,
+        config: Config,
+    ) -> Result<Receiver<LogMessage>, SetLoggerError> {
+        let (logger, receiver) = ChannelLogger::new(log_level, config);
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(receiver)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Takes the desired `Level` and `Config` as arguments. Returns the logger together with
+    /// the `Receiver` of an unbounded channel.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let (channel_logger, receiver) = ChannelLogger::new(LevelFilter::Info, Config::default());
+    /// let _ = CombinedLogger::init(vec![
+    ///     FileLogger::new(LevelFilter::Debug, Config::default(), "my_rust_bin.log", None),
+    ///     channel_logger,
+    /// ]);
+    /// # }
+    /// ```
+    pub fn new(
+        log_level: LevelFilter,
+        config: Config,
+    ) -> (Box<ChannelLogger>, Receiver<LogMessage>) {
+        let (sender, receiver) = mpsc::channel();
+        (
+            ChannelLogger::with_sender(log_level, config, ChannelSender::Unbounded(sender)),
+            receiver,
+        )
+    }
+
+    /// Same as [`ChannelLogger::new`], but uses a channel holding at most `capacity` records.
+    ///
+    /// Records logged while the channel is full are dropped and counted, see [`ChannelLogger::dropped`].
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let (logger, receiver) = ChannelLogger::bounded(LevelFilter::Info, Config::default(), 1);
+    /// for _ in 0..3 {
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("tick")).build());
+    /// }
+    ///
+    /// assert_eq!(receiver.try_iter().count(), 1);
+    /// assert_eq!(logger.dropped(), 2);
+    /// # }
+    /// ```
+    pub fn bounded(
+        log_level: LevelFilter,
+        config: Config,
+        capacity: usize,
+    ) -> (Box<ChannelLogger>, Receiver<LogMessage>) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        (
+            ChannelLogger::with_sender(log_level, config, ChannelSender::Bounded(sender)),
+            receiver,
+        )
+    }
+
+    fn with_sender(
+        log_level: LevelFilter,
+        config: Config,
+        sender: ChannelSender,
+    ) -> Box<ChannelLogger> {
+        let level = LevelHandle::new(log_level, Some(&config));
+        Box::new(ChannelLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            sender,
+            state: Mutex::new(LoggerState::default()),
+            dropped: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Returns the number of records dropped so far because the channel was full or disconnected.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, config: &Config, record: &Record<'_>) -> Result<(), std::io::Error> {
+        // The timestamp and the formatted line have to show the same time
+        let (timestamp, formatted) = with_origin(RecordOrigin::current(), || {
+            let mut formatted = Vec::new();
+            let written = format_record(config, record, &mut formatted)?;
+            Ok::<_, std::io::Error>((write_time(config)?, written.then_some(formatted)))
+        })?;
+        let formatted = match formatted {
+            Some(formatted) => formatted,
+            None => return Ok(()),
+        };
+
+        let message = LogMessage {
+            level: record.level(),
+            target: record.target().to_string(),
+            timestamp,
+            message: record.args().to_string(),
+            formatted: String::from_utf8_lossy(&formatted)
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+        };
+        let sent = match &self.sender {
+            ChannelSender::Bounded(sender) => sender.try_send(message).is_ok(),
+            ChannelSender::Unbounded(sender) => sender.send(message).is_ok(),
+        };
+        if !sent {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+impl Log for ChannelLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut state = self.state.lock().unwrap();
+            let result = state.log(&config, record, |record| self.send(&config, record));
+            state.report(&config, "ChannelLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let mut state = self.state.lock().unwrap();
+        let config = self.config.get();
+        let result = state.flush(|record| self.send(&config, record));
+        state.report(&config, "ChannelLogger", result);
+    }
+}
+
+impl SharedLogger for ChannelLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.state.lock().unwrap().stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -1,12 +1,226 @@
-use crate::{Config, SharedLogger};
+use super::handle::{from_usize, level_changed, level_generation};
+use super::logging::{max_level, PreparedRecord};
+use crate::{Config, FlushError, FlushGuard, FormatFlags, LevelHandle, LoggerStats, SharedLogger};
 use log::{set_boxed_logger, set_max_level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Identifies a logger inside a `CombinedLogger`, to remove it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LoggerId(u64);
+
+struct Loggers {
+    loggers: RwLock<Vec<(LoggerId, Box<dyn SharedLogger>)>>,
+    next_id: AtomicU64,
+    /// Set once the CombinedLogger was installed with `log::set_boxed_logger`
+    global: AtomicBool,
+    /// The level of the loggers in the low 8 bits, the union of their `FormatFlags` in the
+    /// next 16 bits, and the level generation they were computed at in the others
+    cached: AtomicU64,
+}
+
+impl Loggers {
+    fn new(loggers: Vec<(LoggerId, Box<dyn SharedLogger>)>) -> Loggers {
+        Loggers {
+            next_id: AtomicU64::new(loggers.len() as u64),
+            loggers: RwLock::new(loggers),
+            global: AtomicBool::new(false),
+            cached: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Returns the most verbose level of the loggers, recomputed only after a level of
+    /// any logger changed.
+    fn level(&self) -> LevelFilter {
+        self.level_and_format().0
+    }
+
+    /// Returns the level of the loggers, and the union of the `FormatFlags` of their
+    /// configs, recomputed only after a level or a config of any logger changed.
+    fn level_and_format(&self) -> (LevelFilter, FormatFlags) {
+        let generation = level_generation() as u64;
+        let cached = self.cached.load(Ordering::Acquire);
+        if cached >> 24 == generation & (u64::MAX >> 24) {
+            return (
+                from_usize((cached & 0xff) as usize),
+                FormatFlags::from_bits((cached >> 8) as u16),
+            );
+        }
+
+        let (level, format) = self.loggers.read().unwrap().iter().fold(
+            (LevelFilter::Off, FormatFlags::empty()),
+            |(level, format), (_, log)| match log.config() {
+                Some(config) => (
+                    level.max(max_level(&config, log.level())),
+                    format | config.format,
+                ),
+                None => (level.max(log.level()), format),
+            },
+        );
+        self.cached.store(
+            generation << 24 | u64::from(format.bits()) << 8 | level as u64,
+            Ordering::Release,
+        );
+        (level, format)
+    }
+
+    fn update_max_level(&self) {
+        if self.global.load(Ordering::Relaxed) {
+            set_max_level(self.level());
+        }
+    }
+}
 
 /// The CombinedLogger struct. Provides a Logger implementation that proxies multiple Loggers as one.
 ///
 /// The purpose is to allow multiple Loggers to be set globally
 pub struct CombinedLogger {
-    level: LevelFilter,
-    logger: Vec<Box<dyn SharedLogger>>,
+    loggers: Arc<Loggers>,
+    /// Whether the loggers share the rendered fields, see [`CombinedLogger::new_shared`]
+    shared: bool,
+}
+
+/// A handle to add loggers to and remove them from a running `CombinedLogger`.
+///
+/// Returned by [`CombinedLogger::init`] and [`CombinedLogger::handle`]. Handles are cheap
+/// to clone and can be sent to other threads.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # use log::Log;
+/// # fn main() {
+/// let combined_logger = CombinedLogger::new(vec![SimpleLogger::new(LevelFilter::Warn, Config::default())]);
+/// let handle = combined_logger.handle();
+///
+/// // Known only once the configuration was read
+/// let test_logger = TestLogger::new(LevelFilter::Debug, Config::default());
+/// let captured = test_logger.captured();
+/// let id = handle.add(test_logger);
+/// assert_eq!(combined_logger.level(), LevelFilter::Debug);
+///
+/// combined_logger.log(&log::Record::builder().level(Level::Debug).args(format_args!("config loaded")).build());
+/// assert!(captured.contains(Level::Debug, "config loaded"));
+///
+/// assert!(handle.remove(id).is_some());
+/// assert!(handle.remove(id).is_none());
+/// assert_eq!(combined_logger.level(), LevelFilter::Warn);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CombinedHandle {
+    loggers: Arc<Loggers>,
+}
+
+impl CombinedHandle {
+    /// Adds a logger, which receives every record logged from now on.
+    ///
+    /// If the `CombinedLogger` is installed globally, `log::set_max_level` is updated
+    /// to include the level of the new logger.
+    pub fn add(&self, logger: Box<dyn SharedLogger>) -> LoggerId {
+        let id = LoggerId(self.loggers.next_id.fetch_add(1, Ordering::Relaxed));
+        self.loggers.loggers.write().unwrap().push((id, logger));
+        level_changed();
+        self.loggers.update_max_level();
+        id
+    }
+
+    /// Flushes and removes the logger with the given id, returning it, or `None` if it
+    /// was already removed.
+    ///
+    /// If the `CombinedLogger` is installed globally, `log::set_max_level` is recomputed
+    /// from the remaining loggers.
+    ///
+    /// The logger is flushed after it was taken out, so the other loggers are not blocked
+    /// by a slow flush, and the flush itself may log through the `CombinedLogger`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Metadata, Record};
+    /// # use std::sync::{Arc, OnceLock};
+    /// /// Reports through the other loggers when it is flushed
+    /// struct Reporting(Arc<OnceLock<Box<CombinedLogger>>>);
+    /// impl Log for Reporting {
+    ///     fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+    ///     fn log(&self, _: &Record<'_>) {}
+    ///     fn flush(&self) {
+    ///         let combined = self.0.get().unwrap();
+    ///         combined.log(&Record::builder().level(Level::Info).args(format_args!("flushed")).build());
+    ///     }
+    /// }
+    /// impl SharedLogger for Reporting {
+    ///     fn level(&self) -> LevelFilter { LevelFilter::Info }
+    ///     fn config(&self) -> Option<Arc<Config>> { None }
+    ///     fn as_log(self: Box<Self>) -> Box<dyn Log> { self }
+    /// }
+    ///
+    /// let test_logger = TestLogger::new(LevelFilter::Info, Config::default());
+    /// let captured = test_logger.captured();
+    /// let combined = Arc::new(OnceLock::new());
+    /// combined.get_or_init(|| CombinedLogger::new(vec![test_logger]));
+    /// let handle = combined.get().unwrap().handle();
+    ///
+    /// let id = handle.add(Box::new(Reporting(combined.clone())));
+    /// assert!(handle.remove(id).is_some());
+    /// assert!(captured.contains(Level::Info, "flushed"));
+    /// ```
+    pub fn remove(&self, id: LoggerId) -> Option<Box<dyn SharedLogger>> {
+        let removed = {
+            let mut loggers = self.loggers.loggers.write().unwrap();
+            let index = loggers.iter().position(|(logger_id, _)| *logger_id == id)?;
+            loggers.remove(index).1
+        };
+        level_changed();
+        // Flushed without the lock, so the other loggers keep logging meanwhile
+        removed.flush();
+        self.loggers.update_max_level();
+        Some(removed)
+    }
+
+    /// Returns the ids of the current loggers, in the order they were added.
+    pub fn ids(&self) -> Vec<LoggerId> {
+        let loggers = self.loggers.loggers.read().unwrap();
+        loggers.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Returns the `LevelHandle` of every current logger, in the order they were added.
+    ///
+    /// Loggers that do not support runtime level changes yield `None`.
+    pub fn level_handles(&self) -> Vec<Option<LevelHandle>> {
+        let loggers = self.loggers.loggers.read().unwrap();
+        loggers.iter().map(|(_, log)| log.level_handle()).collect()
+    }
+
+    /// Returns the `LoggerStats` of every current logger, in the order they were added.
+    ///
+    /// The `CombinedLogger` keeps no statistics of its own, as every record is counted
+    /// once per inner logger. Loggers that do not keep statistics yield `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let combined_logger = CombinedLogger::new(vec![
+    ///     TestLogger::new(LevelFilter::Warn, Config::default()),
+    ///     TestLogger::new(LevelFilter::Info, Config::default()),
+    /// ]);
+    /// let stats = combined_logger.handle().stats();
+    ///
+    /// combined_logger.log(&Record::builder().level(Level::Info).args(format_args!("started")).build());
+    ///
+    /// assert_eq!(stats[0].as_ref().unwrap().total_written(), 0);
+    /// assert_eq!(stats[1].as_ref().unwrap().written(Level::Info), 1);
+    /// # }
+    /// ```
+    pub fn stats(&self) -> Vec<Option<LoggerStats>> {
+        let loggers = self.loggers.loggers.read().unwrap();
+        loggers.iter().map(|(_, log)| log.stats()).collect()
+    }
 }
 
 impl CombinedLogger {
@@ -17,26 +231,61 @@ impl CombinedLogger {
     /// All loggers need to implement `log::Log` and `logger::SharedLogger` and need to provide a way to be
     /// initialized without calling `set_logger`. All loggers of this library provide a `new(..)`` method
     /// for that purpose.
+    /// Returns a [`CombinedHandle`] to add and remove loggers later on, which also gives
+    /// access to the `LevelHandle`s of the loggers.
     /// Fails if another logger is already set globally.
     ///
     /// # Examples
     /// ```
-    /// # extern crate sp_log2
-    /// # use sp_log2:*;
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
     /// # use std::fs::File;
     /// # fn main() {
-    /// let _ = CombinedLogger::init(
-    ///             vec![
-    ///                 TermLogger::new(LevelFilter::Info, Config::default(), TerminalMode::Mixed, ColorChoice::Auto),
-    ///                 WriteLogger::new(LevelFilter::Info, Config::default(), File::create("my_rust_bin.log").unwrap())
-    ///             ]
-    ///         );
+    /// if let Ok(handle) = CombinedLogger::init(vec![TermLogger::new(
+    ///     LevelFilter::Info,
+    ///     Config::default(),
+    ///     TerminalMode::Mixed,
+    ///     ColorChoice::Auto,
+    /// )]) {
+    ///     // Later, once the path of the log file is known
+    ///     handle.add(WriteLogger::new(LevelFilter::Info, Config::default(), File::create("my_rust_bin.log").unwrap()));
+    /// }
     /// # }
     /// ```
-    pub fn init(logger: Vec<Box<dyn SharedLogger>>) -> Result<(), SetLoggerError> {
+    pub fn init(logger: Vec<Box<dyn SharedLogger>>) -> Result<CombinedHandle, SetLoggerError> {
+        let comblog = CombinedLogger::new(logger);
+        let handle = comblog.handle();
+        let level = comblog.level();
+        set_boxed_logger(comblog)?;
+        handle.loggers.global.store(true, Ordering::Relaxed);
+        set_max_level(level);
+        Ok(handle)
+    }
+
+    /// Same as [`CombinedLogger::init`], but returns a [`FlushGuard`] that flushes all
+    /// loggers when dropped. Hold it in `main`, so the last records are not lost on exit.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// fn main() {
+    ///     let _guard = CombinedLogger::init_with_guard(vec![
+    ///         TermLogger::new(LevelFilter::Warn, Config::default(), TerminalMode::Mixed, ColorChoice::Auto),
+    ///         FileLogger::new(LevelFilter::Info, Config::default(), "my_rust_bin.log", None),
+    ///     ]);
+    /// }
+    /// ```
+    pub fn init_with_guard(
+        logger: Vec<Box<dyn SharedLogger>>,
+    ) -> Result<FlushGuard, SetLoggerError> {
         let comblog = CombinedLogger::new(logger);
-        set_max_level(comblog.level());
-        set_boxed_logger(comblog)
+        let loggers = comblog.loggers.clone();
+        let level = comblog.level();
+        let guard = FlushGuard::install(comblog)?;
+        loggers.global.store(true, Ordering::Relaxed);
+        set_max_level(level);
+        Ok(guard)
     }
 
     /// allows to create a new logger, that can be independently used, no matter whats globally set.
@@ -45,14 +294,19 @@ impl CombinedLogger {
     /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
     ///
     /// Takes all used loggers as a Vector argument. The log level is automatically determined by the
-    /// lowest log level used by the given loggers.
+    /// lowest log level used by the given loggers, including their level directives, and follows
+    /// level changes made through their `LevelHandle`s.
+    ///
+    /// All loggers need to implement log::Log. More loggers can be added later on through
+    /// the [`handle`](CombinedLogger::handle).
     ///
-    /// All loggers need to implement log::Log.
+    /// A record is only passed to the loggers that are enabled for it, so a logger at
+    /// `LevelFilter::Off` never formats anything.
     ///
     /// # Examples
     /// ```
-    /// # extern crate sp_log2
-    /// # use sp_log2:*;
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
     /// # use std::fs::File;
     /// # fn main() {
     /// let combined_logger = CombinedLogger::new(
@@ -63,51 +317,222 @@ impl CombinedLogger {
     ///         );
     /// # }
     /// ```
+    ///
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let term = TestLogger::new(LevelFilter::Warn, Config::default());
+    /// let file = TestLogger::new(LevelFilter::Trace, Config::default());
+    /// let off = TestLogger::new(LevelFilter::Off, Config::default());
+    /// let (term_records, file_records, off_records) = (term.captured(), file.captured(), off.captured());
+    ///
+    /// let combined_logger = CombinedLogger::new(vec![term, file, off]);
+    /// assert_eq!(combined_logger.level(), LevelFilter::Trace);
+    ///
+    /// for level in [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace] {
+    ///     combined_logger.log(&Record::builder().level(level).args(format_args!("{}", level)).build());
+    /// }
+    ///
+    /// let levels = |records: CapturedRecords| {
+    ///     records.take_records().into_iter().map(|record| record.level).collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(levels(term_records), [Level::Error, Level::Warn]);
+    /// assert_eq!(
+    ///     levels(file_records),
+    ///     [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+    /// );
+    /// assert_eq!(levels(off_records), Vec::<Level>::new());
+    /// ```
+    ///
+    /// The level is cached, and recomputed once a level of a logger changed:
+    /// ```
+    /// # use sp_log2::*;
+    /// let file = TestLogger::new(LevelFilter::Warn, Config::default());
+    /// let config = file.config_handle().unwrap();
+    /// let combined_logger = CombinedLogger::new(vec![file]);
+    /// assert_eq!(combined_logger.level(), LevelFilter::Warn);
+    ///
+    /// config.modify(|builder| {
+    ///     builder.add_level_directive("myapp::db", LevelFilter::Debug);
+    /// });
+    /// assert_eq!(combined_logger.level(), LevelFilter::Debug);
+    ///
+    /// let handle = combined_logger.handle();
+    /// let id = handle.add(TestLogger::new(LevelFilter::Trace, Config::default()));
+    /// assert_eq!(combined_logger.level(), LevelFilter::Trace);
+    /// handle.remove(id);
+    /// assert_eq!(combined_logger.level(), LevelFilter::Debug);
+    /// ```
     #[must_use]
     pub fn new(logger: Vec<Box<dyn SharedLogger>>) -> Box<CombinedLogger> {
-        let mut log_level = LevelFilter::Off;
-        for log in &logger {
-            if log_level < log.level() {
-                log_level = log.level();
-            }
-        }
-
+        let loggers = logger
+            .into_iter()
+            .enumerate()
+            .map(|(id, logger)| (LoggerId(id as u64), logger))
+            .collect::<Vec<_>>();
         Box::new(CombinedLogger {
-            level: log_level,
-            logger,
+            loggers: Arc::new(Loggers::new(loggers)),
+            shared: false,
         })
     }
+
+    /// Same as [`CombinedLogger::new`], but renders the time, the file location and the
+    /// message of a record only once for all loggers, instead of once per logger.
+    ///
+    /// The record is passed to the loggers as a [`PreparedRecord`] through
+    /// [`SharedLogger::try_log_prepared`], prepared with the union of the `FormatFlags` of
+    /// their configs. The time is taken once for all loggers, and rendered once per time
+    /// format, the location once per location style. Each logger still applies its own
+    /// filters, layout, formatter and colors.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// let time_format = |format: &str| {
+    ///     ConfigBuilder::new().set_time_format_custom(format).set_formatter(Some("[time]")).build()
+    /// };
+    /// let term = TestLogger::new(LevelFilter::Info, time_format("%H:%M:%S%.9f"));
+    /// let file = TestLogger::new(LevelFilter::Info, time_format("%H:%M:%S%.9f"));
+    /// let yearly = TestLogger::new(LevelFilter::Info, time_format("%Y"));
+    /// let captured = [term.captured(), file.captured(), yearly.captured()];
+    ///
+    /// let combined_logger = CombinedLogger::new_shared(vec![term, file, yearly]);
+    /// combined_logger.log(&Record::builder().level(Level::Info).args(format_args!("started")).build());
+    ///
+    /// let times: Vec<String> = captured.iter().map(|records| records.take_records().remove(0).formatted).collect();
+    /// // The time was taken once, down to the nanosecond
+    /// assert_eq!(times[0], times[1]);
+    /// assert_eq!(times[2], chrono::Local::now().format("%Y").to_string());
+    /// ```
+    #[must_use]
+    pub fn new_shared(logger: Vec<Box<dyn SharedLogger>>) -> Box<CombinedLogger> {
+        let mut combined_logger = CombinedLogger::new(logger);
+        combined_logger.shared = true;
+        combined_logger
+    }
+
+    /// Returns a handle to add loggers to and remove them from this logger.
+    pub fn handle(&self) -> CombinedHandle {
+        CombinedHandle {
+            loggers: self.loggers.clone(),
+        }
+    }
+
+    /// Returns the `LevelHandle` of every inner logger, in the order they were added.
+    ///
+    /// Loggers that do not support runtime level changes yield `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let combined_logger = CombinedLogger::new(vec![
+    ///     SimpleLogger::new(LevelFilter::Warn, Config::default()),
+    ///     SimpleLogger::new(LevelFilter::Info, Config::default()),
+    /// ]);
+    /// let handles = combined_logger.level_handles();
+    ///
+    /// handles[0].as_ref().unwrap().set_level(LevelFilter::Debug);
+    /// assert_eq!(combined_logger.level(), LevelFilter::Debug);
+    /// # }
+    /// ```
+    pub fn level_handles(&self) -> Vec<Option<LevelHandle>> {
+        self.handle().level_handles()
+    }
 }
 
 impl Log for CombinedLogger {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level() <= self.level
+        metadata.level() <= self.loggers.level()
+            && self
+                .loggers
+                .loggers
+                .read()
+                .unwrap()
+                .iter()
+                .any(|(_, log)| log.enabled(metadata))
     }
 
     fn log(&self, record: &Record<'_>) {
-        if self.enabled(record.metadata()) {
-            for log in &self.logger {
+        let (level, format) = self.loggers.level_and_format();
+        if record.level() > level {
+            return;
+        }
+        if self.shared {
+            return self.log_prepared(&PreparedRecord::new(record, format));
+        }
+        for (_, log) in self.loggers.loggers.read().unwrap().iter() {
+            // Loggers at `LevelFilter::Off` or below the record's level are skipped
+            // before any formatting happens
+            if log.enabled(record.metadata()) {
                 log.log(record);
             }
         }
     }
 
+    /// Flushes the loggers in the order they were added.
     fn flush(&self) {
-        for log in &self.logger {
+        for (_, log) in self.loggers.loggers.read().unwrap().iter() {
             log.flush();
         }
     }
 }
 
+impl CombinedLogger {
+    /// Passes a prepared record to the loggers that are enabled for it.
+    fn log_prepared(&self, record: &PreparedRecord<'_>) {
+        let metadata = record.record().metadata();
+        for (_, log) in self.loggers.loggers.read().unwrap().iter() {
+            if log.enabled(metadata) {
+                log.try_log_prepared(record);
+            }
+        }
+    }
+}
+
 impl SharedLogger for CombinedLogger {
     fn level(&self) -> LevelFilter {
-        self.level
+        self.loggers.level()
     }
 
-    fn config(&self) -> Option<&Config> {
+    fn config(&self) -> Option<Arc<Config>> {
         None
     }
 
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        if record.record().level() <= self.loggers.level() {
+            self.log_prepared(record);
+        }
+    }
+
+    fn write_summary(&self) {
+        for (_, log) in self.loggers.loggers.read().unwrap().iter() {
+            log.write_summary();
+        }
+    }
+
+    fn try_flush(&self, timeout: Duration) -> Result<(), FlushError> {
+        let deadline = Instant::now() + timeout;
+        let errors: Vec<_> = self
+            .loggers
+            .loggers
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(id, log)| {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                log.try_flush(remaining).err().map(|err| (*id, err))
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(FlushError::Loggers(errors))
+        }
+    }
+
     fn as_log(self: Box<Self>) -> Box<dyn Log> {
         Box::new(*self)
     }
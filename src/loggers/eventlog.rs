@@ -0,0 +1,230 @@
+//! Module providing the EventLogLogger Implementation
+
+use super::logging::{format_record, level_enabled, metadata_enabled, LoggerState, PreparedRecord};
+use crate::{Config, ConfigHandle, InitError, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record};
+use std::ffi::c_void;
+use std::io;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+const EVENTLOG_ERROR_TYPE: u16 = 0x0001;
+const EVENTLOG_WARNING_TYPE: u16 = 0x0002;
+const EVENTLOG_INFORMATION_TYPE: u16 = 0x0004;
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn RegisterEventSourceW(server_name: *const u16, source_name: *const u16) -> *mut c_void;
+    fn DeregisterEventSource(event_log: *mut c_void) -> i32;
+    #[allow(clippy::too_many_arguments)]
+    fn ReportEventW(
+        event_log: *mut c_void,
+        event_type: u16,
+        category: u16,
+        event_id: u32,
+        user_sid: *mut c_void,
+        num_strings: u16,
+        data_size: u32,
+        strings: *const *const u16,
+        raw_data: *mut c_void,
+    ) -> i32;
+}
+
+/// A registered event source.
+struct EventSource(*mut c_void);
+
+// SAFETY: event log handles may be used from any thread
+unsafe impl Send for EventSource {}
+unsafe impl Sync for EventSource {}
+
+impl Drop for EventSource {
+    fn drop(&mut self) {
+        // SAFETY: the handle was returned by RegisterEventSourceW and is deregistered once
+        unsafe {
+            DeregisterEventSource(self.0);
+        }
+    }
+}
+
+/// Encodes `value` as a nul terminated UTF-16 string.
+fn to_wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(Some(0)).collect()
+}
+
+/// The EventLogLogger struct. Provides a Logger implementation that reports records to the Windows Event Log
+///
+/// Error records are reported as error events, Warn records as warning events and
+/// everything else as information events. The message is formatted with the `Config`.
+///
+/// The source name should be registered in the registry, e.g. by an installer, otherwise
+/// the Event Viewer shows the message together with a note about a missing description.
+pub struct EventLogLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    event_level: LevelFilter,
+    source: EventSource,
+    state: Mutex<LoggerState>,
+}
+
+impl EventLogLogger {
+    /// init function. Globally initializes the EventLogLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level`, `Config`, the event source name and the level of records
+    /// reported to the Event Log as arguments.
+    /// Fails if the event source cannot be registered or another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _ = EventLogLogger::init(LevelFilter::Info, Config::default(), "MyService", None);
+    /// # }
+    /// ```
+    pub fn init(
+        log_level: LevelFilter,
+        config: Config,
+        source_name: &str,
+        event_level: Option<LevelFilter>,
+    ) -> Result<LevelHandle, InitError> {
+        let logger = EventLogLogger::new(log_level, config, source_name, event_level)?;
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Records are reported if they pass both `log_level` and `event_level`. The latter stays
+    /// fixed while the former can be changed through the `LevelHandle`, e.g. to keep the
+    /// Event Log at Warn even if verbose logging is switched on for the whole process.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _ = CombinedLogger::init(vec![
+    ///     FileLogger::new(LevelFilter::Trace, Config::default(), "my_service.log", None),
+    ///     EventLogLogger::new(LevelFilter::Trace, Config::default(), "MyService", Some(LevelFilter::Warn)).unwrap(),
+    /// ]);
+    /// # }
+    /// ```
+    pub fn new(
+        log_level: LevelFilter,
+        config: Config,
+        source_name: &str,
+        event_level: Option<LevelFilter>,
+    ) -> io::Result<Box<EventLogLogger>> {
+        let source_name = to_wide(source_name);
+        // SAFETY: the source name is a valid nul terminated string, a null server name means the local computer
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), source_name.as_ptr()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let level = LevelHandle::new(log_level, Some(&config));
+        Ok(Box::new(EventLogLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            event_level: event_level.unwrap_or(LevelFilter::Trace),
+            source: EventSource(handle),
+            state: Mutex::new(LoggerState::default()),
+        }))
+    }
+
+    fn report(&self, config: &Config, record: &Record<'_>) -> io::Result<()> {
+        let mut message = Vec::new();
+        if !format_record(config, record, &mut message)? {
+            return Ok(());
+        }
+
+        let message = String::from_utf8_lossy(&message);
+        let message = to_wide(message.trim_end_matches(['\r', '\n']));
+        let strings = [message.as_ptr()];
+        let event_type = match record.level() {
+            Level::Error => EVENTLOG_ERROR_TYPE,
+            Level::Warn => EVENTLOG_WARNING_TYPE,
+            Level::Info | Level::Debug | Level::Trace => EVENTLOG_INFORMATION_TYPE,
+        };
+
+        // SAFETY: the handle is registered and `strings` points to one nul terminated string
+        let reported = unsafe {
+            ReportEventW(
+                self.source.0,
+                event_type,
+                0,
+                0,
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                ptr::null_mut(),
+            )
+        };
+        if reported == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Log for EventLogLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.event_level
+            && metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if record.level() > self.event_level {
+            return;
+        }
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut state = self.state.lock().unwrap();
+            let result = state.log(&config, record, |record| self.report(&config, record));
+            state.report(&config, "EventLogLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let config = self.config.get();
+        let mut state = self.state.lock().unwrap();
+        let result = state.flush(|record| self.report(&config, record));
+        state.report(&config, "EventLogLogger", result);
+    }
+}
+
+impl SharedLogger for EventLogLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level().min(self.event_level)
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.state.lock().unwrap().stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -1,30 +1,571 @@
 //! Module providing the FileLogger Implementation
 
-use super::logging::try_log;
-use crate::{Config, SharedLogger};
-use log::{set_boxed_logger, set_max_level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use super::logging::{
+    format_record, hostname, level_enabled, metadata_enabled, LogOutput, PreparedRecord,
+};
+use super::overflow::{lock_output, OverflowQueue};
+use crate::banner::{write_banner, write_summary};
+use crate::{
+    Config, ConfigHandle, FlushError, FlushGuard, InitError, LevelHandle, LoggerStats, SharedLogger,
+};
+use chrono::{Local, NaiveDate};
+use log::{set_boxed_logger, LevelFilter, Log, Metadata, Record};
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs::create_dir_all;
+use std::fs::read_dir;
 use std::fs::remove_file;
 use std::fs::rename;
 use std::fs::File;
 use std::fs::OpenOptions;
+use std::io::BufWriter;
+use std::io::Error;
 use std::io::ErrorKind;
 use std::io::Write;
-use std::sync::Mutex;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Controls when the buffered output of a [`FileLogger`] is written to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Flush after every record
+    #[default]
+    EveryRecord,
+    /// Flush after the given number of records
+    Records(usize),
+    /// Flush once at least the given number of bytes has been buffered
+    Bytes(usize),
+    /// Flush periodically from a background thread, which stops when the logger is dropped
+    Interval(Duration),
+}
+
+/// The currently open log file together with the number of bytes it holds.
+///
+/// The size is tracked in memory so that the rotation check does not need a
+/// `metadata()` syscall per record.
+struct LogFile {
+    /// `None` for a file handed over without its path, which cannot be rotated or reopened
+    path: Option<PathBuf>,
+    file: BufWriter<File>,
+    size: u64,
+    pending_records: usize,
+    pending_bytes: usize,
+}
+
+impl LogFile {
+    fn open(file_path: &Path, truncate: bool, header: Option<&str>) -> std::io::Result<LogFile> {
+        let mut open_options = OpenOptions::new();
+        open_options.create(true);
+        if truncate {
+            open_options.write(true).truncate(true);
+        } else {
+            open_options.append(true);
+        }
+
+        let file = open_options.open(file_path)?;
+        LogFile::from_file(file, Some(file_path.to_path_buf()), header)
+    }
+
+    fn from_file(
+        file: File,
+        path: Option<PathBuf>,
+        header: Option<&str>,
+    ) -> std::io::Result<LogFile> {
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let mut log_file = LogFile {
+            path,
+            file: BufWriter::new(file),
+            size,
+            pending_records: 0,
+            pending_bytes: 0,
+        };
+
+        if let Some(header) = header {
+            writeln!(log_file, "{}", header)?;
+            log_file.flush()?;
+        }
+
+        Ok(log_file)
+    }
+
+    /// Accounts for a finished record and flushes if the policy requires it.
+    fn record_written(&mut self, policy: FlushPolicy) -> std::io::Result<()> {
+        self.pending_records += 1;
+
+        let should_flush = match policy {
+            FlushPolicy::EveryRecord => true,
+            FlushPolicy::Records(records) => self.pending_records >= records,
+            FlushPolicy::Bytes(bytes) => self.pending_bytes >= bytes,
+            FlushPolicy::Interval(_) => false,
+        };
+
+        if should_flush {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Runs `f` with the advisory lock of the file held if `lock_file` is set, see
+    /// [`FileLoggerOptions::lock_file`].
+    ///
+    /// Buffered records are written before the lock is released.
+    fn locked<T>(&mut self, lock_file: bool, f: impl FnOnce(&mut LogFile) -> T) -> T {
+        #[cfg(feature = "file-lock")]
+        if lock_file {
+            self.lock();
+            let result = f(self);
+            // Buffered records must reach the file before another process may write
+            let _ = self.flush();
+            let _ = self.file.get_ref().unlock();
+            return result;
+        }
+        #[cfg(not(feature = "file-lock"))]
+        let _ = lock_file;
+        f(self)
+    }
+
+    /// Takes the lock of the file.
+    ///
+    /// If another process rotated the file while this one waited for the lock, the new
+    /// file at the path is opened and locked instead. The size is taken from the file,
+    /// as the other processes write to it too.
+    #[cfg(feature = "file-lock")]
+    fn lock(&mut self) {
+        let _ = self.file.get_ref().lock();
+        if let Some(path) = &self.path {
+            if !is_same_file(path, self.file.get_ref()) {
+                // The other process wrote the header and banner already
+                match LogFile::open(path, false, None) {
+                    Ok(file) => {
+                        let _ = file.file.get_ref().lock();
+                        *self = file;
+                    }
+                    Err(err) => eprintln!("Error reopening log file: {}", err),
+                }
+            }
+        }
+        if let Ok(metadata) = self.file.get_ref().metadata() {
+            self.size = metadata.len() + self.file.buffer().len() as u64;
+        }
+    }
+}
+
+/// Returns whether `path` still refers to the opened `file`.
+///
+/// Compares the inodes on Unix, and the lengths elsewhere.
+#[cfg(feature = "file-lock")]
+fn is_same_file(path: &Path, file: &File) -> bool {
+    let (Ok(at_path), Ok(opened)) = (std::fs::metadata(path), file.metadata()) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        at_path.dev() == opened.dev() && at_path.ino() == opened.ino()
+    }
+    #[cfg(not(unix))]
+    {
+        at_path.len() == opened.len()
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        self.pending_bytes += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.pending_records = 0;
+        self.pending_bytes = 0;
+        self.file.flush()
+    }
+}
+
+/// Called with the path of a log file after the [`FileLogger`] rotated it, see
+/// [`FileLoggerOptions::on_rotate`].
+pub type RotateHook = Arc<dyn Fn(&Path) + Send + Sync>;
+
+/// The [`RotateHook`] of the options, which cannot derive `Debug` itself.
+#[derive(Clone)]
+struct OnRotate(RotateHook);
+
+impl fmt::Debug for OnRotate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RotateHook")
+    }
+}
+
+/// Options controlling how the [`FileLogger`] opens its file.
+///
+/// The default opens the file in append mode and writes no header.
+#[derive(Debug, Clone, Default)]
+pub struct FileLoggerOptions {
+    truncate: bool,
+    header: Option<String>,
+    flush_policy: FlushPolicy,
+    max_total_size: Option<u64>,
+    on_rotate: Option<OnRotate>,
+    path_template: bool,
+    #[cfg(feature = "file-lock")]
+    lock_file: bool,
+}
+
+impl FileLoggerOptions {
+    /// Creates a new `FileLoggerOptions` with default values.
+    pub fn new() -> FileLoggerOptions {
+        FileLoggerOptions::default()
+    }
+
+    /// Truncates an existing log file when the logger is created instead of appending to it.
+    pub fn truncate(&mut self, truncate: bool) -> &mut FileLoggerOptions {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Sets a header line written whenever a log file is opened.
+    ///
+    /// The header is written once when the logger is created and again after every
+    /// rotation, so each file is self-describing.
+    pub fn header(&mut self, header: Option<String>) -> &mut FileLoggerOptions {
+        self.header = header;
+        self
+    }
+
+    /// Sets when buffered records are written to disk. Defaults to [`FlushPolicy::EveryRecord`].
+    ///
+    /// `Log::flush` always writes out everything, regardless of the policy.
+    ///
+    /// The policy only changes when the records reach the disk, not what is written:
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let dir = std::env::temp_dir().join(format!("sp_log2-flush-policy-{}", std::process::id()));
+    /// let config = ConfigBuilder::new().set_formatter(Some("[level] [target] [message]")).build();
+    /// let mut contents = Vec::new();
+    /// for (name, policy) in [
+    ///     ("every-record.log", FlushPolicy::EveryRecord),
+    ///     ("records.log", FlushPolicy::Records(7)),
+    /// ] {
+    ///     let mut options = FileLoggerOptions::new();
+    ///     options.truncate(true).flush_policy(policy);
+    ///     let path = dir.join(name);
+    ///     let logger = FileLogger::try_with_options(LevelFilter::Info, config.clone(), &path, Some(1000), &options)
+    ///         .unwrap();
+    ///     // Enough to rotate a few times, with records still buffered
+    ///     for i in 0..100 {
+    ///         let message = format!("record {} {}", i, "x".repeat(i % 13));
+    ///         logger.log(&Record::builder().level(Level::Info).target("app").args(format_args!("{}", message)).build());
+    ///     }
+    ///     logger.flush();
+    ///     drop(logger);
+    ///     contents.push((std::fs::read(&path).unwrap(), std::fs::read(dir.join(format!("{}.bak", name))).unwrap()));
+    /// }
+    /// assert!(!contents[0].0.is_empty());
+    /// assert_eq!(contents[0], contents[1]);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn flush_policy(&mut self, flush_policy: FlushPolicy) -> &mut FileLoggerOptions {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    /// Takes the path of the log file as a template. Defaults to `false`, i.e. the path
+    /// is used as it is, even if it contains `%`, `{` or `}`.
+    ///
+    /// In a template, the `%` specifiers of chrono, e.g. `%Y%m%d`, are replaced by the
+    /// local time, and `{pid}` and `{hostname}` by the process id and the hostname. Write
+    /// `%%`, `{{` and `}}` for a literal `%`, `{` and `}`. The template is expanded when
+    /// the file is opened and again when it is rotated, so a file named by date is
+    /// replaced by a file of the new date once it rotates. A path that is not valid UTF-8
+    /// is not a template and is used as it is.
+    ///
+    /// Creating the logger fails with [`std::io::ErrorKind::InvalidInput`] if the path is
+    /// not a valid template.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let mut options = FileLoggerOptions::new();
+    /// options.path_template(true);
+    ///
+    /// let dir = std::env::temp_dir().join(format!("sp_log2-template-{}", std::process::id()));
+    /// let template = dir.join("worker-%Y%m%d-{pid}-100%%.log");
+    /// let file_logger =
+    ///     FileLogger::try_with_options(LevelFilter::Info, Config::default(), &template, None, &options).unwrap();
+    ///
+    /// let expected = format!(
+    ///     "worker-{}-{}-100%.log",
+    ///     chrono::Local::now().format("%Y%m%d"),
+    ///     std::process::id()
+    /// );
+    /// assert_eq!(file_logger.path(), Some(dir.join(expected)));
+    /// assert!(file_logger.path().unwrap().exists());
+    ///
+    /// let err = FileLogger::try_with_options(LevelFilter::Info, Config::default(), "logs/{user}.log", None, &options);
+    /// assert_eq!(err.err().unwrap().kind(), std::io::ErrorKind::InvalidInput);
+    /// # drop(file_logger);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn path_template(&mut self, path_template: bool) -> &mut FileLoggerOptions {
+        self.path_template = path_template;
+        self
+    }
+
+    /// Limits the total size of the log file and its backups, in bytes.
+    ///
+    /// After each rotation, the files in the directory of the log file whose names
+    /// match its path are summed up, together with their `.bak` backups. With a
+    /// [path template](FileLoggerOptions::path_template), this includes e.g. the files of
+    /// previous days for `app-%Y%m%d.log`. Each `%` specifier only matches
+    /// what it can expand to, e.g. four digits for `%Y`, `{pid}` matches digits and
+    /// `{hostname}` the hostname of this machine, so other files in the directory are left
+    /// alone. The oldest of the matching files are deleted until the total is within
+    /// `max_total_size`. The file currently written to is never deleted, and files removed
+    /// by someone else in the meantime are skipped.
+    ///
+    /// Only applies if the `FileLogger` has a `max_size`, which triggers the rotations.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::time::{Duration, SystemTime};
+    /// # fn main() {
+    /// let dir = std::env::temp_dir().join(format!("sp_log2-total-size-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    ///
+    /// // Backups of previous days, each 100 bytes, and a file of someone else
+    /// let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+    /// for (i, name) in ["app-20240101.log", "app-20240102.log", "app-20240103.log"].iter().enumerate() {
+    ///     let file = std::fs::File::create(dir.join(name)).unwrap();
+    ///     file.set_len(100).unwrap();
+    ///     file.set_modified(week_ago + Duration::from_secs(i as u64 * 3600)).unwrap();
+    /// }
+    /// std::fs::write(dir.join("other.log"), [0; 500]).unwrap();
+    /// let notes = std::fs::File::create(dir.join("app-notes.log")).unwrap();
+    /// notes.set_len(500).unwrap();
+    /// notes.set_modified(week_ago - Duration::from_secs(3600)).unwrap();
+    ///
+    /// let mut options = FileLoggerOptions::new();
+    /// options.path_template(true).max_total_size(250);
+    /// let template = format!("{}/app-%Y%m%d.log", dir.display());
+    /// let logger = FileLogger::try_with_options(LevelFilter::Info, Config::default(), &template, Some(50), &options)
+    ///     .unwrap();
+    ///
+    /// // The second record rotates the file, which now holds more than 50 bytes
+    /// for _ in 0..2 {
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", "x".repeat(60))).build());
+    /// }
+    ///
+    /// let exists = |name: &str| dir.join(name).exists();
+    /// assert!(!exists("app-20240101.log"));
+    /// assert!(!exists("app-20240102.log"));
+    /// assert!(exists("app-20240103.log"));
+    /// assert!(exists("other.log"));
+    /// assert!(exists("app-notes.log"));
+    /// assert!(logger.path().unwrap().exists());
+    /// assert!(dir.join(format!("{}.bak", logger.path().unwrap().file_name().unwrap().to_str().unwrap())).exists());
+    /// # drop(logger);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    ///
+    /// Files of other processes match `{pid}`, but files with other names don't:
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::time::{Duration, SystemTime};
+    /// # fn main() {
+    /// let dir = std::env::temp_dir().join(format!("sp_log2-total-size-pid-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    ///
+    /// let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+    /// for name in ["audit.log", "1.log"] {
+    ///     let file = std::fs::File::create(dir.join(name)).unwrap();
+    ///     file.set_len(500).unwrap();
+    ///     file.set_modified(week_ago).unwrap();
+    /// }
+    ///
+    /// let mut options = FileLoggerOptions::new();
+    /// options.path_template(true).max_total_size(250);
+    /// let template = format!("{}/{{pid}}.log", dir.display());
+    /// let logger = FileLogger::try_with_options(LevelFilter::Info, Config::default(), &template, Some(50), &options)
+    ///     .unwrap();
+    /// for _ in 0..2 {
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", "x".repeat(60))).build());
+    /// }
+    ///
+    /// assert!(!dir.join("1.log").exists());
+    /// assert!(dir.join("audit.log").exists());
+    /// # drop(logger);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn max_total_size(&mut self, max_total_size: u64) -> &mut FileLoggerOptions {
+        self.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Sets a hook called with the path of every file that was rotated away, i.e. the
+    /// `.bak` backup, or the file of the previous date if the path template has one.
+    /// Use it e.g. to upload the file and delete it afterwards.
+    ///
+    /// The hook runs on a background thread owned by the logger, never on the thread
+    /// that logs, and without holding any lock of the logger, so logging continues into
+    /// the new file while it runs. Calls happen one at a time, in the order of the
+    /// rotations, each after the new file has been opened. A panic in the hook is caught
+    /// and reported on stderr, later rotations still call it. When the logger is dropped,
+    /// the thread finishes the pending calls and exits.
+    ///
+    /// The `.bak` backup is replaced by the next rotation, so a slow hook may see its
+    /// file replaced, and `max_total_size` may delete the file before the hook gets to it.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::{mpsc, Arc, Mutex};
+    /// # use std::time::Duration;
+    /// # fn main() {
+    /// let (sender, receiver) = mpsc::channel();
+    /// let sender = Mutex::new(sender);
+    /// let mut options = FileLoggerOptions::new();
+    /// options.truncate(true).on_rotate(Arc::new(move |path| {
+    ///     sender.lock().unwrap().send(path.to_path_buf()).unwrap();
+    /// }));
+    ///
+    /// let path = std::env::temp_dir().join(format!("sp_log2-on-rotate-{}.log", std::process::id()));
+    /// let logger = FileLogger::try_with_options(
+    ///     LevelFilter::Info,
+    ///     Config::default(),
+    ///     path.to_str().unwrap(),
+    ///     Some(10),
+    ///     &options,
+    /// )
+    /// .unwrap();
+    /// for _ in 0..2 {
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    /// }
+    ///
+    /// let rotated = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    /// assert_eq!(rotated.to_str().unwrap(), format!("{}.bak", path.display()));
+    /// # drop(logger);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// # std::fs::remove_file(&rotated).unwrap();
+    /// # }
+    /// ```
+    pub fn on_rotate(&mut self, hook: RotateHook) -> &mut FileLoggerOptions {
+        self.on_rotate = Some(OnRotate(hook));
+        self
+    }
+
+    /// Takes an advisory lock on the log file around each record write.
+    ///
+    /// Use this when several processes write to the same file, e.g. the old and new
+    /// instance of a daemon during a graceful restart, so records are not interleaved.
+    /// Flushing, reopening and writing the summary take the lock too.
+    ///
+    /// Rotation happens while the lock is held, and the size that triggers it counts the
+    /// records of all processes. A process that finds the file rotated by another one
+    /// once it has the lock writes to the new file instead of rotating again.
+    ///
+    /// Requires the `file-lock` feature.
+    ///
+    /// # Examples
+    /// Two loggers on the same file, as two processes would have:
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let path = std::env::temp_dir().join(format!("sp_log2-lock-file-{}.log", std::process::id()));
+    /// let mut options = FileLoggerOptions::new();
+    /// options.truncate(true).lock_file(true);
+    /// let config = || ConfigBuilder::new().set_formatter(Some("[message]")).build();
+    /// let a = FileLogger::try_with_options(LevelFilter::Info, config(), &path, Some(100), &options).unwrap();
+    /// let b = FileLogger::try_with_options(LevelFilter::Info, config(), &path, Some(100), &options).unwrap();
+    ///
+    /// // Every line is 60 bytes, so the third line of a file rotates it
+    /// let log = |logger: &FileLogger, name: &str| {
+    ///     let message = format!("{}{}", name, ".".repeat(57));
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+    /// };
+    /// let names = |path: &std::path::Path| -> Vec<String> {
+    ///     let content = std::fs::read_to_string(path).unwrap();
+    ///     content.lines().map(|line| line[..2].to_string()).collect()
+    /// };
+    /// let backup = path.with_extension("log.bak");
+    ///
+    /// log(&a, "a1");
+    /// log(&a, "a2");
+    /// log(&a, "a3");
+    /// // `b` follows the rotation of `a` instead of writing into the backup
+    /// log(&b, "b1");
+    /// assert_eq!(names(&path), ["a3", "b1"]);
+    /// assert_eq!(names(&backup), ["a1", "a2"]);
+    ///
+    /// // `b` rotates, counting the line of `a`, and `a` follows
+    /// log(&b, "b2");
+    /// log(&a, "a4");
+    /// assert_eq!(names(&path), ["b2", "a4"]);
+    /// assert_eq!(names(&backup), ["a3", "b1"]);
+    /// # drop((a, b));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// # std::fs::remove_file(&backup).unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "file-lock")]
+    pub fn lock_file(&mut self, lock_file: bool) -> &mut FileLoggerOptions {
+        self.lock_file = lock_file;
+        self
+    }
+
+    /// Returns whether the file is locked around writes, never without the `file-lock` feature.
+    fn locks_file(&self) -> bool {
+        #[cfg(feature = "file-lock")]
+        return self.lock_file;
+        #[cfg(not(feature = "file-lock"))]
+        false
+    }
+}
 
 /// The FileLogger struct. Provides a Logger implementation for structs implementing `Write`, e.g. File
 pub struct FileLogger {
-    level: LevelFilter,
-    config: Config,
-    writable: Mutex<File>,
+    level: LevelHandle,
+    config: ConfigHandle,
+    writable: Arc<Mutex<LogOutput<LogFile>>>,
     max_size: Option<u64>, // Maximum size in bytes, if specified
-    file_path: String,
+    path_template: Option<String>,
+    options: FileLoggerOptions,
+    rotated: Option<Sender<PathBuf>>,
+    /// Stops the thread of `FlushPolicy::Interval` once the logger is dropped
+    _flush_stop: Option<Sender<()>>,
+    overflow: OverflowQueue,
 }
 
 impl FileLogger {
     /// init function. Globally initializes the FileLogger as the one and only used log facility.
     ///
-    /// Takes the desired `Level`, `Config` and `file_path` and `max_size` struct as arguments. They cannot be changed later on.
-    /// Fails if another Logger was already initialized.
+    /// Takes the desired `Level`, `Config` and `file_path` and `max_size` struct as arguments. The level can be
+    /// changed later on through the returned `LevelHandle`. `file_path` is used as it is,
+    /// see [`FileLoggerOptions::path_template`] to name the file by date or process id.
+    /// Fails if the log file cannot be opened or another Logger was already initialized.
     ///
     /// # Examples
     /// ```
@@ -34,46 +575,308 @@ impl FileLogger {
     /// let _ = FileLogger::init(LevelFilter::Info, Config::default(), "my_rust_bin.log", Some(1024 * 1024 * 10));
     /// # }
     /// ```
+    ///
+    /// With [`ConfigBuilder::set_nonblocking`](crate::ConfigBuilder::set_nonblocking),
+    /// records logged while a record is written are queued, and rotate the file like any
+    /// other record once they are written:
+    /// ```
+    /// # use sp_log2::*;
+    /// # use std::fmt;
+    /// struct Nested;
+    /// impl fmt::Display for Nested {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         log::warn!("nested");
+    ///         f.write_str("outer")
+    ///     }
+    /// }
+    ///
+    /// let dir = std::env::temp_dir().join(format!("sp_log2_nested_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let path = dir.join("nested.log");
+    /// let config = ConfigBuilder::new().set_format(FormatFlags::LEVEL).set_nonblocking(true).build();
+    /// FileLogger::init(LevelFilter::Info, config, &path, Some(10)).unwrap();
+    ///
+    /// log::info!("{}", Nested);
+    /// log::logger().flush();
+    ///
+    /// let backup = std::fs::read_to_string(dir.join("nested.log.bak")).unwrap();
+    /// assert_eq!(backup.trim(), "[INFO] outer");
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), "[WARN] nested");
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
     pub fn init(
         log_level: LevelFilter,
         config: Config,
-        file_path: &str,
+        file_path: impl AsRef<Path>,
         max_size: Option<u64>,
-    ) -> Result<(), SetLoggerError> {
-        set_max_level(log_level);
-        set_boxed_logger(Self::new(log_level, config, file_path, max_size))
+    ) -> Result<LevelHandle, InitError> {
+        Self::init_with_options(
+            log_level,
+            config,
+            file_path,
+            max_size,
+            &FileLoggerOptions::default(),
+        )
     }
 
-    /// Rotates the log file by deleting the current log and creating a new one if it exceeds the maximum size.
-    fn rotate(&self) {
-        if let Some(max_size) = self.max_size {
-            let writable = self.writable.lock().unwrap();
+    /// Same as [`FileLogger::init`], but opens the file according to the given `FileLoggerOptions`.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let mut options = FileLoggerOptions::new();
+    /// options
+    ///     .truncate(true)
+    ///     .header(Some(format!("my_rust_bin v{}", env!("CARGO_PKG_VERSION"))));
+    /// let _ = FileLogger::init_with_options(LevelFilter::Info, Config::default(), "my_rust_bin.log", None, &options);
+    /// # }
+    /// ```
+    pub fn init_with_options(
+        log_level: LevelFilter,
+        config: Config,
+        file_path: impl AsRef<Path>,
+        max_size: Option<u64>,
+        options: &FileLoggerOptions,
+    ) -> Result<LevelHandle, InitError> {
+        let logger = Self::try_with_options(log_level, config, file_path, max_size, options)?;
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
+    }
+
+    /// Same as [`FileLogger::init`], but returns a [`FlushGuard`] that flushes the file
+    /// when dropped. Hold it in `main`, so the last records are not lost on exit.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// fn main() {
+    ///     let _guard = FileLogger::init_with_guard(LevelFilter::Info, Config::default(), "my_rust_bin.log", None);
+    /// }
+    /// ```
+    pub fn init_with_guard(
+        log_level: LevelFilter,
+        config: Config,
+        file_path: impl AsRef<Path>,
+        max_size: Option<u64>,
+    ) -> Result<FlushGuard, InitError> {
+        let logger = Self::try_with_options(
+            log_level,
+            config,
+            file_path,
+            max_size,
+            &FileLoggerOptions::default(),
+        )?;
+        let handle = logger.level.clone();
+        let guard = FlushGuard::install(logger)?;
+        handle.install();
+        Ok(guard)
+    }
 
-            // Check current log file size
-            if let Ok(metadata) = writable.metadata() {
-                if metadata.len() > max_size {
-                    // Close current file by dropping the lock
-                    drop(writable);
+    /// Rotates the log file by moving the current log to a backup and creating a new one if it exceeds the maximum size.
+    ///
+    /// Must be called with the writer lock held, so no record can be written
+    /// into the file while it is being renamed and reopened.
+    fn rotate(&self, writable: &mut LogFile) {
+        if let (Some(max_size), Some(active_path)) = (self.max_size, writable.path.clone()) {
+            if writable.size > max_size {
+                let _ = writable.flush();
 
-                    let backup_path = format!("{}.bak", self.file_path);
+                // A template with a date moves on to a new file once the date changed
+                let file_path = self
+                    .path_template
+                    .as_deref()
+                    .and_then(|template| expand_path_template(template).ok())
+                    .map_or_else(|| active_path.clone(), PathBuf::from);
+                let mut rotated = None;
+                if file_path == active_path {
+                    let backup_path = backup_path(&file_path);
 
-                    if let Err(err) = rename(&self.file_path, &backup_path) {
-                        eprintln!("Error moving log file to backup: {}", err);
+                    match rename(&file_path, &backup_path) {
+                        Ok(()) => rotated = Some(backup_path),
+                        Err(err) => eprintln!("Error moving log file to backup: {}", err),
+                    }
+                } else {
+                    rotated = Some(active_path);
+                    if let Err(err) = create_parent_dirs(&file_path) {
+                        eprintln!("Error creating log directory: {}", err);
                     }
+                }
+
+                // Reopen log file
+                match LogFile::open(&file_path, false, self.options.header.as_deref()) {
+                    Ok(new_file) => {
+                        // Other processes see the new file once the old one is closed
+                        #[cfg(feature = "file-lock")]
+                        if self.options.lock_file {
+                            let _ = new_file.file.get_ref().lock();
+                        }
 
-                    // Reopen log file
-                    let new_file = OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open(&self.file_path)
-                        .unwrap();
+                        *writable = new_file;
+                        if let Err(err) = write_banner(&self.config.get(), writable) {
+                            eprintln!("Error writing log banner: {}", err);
+                        }
+                        if let Some(max_total_size) = self.options.max_total_size {
+                            self.remove_old_files(writable, max_total_size);
+                        }
+                    }
+                    Err(err) => eprintln!("Error reopening log file: {}", err),
+                }
 
-                    *self.writable.lock().unwrap() = new_file;
+                if let (Some(sender), Some(rotated)) = (&self.rotated, rotated) {
+                    let _ = sender.send(rotated);
                 }
             }
         }
     }
 
+    /// Deletes the oldest files of this logger until their total size is within `max_total_size`.
+    ///
+    /// Errors are ignored, as files may be removed by someone else at any time.
+    fn remove_old_files(&self, active: &LogFile, max_total_size: u64) {
+        let active_path = match &active.path {
+            Some(path) => path,
+            None => return,
+        };
+        let dir = match active_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let file_name = |path: &Path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(str::to_string)
+        };
+        let pattern = match &self.path_template {
+            Some(template) => {
+                file_name(Path::new(template)).and_then(|name| NamePattern::parse(&name))
+            }
+            None => file_name(active_path).map(|name| NamePattern::literal(&name)),
+        };
+        let pattern = match pattern {
+            Some(pattern) => pattern,
+            None => return,
+        };
+
+        let entries = match read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            let is_own = pattern.matches(name)
+                || name
+                    .strip_suffix(".bak")
+                    .is_some_and(|name| pattern.matches(name));
+            if !is_own || Some(entry.file_name().as_os_str()) == active_path.file_name() {
+                continue;
+            }
+            if let Ok(metadata) = entry.metadata() {
+                if metadata.is_file() {
+                    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    files.push((modified, entry.path(), metadata.len()));
+                }
+            }
+        }
+
+        let mut total = active.size + files.iter().map(|(_, _, size)| size).sum::<u64>();
+        files.sort();
+        for (_, path, size) in files {
+            if total <= max_total_size {
+                break;
+            }
+            match remove_file(&path) {
+                Ok(()) => total -= size,
+                Err(err) if err.kind() == ErrorKind::NotFound => total -= size,
+                Err(err) => eprintln!("Error removing old log file {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    /// Writes a single record, rotating the file first if it grew too large.
+    fn write_record(
+        &self,
+        config: &Config,
+        writer: &mut LogFile,
+        record: &Record<'_>,
+    ) -> std::io::Result<()> {
+        self.rotate(writer);
+        if !format_record(config, record, writer)? {
+            return Ok(());
+        }
+        writer.record_written(self.options.flush_policy)
+    }
+
+    /// Writes a line taken from the overflow queue like a formatted record.
+    fn write_line(&self, writer: &mut LogFile, line: &[u8]) -> std::io::Result<()> {
+        self.rotate(writer);
+        writer.write_all(line)?;
+        writer.record_written(self.options.flush_policy)
+    }
+
+    /// Reopens the log file at the configured path.
+    ///
+    /// Intended for external rotation tools like `logrotate`, which rename the file
+    /// and expect the process to reopen it (usually on `SIGHUP`). The new handle is
+    /// swapped in while holding the writer lock, so no concurrently logged record is lost.
+    /// The file is always opened in append mode and the header, if any, is written again.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let file_logger = FileLogger::new(LevelFilter::Info, Config::default(), "my_rust_bin.log", None);
+    /// // ... after the file has been moved away
+    /// file_logger.reopen().unwrap();
+    /// # }
+    /// ```
+    pub fn reopen(&self) -> std::io::Result<()> {
+        let lock_file = self.options.locks_file();
+        let mut output = self.writable.lock().unwrap();
+        output.writer.locked(lock_file, |writer| writer.flush())?;
+        let file_path = output.writer.path.clone().ok_or_else(|| {
+            Error::new(
+                ErrorKind::Unsupported,
+                "the log file was handed over without a path",
+            )
+        })?;
+        output.writer = LogFile::open(&file_path, false, self.options.header.as_deref())?;
+        let config = self.config.get();
+        output
+            .writer
+            .locked(lock_file, |writer| write_banner(&config, writer))
+    }
+
+    /// Returns the path of the file currently written to, with the template of the
+    /// path expanded, or `None` if the file was handed over without a path.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let mut options = FileLoggerOptions::new();
+    /// options.path_template(true);
+    /// let file_logger =
+    ///     FileLogger::try_with_options(LevelFilter::Info, Config::default(), "logs/worker-{pid}.log", None, &options)
+    ///         .unwrap();
+    /// println!("Logging to {}", file_logger.path().unwrap().display());
+    /// # }
+    /// ```
+    pub fn path(&self) -> Option<PathBuf> {
+        self.writable.lock().unwrap().writer.path.clone()
+    }
+
     /// allows to create a new logger, that can be independently used, no matter what is globally set.
     ///
     /// no macros are provided for this case and you probably
@@ -81,6 +884,10 @@ impl FileLogger {
     ///
     /// Takes the desired `Level`, `Config` and `Write` struct as arguments. They cannot be changed later on.
     ///
+    /// # Panics
+    ///
+    /// Panics if the log file cannot be opened. Use [`FileLogger::try_new`] to handle the error instead.
+    ///
     /// # Examples
     /// ```
     /// # extern crate sp_log2;
@@ -94,63 +901,672 @@ impl FileLogger {
     pub fn new(
         log_level: LevelFilter,
         config: Config,
-        file_path: &str,
+        file_path: impl AsRef<Path>,
         max_size: Option<u64>,
     ) -> Box<Self> {
-        let backup_path = format!("{}.bak", file_path);
+        Self::try_new(log_level, config, file_path, max_size).unwrap()
+    }
+
+    /// Fallible version of [`FileLogger::new`].
+    ///
+    /// Missing parent directories of `file_path` are created. Returns an error if
+    /// the directories or the log file cannot be created.
+    ///
+    /// `file_path` is used as it is. To name the file by date, process id or hostname,
+    /// see [`FileLoggerOptions::path_template`].
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let file_logger = FileLogger::try_new(LevelFilter::Info, Config::default(), "logs/my_rust_bin.log", None)
+    ///     .expect("failed to open log file");
+    ///
+    /// // Not a template, `%` and braces are kept
+    /// let dir = std::env::temp_dir().join(format!("sp_log2-literal-{}", std::process::id()));
+    /// let path = dir.join("100%-{done}.log");
+    /// let file_logger = FileLogger::try_new(LevelFilter::Info, Config::default(), &path, None).unwrap();
+    /// assert_eq!(file_logger.path(), Some(path.clone()));
+    /// assert!(path.exists());
+    /// # drop(file_logger);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    ///
+    /// Rotation happens under the same lock as writing, so records logged from several
+    /// threads around a rotation all end up either in the backup or in the new file:
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::collections::BTreeSet;
+    /// # use std::sync::{mpsc, Arc, Mutex};
+    /// # use std::thread;
+    /// # fn main() {
+    /// let dir = std::env::temp_dir().join(format!("sp_log2-rotate-stress-{}", std::process::id()));
+    /// let path = dir.join("app.log");
+    /// let config = ConfigBuilder::new().set_formatter(Some("[message]")).build();
+    /// for _ in 0..5 {
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     let sender = Mutex::new(sender);
+    ///     let mut options = FileLoggerOptions::new();
+    ///     options.truncate(true).on_rotate(Arc::new(move |path| {
+    ///         sender.lock().unwrap().send(path.to_path_buf()).unwrap();
+    ///     }));
+    ///
+    ///     // 8 threads log 250 lines of 6 bytes, 12000 bytes in total, so the file rotates once
+    ///     let logger = FileLogger::try_with_options(LevelFilter::Info, config.clone(), &path, Some(9000), &options)
+    ///         .unwrap();
+    ///     drop(options);
+    ///     thread::scope(|scope| {
+    ///         for t in 0..8 {
+    ///             let logger = &logger;
+    ///             scope.spawn(move || {
+    ///                 for i in 0..250 {
+    ///                     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}-{:03}", t, i)).build());
+    ///                 }
+    ///             });
+    ///         }
+    ///     });
+    ///     drop(logger);
+    ///
+    ///     // The hook holds the last sender, so this ends once the logger is gone
+    ///     let rotated: Vec<_> = receiver.iter().collect();
+    ///     assert_eq!(rotated, [dir.join("app.log.bak")]);
+    ///     let mut files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    ///     files.sort();
+    ///     assert_eq!(files, ["app.log", "app.log.bak"]);
+    ///
+    ///     let mut lines = Vec::new();
+    ///     for file in ["app.log.bak", "app.log"] {
+    ///         let content = std::fs::read_to_string(dir.join(file)).unwrap();
+    ///         lines.extend(content.lines().map(str::to_string));
+    ///     }
+    ///     let expected: BTreeSet<String> =
+    ///         (0..8).flat_map(|t| (0..250).map(move |i| format!("{}-{:03}", t, i))).collect();
+    ///     assert_eq!(lines.len(), expected.len());
+    ///     assert_eq!(lines.into_iter().collect::<BTreeSet<_>>(), expected);
+    /// }
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn try_new(
+        log_level: LevelFilter,
+        config: Config,
+        file_path: impl AsRef<Path>,
+        max_size: Option<u64>,
+    ) -> Result<Box<Self>, std::io::Error> {
+        Self::try_with_options(
+            log_level,
+            config,
+            file_path,
+            max_size,
+            &FileLoggerOptions::default(),
+        )
+    }
+
+    /// Same as [`FileLogger::try_new`], but opens the file according to the given `FileLoggerOptions`.
+    pub fn try_with_options(
+        log_level: LevelFilter,
+        config: Config,
+        file_path: impl AsRef<Path>,
+        max_size: Option<u64>,
+        options: &FileLoggerOptions,
+    ) -> Result<Box<Self>, std::io::Error> {
+        // Paths that are not valid UTF-8 are taken as they are
+        let path_template = file_path
+            .as_ref()
+            .to_str()
+            .filter(|_| options.path_template)
+            .map(str::to_string);
+        let file_path = match &path_template {
+            Some(template) => PathBuf::from(expand_path_template(template)?),
+            None => file_path.as_ref().to_path_buf(),
+        };
+        create_parent_dirs(&file_path)?;
+
+        let backup_path = backup_path(&file_path);
 
         // Attempt to remove the existing .bak file, if it exists
         if let Err(err) = remove_file(&backup_path) {
             if err.kind() != ErrorKind::NotFound {
                 eprintln!(
                     "Failed to remove existing backup file {}: {}",
-                    backup_path, err
+                    backup_path.display(),
+                    err
                 );
             }
         }
 
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)
-            .unwrap();
+        let mut file = LogFile::open(&file_path, options.truncate, options.header.as_deref())?;
+        write_banner(&config, &mut file)?;
+        let rotated = match &options.on_rotate {
+            Some(OnRotate(hook)) => Some(spawn_rotate_hook(hook.clone())?),
+            None => None,
+        };
+        let writable = Arc::new(Mutex::new(LogOutput::new(file)));
+        let flush_stop = match options.flush_policy {
+            FlushPolicy::Interval(interval) => Some(spawn_interval_flush(
+                &writable,
+                interval,
+                options.locks_file(),
+            )?),
+            _ => None,
+        };
+        Ok(Self::from_output(
+            log_level,
+            config,
+            writable,
+            max_size,
+            path_template,
+            options,
+            rotated,
+            flush_stop,
+        ))
+    }
+
+    /// Creates a `FileLogger` writing to an already opened `file`, e.g. one opened with
+    /// special flags or permissions.
+    ///
+    /// The file is written from its current position on. `path` is the path of the file,
+    /// used to rotate it once it exceeds `max_size` and by [`FileLogger::reopen`]. It is
+    /// taken as it is, not as a template. Without a path, the file is never rotated.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let path = std::env::temp_dir().join(format!("sp_log2-with-file-{}.log", std::process::id()));
+    /// let file = std::fs::File::create(&path).unwrap();
+    ///
+    /// let file_logger = FileLogger::with_file(LevelFilter::Info, Config::default(), file, None, Some(10));
+    /// assert_eq!(file_logger.path(), None);
+    /// for _ in 0..3 {
+    ///     file_logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+    /// }
+    ///
+    /// // Without a path, the file is not rotated
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_file(
+        log_level: LevelFilter,
+        config: Config,
+        file: File,
+        path: Option<PathBuf>,
+        max_size: Option<u64>,
+    ) -> Box<Self> {
+        let options = FileLoggerOptions::default();
+        let mut file = LogFile {
+            path,
+            size: file.metadata().map(|m| m.len()).unwrap_or(0),
+            file: BufWriter::new(file),
+            pending_records: 0,
+            pending_bytes: 0,
+        };
+        if let Err(err) = write_banner(&config, &mut file) {
+            eprintln!("Error writing log banner: {}", err);
+        }
+        let writable = Arc::new(Mutex::new(LogOutput::new(file)));
+        Self::from_output(
+            log_level, config, writable, max_size, None, &options, None, None,
+        )
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    fn from_output(
+        log_level: LevelFilter,
+        config: Config,
+        writable: Arc<Mutex<LogOutput<LogFile>>>,
+        max_size: Option<u64>,
+        path_template: Option<String>,
+        options: &FileLoggerOptions,
+        rotated: Option<Sender<PathBuf>>,
+        flush_stop: Option<Sender<()>>,
+    ) -> Box<Self> {
+        let overflow = OverflowQueue::new(writable.lock().unwrap().state.stats.clone());
+        let level = LevelHandle::new(log_level, Some(&config));
         Box::new(Self {
-            level: log_level,
-            config,
-            writable: Mutex::new(file),
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            writable,
             max_size,
-            file_path: file_path.to_string(),
+            path_template,
+            options: options.clone(),
+            rotated,
+            _flush_stop: flush_stop,
+            overflow,
         })
     }
 }
 
+/// Starts the thread calling `hook` with the rotated files sent through the returned channel.
+///
+/// The thread exits once the sender, held by the logger, is dropped.
+fn spawn_rotate_hook(hook: RotateHook) -> std::io::Result<Sender<PathBuf>> {
+    let (sender, receiver) = mpsc::channel::<PathBuf>();
+    thread::Builder::new()
+        .name("sp_log2-on-rotate".to_string())
+        .spawn(move || {
+            for path in receiver {
+                if panic::catch_unwind(AssertUnwindSafe(|| hook(&path))).is_err() {
+                    eprintln!("sp_log2: on_rotate hook panicked for {}", path.display());
+                }
+            }
+        })?;
+    Ok(sender)
+}
+
+/// Starts the thread flushing `writable` every `interval`, for `FlushPolicy::Interval`.
+///
+/// The thread exits once the returned sender, held by the logger, is dropped.
+fn spawn_interval_flush(
+    writable: &Arc<Mutex<LogOutput<LogFile>>>,
+    interval: Duration,
+    lock_file: bool,
+) -> std::io::Result<Sender<()>> {
+    let (sender, stop) = mpsc::channel::<()>();
+    let writable = Arc::downgrade(writable);
+    thread::Builder::new()
+        .name("sp_log2-flush".to_string())
+        .spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+                let Some(writable) = writable.upgrade() else {
+                    break;
+                };
+                let mut output = writable.lock().unwrap();
+                let _ = output.writer.locked(lock_file, |writer| writer.flush());
+            }
+        })?;
+    Ok(sender)
+}
+
+/// Returns the path of the backup of `file_path`, with `.bak` appended to the file name.
+fn backup_path(file_path: &Path) -> PathBuf {
+    let mut file_name = file_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".bak");
+    file_path.with_file_name(file_name)
+}
+
+/// Creates the missing parent directories of `file_path`.
+fn create_parent_dirs(file_path: &Path) -> std::io::Result<()> {
+    // A bare filename has an empty parent, which needs no directory to be created
+    if let Some(parent) = file_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            create_dir_all(parent)?;
+        }
+    }
+    Ok(())
+}
+
+/// The file name part of a path template, matching the names of files it expands to.
+struct NamePattern {
+    parts: Vec<NamePart>,
+}
+
+enum NamePart {
+    Literal(char),
+    /// A `%` specifier or `{pid}`, matching `min..=max` chars of the given class
+    Run {
+        class: CharClass,
+        min: usize,
+        max: usize,
+    },
+}
+
+/// The chars a [`NamePart::Run`] consists of.
+#[derive(Default)]
+struct CharClass {
+    digits: bool,
+    letters: bool,
+    others: Vec<char>,
+}
+
+impl CharClass {
+    fn insert(&mut self, c: char) {
+        if c.is_ascii_digit() {
+            self.digits = true;
+        } else if c.is_alphabetic() {
+            self.letters = true;
+        } else if !self.others.contains(&c) {
+            self.others.push(c);
+        }
+    }
+
+    fn contains(&self, c: char) -> bool {
+        if c.is_ascii_digit() {
+            self.digits
+        } else if c.is_alphabetic() {
+            self.letters
+        } else {
+            self.others.contains(&c)
+        }
+    }
+}
+
+impl NamePattern {
+    /// Parses the file name part of a path template.
+    ///
+    /// Returns `None` if the template is invalid, in which case no file is considered
+    /// to belong to the logger.
+    fn parse(template: &str) -> Option<NamePattern> {
+        let mut parts = Vec::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '%' if chars.peek() == Some(&'%') => {
+                    chars.next();
+                    parts.push(NamePart::Literal('%'));
+                }
+                '%' => {
+                    // The padding flags, width and modifiers up to the specifier letter
+                    let mut specifier = String::from('%');
+                    for c in chars.by_ref() {
+                        specifier.push(c);
+                        if c.is_ascii_alphabetic() {
+                            break;
+                        }
+                    }
+                    parts.push(specifier_part(&specifier)?);
+                }
+                '{' | '}' if chars.peek() == Some(&c) => {
+                    chars.next();
+                    parts.push(NamePart::Literal(c));
+                }
+                '{' => {
+                    let mut token = String::new();
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            break;
+                        }
+                        token.push(c);
+                    }
+                    match token.as_str() {
+                        "pid" => parts.push(NamePart::Run {
+                            class: CharClass {
+                                digits: true,
+                                ..CharClass::default()
+                            },
+                            min: 1,
+                            max: u32::MAX.to_string().len(),
+                        }),
+                        "hostname" => parts.extend(hostname().chars().map(NamePart::Literal)),
+                        _ => return None,
+                    }
+                }
+                c => parts.push(NamePart::Literal(c)),
+            }
+        }
+        Some(NamePattern { parts })
+    }
+
+    /// Returns a pattern matching `name` only.
+    fn literal(name: &str) -> NamePattern {
+        NamePattern {
+            parts: name.chars().map(NamePart::Literal).collect(),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        matches_from(&self.parts, &name)
+    }
+}
+
+fn matches_from(parts: &[NamePart], name: &[char]) -> bool {
+    match parts.split_first() {
+        None => name.is_empty(),
+        Some((NamePart::Literal(c), rest)) => {
+            name.first() == Some(c) && matches_from(rest, &name[1..])
+        }
+        Some((NamePart::Run { class, min, max }, rest)) => {
+            let run = name
+                .iter()
+                .take(*max)
+                .take_while(|c| class.contains(**c))
+                .count();
+            (*min..=run).any(|len| matches_from(rest, &name[len..]))
+        }
+    }
+}
+
+/// Returns the shape of what a chrono `%` specifier expands to.
+///
+/// The specifier is formatted for dates spread over a few years, covering every month,
+/// weekday and hour, and the part matches the chars and lengths seen. Returns `None` if
+/// chrono cannot format the specifier.
+fn specifier_part(specifier: &str) -> Option<NamePart> {
+    let start = NaiveDate::from_ymd_opt(2000, 1, 1)?.and_hms_opt(0, 0, 0)?;
+    let step = chrono::Duration::days(33)
+        + chrono::Duration::hours(5)
+        + chrono::Duration::minutes(7)
+        + chrono::Duration::seconds(11);
+    let end = NaiveDate::from_ymd_opt(2099, 12, 31)?.and_hms_opt(23, 59, 59)?;
+    let samples = (0..24)
+        .map(|i| start + step * i)
+        .chain([end])
+        .filter_map(|date| date.and_local_timezone(Local).earliest())
+        .chain([Local::now()]);
+
+    let mut class = CharClass::default();
+    let (mut min, mut max) = (usize::MAX, 0);
+    for date in samples {
+        let mut value = String::new();
+        write!(value, "{}", date.format(specifier)).ok()?;
+        value.chars().for_each(|c| class.insert(c));
+        let len = value.chars().count();
+        min = min.min(len);
+        max = max.max(len);
+    }
+    Some(NamePart::Run { class, min, max })
+}
+
+/// Expands the `{pid}` and `{hostname}` tokens and the chrono `%` specifiers of a log
+/// file path template.
+fn expand_path_template(template: &str) -> std::io::Result<String> {
+    let invalid = |message: String| Error::new(ErrorKind::InvalidInput, message);
+
+    // The tokens are expanded first, escaping `%` in their values for chrono
+    let mut format = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        format.push_str(&rest[..start]);
+        let brace = &rest[start..];
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            format.push_str(&brace[..1]);
+            rest = &brace[2..];
+            continue;
+        }
+        if brace.starts_with('}') {
+            return Err(invalid(format!(
+                "log file path `{}`: unmatched '}}', write '}}}}' for a literal one",
+                template
+            )));
+        }
+
+        let end = brace.find('}').ok_or_else(|| {
+            invalid(format!(
+                "log file path `{}`: unclosed '{{', write '{{{{' for a literal one",
+                template
+            ))
+        })?;
+        let value = match &brace[1..end] {
+            "pid" => std::process::id().to_string(),
+            "hostname" => hostname().to_string(),
+            token => {
+                return Err(invalid(format!(
+                    "log file path `{}`: unknown token '{{{}}}', expected one of: {{pid}}, {{hostname}}",
+                    template, token
+                )))
+            }
+        };
+        format.push_str(&value.replace('%', "%%"));
+        rest = &brace[end + 1..];
+    }
+    format.push_str(rest);
+
+    let mut path = String::with_capacity(format.len());
+    write!(path, "{}", Local::now().format(&format)).map_err(|_| {
+        invalid(format!(
+            "log file path `{}`: invalid '%' specifier, write '%%' for a literal '%'",
+            template
+        ))
+    })?;
+    Ok(path)
+}
+
 impl Log for FileLogger {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level() <= self.level
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
     }
 
     fn log(&self, record: &Record<'_>) {
-        if self.enabled(record.metadata()) {
-            self.rotate();
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let Some(mut write_lock) = lock_output(&self.writable, &config) else {
+                return self.overflow.push(&config, record, false);
+            };
+            let LogOutput { writer, state } = &mut *write_lock;
+            writer.locked(self.options.locks_file(), |writer| {
+                // Records queued while the lock was taken go first
+                let result = self.overflow.drain(|line, _| self.write_line(writer, line));
+                state.report(&config, "FileLogger", result);
 
-            let mut write_lock = self.writable.lock().unwrap();
-            let _ = try_log(&self.config, record, &mut *write_lock);
+                let result = state.log(&config, record, |record| {
+                    self.write_record(&config, writer, record)
+                });
+                state.report(&config, "FileLogger", result);
+            });
         }
     }
 
     fn flush(&self) {
-        let _ = self.writable.lock().unwrap().flush();
+        let mut write_lock = self.writable.lock().unwrap();
+        let LogOutput { writer, state } = &mut *write_lock;
+        let config = self.config.get();
+        let result = writer.locked(self.options.locks_file(), |writer| {
+            self.overflow
+                .drain(|line, _| self.write_line(writer, line))
+                .and_then(|()| state.flush(|record| self.write_record(&config, writer, record)))
+                .and(writer.flush())
+        });
+        state.report(&config, "FileLogger", result);
+    }
+}
+
+impl Drop for FileLogger {
+    fn drop(&mut self) {
+        if let Ok(mut output) = self.writable.lock() {
+            let _ = output
+                .writer
+                .locked(self.options.locks_file(), |writer| writer.flush());
+        }
     }
 }
 
 impl SharedLogger for FileLogger {
     fn level(&self) -> LevelFilter {
-        self.level
+        self.level.get_level()
     }
 
-    fn config(&self) -> Option<&Config> {
-        Some(&self.config)
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.writable.lock().unwrap().state.stats.clone())
+    }
+
+    fn write_summary(&self) {
+        let mut write_lock = self.writable.lock().unwrap();
+        let LogOutput { writer, state } = &mut *write_lock;
+        let config = self.config.get();
+        let result = writer.locked(self.options.locks_file(), |writer| {
+            write_summary(&config, &state.stats, writer)
+        });
+        state.report(&config, "FileLogger", result);
+    }
+
+    /// Waits at most `timeout` for the file, which a thread writing a record holds, then
+    /// flushes it on the calling thread. Nothing is left running on a timeout.
+    ///
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::fmt;
+    /// # use std::sync::Arc;
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// struct Slow;
+    ///
+    /// impl fmt::Display for Slow {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         thread::sleep(Duration::from_millis(500));
+    ///         f.write_str("slow")
+    ///     }
+    /// }
+    ///
+    /// let dir = std::env::temp_dir().join(format!("sp_log2_try_flush_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let path = dir.join("slow.log");
+    /// let logger: Arc<FileLogger> =
+    ///     FileLogger::new(LevelFilter::Info, Config::default(), path.to_str().unwrap(), None).into();
+    ///
+    /// let writing = {
+    ///     let logger = logger.clone();
+    ///     thread::spawn(move || {
+    ///         logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", Slow)).build())
+    ///     })
+    /// };
+    /// thread::sleep(Duration::from_millis(100));
+    /// assert!(matches!(logger.try_flush(Duration::from_millis(10)), Err(FlushError::TimedOut)));
+    ///
+    /// writing.join().unwrap();
+    /// logger.try_flush(Duration::from_secs(5)).unwrap();
+    /// assert!(std::fs::read_to_string(&path).unwrap().ends_with(": slow\n"));
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    fn try_flush(&self, timeout: Duration) -> Result<(), FlushError> {
+        // Only waiting for the lock is bounded, the flush itself runs on this thread
+        let deadline = Instant::now() + timeout;
+        let mut write_lock = loop {
+            match self.writable.try_lock() {
+                Ok(guard) => break guard,
+                Err(TryLockError::Poisoned(err)) => break err.into_inner(),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(TryLockError::WouldBlock) => return Err(FlushError::TimedOut),
+            }
+        };
+        let LogOutput { writer, state } = &mut *write_lock;
+        let config = self.config.get();
+        writer
+            .locked(self.options.locks_file(), |writer| {
+                self.overflow
+                    .drain(|line, _| self.write_line(writer, line))
+                    .and_then(|()| state.flush(|record| self.write_record(&config, writer, record)))
+                    .and(writer.flush())
+            })
+            .map_err(FlushError::Io)
     }
 
     fn as_log(self: Box<Self>) -> Box<dyn Log> {
@@ -0,0 +1,76 @@
+use crate::SharedLogger;
+use log::{set_boxed_logger, Log, Metadata, Record, SetLoggerError};
+use std::fmt;
+use std::sync::Arc;
+
+/// Flushes the globally installed logger when dropped.
+///
+/// Returned by the `init_with_guard` functions. `log` keeps the logger in a `static`,
+/// which is never dropped, so buffered output would be lost when `main` returns. Hold
+/// the guard in `main` to flush the logger on the way out:
+///
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// fn main() {
+///     let _guard = TermLogger::init_with_guard(
+///         LevelFilter::Info,
+///         Config::default(),
+///         TerminalMode::Mixed,
+///         ColorChoice::Auto,
+///     );
+///
+///     // ...
+/// }
+/// ```
+///
+/// Before flushing, the guard writes the summary of the session if the banner of the
+/// `Config` asks for one, see [`Banner::with_summary`](crate::Banner::with_summary).
+///
+/// Forgetting the guard with `std::mem::forget` is safe, the logger is then simply not
+/// flushed at the end.
+#[must_use = "the logger is flushed when the guard is dropped, bind it with `let _guard = ...`"]
+pub struct FlushGuard {
+    logger: Arc<dyn SharedLogger>,
+}
+
+impl FlushGuard {
+    /// Installs `logger` as the global logger, keeping a reference to flush it later.
+    pub(crate) fn install<L: SharedLogger + 'static>(
+        logger: Box<L>,
+    ) -> Result<FlushGuard, SetLoggerError> {
+        let logger: Arc<dyn SharedLogger> = Arc::<L>::from(logger);
+        set_boxed_logger(Box::new(SharedLog(logger.clone())))?;
+        Ok(FlushGuard { logger })
+    }
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        self.logger.write_summary();
+        self.logger.flush();
+    }
+}
+
+impl fmt::Debug for FlushGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlushGuard").finish_non_exhaustive()
+    }
+}
+
+/// The global logger, shared with a `FlushGuard`.
+struct SharedLog(Arc<dyn SharedLogger>);
+
+impl Log for SharedLog {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.0.log(record)
+    }
+
+    fn flush(&self) {
+        self.0.flush()
+    }
+}
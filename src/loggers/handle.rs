@@ -0,0 +1,243 @@
+use super::logging::max_level;
+use crate::{Config, ConfigBuilder};
+use log::{set_max_level, LevelFilter, STATIC_MAX_LEVEL};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Counts the level changes of all loggers, so a cached combined level can tell it is stale.
+static LEVEL_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of level changes so far, see [`level_changed`].
+pub(crate) fn level_generation() -> usize {
+    LEVEL_GENERATION.load(Ordering::Acquire)
+}
+
+/// Marks the cached levels of all `CombinedLogger`s as stale.
+pub(crate) fn level_changed() {
+    LEVEL_GENERATION.fetch_add(1, Ordering::Release);
+}
+
+struct SharedLevel {
+    level: AtomicUsize,
+    /// Most verbose level enabled by the level directives of the logger's config
+    directives: AtomicUsize,
+    /// Set once the owning logger was installed with `log::set_boxed_logger`
+    global: AtomicBool,
+}
+
+/// A handle to change the level of a running logger.
+///
+/// Every logger of this library keeps its level behind a `LevelHandle`, which is returned
+/// by the `init` functions and by `SharedLogger::level_handle`. Handles are cheap to clone
+/// and can be sent to other threads, e.g. to an admin endpoint.
+///
+/// The level never exceeds `log::STATIC_MAX_LEVEL`, set by the `max_level_*` and
+/// `release_max_level_*` features, so a logger created or set to a more verbose level
+/// is clamped to it.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # fn main() {
+/// let logger = SimpleLogger::new(LevelFilter::Info, Config::default());
+/// let handle = logger.level_handle().unwrap();
+///
+/// handle.set_level(LevelFilter::Debug);
+/// assert_eq!(logger.level(), LevelFilter::Debug);
+/// assert_eq!(handle.get_level(), LevelFilter::Debug);
+/// # }
+/// ```
+///
+/// Built with e.g. the `max_level_info` feature, debug records are rejected whatever
+/// the level passed in:
+/// ```
+/// # use sp_log2::*;
+/// # use log::{Log, Metadata};
+/// let logger = TermLogger::new(LevelFilter::Trace, Config::default(), TerminalMode::Mixed, ColorChoice::Never);
+/// let debug = Metadata::builder().level(Level::Debug).build();
+///
+/// assert_eq!(logger.level(), LevelFilter::Trace.min(log::STATIC_MAX_LEVEL));
+/// assert_eq!(logger.enabled(&debug), Level::Debug <= log::STATIC_MAX_LEVEL);
+/// ```
+#[derive(Clone)]
+pub struct LevelHandle(Arc<SharedLevel>);
+
+impl LevelHandle {
+    pub(crate) fn new(level: LevelFilter, config: Option<&Config>) -> LevelHandle {
+        let handle = LevelHandle(Arc::new(SharedLevel {
+            level: AtomicUsize::new(level.min(STATIC_MAX_LEVEL) as usize),
+            directives: AtomicUsize::new(LevelFilter::Off as usize),
+            global: AtomicBool::new(false),
+        }));
+        if let Some(config) = config {
+            handle.0.directives.store(
+                max_level(config, LevelFilter::Off) as usize,
+                Ordering::Relaxed,
+            );
+        }
+        handle
+    }
+
+    /// Returns the current level of the logger.
+    pub fn get_level(&self) -> LevelFilter {
+        from_usize(self.0.level.load(Ordering::Relaxed))
+    }
+
+    /// Changes the level of the logger, taking effect for the next record.
+    ///
+    /// `log::set_max_level` is updated as well. If the logger was installed by its own
+    /// `init` function the global maximum follows the new level exactly; for a logger
+    /// inside a `CombinedLogger` it is only ever raised, as other loggers may still need it.
+    ///
+    /// With the `max_level_info` feature, neither the constructor nor `set_level` can go
+    /// past `Info`:
+    /// ```
+    /// # #[cfg(all(feature = "max_level_info", debug_assertions))]
+    /// # {
+    /// # use sp_log2::*;
+    /// # use log::{Log, Metadata, Record};
+    /// let logger = TestLogger::new(LevelFilter::Trace, Config::default());
+    /// let handle = logger.level_handle().unwrap();
+    /// assert_eq!(handle.get_level(), LevelFilter::Info);
+    ///
+    /// handle.set_level(LevelFilter::Debug);
+    /// assert_eq!(handle.get_level(), LevelFilter::Info);
+    /// handle.set_level(LevelFilter::Warn);
+    /// assert_eq!(handle.get_level(), LevelFilter::Warn);
+    /// handle.set_level(LevelFilter::Trace);
+    ///
+    /// logger.log(&Record::builder().level(Level::Debug).args(format_args!("dropped")).build());
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("kept")).build());
+    /// assert!(!logger.enabled(&Metadata::builder().level(Level::Debug).build()));
+    /// assert_eq!(logger.take_records().len(), 1);
+    /// # }
+    /// ```
+    pub fn set_level(&self, level: LevelFilter) {
+        self.0
+            .level
+            .store(level.min(STATIC_MAX_LEVEL) as usize, Ordering::Relaxed);
+        level_changed();
+        self.update_max_level();
+    }
+
+    /// Picks up the level directives of a new config.
+    fn set_directives(&self, config: &Config) {
+        self.0.directives.store(
+            max_level(config, LevelFilter::Off) as usize,
+            Ordering::Relaxed,
+        );
+        level_changed();
+        self.update_max_level();
+    }
+
+    fn update_max_level(&self) {
+        let max = self.max_level();
+        if self.0.global.load(Ordering::Relaxed) || max > log::max_level() {
+            set_max_level(max);
+        }
+    }
+
+    /// Marks the logger as the globally installed one and updates `log::set_max_level`.
+    pub(crate) fn install(&self) {
+        self.0.global.store(true, Ordering::Relaxed);
+        set_max_level(self.max_level());
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.get_level()
+            .max(from_usize(self.0.directives.load(Ordering::Relaxed)))
+            .min(STATIC_MAX_LEVEL)
+    }
+}
+
+impl fmt::Debug for LevelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LevelHandle")
+            .field(&self.get_level())
+            .finish()
+    }
+}
+
+pub(crate) fn from_usize(level: usize) -> LevelFilter {
+    LevelFilter::iter().nth(level).unwrap_or(LevelFilter::Trace)
+}
+
+/// A handle to replace the `Config` of a running logger.
+///
+/// Loggers keep their config behind a `ConfigHandle`, which is returned by
+/// `SharedLogger::config_handle`. Every record takes a snapshot of the current config,
+/// so a change applies to the very next record, and a record being written keeps
+/// the config it started with. Handles are cheap to clone and can be sent to other threads.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # use std::sync::{Arc, Mutex};
+/// # use std::io::{self, Write};
+/// # #[derive(Clone, Default)]
+/// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+/// # impl Write for Buffer {
+/// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+/// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+/// # }
+/// # fn main() {
+/// let buffer = Buffer::default();
+/// let logger = WriteLogger::new(LevelFilter::Info, Config::default(), buffer.clone());
+/// let handle = logger.config_handle().unwrap();
+///
+/// handle.modify(|builder| {
+///     builder.set_formatter(Some("[level]: [message]"));
+/// });
+/// logger.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+///
+/// let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+/// assert_eq!(output, "[INFO]: hello\n");
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ConfigHandle {
+    config: Arc<RwLock<Arc<Config>>>,
+    level: LevelHandle,
+}
+
+impl ConfigHandle {
+    pub(crate) fn new(config: Config, level: LevelHandle) -> ConfigHandle {
+        ConfigHandle {
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            level,
+        }
+    }
+
+    /// Returns a snapshot of the current config.
+    pub fn get(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replaces the config, taking effect for the next record.
+    ///
+    /// Level directives of the new config also update `log::set_max_level`, like
+    /// `LevelHandle::set_level` does.
+    pub fn replace(&self, config: Config) {
+        self.level.set_directives(&config);
+        *self.config.write().unwrap() = Arc::new(config);
+    }
+
+    /// Changes the current config through a `ConfigBuilder`, taking effect for the next record.
+    ///
+    /// Concurrent calls to `modify` may overwrite each other, use a single writer.
+    pub fn modify<F: FnOnce(&mut ConfigBuilder)>(&self, f: F) {
+        let mut builder = ConfigBuilder::from(Config::clone(&self.get()));
+        f(&mut builder);
+        self.replace(builder.build());
+    }
+}
+
+impl fmt::Debug for ConfigHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ConfigHandle").field(&self.get()).finish()
+    }
+}
@@ -0,0 +1,247 @@
+//! Module providing the JournaldLogger Implementation
+
+use super::logging::{
+    level_enabled, level_in_range, mark_written, metadata_enabled, should_skip, LoggerState,
+};
+use crate::{Config, ConfigHandle, InitError, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record};
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Messages longer than this are truncated if the journal rejects the datagram as too large.
+const TRUNCATED_MESSAGE_LEN: usize = 32 * 1024;
+
+/// `EMSGSIZE` on Linux
+const MESSAGE_TOO_LONG: i32 = 90;
+
+/// The JournaldLogger struct. Provides a Logger implementation that sends records to the systemd journal
+///
+/// Records are sent over the native journal protocol with the fields `PRIORITY`,
+/// `MESSAGE`, `TARGET`, `SYSLOG_IDENTIFIER`, `CODE_FILE`, `CODE_LINE` and `CODE_FUNC`,
+/// where `CODE_FUNC` holds the module path, as the `log` crate does not provide the function.
+/// The `Config` filters apply, but the text layout does not, as the journal stores
+/// those details in fields of their own.
+///
+/// Every record is sent as a single datagram. If the journal rejects one as too large,
+/// the message is truncated to 32 KiB and sent again.
+pub struct JournaldLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    socket: UnixDatagram,
+    state: Mutex<LoggerState>,
+    identifier: String,
+}
+
+impl JournaldLogger {
+    /// init function. Globally initializes the JournaldLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level` and `Config` as arguments.
+    /// Fails if the journal socket does not exist or another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _ = JournaldLogger::init(LevelFilter::Info, Config::default());
+    /// # }
+    /// ```
+    pub fn init(log_level: LevelFilter, config: Config) -> Result<LevelHandle, InitError> {
+        let logger = JournaldLogger::new(log_level, config)?;
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Fails with `io::ErrorKind::NotFound` if the journal socket does not exist, e.g. on
+    /// systems without systemd, so callers can fall back to another logger.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let logger: Box<dyn SharedLogger> = match JournaldLogger::new(LevelFilter::Info, Config::default()) {
+    ///     Ok(journald_logger) => journald_logger,
+    ///     Err(_) => TermLogger::new(LevelFilter::Info, Config::default(), TerminalMode::Mixed, ColorChoice::Auto),
+    /// };
+    /// # }
+    /// ```
+    pub fn new(log_level: LevelFilter, config: Config) -> io::Result<Box<JournaldLogger>> {
+        JournaldLogger::with_socket(log_level, config, JOURNAL_SOCKET)
+    }
+
+    /// Same as [`JournaldLogger::new`], but sends the records to the socket at `path`.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::os::unix::net::UnixDatagram;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let path = std::env::temp_dir().join(format!("sp_log2-journal-{}.sock", std::process::id()));
+    /// # let _ = std::fs::remove_file(&path);
+    /// let receiver = UnixDatagram::bind(&path).unwrap();
+    ///
+    /// let logger = JournaldLogger::with_socket(LevelFilter::Info, Config::default(), &path).unwrap();
+    /// logger.log(&Record::builder().level(Level::Warn).target("db").args(format_args!("slow\nquery")).build());
+    ///
+    /// let mut datagram = [0; 512];
+    /// let len = receiver.recv(&mut datagram).unwrap();
+    /// let fields = &datagram[..len];
+    /// assert!(fields.starts_with(b"PRIORITY=4\n"));
+    /// // Values containing newlines are length prefixed
+    /// let message = b"MESSAGE\n\x0a\0\0\0\0\0\0\0slow\nquery\n";
+    /// assert!(fields.windows(message.len()).any(|w| w == message));
+    /// assert!(fields.windows(10).any(|w| w == b"TARGET=db\n"));
+    /// # drop(receiver);
+    /// # let _ = std::fs::remove_file(&path);
+    /// # }
+    /// ```
+    pub fn with_socket<P: AsRef<Path>>(
+        log_level: LevelFilter,
+        config: Config,
+        path: P,
+    ) -> io::Result<Box<JournaldLogger>> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+
+        let identifier = std::env::current_exe()
+            .ok()
+            .and_then(|exe| {
+                exe.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+
+        let level = LevelHandle::new(log_level, Some(&config));
+        Ok(Box::new(JournaldLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            socket,
+            state: Mutex::new(LoggerState::default()),
+            identifier,
+        }))
+    }
+
+    fn send(&self, config: &Config, record: &Record<'_>) -> io::Result<()> {
+        if should_skip(config, record) || !level_in_range(config, record.level()) {
+            return Ok(());
+        }
+        mark_written();
+
+        let message = record.args().to_string();
+        match self.socket.send(&self.fields(record, &message)) {
+            Err(err) if err.raw_os_error() == Some(MESSAGE_TOO_LONG) => {
+                let mut end = TRUNCATED_MESSAGE_LEN.min(message.len());
+                while !message.is_char_boundary(end) {
+                    end -= 1;
+                }
+                self.socket.send(&self.fields(record, &message[..end]))?;
+            }
+            result => {
+                result?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fields(&self, record: &Record<'_>, message: &str) -> Vec<u8> {
+        let priority = match record.level() {
+            Level::Error => "3",
+            Level::Warn => "4",
+            Level::Info => "6",
+            Level::Debug | Level::Trace => "7",
+        };
+
+        let mut fields = Vec::with_capacity(message.len() + 256);
+        add_field(&mut fields, "PRIORITY", priority);
+        add_field(&mut fields, "MESSAGE", message);
+        add_field(&mut fields, "TARGET", record.target());
+        if !self.identifier.is_empty() {
+            add_field(&mut fields, "SYSLOG_IDENTIFIER", &self.identifier);
+        }
+        if let Some(file) = record.file() {
+            add_field(&mut fields, "CODE_FILE", file);
+        }
+        if let Some(line) = record.line() {
+            add_field(&mut fields, "CODE_LINE", &line.to_string());
+        }
+        if let Some(module_path) = record.module_path() {
+            add_field(&mut fields, "CODE_FUNC", module_path);
+        }
+        fields
+    }
+}
+
+/// Appends a field in the journal's native format.
+fn add_field(fields: &mut Vec<u8>, name: &str, value: &str) {
+    fields.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        fields.push(b'\n');
+        fields.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        fields.push(b'=');
+    }
+    fields.extend_from_slice(value.as_bytes());
+    fields.push(b'\n');
+}
+
+impl Log for JournaldLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut state = self.state.lock().unwrap();
+            let result = state.log(&config, record, |record| self.send(&config, record));
+            state.report(&config, "JournaldLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let config = self.config.get();
+        let mut state = self.state.lock().unwrap();
+        let result = state.flush(|record| self.send(&config, record));
+        state.report(&config, "JournaldLogger", result);
+    }
+}
+
+impl SharedLogger for JournaldLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.state.lock().unwrap().stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
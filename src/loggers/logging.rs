@@ -1,252 +1,1810 @@
-use crate::config::{Format, TargetPadding, TimeFormat};
-use crate::{Config, LevelPadding, ThreadLogMode, ThreadPadding};
-use chrono::DateTime;
-use log::Record;
-use std::any::Any;
+use super::stats::LoggerStats;
+use crate::config::{
+    ColorParseError, ConfigError, FilterMatch, FormatFlags, LevelStyle, LocationStyle, LogField,
+    MultilineMode, TargetPadding, TargetStyle, TimeFormat,
+};
+use crate::context;
+use crate::json::write_json;
+use crate::suspend::is_suspended;
+use crate::{Config, LevelPadding, ThreadLogMode, ThreadPadding, UtcOffset};
+use chrono::{DateTime, FixedOffset, Local};
+use log::{Level, LevelFilter, Metadata, Record};
+use std::borrow::Cow;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::fmt::Write as _;
 use std::io::{Error, Write};
+use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
 use std::thread;
-use termcolor2::{BufferedStandardStream, Color, ColorSpec, WriteColor};
+use std::time::{Duration, Instant};
+use termcolor2::{Ansi, Color, ColorSpec, WriteColor};
 
-/// Attempts to log a message based on the provided configuration.
-/// Writes the log message to the provided writer if it should not be skipped.
+/// Time, thread and context a record was logged from.
+///
+/// Loggers that hand records over to another thread capture this on the calling
+/// thread, and install it on the writing thread with [`with_origin`], so the time,
+/// thread and context fields describe the original call site.
+#[derive(Debug, Clone)]
+pub(crate) struct RecordOrigin {
+    pub(crate) time: DateTime<Local>,
+    pub(crate) instant: Instant,
+    pub(crate) thread_name: Option<String>,
+    pub(crate) thread_id: String,
+    pub(crate) context: Vec<(String, String)>,
+}
+
+impl RecordOrigin {
+    /// Captures the origin of a record logged right now on the current thread.
+    pub(crate) fn capture() -> RecordOrigin {
+        RecordOrigin {
+            // A prepared record shares its time with the other loggers
+            time: with_prepared(|fields| fields.time)
+                .flatten()
+                .unwrap_or_else(Local::now),
+            instant: Instant::now(),
+            thread_name: thread::current().name().map(str::to_string),
+            thread_id: current_thread_id(),
+            context: context::snapshot(),
+        }
+    }
+
+    /// Returns the origin installed with [`with_origin`], or captures it on the current thread.
+    pub(crate) fn current() -> RecordOrigin {
+        ORIGIN
+            .with(|o| o.borrow().clone())
+            .unwrap_or_else(RecordOrigin::capture)
+    }
+}
+
+thread_local! {
+    static ORIGIN: RefCell<Option<RecordOrigin>> = const { RefCell::new(None) };
+    static DELTA: Cell<Option<DeltaClock>> = const { Cell::new(None) };
+    static PREPARED: RefCell<Option<Rc<PreparedFields>>> = const { RefCell::new(None) };
+}
+
+/// A record that a [`CombinedLogger`](crate::CombinedLogger) created with
+/// [`new_shared`](crate::CombinedLogger::new_shared) passes to its loggers, with the time,
+/// the file location and the message rendered only once for all of them.
+///
+/// See [`SharedLogger::try_log_prepared`](crate::SharedLogger::try_log_prepared).
+pub struct PreparedRecord<'a> {
+    record: &'a Record<'a>,
+    fields: Rc<PreparedFields>,
+}
+
+/// The time format, offset and whether the offset is included, which render a time alike.
+type TimeKey = (TimeFormat, Option<UtcOffset>, bool);
+
+/// The fields of a [`PreparedRecord`], each rendered by the first logger that writes it.
+struct PreparedFields {
+    /// The time of the record, if any of the loggers writes it
+    time: Option<DateTime<Local>>,
+    /// The time rendered in each time format used so far
+    times: RefCell<Vec<(TimeKey, String)>>,
+    /// The location rendered in each location style used so far, if any of the loggers
+    /// writes it
+    locations: Option<RefCell<Vec<(LocationStyle, String)>>>,
+    message: OnceCell<String>,
+}
+
+impl<'a> PreparedRecord<'a> {
+    /// Prepares `record` for loggers that write the fields in `format`, e.g. the union of
+    /// the `FormatFlags` of their configs.
+    pub fn new(record: &'a Record<'a>, format: FormatFlags) -> PreparedRecord<'a> {
+        PreparedRecord {
+            record,
+            fields: Rc::new(PreparedFields {
+                time: format.contains(FormatFlags::TIME).then(Local::now),
+                times: RefCell::new(Vec::new()),
+                locations: format
+                    .contains(FormatFlags::FILE_LOCATION)
+                    .then(|| RefCell::new(Vec::new())),
+                message: OnceCell::new(),
+            }),
+        }
+    }
+
+    /// Returns the record.
+    pub fn record(&self) -> &'a Record<'a> {
+        self.record
+    }
+
+    /// Runs `f`, which formats the record, e.g. with [`format_record`], taking the time,
+    /// the location and the message from the prepared ones.
+    pub fn with_fields<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = PREPARED.with(|p| p.replace(Some(self.fields.clone())));
+        let result = f();
+        PREPARED.with(|p| *p.borrow_mut() = previous);
+        result
+    }
+}
+
+/// Passes the fields of the record being formatted to `f`, if it was prepared.
+fn with_prepared<R>(f: impl FnOnce(&PreparedFields) -> R) -> Option<R> {
+    PREPARED
+        .with(|p| p.borrow().clone())
+        .map(|fields| f(&fields))
+}
+
+/// Times of the previous and the current record written by a logger, for the `[delta]` placeholder.
+#[derive(Debug, Clone, Copy)]
+struct DeltaClock {
+    previous: Option<Instant>,
+    written: Option<Instant>,
+}
+
+/// Runs `f`, which writes records of a logger whose previous record was written at `previous`.
+///
+/// Returns the result of `f` and the time a record was written by it, if any.
+fn with_delta_clock<R>(previous: Option<Instant>, f: impl FnOnce() -> R) -> (R, Option<Instant>) {
+    let outer = DELTA.with(|d| {
+        d.replace(Some(DeltaClock {
+            previous,
+            written: None,
+        }))
+    });
+    let result = f();
+    let clock = DELTA.with(|d| d.replace(outer));
+    (result, clock.and_then(|clock| clock.written))
+}
+
+/// Marks the current record as written, so the next `[delta]` of the logger is measured from it.
+///
+/// Called once the record passed all filters, right before it is formatted.
+pub fn mark_written() {
+    let now = ORIGIN
+        .with(|o| o.borrow().as_ref().map(|origin| origin.instant))
+        .unwrap_or_else(Instant::now);
+    DELTA.with(|d| {
+        if let Some(clock) = d.get() {
+            d.set(Some(DeltaClock {
+                written: Some(now),
+                ..clock
+            }));
+        }
+    });
+}
+
+/// Writes the time since the previous record written by the logger, e.g. `+12.4ms`.
+///
+/// The first record of a logger has a delta of `+0ms`.
+pub fn write_delta() -> String {
+    let clock = match DELTA.with(Cell::get) {
+        Some(clock) => clock,
+        None => return String::new(),
+    };
+
+    match (clock.previous, clock.written) {
+        (Some(previous), Some(written)) => {
+            let delta = written.saturating_duration_since(previous);
+            if delta.as_secs() == 0 {
+                format!("+{:.1}ms", delta.as_secs_f64() * 1000.0)
+            } else {
+                format!("+{:.3}s", delta.as_secs_f64())
+            }
+        }
+        _ => String::from("+0ms"),
+    }
+}
+
+/// Runs `f` with the time, thread and context fields taken from `origin` instead of the current thread.
+pub(crate) fn with_origin<R>(origin: RecordOrigin, f: impl FnOnce() -> R) -> R {
+    let previous = ORIGIN.with(|o| o.replace(Some(origin)));
+    let result = f();
+    ORIGIN.with(|o| *o.borrow_mut() = previous);
+    result
+}
+
+/// A logger's writer together with the state it keeps between records, guarded by one mutex.
+pub(crate) struct LogOutput<W> {
+    pub(crate) writer: W,
+    pub(crate) state: LoggerState,
+}
+
+impl<W> LogOutput<W> {
+    pub(crate) fn new(writer: W) -> LogOutput<W> {
+        LogOutput {
+            writer,
+            state: LoggerState::default(),
+        }
+    }
+}
+
+/// State a logger keeps between records.
+#[derive(Debug, Default)]
+pub(crate) struct LoggerState {
+    last: Option<LastRecord>,
+    repeated: usize,
+    last_written: Option<Instant>,
+    last_error: Option<Instant>,
+    pub(crate) stats: LoggerStats,
+}
+
+/// The shortest time between two errors passed to the error handler of a logger.
+const ERROR_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The last record written while repeat suppression is enabled.
+#[derive(Debug)]
+struct LastRecord {
+    level: Level,
+    target: String,
+    message: String,
+    since: Instant,
+}
+
+impl LoggerState {
+    /// Passes `record` to `write`, unless it repeats the previous record and
+    /// repeat suppression is enabled in `config`.
+    ///
+    /// Repeats are counted instead, and a summary is written before the next different
+    /// record, before the first repeat after the window expired, or on [`LoggerState::flush`].
+    pub(crate) fn log<F>(
+        &mut self,
+        config: &Config,
+        record: &Record<'_>,
+        write: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Record<'_>) -> Result<(), Error>,
+    {
+        let (result, written) = with_delta_clock(self.last_written, || {
+            self.log_untimed(config, record, write)
+        });
+        self.last_written = written.or(self.last_written);
+        match (&result, written) {
+            (Err(_), _) => self.stats.add_dropped(),
+            (Ok(()), Some(_)) => self.stats.add_written(record.level()),
+            (Ok(()), None) => self.stats.add_skipped(),
+        }
+        result
+    }
+
+    fn log_untimed<F>(
+        &mut self,
+        config: &Config,
+        record: &Record<'_>,
+        mut write: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Record<'_>) -> Result<(), Error>,
+    {
+        // Records that are not written must not break a run of repeats
+        if !config.repeat_suppression
+            || is_filtered(config, record)
+            || !level_in_range(config, record.level())
+        {
+            return write(record);
+        }
+
+        let message = record.args().to_string();
+        if let Some(last) = &self.last {
+            if last.level == record.level()
+                && last.target == record.target()
+                && last.message == message
+                && last.since.elapsed() < config.repeat_window
+            {
+                self.repeated += 1;
+                return Ok(());
+            }
+        }
+
+        self.flush_untimed(&mut write)?;
+        self.last = Some(LastRecord {
+            level: record.level(),
+            target: record.target().to_string(),
+            message,
+            since: Instant::now(),
+        });
+        write(record)
+    }
+
+    /// Passes the error of a failed write or flush of the logger named `logger` to the
+    /// error handler of `config`, or writes it to stderr if there is none. Errors within
+    /// [`ERROR_INTERVAL`] of the previously reported one are dropped.
+    pub(crate) fn report(&mut self, config: &Config, logger: &str, result: Result<(), Error>) {
+        let Err(error) = result else {
+            return;
+        };
+        if self
+            .last_error
+            .is_some_and(|last| last.elapsed() < ERROR_INTERVAL)
+        {
+            return;
+        }
+        self.last_error = Some(Instant::now());
+        notify_error(config, logger, &error);
+    }
+
+    /// Writes the summary of suppressed repeats, if there are any.
+    pub(crate) fn flush<F>(&mut self, write: F) -> Result<(), Error>
+    where
+        F: FnMut(&Record<'_>) -> Result<(), Error>,
+    {
+        let (result, written) = with_delta_clock(self.last_written, || self.flush_untimed(write));
+        self.last_written = written.or(self.last_written);
+        result
+    }
+
+    fn flush_untimed<F>(&mut self, mut write: F) -> Result<(), Error>
+    where
+        F: FnMut(&Record<'_>) -> Result<(), Error>,
+    {
+        if self.repeated == 0 {
+            return Ok(());
+        }
+
+        let repeated = std::mem::take(&mut self.repeated);
+        match &self.last {
+            Some(last) => write(
+                &Record::builder()
+                    .level(last.level)
+                    .target(&last.target)
+                    .args(format_args!("last message repeated {} times", repeated))
+                    .build(),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Passes `error` of the logger named `logger` to the error handler of `config`, or
+/// writes it to stderr if there is none.
+pub(crate) fn notify_error(config: &Config, logger: &str, error: &Error) {
+    match &config.error_handler {
+        Some(handler) => (handler.0)(logger, error),
+        None => {
+            let _ = writeln!(
+                std::io::stderr(),
+                "sp_log2: {} failed to write a record: {}",
+                logger,
+                error
+            );
+        }
+    }
+}
+
+thread_local! {
+    /// The id of the current thread, formatted once.
+    static THREAD_ID: String = format!("{:?}", thread::current().id())
+        .replace("ThreadId(", "")
+        .replace(")", "");
+
+    /// Strings reused for the records formatted on this thread, to save allocations.
+    static BUFFERS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Buffers growing beyond this capacity, e.g. for a huge message, are not reused.
+const MAX_BUFFER_CAPACITY: usize = 16 * 1024;
+
+fn current_thread_id() -> String {
+    THREAD_ID.with(String::clone)
+}
+
+/// Runs `f` with an empty string taken from the buffers of this thread, which it is
+/// returned to afterwards. Records logged while formatting another take a buffer of their own.
+fn with_buffer<R>(f: impl FnOnce(&mut String) -> R) -> R {
+    let mut buffer = BUFFERS
+        .try_with(|b| b.borrow_mut().pop())
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let result = f(&mut buffer);
+    if buffer.capacity() <= MAX_BUFFER_CAPACITY {
+        buffer.clear();
+        let _ = BUFFERS.try_with(|b| b.borrow_mut().push(buffer));
+    }
+    result
+}
+
+/// Formats `record` the way the loggers of this crate do and writes it to `out`.
+///
+/// This applies the filters, sampling and level range of `config`, and renders the record
+/// in the default layout or with the formatter, including the line ending. Returns
+/// `Ok(false)` if the record was skipped, in which case nothing is written, and
+/// `Ok(true)` if it was written.
+///
+/// Use it to build a `Log` implementation for a sink of your own that renders records
+/// exactly like the other loggers. The level of the logger and the repeat suppression of
+/// `config` are up to the caller, as are colors on a terminal. Writers get the styles of
+/// the formatter as ANSI escape sequences if enabled with
+/// [`ConfigBuilder::set_write_log_enable_colors`](crate::ConfigBuilder::set_write_log_enable_colors).
+///
+/// As sampling counts the records passed in, call it once per record.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// use log::{Log, Metadata, Record};
+/// use std::sync::Mutex;
+///
+/// /// Collects the rendered lines, standing in for a proprietary sink.
+/// struct SinkLogger {
+///     config: Config,
+///     lines: Mutex<Vec<String>>,
+///     skipped: Mutex<usize>,
+/// }
+///
+/// impl Log for SinkLogger {
+///     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+///         metadata.level() <= Level::Info
+///     }
+///
+///     fn log(&self, record: &Record<'_>) {
+///         if !self.enabled(record.metadata()) {
+///             return;
+///         }
+///         let mut line = Vec::new();
+///         match sp_log2::format_record(&self.config, record, &mut line) {
+///             Ok(true) => self.lines.lock().unwrap().push(String::from_utf8_lossy(&line).into_owned()),
+///             Ok(false) => *self.skipped.lock().unwrap() += 1,
+///             Err(err) => eprintln!("failed to format a record: {}", err),
+///         }
+///     }
+///
+///     fn flush(&self) {}
+/// }
+///
+/// let logger = SinkLogger {
+///     config: ConfigBuilder::new()
+///         .set_formatter(Some("[level] [target]: [message]"))
+///         .add_filter_ignore_str("app::noisy")
+///         .build(),
+///     lines: Mutex::new(Vec::new()),
+///     skipped: Mutex::new(0),
+/// };
+/// logger.log(&Record::builder().level(Level::Info).target("app::db").args(format_args!("connected")).build());
+/// logger.log(&Record::builder().level(Level::Info).target("app::noisy").args(format_args!("tick")).build());
+///
+/// assert_eq!(*logger.lines.lock().unwrap(), ["[INFO] app::db: connected\n"]);
+/// assert_eq!(*logger.skipped.lock().unwrap(), 1);
+/// ```
+#[inline(always)]
+pub fn format_record<W>(config: &Config, record: &Record<'_>, out: &mut W) -> Result<bool, Error>
+where
+    W: Write,
+{
+    if should_skip(config, record) {
+        return Ok(false);
+    }
+
+    if !level_in_range(config, record.level()) {
+        return Ok(false);
+    }
+
+    render_record(config, record, out)?;
+    Ok(true)
+}
+
+/// Writes `record` to `out` like [`format_record`], without checking the filters and the
+/// level range first.
+pub(crate) fn render_record<W>(
+    config: &Config,
+    record: &Record<'_>,
+    out: &mut W,
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    if config.json_output {
+        return write_json(config, record, out);
+    }
+    if config.formatter_for(record.level()).is_none() {
+        return with_buffer(|prefix| {
+            with_buffer(|suffix| {
+                with_buffer(|args| write_default_layout(config, record, out, prefix, suffix, args))
+            })
+        });
+    }
+
+    let mut level = String::new();
+    let mut time = String::new();
+    let mut thread = String::new();
+    let mut target = String::new();
+    let mut location = String::new();
+    let mut module = String::new();
+
+    if config.format.contains(FormatFlags::TIME) {
+        time = write_time(config)?;
+    }
+
+    if config.format.contains(FormatFlags::LEVEL) {
+        level = write_level(record, config)?;
+    }
+
+    if config.format.contains(FormatFlags::THREAD) {
+        thread = write_thread(config)?;
+    }
+
+    if config.format.contains(FormatFlags::TARGET) {
+        target = write_target(record, config)?;
+    }
+
+    if config.format.contains(FormatFlags::FILE_LOCATION) {
+        location = write_location(record, config)?;
+    }
+
+    if config.format.contains(FormatFlags::MODULE) {
+        module = write_module(record)?;
+    }
+
+    let args = write_args(record, config)?;
+    mark_written();
+
+    for args in message_pieces(&args, config) {
+        parse_and_format_log(
+            out, config, record, &level, &time, &thread, &target, &location, &module, &args,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes a record in the default layout, rendering the fields in front of the message
+/// into `prefix`, the fields after it into `suffix` and the message into `args`, three
+/// empty buffers, instead of allocating a string per field.
+fn write_default_layout<W: Write>(
+    config: &Config,
+    record: &Record<'_>,
+    write: &mut W,
+    prefix: &mut String,
+    suffix: &mut String,
+    args: &mut String,
+) -> Result<(), Error> {
+    let (before, after) = config.fields_around_message();
+    for &field in before {
+        let line_start = prefix.is_empty();
+        push_field(prefix, field, line_start, record, config)?;
+    }
+    for &field in after {
+        push_field(suffix, field, false, record, config)?;
+    }
+
+    let context = current_context(config);
+    if !context.is_empty() {
+        prefix.push_str(&config.field_separator);
+        prefix.push('{');
+        for (i, (key, value)) in context.iter().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            let _ = write!(prefix, "{}{}={}", separator, key, value);
+        }
+        prefix.push('}');
+    }
+
+    push_args(args, record, config);
+    mark_written();
+
+    for args in message_pieces(args, config) {
+        let mut write = ColumnWriter::new(&mut *write);
+        write.write_all(prefix.as_bytes())?;
+
+        write_message_separator(&mut write, config)?;
+        // The line ending goes after the location, to end the line once
+        let (message, line_ending) = split_line_ending(&args, config);
+        let mut message = join_message_lines(message, config, write.column());
+        if config.inline_tags {
+            message = Cow::Owned(render_inline_tags(
+                &message,
+                None,
+                COLORS && config.write_log_enable_colors,
+            )?);
+        }
+        write.write_all(message.as_bytes())?;
+        write.write_all(suffix.as_bytes())?;
+        write.write_all(line_ending.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Renders `field` of the default layout into `buf`, with the space in front of it.
+///
+/// The time has no space in front of it at the start of the line.
+fn push_field(
+    buf: &mut String,
+    field: LogField,
+    line_start: bool,
+    record: &Record<'_>,
+    config: &Config,
+) -> Result<(), Error> {
+    match field {
+        LogField::Time => {
+            if config.format.contains(FormatFlags::TIME) {
+                if !line_start {
+                    buf.push_str(&config.field_separator);
+                }
+                push_time(buf, config);
+            }
+        }
+        LogField::Uptime => {
+            if config.format.contains(FormatFlags::UPTIME) {
+                let _ = write!(buf, "{}{}", config.field_separator, write_uptime(config));
+            }
+        }
+        LogField::Hostname => {
+            let hostname = write_hostname(config);
+            if !hostname.is_empty() {
+                let _ = write!(buf, "{}{}", config.field_separator, hostname);
+            }
+        }
+        LogField::Level => {
+            if config.format.contains(FormatFlags::LEVEL) {
+                let icon = if config.use_level_icons {
+                    write_level_icon(record, config)
+                } else {
+                    String::new()
+                };
+                buf.push_str(&config.field_separator);
+                if icon.is_empty() {
+                    let (open, close) = &config.level_delimiters;
+                    buf.push_str(open);
+                    push_level(buf, record, config);
+                    buf.push_str(close);
+                } else {
+                    buf.push_str(&icon);
+                }
+            }
+        }
+        LogField::Thread => {
+            let thread = if config.format.contains(FormatFlags::THREAD) {
+                write_thread(config)?
+            } else {
+                String::new()
+            };
+            push_process_thread(buf, config, &thread);
+        }
+        LogField::Target => {
+            if config.format.contains(FormatFlags::TARGET) {
+                buf.push_str(&config.field_separator);
+                push_target(buf, record, config);
+                buf.push_str(&config.target_suffix);
+            }
+        }
+        LogField::Module => {
+            if config.format.contains(FormatFlags::MODULE) {
+                let _ = write!(buf, "{}{}", config.field_separator, write_module(record)?);
+            }
+        }
+        LogField::Location => {
+            if config.format.contains(FormatFlags::FILE_LOCATION) {
+                let location = write_location(record, config)?;
+                let _ = write!(buf, "{}[{}]", config.field_separator, location);
+            }
+        }
+        LogField::Message => {}
+    }
+    Ok(())
+}
+
+/// A writer counting the chars written through it, to align the message of the default layout.
+///
+/// Colors are passed on without being counted.
+pub(crate) struct ColumnWriter<W> {
+    inner: W,
+    column: usize,
+}
+
+impl<W> ColumnWriter<W> {
+    pub(crate) fn new(inner: W) -> ColumnWriter<W> {
+        ColumnWriter { inner, column: 0 }
+    }
+
+    /// Returns the number of chars written so far.
+    pub(crate) fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl<W: Write> Write for ColumnWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let written = self.inner.write(buf)?;
+        // Every byte but the continuation bytes of UTF-8 starts a char
+        self.column += buf[..written]
+            .iter()
+            .filter(|&&byte| byte & 0xC0 != 0x80)
+            .count();
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for ColumnWriter<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> Result<(), Error> {
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.inner.reset()
+    }
+}
+
+/// Writes the field separator between the prefix and the message of the default layout,
+/// or spaces up to the message column if one is set.
+pub(crate) fn write_message_separator<W: Write>(
+    write: &mut ColumnWriter<W>,
+    config: &Config,
+) -> Result<(), Error> {
+    match config.message_column {
+        Some(column) => {
+            let padding = column.saturating_sub(write.column).max(1);
+            write!(write, "{:1$}", "", padding)
+        }
+        None => write.write_all(config.field_separator.as_bytes()),
+    }
+}
+
+/// Wraps the lines of `message`, which starts at `column`, between words so they fit in
+/// `width` chars, indenting the wrapped lines to `column`.
+///
+/// Words that do not fit on a line of their own are not split. ANSI escape sequences do
+/// not count towards the width.
+pub(crate) fn wrap_message(message: &str, width: usize, column: usize) -> String {
+    let mut wrapped = String::with_capacity(message.len());
+    for (i, line) in message.split('\n').enumerate() {
+        // Only the first line starts after the fields
+        let mut line_width = if i > 0 {
+            wrapped.push('\n');
+            0
+        } else {
+            column
+        };
+        let mut words = 0;
+        for (j, word) in line.split(' ').enumerate() {
+            let word_width = display_width(word);
+            if j > 0 {
+                if words > 0 && line_width + 1 + word_width > width {
+                    wrapped.push('\n');
+                    wrapped.extend(std::iter::repeat_n(' ', column));
+                    line_width = column;
+                    words = 0;
+                } else {
+                    wrapped.push(' ');
+                    line_width += 1;
+                }
+            }
+            wrapped.push_str(word);
+            line_width += word_width;
+            if !word.is_empty() {
+                words += 1;
+            }
+        }
+    }
+    wrapped
+}
+
+/// Replaces the inline tags of `message`, like `<b>` or `<red>`, with ANSI escape sequences,
+/// or removes them if `colored` is false.
+///
+/// `base` is the style around the message, which `</>` goes back to. Unknown tags are
+/// kept as they are.
+pub(crate) fn render_inline_tags(
+    message: &str,
+    base: Option<&ColorSpec>,
+    colored: bool,
+) -> Result<String, Error> {
+    let base = base.cloned().unwrap_or_default();
+    let mut spec = base.clone();
+    let mut rendered = Ansi::new(Vec::with_capacity(message.len()));
+    let mut rest = message;
+    while let Some(start) = rest.find('<') {
+        rendered.write_all(&rest.as_bytes()[..start])?;
+        rest = &rest[start + 1..];
+        let tag = rest.find('>').and_then(|end| {
+            let tag = &rest[..end];
+            inline_tag_spec(tag, &spec, &base).map(|next| (tag, next))
+        });
+        match tag {
+            Some((tag, next)) => {
+                if colored {
+                    // Setting a color resets the previous one first
+                    set_color(&mut rendered, &next)?;
+                }
+                spec = next;
+                rest = &rest[tag.len() + 1..];
+            }
+            None => rendered.write_all(b"<")?,
+        }
+    }
+    rendered.write_all(rest.as_bytes())?;
+    Ok(String::from_utf8_lossy(&rendered.into_inner()).into_owned())
+}
+
+/// Returns the style after the inline tag `tag` in `spec`, or `None` for an unknown tag.
+///
+/// Closing tags go back to `base`.
+fn inline_tag_spec(tag: &str, spec: &ColorSpec, base: &ColorSpec) -> Option<ColorSpec> {
+    if let Some(closed) = tag.strip_prefix('/') {
+        return (closed.is_empty() || inline_tag_spec(closed, spec, base).is_some())
+            .then(|| base.clone());
+    }
+
+    let mut spec = spec.clone();
+    match tag {
+        "b" | "bold" => spec.set_bold(true),
+        "i" | "italic" => spec.set_italic(true),
+        "u" | "underline" => spec.set_underline(true),
+        "dim" | "dimmed" => spec.set_dimmed(true),
+        _ => match tag.strip_prefix("on-") {
+            Some(color) => spec.set_bg(Some(named_color(color)?)),
+            None => spec.set_fg(Some(named_color(tag)?)),
+        },
+    };
+    Some(spec)
+}
+
+/// Returns the color of an inline tag. Only names are accepted, as messages are arbitrary text.
+fn named_color(name: &str) -> Option<Color> {
+    match name {
+        "black" => Some(Color::Black),
+        "blue" => Some(Color::Blue),
+        "green" => Some(Color::Green),
+        "red" => Some(Color::Red),
+        "cyan" => Some(Color::Cyan),
+        "magenta" => Some(Color::Magenta),
+        "yellow" => Some(Color::Yellow),
+        "white" => Some(Color::White),
+        _ => None,
+    }
+}
+
+/// Returns the number of chars of `text` shown on a terminal, skipping ANSI escape sequences.
+fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // A CSI sequence ends with a char in the range '@'..='~'
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Writes the current time based on the configured format.
+#[inline(always)]
+pub fn write_time(config: &Config) -> Result<String, Error> {
+    let mut time = String::new();
+    push_time(&mut time, config);
+    Ok(time)
+}
+
+/// Appends the current time to `out`, like [`write_time`].
+fn push_time(out: &mut String, config: &Config) {
+    // A clock of the config renders its own time
+    let prepared = config.clock.is_none().then(|| {
+        with_prepared(|fields| {
+            let key = (
+                config.time_format.clone(),
+                config.time_offset,
+                config.time_include_offset,
+            );
+            let mut times = fields.times.borrow_mut();
+            match times.iter().find(|(format, _)| *format == key) {
+                Some((_, time)) => out.push_str(time),
+                None => {
+                    let mut time = String::new();
+                    render_time(&mut time, config);
+                    out.push_str(&time);
+                    times.push((key, time));
+                }
+            }
+        })
+    });
+    if prepared.flatten().is_none() {
+        render_time(out, config);
+    }
+}
+
+/// Renders the current time with the time format of `config` into `out`.
+fn render_time(out: &mut String, config: &Config) {
+    let dt: DateTime<FixedOffset> = match &config.clock {
+        Some(clock) => (clock.0)(),
+        None => ORIGIN
+            .with(|o| o.borrow().as_ref().map(|origin| origin.time))
+            .or_else(|| with_prepared(|fields| fields.time).flatten())
+            .unwrap_or_else(Local::now)
+            .fixed_offset(),
+    };
+    let dt = match config.time_offset {
+        Some(offset) => dt.with_timezone(&offset),
+        None => dt,
+    };
+
+    let _ = match &config.time_format {
+        TimeFormat::Rfc2822 => write!(out, "{}", dt.to_rfc2822()),
+        TimeFormat::Rfc3339 => write!(out, "{}", dt.to_rfc3339()),
+        TimeFormat::Rfc3339Opts(precision, use_z) => {
+            write!(out, "{}", dt.to_rfc3339_opts(*precision, *use_z))
+        }
+        TimeFormat::UnixSeconds => write!(out, "{}", dt.timestamp()),
+        TimeFormat::UnixMillis => write!(out, "{}", dt.timestamp_millis()),
+        TimeFormat::Custom(format) if config.time_include_offset => {
+            write!(out, "{}{}", dt.format(format), dt.offset())
+        }
+        TimeFormat::Custom(format) => write!(out, "{}", dt.format(format)),
+    };
+}
+
+/// Writes the log level to a string based on the configured padding.
+#[inline(always)]
+pub fn write_level(record: &Record<'_>, config: &Config) -> Result<String, Error> {
+    let mut level = String::new();
+    push_level(&mut level, record, config);
+    Ok(level)
+}
+
+/// Appends the log level to `out`, like [`write_level`].
+fn push_level(out: &mut String, record: &Record<'_>, config: &Config) {
+    let text = config.level_text(record.level());
+    let width = config
+        .level_padding_width
+        .unwrap_or_else(|| config.level_width());
+    let _ = match config.level_padding {
+        LevelPadding::Left => write!(out, "{: >width$}", text, width = width),
+        LevelPadding::Right => write!(out, "{: <width$}", text, width = width),
+        LevelPadding::Off => {
+            out.push_str(&text);
+            Ok(())
+        }
+    };
+}
+
+/// Writes the icon of the log record's level, padded like the level, or nothing if it has none.
+#[inline(always)]
+pub fn write_level_icon(record: &Record<'_>, config: &Config) -> String {
+    let icon = config.level_icon(record.level());
+    if icon.is_empty() {
+        return String::new();
+    }
+    let width = config.level_icon_width();
+    match config.level_padding {
+        LevelPadding::Left => format!("{: >width$}", icon, width = width),
+        LevelPadding::Right => format!("{: <width$}", icon, width = width),
+        LevelPadding::Off => icon.to_string(),
+    }
+}
+
+/// Writes the target (module) of the log record based on the configured padding.
+#[inline(always)]
+pub fn write_target(record: &Record<'_>, config: &Config) -> Result<String, Error> {
+    let mut target = String::new();
+    push_target(&mut target, record, config);
+    Ok(target)
+}
+
+/// Appends the target of the log record to `out`, like [`write_target`].
+fn push_target(out: &mut String, record: &Record<'_>, config: &Config) {
+    let target = shorten_target(record.target(), config.target_style);
+    let _ = match config.target_padding {
+        TargetPadding::Left(pad) => write!(out, "{:>pad$}", target, pad = pad),
+        TargetPadding::Right(pad) => write!(out, "{:<pad$}", target, pad = pad),
+        TargetPadding::Exact(width) => {
+            out.push_str(&pad_or_truncate(&target, width));
+            Ok(())
+        }
+        TargetPadding::Off => {
+            out.push_str(&target);
+            Ok(())
+        }
+    };
+}
+
+/// Shortens the segments of the module path `target` with `style`.
+fn shorten_target(target: &str, style: TargetStyle) -> Cow<'_, str> {
+    match style {
+        TargetStyle::Full => Cow::Borrowed(target),
+        TargetStyle::LastSegment => Cow::Borrowed(target.rsplit("::").next().unwrap_or_default()),
+        TargetStyle::Abbreviated(keep) => {
+            let segments: Vec<&str> = target.split("::").collect();
+            let abbreviated = segments.len().saturating_sub(keep);
+            Cow::Owned(
+                segments
+                    .iter()
+                    .enumerate()
+                    .map(|(i, segment)| match segment.chars().next() {
+                        Some(first) if i < abbreviated => first.to_string(),
+                        _ => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("::"),
+            )
+        }
+    }
+}
+
+/// Writes the file and line number of the log record's source location.
+#[inline(always)]
+pub fn write_location(record: &Record<'_>, config: &Config) -> Result<String, Error> {
+    let prepared = with_prepared(|fields| {
+        let mut locations = fields.locations.as_ref()?.borrow_mut();
+        let style = &config.location_style;
+        if let Some((_, location)) = locations.iter().find(|(other, _)| other == style) {
+            return Some(location.clone());
+        }
+        let location = render_location(record, style);
+        locations.push((style.clone(), location.clone()));
+        Some(location)
+    });
+    match prepared.flatten() {
+        Some(location) => Ok(location),
+        None => Ok(render_location(record, &config.location_style)),
+    }
+}
+
+fn render_location(record: &Record<'_>, location_style: &LocationStyle) -> String {
+    let file = record.file().unwrap_or("<unknown>").replace('\\', "/");
+    let file = match location_style {
+        LocationStyle::Full => file.as_str(),
+        LocationStyle::FileName => file.rsplit('/').next().unwrap_or_default(),
+        LocationStyle::StripPrefix(prefix) => {
+            let prefix = prefix.replace('\\', "/");
+            let prefix = prefix.trim_end_matches('/');
+            match file.strip_prefix(prefix) {
+                // Only whole directories are stripped
+                Some(rest) if !prefix.is_empty() && rest.starts_with('/') => &rest[1..],
+                _ => file.as_str(),
+            }
+        }
+    };
+    if let Some(line) = record.line() {
+        format!("{}:{}", file, line)
+    } else {
+        format!("{}:<unknown>", file)
+    }
+}
+
+/// Writes the module path of the log record.
+#[inline(always)]
+pub fn write_module(record: &Record<'_>) -> Result<String, Error> {
+    let module = record.module_path().unwrap_or("<unknown>");
+
+    Ok(module.to_string())
+}
+
+/// Writes the current thread's name or ID, or both, depending on the thread log mode.
+pub fn write_thread(config: &Config) -> Result<String, Error> {
+    match config.thread_log_mode {
+        ThreadLogMode::IDs => write_thread_id(config),
+        ThreadLogMode::Names | ThreadLogMode::Both => write_thread_name(config),
+        ThreadLogMode::NameAndId => write_thread_name_and_id(config),
+    }
+}
+
+/// Writes the current thread's name based on the configuration.
+pub fn write_thread_name(config: &Config) -> Result<String, Error> {
+    if let Some(name) = origin_thread_name() {
+        Ok(pad_thread(name, config))
+    } else if config.thread_log_mode == ThreadLogMode::Both {
+        write_thread_id(config)
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Writes the current thread's ID based on the configuration.
+pub fn write_thread_id(config: &Config) -> Result<String, Error> {
+    Ok(pad_thread(origin_thread_id(), config))
+}
+
+/// Writes the current thread's name and ID, joined with the thread separator, or only
+/// the ID if the thread is unnamed. The padding applies to the combined string.
+pub fn write_thread_name_and_id(config: &Config) -> Result<String, Error> {
+    let id = origin_thread_id();
+    let thread = match origin_thread_name() {
+        Some(name) => format!("{}{}{}", name, config.thread_separator, id),
+        None => id,
+    };
+    Ok(pad_thread(thread, config))
+}
+
+fn origin_thread_name() -> Option<String> {
+    ORIGIN.with(|o| match o.borrow().as_ref() {
+        Some(origin) => origin.thread_name.clone(),
+        None => thread::current().name().map(str::to_string),
+    })
+}
+
+fn origin_thread_id() -> String {
+    ORIGIN
+        .with(|o| o.borrow().as_ref().map(|origin| origin.thread_id.clone()))
+        .unwrap_or_else(current_thread_id)
+}
+
+fn pad_thread(thread: String, config: &Config) -> String {
+    match config.thread_padding {
+        ThreadPadding::Left(width) => format!("{:>width$}", thread, width = width),
+        ThreadPadding::Right(width) => format!("{:<width$}", thread, width = width),
+        ThreadPadding::Exact(width) => pad_or_truncate(&thread, width),
+        ThreadPadding::Off => thread,
+    }
+}
+
+/// Pads `value` with spaces on the right to exactly `width` chars, or truncates it on a
+/// char boundary, ending it with an ellipsis, if it is longer.
+fn pad_or_truncate(value: &str, width: usize) -> String {
+    match value.char_indices().nth(width) {
+        None => format!("{:<width$}", value, width = width),
+        Some(_) if width == 0 => String::new(),
+        Some(_) => {
+            let (end, _) = value.char_indices().nth(width - 1).unwrap_or_default();
+            format!("{}…", &value[..end])
+        }
+    }
+}
+
+/// Returns the id of this process, which is looked up once.
+pub fn process_id() -> &'static str {
+    static PROCESS_ID: OnceLock<String> = OnceLock::new();
+    PROCESS_ID.get_or_init(|| std::process::id().to_string())
+}
+
+/// Writes the time since the `Config` was created, or nothing if `FormatFlags::UPTIME` is disabled.
+pub fn write_uptime(config: &Config) -> String {
+    if !config.format.contains(FormatFlags::UPTIME) {
+        return String::new();
+    }
+
+    let instant = ORIGIN
+        .with(|o| o.borrow().as_ref().map(|origin| origin.instant))
+        .unwrap_or_else(Instant::now);
+    let uptime = instant.saturating_duration_since(config.created);
+    match config.uptime_precision {
+        0 => format!("{:>4}s", uptime.as_secs()),
+        precision => format!(
+            "{:>4}.{:0precision$}s",
+            uptime.as_secs(),
+            uptime.subsec_nanos() / 10u32.pow(9 - precision as u32),
+            precision = precision
+        ),
+    }
+}
+
+/// Returns the hostname of this machine, which is looked up once.
+///
+/// Returns an empty string if the hostname cannot be determined.
+pub fn hostname() -> &'static str {
+    static HOSTNAME: OnceLock<String> = OnceLock::new();
+    HOSTNAME.get_or_init(sys::hostname)
+}
+
+/// Returns the hostname for the log format, or an empty string if `FormatFlags::HOSTNAME` is disabled.
+pub fn write_hostname(config: &Config) -> &str {
+    if !config.format.contains(FormatFlags::HOSTNAME) {
+        return "";
+    }
+    match &config.hostname {
+        Some(hostname) => hostname,
+        None => hostname(),
+    }
+}
+
+/// Returns the context values of the current record, if `FormatFlags::CONTEXT` is enabled.
+///
+/// These are the values captured with the origin of the record, or else the current ones.
+pub fn current_context(config: &Config) -> Vec<(String, String)> {
+    if !config.format.contains(FormatFlags::CONTEXT) {
+        return Vec::new();
+    }
+    ORIGIN
+        .with(|o| o.borrow().as_ref().map(|origin| origin.context.clone()))
+        .unwrap_or_else(context::snapshot)
+}
+
+/// Writes the context values as `key=value` pairs separated by spaces.
+pub fn write_context(context: &[(String, String)]) -> String {
+    context
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int};
+
+    extern "C" {
+        fn gethostname(name: *mut c_char, len: usize) -> c_int;
+    }
+
+    pub(super) fn hostname() -> String {
+        let mut buffer = [0 as c_char; 256];
+        // SAFETY: the buffer is valid for its length, and its last byte stays zero,
+        // so it is nul terminated even if the name was truncated
+        if unsafe { gethostname(buffer.as_mut_ptr(), buffer.len() - 1) } != 0 {
+            return String::new();
+        }
+        // SAFETY: see above
+        unsafe { CStr::from_ptr(buffer.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetComputerNameW(buffer: *mut u16, size: *mut u32) -> i32;
+    }
+
+    pub(super) fn hostname() -> String {
+        let mut buffer = [0u16; 256];
+        let mut size = buffer.len() as u32;
+        // SAFETY: `size` holds the length of the buffer, and is set to the length of the name
+        if unsafe { GetComputerNameW(buffer.as_mut_ptr(), &mut size) } == 0 {
+            return String::new();
+        }
+        String::from_utf16_lossy(&buffer[..size as usize])
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub(super) fn hostname() -> String {
+        std::env::var("HOSTNAME").unwrap_or_default()
+    }
+}
+
+/// Writes the process id and `thread` of the default layout, as ` (pid:thread)`,
+/// ` (pid)` or ` (thread)` depending on which of them are enabled.
+pub fn write_process_thread<W>(write: &mut W, config: &Config, thread: &str) -> Result<(), Error>
+where
+    W: Write,
+{
+    let mut buf = String::new();
+    push_process_thread(&mut buf, config, thread);
+    write.write_all(buf.as_bytes())
+}
+
+/// Renders the process id and `thread` like [`write_process_thread`] into `buf`.
+fn push_process_thread(buf: &mut String, config: &Config, thread: &str) {
+    let process = if config.format.contains(FormatFlags::PROCESS_ID) {
+        process_id()
+    } else {
+        ""
+    };
+    if process.is_empty() && thread.is_empty() {
+        return;
+    }
+
+    let (open, close) = &config.thread_delimiters;
+    buf.push_str(&config.field_separator);
+    buf.push_str(open);
+    buf.push_str(process);
+    if !process.is_empty() && !thread.is_empty() {
+        buf.push(':');
+    }
+    buf.push_str(thread);
+    buf.push_str(close);
+}
+
+/// Writes the arguments of the log record, appending a line ending.
+#[inline(always)]
+pub fn write_args(record: &Record<'_>, config: &Config) -> Result<String, Error> {
+    let mut args = String::new();
+    push_args(&mut args, record, config);
+    Ok(args)
+}
+
+/// Appends the arguments of the log record and a line ending to `out`, like [`write_args`].
+fn push_args(out: &mut String, record: &Record<'_>, config: &Config) {
+    let prepared = with_prepared(|fields| {
+        let message = fields.message.get_or_init(|| record.args().to_string());
+        push_message(out, message, config);
+    });
+    if prepared.is_none() {
+        if config.escape_newlines {
+            out.push_str(&escape_line_breaks(&record.args().to_string()));
+        } else {
+            let _ = write!(out, "{}", record.args());
+        }
+    }
+    out.push_str(&config.line_ending);
+}
+
+/// Appends the rendered `message`, escaped if `config` escapes line breaks.
+fn push_message(out: &mut String, message: &str, config: &Config) {
+    if config.escape_newlines {
+        out.push_str(&escape_line_breaks(message));
+    } else {
+        out.push_str(message);
+    }
+}
+
+/// Escapes the line breaks and other control chars of `message`, except tabs, like
+/// `\n`, so it stays on one line.
+fn escape_line_breaks(message: &str) -> String {
+    let mut escaped = String::with_capacity(message.len());
+    for c in message.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push(c),
+            // Including the line and paragraph separators, which end lines for some readers
+            c if c.is_control() || matches!(c, '\u{2028}' | '\u{2029}') => {
+                escaped.extend(c.escape_unicode())
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Splits `args`, the message written by [`write_args`], into the messages written as
+/// records of their own, which are its lines with `MultilineMode::PrefixEach`.
+pub(crate) fn message_pieces<'a>(
+    args: &'a str,
+    config: &'a Config,
+) -> impl Iterator<Item = Cow<'a, str>> {
+    let prefix_each = config.multiline == MultilineMode::PrefixEach;
+    let (message, line_ending) = split_line_ending(args, config);
+    let whole = (!prefix_each).then_some(Cow::Borrowed(args));
+    let lines = prefix_each.then(|| {
+        message_lines(message).map(move |line| Cow::Owned(format!("{}{}", line, line_ending)))
+    });
+    whole.into_iter().chain(lines.into_iter().flatten())
+}
+
+/// Joins the lines of `message` with the line ending of the config, indenting the lines
+/// after the first for `MultilineMode::Indent`, where the message starts at `column`, and
+/// `MultilineMode::IndentBy`.
+pub(crate) fn join_message_lines<'a>(
+    message: &'a str,
+    config: &Config,
+    column: usize,
+) -> Cow<'a, str> {
+    let indent = match config.multiline {
+        MultilineMode::Indent => column,
+        MultilineMode::IndentBy(indent) => indent,
+        MultilineMode::AsIs | MultilineMode::PrefixEach => return Cow::Borrowed(message),
+    };
+    let (body, line_ending) = split_line_ending(message, config);
+    if !body.contains('\n') {
+        return Cow::Borrowed(message);
+    }
+    let separator = format!("{}{:indent$}", config.line_ending, "");
+    let mut joined = message_lines(body).collect::<Vec<_>>().join(&separator);
+    joined.push_str(line_ending);
+    Cow::Owned(joined)
+}
+
+/// Splits the line ending of the config off the end of `message`, if it has one.
+fn split_line_ending<'a>(message: &'a str, config: &'a Config) -> (&'a str, &'a str) {
+    match message.strip_suffix(config.line_ending.as_str()) {
+        Some(body) => (body, config.line_ending.as_str()),
+        None => (message, ""),
+    }
+}
+
+/// Returns the lines of `message`, split at `\n` and `\r\n`.
+fn message_lines(message: &str) -> impl Iterator<Item = &str> {
+    message
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+}
+
+/// Returns the level records from `target` are checked against.
+///
+/// This is the level of the most specific level directive or allow entry with a level
+/// matching the target, or `level` if none matches.
+#[inline(always)]
+pub fn target_level(config: &Config, target: &str, level: LevelFilter) -> LevelFilter {
+    let matches = |(prefix, _): &&(Cow<'static, str>, LevelFilter)| target.starts_with(&**prefix);
+    let directive = config.level_directives.iter().find(matches);
+    let allow = config.filter_allow_levels.iter().find(matches);
+    // The longer prefix wins, an allow entry wins over a directive for the same prefix
+    directive
+        .into_iter()
+        .chain(allow)
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(level, |(_, directive)| *directive)
+}
+
+/// Determines whether `level` lies within the level range of the configuration.
+///
+/// More severe levels compare as smaller, so the range is `most_severe..=least_severe`.
+#[inline(always)]
+pub fn level_in_range(config: &Config, level: Level) -> bool {
+    level >= config.most_severe && level <= config.least_severe
+}
+
+/// Determines whether a record passes the logger's `level`, or the level directive for its target,
+/// and is not [suspended](crate::suspend).
+///
+/// Records above `log::STATIC_MAX_LEVEL` are rejected before anything else, so the
+/// `max_level_*` features let the compiler drop the rest of the check for them.
+#[inline(always)]
+pub fn level_enabled(config: &Config, metadata: &Metadata<'_>, level: LevelFilter) -> bool {
+    metadata.level() <= log::STATIC_MAX_LEVEL
+        && metadata.level() <= target_level(config, metadata.target(), level)
+        && !is_suspended(metadata.level())
+}
+
+/// Determines whether a logger would write records with the given `metadata`, for
+/// `Log::enabled`.
+///
+/// Besides [`level_enabled`] this checks the level range and the target filters, as
+/// far as they can be decided without the record. Records it rejects would be
+/// skipped by [`should_skip`] anyway, so callers can avoid formatting them.
+#[inline(always)]
+pub fn metadata_enabled(config: &Config, metadata: &Metadata<'_>, level: LevelFilter) -> bool {
+    level_enabled(config, metadata, level)
+        && level_in_range(config, metadata.level())
+        && !is_metadata_filtered(config, metadata)
+}
+
+/// Returns the most verbose level a logger with the given `level` and `config` can log at.
+///
+/// Used for `log::set_max_level`, so records enabled by a level directive or an allow
+/// entry with a level are not discarded before they reach the logger.
+pub fn max_level(config: &Config, level: LevelFilter) -> LevelFilter {
+    config
+        .level_directives
+        .iter()
+        .chain(&config.filter_allow_levels)
+        .map(|(_, directive)| *directive)
+        .fold(level, Ord::max)
+}
+
+/// Determines whether the log record should be skipped based on the configuration's filters.
+///
+/// Besides the filters this applies sampling, so it must be called exactly once per record
+/// that is about to be written.
+#[inline(always)]
+pub fn should_skip(config: &Config, record: &Record<'_>) -> bool {
+    is_filtered(config, record) || is_sampled_out(config, record)
+}
+
+/// Determines whether the target and message filters of the configuration reject the record.
+///
+/// Unlike [`should_skip`] this has no side effects and can be evaluated any number of times.
 #[inline(always)]
-pub fn try_log<W>(config: &Config, record: &Record<'_>, write: &mut W) -> Result<(), Error>
-where
-    W: Write + Sized + Any,
-{
-    if should_skip(config, record) {
-        return Ok(());
+pub fn is_filtered(config: &Config, record: &Record<'_>) -> bool {
+    let paths = match config.filter_match {
+        FilterMatch::TargetOnly => [Some(record.target()), None],
+        FilterMatch::ModuleOnly => [record.module_path(), None],
+        FilterMatch::Either => [Some(record.target()), record.module_path()],
+    };
+    if is_path_filtered(config, &paths, true) {
+        return true;
     }
 
-    if record.level() > config.min_level || record.level() < config.max_level {
-        return Ok(());
+    if has_message_filters(config) {
+        // Only render the message if there is something to check it against
+        let rendered;
+        let message = match record.args().as_str() {
+            Some(message) => message,
+            None => {
+                rendered = record.args().to_string();
+                &rendered
+            }
+        };
+        return skip_message(config, message);
     }
 
-    let mut level = String::new();
-    let mut time = String::new();
-    let mut thread = String::new();
-    let mut target = String::new();
-    let mut location = String::new();
-    let mut module = String::new();
+    false
+}
 
-    if config.format & Format::Time != 0 {
-        time = write_time(config)?;
+/// Determines whether the target filters reject records with the given `metadata`.
+///
+/// The module path is not part of the metadata, so with `FilterMatch::ModuleOnly` nothing
+/// is rejected, and with `FilterMatch::Either` only targets matching an ignore filter.
+#[inline(always)]
+fn is_metadata_filtered(config: &Config, metadata: &Metadata<'_>) -> bool {
+    match config.filter_match {
+        FilterMatch::TargetOnly => is_path_filtered(config, &[Some(metadata.target()), None], true),
+        FilterMatch::ModuleOnly => false,
+        FilterMatch::Either => is_path_filtered(config, &[Some(metadata.target()), None], false),
     }
+}
 
-    if config.format & Format::LevelFlag != 0 {
-        level = write_level(record, config)?;
+/// Determines whether the allow and ignore filters reject a record with the given paths.
+///
+/// If `complete` is false, some paths of the record are unknown, so not matching an allow
+/// filter does not reject it.
+#[inline(always)]
+fn is_path_filtered(config: &Config, paths: &[Option<&str>; 2], complete: bool) -> bool {
+    let paths = || paths.iter().flatten();
+
+    // If an allowed list is available, check that the target matches at least one allow filter
+    let has_allow = !config.filter_allow.is_empty();
+    let allowed = paths().any(|path| config.filter_allow.iter().any(|v| v.matches(path)));
+
+    #[cfg(feature = "regex")]
+    let has_allow = has_allow || !config.filter_allow_regex.is_empty();
+    #[cfg(feature = "regex")]
+    let allowed =
+        allowed || paths().any(|path| config.filter_allow_regex.iter().any(|r| r.is_match(path)));
+
+    if complete && has_allow && !allowed {
+        // If not, skip any further writing
+        return true;
     }
 
-    if config.format & Format::Thread != 0 {
-        thread = match config.thread_log_mode {
-            ThreadLogMode::IDs => write_thread_id(config)?,
-            ThreadLogMode::Names | ThreadLogMode::Both => write_thread_name(config)?,
-        }
+    // Check that the target does not match any ignore filters
+    if paths().any(|path| config.filter_ignore.iter().any(|v| v.matches(path))) {
+        return true;
     }
 
-    if config.format & Format::Target != 0 {
-        target = write_target(record, config)?;
+    #[cfg(feature = "regex")]
+    if paths().any(|path| config.filter_ignore_regex.iter().any(|r| r.is_match(path))) {
+        return true;
     }
 
-    if config.format & Format::FileLocation != 0 {
-        location = write_location(record)?;
+    false
+}
+
+/// Determines whether the sample rate for the record's target drops it.
+///
+/// Records at `Warn` and above are never sampled.
+#[inline(always)]
+fn is_sampled_out(config: &Config, record: &Record<'_>) -> bool {
+    if config.sample_rates.is_empty() || record.level() <= Level::Warn {
+        return false;
     }
 
-    if config.format & Format::Module != 0 {
-        module = write_module(record)?;
+    match config
+        .sample_rates
+        .iter()
+        .find(|sample| record.target().starts_with(&*sample.prefix))
+    {
+        Some(sample) => sample.counter.fetch_add(1, Ordering::Relaxed) % sample.rate != 0,
+        None => false,
     }
+}
 
-    let args = write_args(record, &config.line_ending)?;
+#[inline(always)]
+fn has_message_filters(config: &Config) -> bool {
+    let has_filters =
+        !config.filter_allow_message.is_empty() || !config.filter_ignore_message.is_empty();
 
-    if config.formatter.is_some() {
-        parse_and_format_log(
-            write, config, &level, &time, &thread, &target, &location, &module, &args,
-        )?;
-    } else {
-        if !time.is_empty() {
-            write!(write, "{}", time)?;
-        }
+    #[cfg(feature = "regex")]
+    let has_filters = has_filters
+        || !config.filter_allow_message_regex.is_empty()
+        || !config.filter_ignore_message_regex.is_empty();
 
-        if !level.is_empty() {
-            write!(write, " [{}]", level)?;
-        }
+    has_filters
+}
 
-        if !thread.is_empty() {
-            write!(write, " ({})", thread)?;
-        }
+fn skip_message(config: &Config, message: &str) -> bool {
+    let has_allow = !config.filter_allow_message.is_empty();
+    let allowed = config
+        .filter_allow_message
+        .iter()
+        .any(|v| message.contains(&**v));
 
-        if !target.is_empty() {
-            write!(write, " {}:", target)?;
-        }
+    #[cfg(feature = "regex")]
+    let has_allow = has_allow || !config.filter_allow_message_regex.is_empty();
+    #[cfg(feature = "regex")]
+    let allowed = allowed
+        || config
+            .filter_allow_message_regex
+            .iter()
+            .any(|r| r.is_match(message));
 
-        write!(write, " {}", args)?;
+    if has_allow && !allowed {
+        return true;
+    }
 
-        if !location.is_empty() {
-            write!(write, " [{}]", location)?;
-        }
+    if config
+        .filter_ignore_message
+        .iter()
+        .any(|v| message.contains(&**v))
+    {
+        return true;
+    }
 
-        writeln!(write)?;
+    #[cfg(feature = "regex")]
+    if config
+        .filter_ignore_message_regex
+        .iter()
+        .any(|r| r.is_match(message))
+    {
+        return true;
     }
 
-    Ok(())
+    false
 }
 
-/// Writes the current time based on the configured format.
-#[inline(always)]
-pub fn write_time(config: &Config) -> Result<String, Error> {
-    use chrono::Local;
-
-    let dt: DateTime<Local> = Local::now();
-
-    let formatted_time = match config.time_format.clone() {
-        TimeFormat::Rfc2822 => dt.to_rfc2822(),
-        TimeFormat::Rfc3339 => dt.to_rfc3339(),
-        TimeFormat::Custom(format) => dt.format(format).to_string(),
-    };
+/// False with the `no-color` feature, which compiles out writing colors and styles.
+pub(crate) const COLORS: bool = cfg!(not(feature = "no-color"));
 
-    Ok(formatted_time)
+/// Sets the color of `writer` to `spec`.
+#[cfg(not(feature = "no-color"))]
+pub(crate) fn set_color<W: WriteColor + ?Sized>(
+    writer: &mut W,
+    spec: &ColorSpec,
+) -> Result<(), Error> {
+    writer.set_color(spec)
 }
 
-/// Writes the log level to a string based on the configured padding.
-#[inline(always)]
-pub fn write_level(record: &Record<'_>, config: &Config) -> Result<String, Error> {
-    let level = match config.level_padding {
-        LevelPadding::Left => format!("{: >5}", record.level()),
-        LevelPadding::Right => format!("{: <5}", record.level()),
-        LevelPadding::Off => record.level().to_string(),
-    };
-
-    let formatted_level = level.to_string();
-
-    Ok(formatted_level)
+/// Does nothing, the `no-color` feature never writes escape sequences.
+#[cfg(feature = "no-color")]
+pub(crate) fn set_color<W: WriteColor + ?Sized>(_: &mut W, _: &ColorSpec) -> Result<(), Error> {
+    Ok(())
 }
 
-/// Writes the target (module) of the log record based on the configured padding.
-#[inline(always)]
-pub fn write_target(record: &Record<'_>, config: &Config) -> Result<String, Error> {
-    let target = match config.target_padding {
-        TargetPadding::Left(pad) => format!("{:>pad$}", record.target(), pad = pad),
-        TargetPadding::Right(pad) => format!("{:<pad$}", record.target(), pad = pad),
-        TargetPadding::Off => record.target().to_string(),
-    };
-    Ok(target)
+/// Resets the color of `writer`.
+#[cfg(not(feature = "no-color"))]
+pub(crate) fn reset_color<W: WriteColor + ?Sized>(writer: &mut W) -> Result<(), Error> {
+    writer.reset()
 }
 
-/// Writes the file and line number of the log record's source location.
-#[inline(always)]
-pub fn write_location(record: &Record<'_>) -> Result<String, Error> {
-    let file = record.file().unwrap_or("<unknown>").replace("\\", "/");
-    let location = if let Some(line) = record.line() {
-        format!("{}:{}", file, line)
-    } else {
-        format!("{}:<unknown>", file)
-    };
-    Ok(location)
+/// Does nothing, the `no-color` feature never writes escape sequences.
+#[cfg(feature = "no-color")]
+pub(crate) fn reset_color<W: WriteColor + ?Sized>(_: &mut W) -> Result<(), Error> {
+    Ok(())
 }
 
-/// Writes the module path of the log record.
-#[inline(always)]
-pub fn write_module(record: &Record<'_>) -> Result<String, Error> {
-    let module = record.module_path().unwrap_or("<unknown>");
-
-    Ok(module.to_string())
-}
+/// The placeholders the formatter replaces with the fields of a record.
+pub(crate) const PLACEHOLDERS: &[&str] = &[
+    "time",
+    "thread",
+    "target",
+    "level",
+    "file",
+    "filename",
+    "filepath",
+    "line",
+    "pid",
+    "hostname",
+    "uptime",
+    "delta",
+    "module",
+    "message",
+    "context",
+    "icon",
+    "thread_id",
+    "thread_name",
+];
 
-/// Writes the current thread's name based on the configuration.
-pub fn write_thread_name(config: &Config) -> Result<String, Error> {
-    if let Some(name) = thread::current().name() {
-        let thread_name = match config.thread_padding {
-            ThreadPadding::Left { 0: qty } => {
-                format!("{:>width$}", name, width = qty)
-            }
-            ThreadPadding::Right { 0: qty } => {
-                format!("{:<width$}", name, width = qty)
-            }
-            ThreadPadding::Off => name.to_string(),
-        };
-        Ok(thread_name)
-    } else if config.thread_log_mode == ThreadLogMode::Both {
-        write_thread_id(config)
-    } else {
-        Ok(String::new())
-    }
+/// Checks that every placeholder of `format` is closed and known, either built in or one of
+/// the `custom` ones, and has known styles, following the same rules as [`parse_and_format_log`].
+pub(crate) fn check_formatter(
+    format: &str,
+    custom: &[(String, String)],
+) -> Result<(), ConfigError> {
+    check_tokens(FormatTokens::new(format), custom)
 }
 
-/// Writes the current thread's ID based on the configuration.
-pub fn write_thread_id(config: &Config) -> Result<String, Error> {
-    let id = format!("{:?}", thread::current().id())
-        .replace("ThreadId(", "")
-        .replace(")", "");
-    let thread_id = match config.thread_padding {
-        ThreadPadding::Left { 0: qty } => {
-            format!("{:>width$}", id, width = qty)
-        }
-        ThreadPadding::Right { 0: qty } => {
-            format!("{:<width$}", id, width = qty)
-        }
-        ThreadPadding::Off => id.to_string(),
+fn check_tokens(tokens: FormatTokens<'_>, custom: &[(String, String)]) -> Result<(), ConfigError> {
+    let is_known = |key: &str| {
+        PLACEHOLDERS.contains(&key)
+            || key.starts_with("context.")
+            || custom.iter().any(|(name, _)| name == key)
     };
-    Ok(thread_id)
-}
-
-/// Writes the arguments of the log record, appending a line ending.
-#[inline(always)]
-pub fn write_args(record: &Record<'_>, line_ending: &str) -> Result<String, Error> {
-    Ok(format!("{}{}", record.args(), line_ending))
-}
+    for token in tokens {
+        match token {
+            FormatToken::Literal(_) => {}
+            FormatToken::Placeholder {
+                placeholder,
+                position,
+                ..
+            } => {
+                let mut parts = placeholder.split(':');
+                let key = parts.next().unwrap_or_default();
+                if !is_known(key) {
+                    return Err(ConfigError::UnknownPlaceholder {
+                        key: key.to_string(),
+                        position,
+                    });
+                }
 
-/// Determines whether the log record should be skipped based on the configuration's filters.
-#[inline(always)]
-pub fn should_skip(config: &Config, record: &Record<'_>) -> bool {
-    // If a module path and allowed list are available
-    match (record.target(), &*config.filter_allow) {
-        (path, allowed) if !allowed.is_empty() => {
-            // Check that the module path matches at least one allow filter
-            if !allowed.iter().any(|v| path.starts_with(&**v)) {
-                // If not, skip any further writing
-                return true;
+                let mut offset = position + key.len() + 1;
+                let mut parts = parts.peekable();
+                while let Some(style) = parts.next() {
+                    let known = if style.eq_ignore_ascii_case("bg") {
+                        // `bg` makes the following color the background
+                        parts.peek().is_some_and(|color| apply_style(color).is_ok())
+                    } else {
+                        is_style(style)
+                    };
+                    if !known {
+                        return Err(ConfigError::UnknownStyle {
+                            style: style.to_string(),
+                            position: offset,
+                        });
+                    }
+                    offset += style.len() + 1;
+                }
             }
-        }
-        _ => {}
-    }
-
-    // If a module path and ignore list are available
-    match (record.target(), &*config.filter_ignore) {
-        (path, ignore) if !ignore.is_empty() => {
-            // Check that the module path does not match any ignore filters
-            if ignore.iter().any(|v| path.starts_with(&**v)) {
-                // If not, skip any further writing
-                return true;
+            FormatToken::Group {
+                key,
+                body,
+                position,
+            } => {
+                if !is_known(key) {
+                    return Err(ConfigError::UnknownPlaceholder {
+                        key: key.to_string(),
+                        position,
+                    });
+                }
+                check_tokens(body, custom)?;
+            }
+            FormatToken::Unclosed { position, .. } => {
+                return Err(ConfigError::UnclosedPlaceholder { position })
             }
         }
-        _ => {}
     }
 
-    false
+    Ok(())
+}
+
+/// Determines whether `style` is a valid style of a formatter placeholder.
+fn is_style(style: &str) -> bool {
+    FieldSpec::parse(style).is_some()
+        || matches!(
+            style.to_ascii_lowercase().as_str(),
+            "bold"
+                | "italic"
+                | "dim"
+                | "underline"
+                | "strikethrough"
+                | "nb"
+                | "nobrackets"
+                | "no_brackets"
+                | "levelcolor"
+                | "level_color"
+        )
+        || apply_style(style).is_ok()
 }
 
+/// Parses a color style of the formatter, e.g. `red`, `#89dceb`, `rgb(137 180 250)`, or
+/// `ansi(208)`, `@208` or `208` for an ANSI 256 color, or with a `bg` prefix for the
+/// background, e.g. `bgred` or `bg#1e1e2e`.
+///
+/// Returns the color and whether it is a foreground color.
 #[inline]
-fn apply_style(style: &str) -> Option<(Color, bool)> {
-    let is_bg = style.starts_with("bg");
-    let new_style = match is_bg {
-        true => &style[2..],
-        false => style,
+pub(crate) fn apply_style(style: &str) -> Result<(Color, bool), ColorParseError> {
+    let (color, is_fg) = match style.strip_prefix("bg") {
+        Some(color) => (color.trim_start(), false),
+        None => (style, true),
     };
+    Ok((parse_color(color)?, is_fg))
+}
 
-    if let Ok(color) = Color::from_str(new_style) {
-        return Some((color, !is_bg));
+/// Parses a color, accepting `ansi(n)` and `@n` for ANSI 256 colors besides the colors
+/// of `Color::from_str`.
+fn parse_color(color: &str) -> Result<Color, ColorParseError> {
+    let index = color.strip_prefix('@').or_else(|| {
+        color
+            .strip_prefix("ansi(")
+            .and_then(|index| index.strip_suffix(')'))
+    });
+    match index {
+        // Indexes above 255 fail like bare ones
+        Some(index) if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) => {
+            Color::from_str(index)
+        }
+        _ => Color::from_str(color),
     }
-
-    None
 }
 
 #[inline]
 #[allow(clippy::too_many_arguments)]
 pub fn parse_and_format_log_term(
-    writer: &mut BufferedStandardStream,
-    level_color: Option<Color>,
+    writer: &mut dyn WriteColor,
+    level_style: Option<&LevelStyle>,
     config: &Config,
+    record: &Record<'_>,
     level: &str,
     time: &str,
     thread: &str,
@@ -257,8 +1815,9 @@ pub fn parse_and_format_log_term(
 ) -> Result<(), Error> {
     parse_and_format_log_internal(
         writer,
-        level_color,
+        level_style,
         config,
+        record,
         level,
         time,
         thread,
@@ -275,6 +1834,7 @@ pub fn parse_and_format_log_term(
 pub fn parse_and_format_log<W>(
     writer: &mut W,
     config: &Config,
+    record: &Record<'_>,
     level: &str,
     time: &str,
     thread: &str,
@@ -284,18 +1844,31 @@ pub fn parse_and_format_log<W>(
     message: &str,
 ) -> Result<(), Error>
 where
-    W: Write + Sized + Any,
+    W: Write + Sized,
 {
+    // Colors of writers other than terminals are written as ANSI escape sequences
     parse_and_format_log_internal(
-        writer, None, config, level, time, thread, target, file, module, message, false,
+        &mut Ansi::new(writer),
+        None,
+        config,
+        record,
+        level,
+        time,
+        thread,
+        target,
+        file,
+        module,
+        message,
+        false,
     )
 }
 
 #[allow(clippy::too_many_arguments)]
 fn parse_and_format_log_internal<W>(
     writer: &mut W,
-    level_color: Option<Color>,
+    level_style: Option<&LevelStyle>,
     config: &Config,
+    record: &Record<'_>,
     level: &str,
     time: &str,
     thread: &str,
@@ -306,115 +1879,451 @@ fn parse_and_format_log_internal<W>(
     is_terminal: bool,
 ) -> Result<(), Error>
 where
-    W: Write + Sized + Any,
+    W: WriteColor + ?Sized,
 {
-    let format_str = config.formatter.clone().unwrap();
-    let mut last_end = 0; // Tracks the position of the last match's end
-    let mut chars = format_str.chars().enumerate().peekable(); // To look ahead for brackets
-
-    while let Some((i, c)) = chars.next() {
-        if c == '[' {
-            // Check for double brackets `[[`
-            if let Some((_, next_c)) = chars.peek() {
-                if *next_c == '[' {
-                    chars.next(); // Consume the second `[`
-
-                    // Find the closing double brackets `]]`
-                    if let Some(end) = format_str[i + 2..].find("]]") {
-                        let end = i + 2 + end;
-
-                        // Write the part before the placeholder
-                        if last_end < i {
-                            write!(writer, "{}", &format_str[last_end..i])?;
-                        }
+    let format_str = config.formatter_for(record.level()).unwrap_or_default();
 
-                        // Include the brackets in the output by simply writing them
-                        write!(writer, "[")?;
-                        let placeholder = &format_str[i + 2..end];
-                        process_placeholder(
-                            writer,
-                            placeholder,
-                            level_color.clone(),
-                            config,
-                            level,
-                            time,
-                            thread,
-                            target,
-                            file,
-                            module,
-                            message,
-                            is_terminal,
-                        )?;
-                        write!(writer, "]")?;
-
-                        last_end = end + 2; // Update last_end to the character after `]]`
-                        continue;
-                    }
-                }
-            }
+    let (filepath, line) = if config.format.contains(FormatFlags::FILE_LOCATION) {
+        (
+            record.file().unwrap_or_default().replace('\\', "/"),
+            record
+                .line()
+                .map(|line| line.to_string())
+                .unwrap_or_default(),
+        )
+    } else {
+        (String::new(), String::new())
+    };
+    let filename = filepath.rsplit('/').next().unwrap_or_default();
+    let (thread_id, thread_name) = if config.format.contains(FormatFlags::THREAD) {
+        (
+            write_thread_id(config)?,
+            origin_thread_name()
+                .map(|name| pad_thread(name, config))
+                .unwrap_or_default(),
+        )
+    } else {
+        (String::new(), String::new())
+    };
+    let pid = if config.format.contains(FormatFlags::PROCESS_ID) {
+        process_id()
+    } else {
+        ""
+    };
+    let hostname = write_hostname(config);
+    let uptime = write_uptime(config);
+    let delta = write_delta();
+    let icon = write_level_icon(record, config);
+    let context = current_context(config);
+    let context_pairs = write_context(&context);
+
+    let fields = Fields {
+        level,
+        time,
+        thread,
+        thread_id: &thread_id,
+        thread_name: &thread_name,
+        target,
+        file,
+        filename,
+        filepath: &filepath,
+        line: &line,
+        pid,
+        hostname,
+        uptime: &uptime,
+        delta: &delta,
+        icon: &icon,
+        module,
+        message,
+        context: &context,
+        context_pairs: &context_pairs,
+        custom: &config.custom_placeholders,
+    };
+
+    write_tokens(
+        writer,
+        FormatTokens::new(format_str),
+        level_style,
+        config,
+        &fields,
+        is_terminal,
+        &mut 0,
+    )
+}
 
-            // Handle single brackets `[`
-            if let Some(end) = format_str[i + 1..].find(']') {
-                let end = i + 1 + end;
+/// The fields of a record, as rendered for the formatter.
+struct Fields<'a> {
+    level: &'a str,
+    time: &'a str,
+    thread: &'a str,
+    thread_id: &'a str,
+    thread_name: &'a str,
+    target: &'a str,
+    file: &'a str,
+    filename: &'a str,
+    filepath: &'a str,
+    line: &'a str,
+    pid: &'a str,
+    hostname: &'a str,
+    uptime: &'a str,
+    delta: &'a str,
+    icon: &'a str,
+    module: &'a str,
+    message: &'a str,
+    context: &'a [(String, String)],
+    context_pairs: &'a str,
+    custom: &'a [(String, String)],
+}
 
-                // Write the part before the placeholder
-                if last_end < i {
-                    write!(writer, "{}", &format_str[last_end..i])?;
-                }
+impl<'a> Fields<'a> {
+    /// Returns the field of a placeholder key, built in keys taking precedence over custom ones.
+    fn get(&self, key: &str) -> Option<&'a str> {
+        match key {
+            "time" => Some(self.time),
+            "thread" => Some(self.thread),
+            "thread_id" => Some(self.thread_id),
+            "thread_name" => Some(self.thread_name),
+            "target" => Some(self.target),
+            "level" => Some(self.level),
+            "file" => Some(self.file),
+            "filename" => Some(self.filename),
+            "filepath" => Some(self.filepath),
+            "line" => Some(self.line),
+            "pid" => Some(self.pid),
+            "hostname" => Some(self.hostname),
+            "uptime" => Some(self.uptime),
+            "delta" => Some(self.delta),
+            "icon" => Some(self.icon),
+            "module" => Some(self.module),
+            "message" => Some(self.message),
+            "context" => Some(self.context_pairs),
+            _ => match key.strip_prefix("context.") {
+                Some(name) => Some(
+                    self.context
+                        .iter()
+                        .find(|(key, _)| key == name)
+                        .map_or("", |(_, value)| value.as_str()),
+                ),
+                None => self
+                    .custom
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, value)| value.as_str()),
+            },
+        }
+    }
+}
 
-                let placeholder = &format_str[i + 1..end];
+fn write_tokens<W>(
+    writer: &mut W,
+    tokens: FormatTokens<'_>,
+    level_style: Option<&LevelStyle>,
+    config: &Config,
+    fields: &Fields<'_>,
+    is_terminal: bool,
+    column: &mut usize,
+) -> Result<(), Error>
+where
+    W: WriteColor + ?Sized,
+{
+    for token in tokens {
+        match token {
+            FormatToken::Literal(literal) => {
+                write!(writer, "{}", literal)?;
+                advance_column(column, literal);
+            }
+            FormatToken::Placeholder {
+                placeholder,
+                bracketed,
+                ..
+            } => {
+                if bracketed {
+                    write!(writer, "[")?;
+                    *column += 1;
+                }
                 process_placeholder(
                     writer,
                     placeholder,
-                    level_color.clone(),
+                    level_style,
                     config,
-                    level,
-                    time,
-                    thread,
-                    target,
-                    file,
-                    module,
-                    message,
+                    fields,
                     is_terminal,
+                    column,
                 )?;
+                if bracketed {
+                    write!(writer, "]")?;
+                    *column += 1;
+                }
+            }
+            FormatToken::Group { key, body, .. } => {
+                if fields.get(key).is_some_and(|field| !field.is_empty()) {
+                    write_tokens(
+                        writer,
+                        body,
+                        level_style,
+                        config,
+                        fields,
+                        is_terminal,
+                        column,
+                    )?;
+                }
+            }
+            // Unclosed brackets are written as they are
+            FormatToken::Unclosed { rest, .. } => {
+                write!(writer, "{}", rest)?;
+                advance_column(column, rest);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves `column`, the number of chars written on the current line, past `text`.
+fn advance_column(column: &mut usize, text: &str) {
+    match text.rsplit_once('\n') {
+        Some((_, last_line)) => *column = display_width(last_line),
+        None => *column += display_width(text),
+    }
+}
+
+/// A part of a formatter string.
+enum FormatToken<'a> {
+    /// Text that is written as it is
+    Literal(&'a str),
+    /// A `[key:styles]` placeholder, `bracketed` if it was written as `[[key:styles]]`
+    Placeholder {
+        placeholder: &'a str,
+        bracketed: bool,
+        position: usize,
+    },
+    /// A `[?key:body]` group, whose body is only written if the field of `key` is not empty
+    Group {
+        key: &'a str,
+        body: FormatTokens<'a>,
+        position: usize,
+    },
+    /// The rest of the string, starting with a `[` that is never closed
+    Unclosed { rest: &'a str, position: usize },
+}
+
+/// Splits a formatter string into literals and placeholders.
+///
+/// `\[` and `\]` stand for literal brackets and `\\` for a literal backslash.
+/// All positions are byte offsets.
+struct FormatTokens<'a> {
+    format: &'a str,
+    pos: usize,
+}
+
+impl<'a> FormatTokens<'a> {
+    fn new(format: &'a str) -> FormatTokens<'a> {
+        FormatTokens { format, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for FormatTokens<'a> {
+    type Item = FormatToken<'a>;
+
+    fn next(&mut self) -> Option<FormatToken<'a>> {
+        let start = self.pos;
+        let rest = &self.format[start..];
+        for (i, b) in rest.bytes().enumerate() {
+            match b {
+                b'\\' if i > 0 => {
+                    // Emit the literal up to the escape first
+                    self.pos = start + i;
+                    return Some(FormatToken::Literal(&rest[..i]));
+                }
+                b'\\' => match rest.as_bytes().get(1) {
+                    Some(b'[' | b']' | b'\\') => {
+                        self.pos = start + 2;
+                        return Some(FormatToken::Literal(&rest[1..2]));
+                    }
+                    _ => {
+                        // Any other backslash is a literal
+                        continue;
+                    }
+                },
+                b'[' if i > 0 => {
+                    self.pos = start + i;
+                    return Some(FormatToken::Literal(&rest[..i]));
+                }
+                b'[' if rest[1..].starts_with('?') => {
+                    return Some(match (rest.find(':'), closing_bracket(rest)) {
+                        (Some(colon), Some(end)) if colon < end => {
+                            self.pos = start + end + 1;
+                            FormatToken::Group {
+                                key: &rest[2..colon],
+                                body: FormatTokens {
+                                    format: &self.format[..start + end],
+                                    pos: start + colon + 1,
+                                },
+                                position: start + 2,
+                            }
+                        }
+                        _ => {
+                            self.pos = self.format.len();
+                            FormatToken::Unclosed {
+                                rest,
+                                position: start,
+                            }
+                        }
+                    });
+                }
+                b'[' => {
+                    // `[[key]]` wraps the value in brackets, if the closing `]]` exists
+                    if rest[1..].starts_with('[') {
+                        if let Some(end) = rest[2..].find("]]") {
+                            self.pos = start + 2 + end + 2;
+                            return Some(FormatToken::Placeholder {
+                                placeholder: &rest[2..2 + end],
+                                bracketed: true,
+                                position: start + 2,
+                            });
+                        }
+                    }
+                    return Some(match rest[1..].find(']') {
+                        Some(end) => {
+                            self.pos = start + 1 + end + 1;
+                            FormatToken::Placeholder {
+                                placeholder: &rest[1..1 + end],
+                                bracketed: false,
+                                position: start + 1,
+                            }
+                        }
+                        None => {
+                            self.pos = self.format.len();
+                            FormatToken::Unclosed {
+                                rest,
+                                position: start,
+                            }
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if rest.is_empty() {
+            return None;
+        }
+        self.pos = self.format.len();
+        Some(FormatToken::Literal(rest))
+    }
+}
 
-                last_end = end + 1; // Update last_end to the character after `]`
+/// Returns the position of the `]` closing the `[` that `format` starts with,
+/// skipping nested and escaped brackets.
+fn closing_bracket(format: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, b) in format.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
             }
+            _ => {}
         }
     }
+    None
+}
+
+/// Width, alignment and precision of a formatter placeholder, e.g. the `<25` of `[target:<25]`.
+///
+/// The syntax follows `std::fmt`: an alignment of `<`, `>` or `^` followed by a width,
+/// and/or a `.` followed by the maximum number of characters, e.g. `>10.10`.
+struct FieldSpec {
+    align: char,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl FieldSpec {
+    fn parse(part: &str) -> Option<FieldSpec> {
+        let (align, rest) = match part.chars().next()? {
+            align @ ('<' | '>' | '^') => (align, &part[1..]),
+            '.' => ('<', part),
+            _ => return None,
+        };
+        let (width, precision) = match rest.split_once('.') {
+            Some((width, precision)) => (width, Some(precision.parse().ok()?)),
+            None => (rest, None),
+        };
+        let width = match width {
+            "" => 0,
+            width => width.parse().ok()?,
+        };
 
-    // Write any remaining part of the format_str after the last match
-    if last_end < format_str.len() {
-        write!(writer, "{}", &format_str[last_end..])?;
+        Some(FieldSpec {
+            align,
+            width,
+            precision,
+        })
     }
 
-    Ok(())
+    /// Truncates and pads `value`, counting chars. If `keep_tail` is set, truncation
+    /// keeps the end of the value, which is the informative part of paths.
+    fn apply<'a>(&self, value: &'a str, keep_tail: bool) -> Cow<'a, str> {
+        let mut value = Cow::Borrowed(value);
+        if let Some(precision) = self.precision {
+            let len = value.chars().count();
+            if len > precision {
+                value = Cow::Owned(if keep_tail {
+                    value.chars().skip(len - precision).collect()
+                } else {
+                    value.chars().take(precision).collect()
+                });
+            }
+        }
+
+        let len = value.chars().count();
+        if len >= self.width {
+            return value;
+        }
+        let padding = self.width - len;
+        let (left, right) = match self.align {
+            '>' => (padding, 0),
+            '^' => (padding / 2, padding - padding / 2),
+            _ => (0, padding),
+        };
+        Cow::Owned(format!("{:left$}{}{:right$}", "", value, ""))
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
 fn process_placeholder<W>(
     writer: &mut W,
     placeholder: &str,
-    level_color: Option<Color>,
+    level_style: Option<&LevelStyle>,
     config: &Config,
-    level: &str,
-    time: &str,
-    thread: &str,
-    target: &str,
-    file: &str,
-    module: &str,
-    message: &str,
+    fields: &Fields<'_>,
     is_terminal: bool,
+    column: &mut usize,
 ) -> Result<(), Error>
 where
-    W: Write + Sized + Any,
+    W: WriteColor + ?Sized,
 {
     let parts: Vec<&str> = placeholder.split(':').collect();
     let key = parts[0];
+    let spec = parts[1..].iter().find_map(|part| FieldSpec::parse(part));
 
     let mut use_bracket_level = true;
+    // The style is reset after the field if it was set
+    let mut colored = None;
+    let styled = COLORS
+        && if is_terminal {
+            config.enable_colors
+        } else {
+            config.write_log_enable_colors
+        };
 
-    if is_terminal {
+    if is_terminal || styled {
         let styles = if parts.len() > 1 {
             parts[1..].to_vec()
         } else {
@@ -428,21 +2337,33 @@ where
         let mut dim = false;
         let mut underline = false;
         let mut strikethrough = false;
+        let mut level_colored = false;
 
-        for style in styles {
+        let mut styles = styles.into_iter();
+        while let Some(style) = styles.next() {
+            if FieldSpec::parse(style).is_some() {
+                continue;
+            }
             match style.to_ascii_lowercase().as_str() {
+                // `bg` makes the following color the background
+                "bg" => {
+                    if let Ok((color, _)) = apply_style(styles.next().unwrap_or_default()) {
+                        bg_color = bg_color.or(Some(color));
+                    }
+                }
                 "bold" => bold = true,
                 "italic" => italic = true,
                 "dim" => dim = true,
                 "underline" => underline = true,
                 "strikethrough" => strikethrough = true,
+                "levelcolor" | "level_color" => level_colored = true,
                 "nb" | "nobrackets" | "no_brackets" => {
                     if key == "level" {
                         use_bracket_level = false;
                     }
                 }
                 _ => {
-                    if let Some((color, is_fg)) = apply_style(style) {
+                    if let Ok((color, is_fg)) = apply_style(style) {
                         if is_fg {
                             fg_color = fg_color.or(Some(color));
                         } else {
@@ -453,50 +2374,99 @@ where
             }
         }
 
-        if key == "level" {
-            fg_color = fg_color.or(level_color.clone());
+        // With a colored line, every field starts out in the level style
+        if key == "level" || key == "icon" || config.colorize_line || level_colored {
+            if let Some(style) = level_style {
+                fg_color = fg_color.or(style.fg.clone());
+                bg_color = bg_color.or(style.bg.clone());
+                bold |= style.bold;
+                italic |= style.italic;
+                dim |= style.dimmed;
+                underline |= style.underline;
+            }
         }
 
-        if config.enable_colors {
-            if let Some(writer) = (writer as &mut dyn Any).downcast_mut::<BufferedStandardStream>()
-            {
-                writer.set_color(
-                    ColorSpec::new()
-                        .set_fg(fg_color)
-                        .set_bg(bg_color)
-                        .set_bold(bold)
-                        .set_italic(italic)
-                        .set_dimmed(dim)
-                        .set_underline(underline)
-                        .set_strikethrough(strikethrough),
-                )?;
-            }
+        let mut color_spec = ColorSpec::new();
+        color_spec
+            .set_fg(fg_color)
+            .set_bg(bg_color)
+            .set_bold(bold)
+            .set_italic(italic)
+            .set_dimmed(dim)
+            .set_underline(underline)
+            .set_strikethrough(strikethrough);
+        if styled && !color_spec.is_none() {
+            set_color(writer, &color_spec)?;
+            colored = Some(color_spec);
         }
     }
 
-    match key {
-        "time" => write!(writer, "{}", time)?,
-        "thread" => write!(writer, "{}", thread)?,
-        "target" => write!(writer, "{}", target)?,
-        "level" => {
-            if use_bracket_level {
-                write!(writer, "[{}]", level)?
-            } else {
-                write!(writer, "{}", level)?
-            }
+    let field = fields.get(key);
+    let unknown;
+    let value = match field {
+        Some(field) => field,
+        None if config.strict_formatter => {
+            unknown = format!("<unknown:{}>", key);
+            &unknown
+        }
+        None => placeholder,
+    };
+
+    // The message ends with the line ending, which stays outside the field
+    let (value, line_ending) = match key {
+        "message" => match value.strip_suffix(config.line_ending.as_str()) {
+            Some(message) => (message, config.line_ending.as_str()),
+            None => (value, ""),
+        },
+        _ => (value, ""),
+    };
+
+    let value = match (&spec, key) {
+        (Some(spec), _) if field.is_some() => {
+            // The placeholder width replaces the padding from the config
+            let value = match key {
+                "thread" | "thread_id" | "thread_name" | "target" | "level" | "icon" => {
+                    value.trim_matches(' ')
+                }
+                _ => value,
+            };
+            spec.apply(value, matches!(key, "target" | "module" | "file"))
         }
-        "file" => write!(writer, "{}", file)?,
-        "module" => write!(writer, "{}", module)?,
-        "message" => write!(writer, "{}", message)?,
-        _ => write!(writer, "{}", placeholder)?,
+        _ => Cow::Borrowed(value),
+    };
+
+    let value = match key {
+        "message" => match join_message_lines(&value, config, *column) {
+            Cow::Owned(joined) => Cow::Owned(joined),
+            Cow::Borrowed(_) => value,
+        },
+        _ => value,
+    };
+    let value = if key == "message" && config.inline_tags {
+        let colored_tags = styled && writer.supports_color();
+        Cow::Owned(render_inline_tags(&value, colored.as_ref(), colored_tags)?)
+    } else {
+        value
+    };
+
+    if key == "level" && use_bracket_level {
+        write!(writer, "[{}]", value)?;
+        *column += 2;
+    } else {
+        write!(writer, "{}", value)?;
     }
+    advance_column(column, &value);
 
-    if is_terminal && config.enable_colors {
-        if let Some(writer) = (writer as &mut dyn Any).downcast_mut::<BufferedStandardStream>() {
-            writer.reset()?;
+    if colored.is_some() {
+        reset_color(writer)?;
+        // Back to the color of the line
+        if let Some(style) = level_style.filter(|_| config.colorize_line) {
+            set_color(writer, &style.color_spec())?;
         }
     }
 
+    write!(writer, "{}", line_ending)?;
+
     Ok(())
 }
 
@@ -1,12 +1,50 @@
+#[cfg(feature = "android")]
+mod android;
+mod asynclog;
+mod chain;
+mod channellog;
 mod comlog;
+#[cfg(all(windows, feature = "eventlog"))]
+mod eventlog;
 mod filelog;
+mod guard;
+mod handle;
+#[cfg(all(target_os = "linux", feature = "journald"))]
+mod journald;
 pub mod logging;
+#[cfg(feature = "net")]
+mod netlog;
+mod overflow;
+mod ringlog;
 mod splog;
+mod stats;
+#[cfg(all(unix, feature = "syslog"))]
+mod syslog;
 mod termlog;
+mod testlog;
 mod writelog;
 
-pub use self::comlog::CombinedLogger;
-pub use self::filelog::FileLogger;
-pub use self::splog::SimpleLogger;
-pub use self::termlog::{TermLogger, TerminalMode};
-pub use self::writelog::WriteLogger;
+#[cfg(feature = "android")]
+pub use self::android::AndroidLogger;
+pub use self::asynclog::{AsyncLogger, OverflowPolicy, WorkerGuard};
+pub use self::chain::{ChainError, ChainReport, LoggerChain, LoggerFactory};
+pub use self::channellog::{ChannelLogger, LogMessage};
+pub use self::comlog::{CombinedHandle, CombinedLogger, LoggerId};
+#[cfg(all(windows, feature = "eventlog"))]
+pub use self::eventlog::EventLogLogger;
+pub use self::filelog::{FileLogger, FileLoggerOptions, FlushPolicy, RotateHook};
+pub use self::guard::FlushGuard;
+pub use self::handle::{ConfigHandle, LevelHandle};
+#[cfg(all(target_os = "linux", feature = "journald"))]
+pub use self::journald::JournaldLogger;
+pub use self::logging::{format_record, PreparedRecord};
+#[cfg(feature = "net")]
+pub use self::netlog::{ConnectionState, NetLogger, NetLoggerOptions, NetStatus};
+pub use self::ringlog::{RingBuffer, RingBufferCapacity, RingBufferLogger};
+pub use self::splog::{OutputStream, SimpleLogger};
+pub use self::stats::LoggerStats;
+#[cfg(all(unix, feature = "syslog"))]
+pub use self::syslog::{Facility, SyslogLogger, SyslogOptions};
+pub use self::termlog::{OutputHook, TermLogger, TerminalMode};
+pub use self::testlog::{CapturedRecord, CapturedRecords, TestLogger};
+pub use self::writelog::{SharedWriter, WriteLogger};
@@ -0,0 +1,550 @@
+//! Module providing the NetLogger Implementation
+
+use super::logging::{format_record, level_enabled, metadata_enabled, LoggerState, PreparedRecord};
+use crate::{Config, ConfigHandle, InitError, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// State of the connection of a `NetLogger`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// Records are being sent
+    Connected,
+    /// The connection was lost or could not be established, records are buffered
+    /// until the next reconnect attempt succeeds
+    Disconnected,
+}
+
+/// Options for the connection of a `NetLogger`.
+#[derive(Debug, Clone)]
+pub struct NetLoggerOptions {
+    pub(crate) timeout: Duration,
+    pub(crate) buffer_records: usize,
+    pub(crate) min_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+}
+
+impl Default for NetLoggerOptions {
+    fn default() -> Self {
+        NetLoggerOptions {
+            timeout: Duration::from_millis(500),
+            buffer_records: 1000,
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl NetLoggerOptions {
+    /// Creates the default options
+    pub fn new() -> NetLoggerOptions {
+        NetLoggerOptions::default()
+    }
+
+    /// Sets how long a logging call may wait for connecting and sending, in total.
+    /// Default is 500ms.
+    ///
+    /// Records that could not be sent in time stay buffered and are sent by the next
+    /// call, so a slow receiver or an address that does not answer never blocks the
+    /// logging thread for longer than this.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::io::Read;
+    /// # use std::net::TcpListener;
+    /// # use std::thread;
+    /// # use std::time::{Duration, Instant};
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let mut options = NetLoggerOptions::new();
+    /// options.timeout(Duration::from_millis(100));
+    /// let config = ConfigBuilder::new().set_formatter(Some("[message]")).build();
+    /// let logger = NetLogger::tcp(LevelFilter::Info, config, listener.local_addr().unwrap(), &options).unwrap();
+    ///
+    /// // A receiver reading far slower than the records are logged
+    /// thread::spawn(move || {
+    ///     let (mut stream, _) = listener.accept().unwrap();
+    ///     let mut buf = [0; 4096];
+    ///     while stream.read(&mut buf).map_or(false, |len| len > 0) {
+    ///         thread::sleep(Duration::from_millis(10));
+    ///     }
+    /// });
+    ///
+    /// let message = "x".repeat(1 << 20);
+    /// for _ in 0..32 {
+    ///     let start = Instant::now();
+    ///     logger.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+    ///     assert!(start.elapsed() < Duration::from_secs(1));
+    /// }
+    /// assert!(logger.status().buffered() > 0);
+    /// assert_eq!(logger.status().state(), ConnectionState::Connected);
+    /// # }
+    /// ```
+    pub fn timeout(&mut self, timeout: Duration) -> &mut NetLoggerOptions {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many records are kept while a TCP connection is down.
+    /// Once exceeded the oldest records are dropped. Default is 1000.
+    pub fn buffer_records(&mut self, records: usize) -> &mut NetLoggerOptions {
+        self.buffer_records = records;
+        self
+    }
+
+    /// Sets the delays between reconnect attempts, doubling from `min` up to `max`.
+    /// Default is 100ms up to 30s.
+    pub fn backoff(&mut self, min: Duration, max: Duration) -> &mut NetLoggerOptions {
+        self.min_backoff = min;
+        self.max_backoff = max.max(min);
+        self
+    }
+}
+
+struct StatusInner {
+    state: AtomicU8,
+    buffered: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+/// The connection status of a `NetLogger`.
+///
+/// Cheap to clone, all clones observe the same logger. Obtained from `NetLogger::status`
+/// or the `init` functions, e.g. to surface a "log shipping degraded" warning.
+#[derive(Clone)]
+pub struct NetStatus(Arc<StatusInner>);
+
+impl NetStatus {
+    fn new() -> NetStatus {
+        NetStatus(Arc::new(StatusInner {
+            state: AtomicU8::new(ConnectionState::Disconnected as u8),
+            buffered: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Returns the current connection state.
+    pub fn state(&self) -> ConnectionState {
+        if self.0.state.load(Ordering::Relaxed) == ConnectionState::Connected as u8 {
+            ConnectionState::Connected
+        } else {
+            ConnectionState::Disconnected
+        }
+    }
+
+    /// Returns the number of records waiting for a reconnect.
+    pub fn buffered(&self) -> usize {
+        self.0.buffered.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of records dropped so far, because the buffer was full or a
+    /// datagram could not be sent.
+    pub fn dropped(&self) -> usize {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        self.0.state.store(state as u8, Ordering::Relaxed);
+    }
+}
+
+struct TcpSink {
+    addrs: Vec<SocketAddr>,
+    stream: Option<TcpStream>,
+    backlog: VecDeque<Vec<u8>>,
+    /// How much of the first line of the backlog was sent already
+    sent: usize,
+    backoff: Duration,
+    next_attempt: Instant,
+}
+
+enum Sink {
+    Tcp(TcpSink),
+    Udp(UdpSocket),
+}
+
+struct NetOutput {
+    sink: Sink,
+    state: LoggerState,
+}
+
+/// The NetLogger struct. Provides a Logger implementation that ships records over TCP or UDP
+///
+/// Every record is formatted with the `Config` and sent as one line, e.g. to a logstash
+/// or vector agent. A dropped TCP connection is re-established with exponential backoff,
+/// records logged in the meantime are buffered. Logging calls never wait longer than
+/// the configured timeout.
+pub struct NetLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    output: Mutex<NetOutput>,
+    options: NetLoggerOptions,
+    status: NetStatus,
+}
+
+impl NetLogger {
+    /// init function. Globally initializes a TCP NetLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level`, `Config`, address and `NetLoggerOptions` as arguments and
+    /// returns the `NetStatus` of the connection.
+    /// Fails if the address cannot be resolved or another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _ = NetLogger::init_tcp(LevelFilter::Info, Config::default(), "127.0.0.1:9000", &NetLoggerOptions::new());
+    /// # }
+    /// ```
+    pub fn init_tcp<A: ToSocketAddrs>(
+        log_level: LevelFilter,
+        config: Config,
+        addr: A,
+        options: &NetLoggerOptions,
+    ) -> Result<NetStatus, InitError> {
+        NetLogger::install(NetLogger::tcp(log_level, config, addr, options)?)
+    }
+
+    /// Same as [`NetLogger::init_tcp`], but sends every record as a UDP datagram.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _ = NetLogger::init_udp(LevelFilter::Info, Config::default(), "127.0.0.1:9000", &NetLoggerOptions::new());
+    /// # }
+    /// ```
+    pub fn init_udp<A: ToSocketAddrs>(
+        log_level: LevelFilter,
+        config: Config,
+        addr: A,
+        options: &NetLoggerOptions,
+    ) -> Result<NetStatus, InitError> {
+        NetLogger::install(NetLogger::udp(log_level, config, addr, options)?)
+    }
+
+    fn install(logger: Box<NetLogger>) -> Result<NetStatus, InitError> {
+        let handle = logger.level.clone();
+        let status = logger.status();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(status)
+    }
+
+    /// allows to create a new TCP logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init_tcp()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// The address is resolved once. The connection is established on the first record,
+    /// so this does not fail if the receiver is not yet listening.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::io::{BufRead, BufReader};
+    /// # use std::net::TcpListener;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let config = ConfigBuilder::new().set_formatter(Some("[level] [message]")).build();
+    /// let logger = NetLogger::tcp(LevelFilter::Info, config, listener.local_addr().unwrap(), &NetLoggerOptions::new()).unwrap();
+    ///
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("shipped")).build());
+    /// assert_eq!(logger.status().state(), ConnectionState::Connected);
+    ///
+    /// let (stream, _) = listener.accept().unwrap();
+    /// let mut line = String::new();
+    /// BufReader::new(stream).read_line(&mut line).unwrap();
+    /// assert_eq!(line, "[INFO] shipped\n");
+    /// # }
+    /// ```
+    pub fn tcp<A: ToSocketAddrs>(
+        log_level: LevelFilter,
+        config: Config,
+        addr: A,
+        options: &NetLoggerOptions,
+    ) -> io::Result<Box<NetLogger>> {
+        let sink = Sink::Tcp(TcpSink {
+            addrs: resolve(addr)?,
+            stream: None,
+            backlog: VecDeque::new(),
+            sent: 0,
+            backoff: options.min_backoff,
+            next_attempt: Instant::now(),
+        });
+        Ok(NetLogger::with_sink(log_level, config, sink, options))
+    }
+
+    /// allows to create a new UDP logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init_udp()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Every record is sent as one datagram, on a best-effort basis.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::net::UdpSocket;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    /// let config = ConfigBuilder::new().set_formatter(Some("[message]")).build();
+    /// let logger = NetLogger::udp(LevelFilter::Info, config, receiver.local_addr().unwrap(), &NetLoggerOptions::new()).unwrap();
+    ///
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("shipped")).build());
+    ///
+    /// let mut datagram = [0; 64];
+    /// let len = receiver.recv(&mut datagram).unwrap();
+    /// assert_eq!(&datagram[..len], b"shipped\n");
+    /// # }
+    /// ```
+    pub fn udp<A: ToSocketAddrs>(
+        log_level: LevelFilter,
+        config: Config,
+        addr: A,
+        options: &NetLoggerOptions,
+    ) -> io::Result<Box<NetLogger>> {
+        let addrs = resolve(addr)?;
+        let local: SocketAddr = if addrs[0].is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(&addrs[..])?;
+        socket.set_write_timeout(Some(options.timeout))?;
+
+        let logger = NetLogger::with_sink(log_level, config, Sink::Udp(socket), options);
+        logger.status.set_state(ConnectionState::Connected);
+        Ok(logger)
+    }
+
+    fn with_sink(
+        log_level: LevelFilter,
+        config: Config,
+        sink: Sink,
+        options: &NetLoggerOptions,
+    ) -> Box<NetLogger> {
+        let level = LevelHandle::new(log_level, Some(&config));
+        Box::new(NetLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            output: Mutex::new(NetOutput {
+                sink,
+                state: LoggerState::default(),
+            }),
+            options: options.clone(),
+            status: NetStatus::new(),
+        })
+    }
+
+    /// Returns the connection status of this logger, which stays accessible after it was moved.
+    pub fn status(&self) -> NetStatus {
+        self.status.clone()
+    }
+
+    fn send(
+        &self,
+        sink: &mut Sink,
+        config: &Config,
+        record: &Record<'_>,
+        deadline: Instant,
+    ) -> io::Result<()> {
+        let mut line = Vec::new();
+        if !format_record(config, record, &mut line)? {
+            return Ok(());
+        }
+        // Receivers split on newlines, so every record ends in exactly one
+        while matches!(line.last(), Some(b'\n' | b'\r')) {
+            line.pop();
+        }
+        line.push(b'\n');
+
+        match sink {
+            Sink::Udp(socket) => {
+                if socket.send(&line).is_err() {
+                    self.status.0.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Sink::Tcp(tcp) => {
+                tcp.backlog.push_back(line);
+                if tcp.backlog.len() > self.options.buffer_records.max(1) {
+                    // A line that was sent partially has to be finished first
+                    tcp.backlog.remove(usize::from(tcp.sent > 0));
+                    self.status.0.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                self.drain(tcp, deadline);
+                self.status
+                    .0
+                    .buffered
+                    .store(tcp.backlog.len(), Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the buffered records until `deadline`, connecting first if necessary and due.
+    fn drain(&self, tcp: &mut TcpSink, deadline: Instant) {
+        if tcp.stream.is_none() {
+            if Instant::now() < tcp.next_attempt {
+                return;
+            }
+            match self.connect(&tcp.addrs, deadline) {
+                Ok(stream) => {
+                    tcp.stream = Some(stream);
+                    tcp.backoff = self.options.min_backoff;
+                    self.status.set_state(ConnectionState::Connected);
+                }
+                Err(_) => {
+                    tcp.next_attempt = Instant::now() + tcp.backoff;
+                    tcp.backoff = (tcp.backoff * 2).min(self.options.max_backoff);
+                    return;
+                }
+            }
+        }
+
+        let stream = tcp.stream.as_mut().unwrap();
+        while let Some(line) = tcp.backlog.front() {
+            // The rest is sent by the next call
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            }
+            let result = stream
+                .set_write_timeout(Some(remaining))
+                .and_then(|()| stream.write(&line[tcp.sent..]));
+            match result {
+                Ok(len) if len > 0 => {
+                    tcp.sent += len;
+                    if tcp.sent == line.len() {
+                        tcp.backlog.pop_front();
+                        tcp.sent = 0;
+                    }
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::TimedOut
+                            | io::ErrorKind::Interrupted
+                    ) => {}
+                _ => {
+                    // The line may have been sent partially, it is resent as a whole
+                    tcp.stream = None;
+                    tcp.sent = 0;
+                    tcp.next_attempt = Instant::now();
+                    self.status.set_state(ConnectionState::Disconnected);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Connects to the first address that answers before `deadline`.
+    fn connect(&self, addrs: &[SocketAddr], deadline: Instant) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in addrs {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            match TcpStream::connect_timeout(addr, remaining) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| io::ErrorKind::TimedOut.into()))
+    }
+}
+
+fn resolve<A: ToSocketAddrs>(addr: A) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "address resolved to nothing",
+        ));
+    }
+    Ok(addrs)
+}
+
+impl Log for NetLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let deadline = Instant::now() + self.options.timeout;
+            let mut output = self.output.lock().unwrap();
+            let NetOutput { sink, state } = &mut *output;
+            let result = state.log(&config, record, |record| {
+                self.send(sink, &config, record, deadline)
+            });
+            state.report(&config, "NetLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let config = self.config.get();
+        let deadline = Instant::now() + self.options.timeout;
+        let mut output = self.output.lock().unwrap();
+        let NetOutput { sink, state } = &mut *output;
+        let result = state.flush(|record| self.send(sink, &config, record, deadline));
+        state.report(&config, "NetLogger", result);
+        if let Sink::Tcp(tcp) = sink {
+            self.drain(tcp, deadline);
+            if let Some(stream) = &mut tcp.stream {
+                let _ = stream.flush();
+            }
+        }
+    }
+}
+
+impl SharedLogger for NetLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.output.lock().unwrap().state.stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
@@ -0,0 +1,253 @@
+//! Queue for records logged while the lock of their logger was taken, see
+//! [`ConfigBuilder::set_nonblocking`](crate::ConfigBuilder::set_nonblocking).
+
+use super::logging::format_record;
+use super::stats::LoggerStats;
+use crate::Config;
+use log::{Level, Record};
+use std::cell::{RefCell, UnsafeCell};
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError, TryLockError};
+
+/// Number of records the queue holds.
+const CAPACITY: usize = 64;
+/// Size of a queued record in bytes, longer records are cut.
+const LINE_SIZE: usize = 256;
+
+thread_local! {
+    /// Addresses of the output locks the current thread holds or is waiting for
+    static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The lock of a logger's output, taken by [`lock_output`].
+pub(crate) struct OutputGuard<'a, T> {
+    guard: MutexGuard<'a, T>,
+    /// The address the current thread registered in `HELD`
+    held: Option<usize>,
+}
+
+impl<T> Deref for OutputGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for OutputGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for OutputGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(addr) = self.held {
+            let _ = HELD.try_with(|held| {
+                if let Ok(mut held) = held.try_borrow_mut() {
+                    if let Some(index) = held.iter().rposition(|held| *held == addr) {
+                        held.remove(index);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Locks `mutex`, or if `config` makes logging nonblocking, returns `None` if the current
+/// thread already holds it, e.g. when a record is logged while writing another one.
+///
+/// Other threads holding the lock are waited for either way.
+pub(crate) fn lock_output<'a, T>(
+    mutex: &'a Mutex<T>,
+    config: &Config,
+) -> Option<OutputGuard<'a, T>> {
+    if !config.nonblocking {
+        return Some(OutputGuard {
+            guard: mutex.lock().unwrap(),
+            held: None,
+        });
+    }
+
+    let addr = mutex as *const Mutex<T> as usize;
+    // Registered before locking, so a signal handler interrupting the wait sees it
+    let reentrant = HELD.try_with(|held| match held.try_borrow_mut() {
+        Ok(mut held) if !held.contains(&addr) => {
+            held.push(addr);
+            false
+        }
+        // Borrowed by the code this one interrupted
+        _ => true,
+    });
+    match reentrant {
+        Ok(true) => None,
+        Ok(false) => Some(OutputGuard {
+            guard: mutex.lock().unwrap_or_else(PoisonError::into_inner),
+            held: Some(addr),
+        }),
+        // The thread is exiting, so it cannot be tracked
+        Err(_) => match mutex.try_lock() {
+            Ok(guard) => Some(OutputGuard { guard, held: None }),
+            Err(TryLockError::Poisoned(err)) => Some(OutputGuard {
+                guard: err.into_inner(),
+                held: None,
+            }),
+            Err(TryLockError::WouldBlock) => None,
+        },
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Line {
+    level: Level,
+    stderr: bool,
+    len: usize,
+    bytes: [u8; LINE_SIZE],
+}
+
+impl Write for Line {
+    /// Takes as much of `buf` as fits, the rest is cut.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let taken = buf.len().min(LINE_SIZE - self.len);
+        self.bytes[self.len..self.len + taken].copy_from_slice(&buf[..taken]);
+        self.len += taken;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Slot {
+    /// The position the slot is free for, or that position + 1 once its line is written
+    seq: AtomicUsize,
+    line: UnsafeCell<Line>,
+}
+
+/// A fixed-size queue of formatted records, which any thread can add to without
+/// taking a lock, and the holder of the logger's lock takes out again.
+pub(crate) struct OverflowQueue {
+    /// Allocated by the first record that needs it
+    slots: OnceLock<Box<[Slot]>>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    stats: LoggerStats,
+}
+
+// A line is only accessed by the thread that claimed its slot through `seq`
+unsafe impl Sync for OverflowQueue {}
+
+impl OverflowQueue {
+    /// Creates an empty queue, counting the records it handles in `stats`.
+    pub(crate) fn new(stats: LoggerStats) -> OverflowQueue {
+        OverflowQueue {
+            slots: OnceLock::new(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            stats,
+        }
+    }
+
+    /// Formats `record` and adds it to the queue, to be written to stderr if `stderr` is set.
+    ///
+    /// The record is counted as skipped if the `Config` leaves it out, and as dropped if
+    /// the queue is full.
+    pub(crate) fn push(&self, config: &Config, record: &Record<'_>, stderr: bool) {
+        let mut line = Line {
+            level: record.level(),
+            stderr,
+            len: 0,
+            bytes: [0; LINE_SIZE],
+        };
+        match format_record(config, record, &mut line) {
+            Ok(true) => {}
+            Ok(false) => return self.stats.add_skipped(),
+            Err(_) => return self.stats.add_dropped(),
+        }
+        if line.len == LINE_SIZE {
+            cut_line(&mut line, config.line_ending.as_bytes());
+        }
+
+        let slots = self.slots.get_or_init(|| {
+            (0..CAPACITY)
+                .map(|seq| Slot {
+                    seq: AtomicUsize::new(seq),
+                    line: UnsafeCell::new(line),
+                })
+                .collect()
+        });
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &slots[pos % CAPACITY];
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq == pos {
+                match self.head.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: the slot was claimed above, no other thread accesses it
+                        // before `seq` is advanced
+                        unsafe { *slot.line.get() = line };
+                        slot.seq.store(pos + 1, Ordering::Release);
+                        return;
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if seq < pos {
+                // The slot still holds the line from one round before
+                return self.stats.add_dropped();
+            } else {
+                pos = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Passes the queued lines to `write` with their `stderr` flag, oldest first.
+    ///
+    /// Must only be called while holding the lock of the logger.
+    pub(crate) fn drain<F>(&self, mut write: F) -> io::Result<()>
+    where
+        F: FnMut(&[u8], bool) -> io::Result<()>,
+    {
+        let Some(slots) = self.slots.get() else {
+            return Ok(());
+        };
+        loop {
+            let pos = self.tail.load(Ordering::Relaxed);
+            let slot = &slots[pos % CAPACITY];
+            if slot.seq.load(Ordering::Acquire) != pos + 1 {
+                return Ok(());
+            }
+            // SAFETY: the line was completely written, and the slot is not reused
+            // before `seq` is advanced
+            let line = unsafe { *slot.line.get() };
+            slot.seq.store(pos + CAPACITY, Ordering::Release);
+            self.tail.store(pos + 1, Ordering::Relaxed);
+
+            match write(&line.bytes[..line.len], line.stderr) {
+                Ok(()) => self.stats.add_written(line.level),
+                Err(err) => {
+                    self.stats.add_dropped();
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+/// Ends a line that was cut at `LINE_SIZE` with `line_ending`, cutting it at a char boundary.
+fn cut_line(line: &mut Line, line_ending: &[u8]) {
+    let end = LINE_SIZE.saturating_sub(line_ending.len());
+    let end = match std::str::from_utf8(&line.bytes[..end]) {
+        Ok(_) => end,
+        Err(err) => err.valid_up_to(),
+    };
+    line.bytes[end..end + line_ending.len()].copy_from_slice(line_ending);
+    line.len = end + line_ending.len();
+}
@@ -0,0 +1,254 @@
+//! Module providing the RingBufferLogger Implementation
+
+use super::logging::{format_record, level_enabled, metadata_enabled, LoggerState, PreparedRecord};
+use crate::{Config, ConfigHandle, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How many lines a `RingBufferLogger` keeps before overwriting the oldest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingBufferCapacity {
+    /// Keep at most this many records
+    Records(usize),
+    /// Keep at most this many bytes of formatted output. The newest record is always kept.
+    Bytes(usize),
+}
+
+struct Lines {
+    lines: VecDeque<Vec<u8>>,
+    bytes: usize,
+    capacity: RingBufferCapacity,
+    /// Allocation of the last evicted line, reused for the next record
+    spare: Vec<u8>,
+}
+
+impl Lines {
+    fn push(&mut self, line: Vec<u8>) {
+        self.bytes += line.len();
+        self.lines.push_back(line);
+
+        while self.lines.len() > 1 && self.is_over_capacity() {
+            let evicted = self.lines.pop_front().unwrap();
+            self.bytes -= evicted.len();
+            self.spare = evicted;
+        }
+    }
+
+    fn is_over_capacity(&self) -> bool {
+        match self.capacity {
+            RingBufferCapacity::Records(records) => self.lines.len() > records,
+            RingBufferCapacity::Bytes(bytes) => self.bytes > bytes,
+        }
+    }
+}
+
+/// The lines kept by a `RingBufferLogger`.
+///
+/// Cheap to clone, all clones share the same lines. Obtained from `RingBufferLogger::buffer`
+/// or `RingBufferLogger::init`, so it can be handed to a panic hook or error handler after
+/// the logger was moved into a `CombinedLogger` or installed globally.
+#[derive(Clone)]
+pub struct RingBuffer(Arc<Mutex<(Lines, LoggerState)>>);
+
+impl RingBuffer {
+    fn lock(&self) -> MutexGuard<'_, (Lines, LoggerState)> {
+        // Dumping from a panic hook must work even if the panic happened while logging
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the kept lines, oldest first, exactly as they were formatted.
+    pub fn dump(&self) -> Vec<String> {
+        self.lock()
+            .0
+            .lines
+            .iter()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect()
+    }
+
+    /// Writes the kept lines, oldest first, into `writer`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let buffer = self.lock();
+        for line in &buffer.0.lines {
+            writer.write_all(line)?;
+        }
+        writer.flush()
+    }
+
+    /// Removes all kept lines.
+    pub fn clear(&self) {
+        let mut buffer = self.lock();
+        buffer.0.lines.clear();
+        buffer.0.bytes = 0;
+    }
+}
+
+/// The RingBufferLogger struct. Provides a Logger implementation that keeps the last records in memory
+///
+/// Records are formatted like in every other logger, so a dump looks identical to a file log.
+/// Meant to run at a verbose level next to less verbose loggers, to attach the recent
+/// history to crash or error reports.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # fn main() {
+/// let config = ConfigBuilder::new().set_formatter(Some("[message]")).build();
+/// let logger = RingBufferLogger::new(LevelFilter::Trace, config, RingBufferCapacity::Records(2));
+///
+/// for message in ["one", "two", "three"] {
+///     logger.log(&Record::builder().args(format_args!("{}", message)).build());
+/// }
+///
+/// assert_eq!(logger.buffer().dump(), vec!["two\n", "three\n"]);
+/// # }
+/// ```
+pub struct RingBufferLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    buffer: RingBuffer,
+}
+
+impl RingBufferLogger {
+    /// init function. Globally initializes the RingBufferLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level`, `Config` and `RingBufferCapacity` as arguments and returns
+    /// the `RingBuffer` to dump. Fails if another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let _ = RingBufferLogger::init(LevelFilter::Trace, Config::default(), RingBufferCapacity::Records(500));
+    /// # }
+    /// ```
+    pub fn init(
+        log_level: LevelFilter,
+        config: Config,
+        capacity: RingBufferCapacity,
+    ) -> Result<RingBuffer, SetLoggerError> {
+        let logger = RingBufferLogger::new(log_level, config, capacity);
+        let handle = logger.level.clone();
+        let buffer = logger.buffer();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(buffer)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Takes the desired `Level`, `Config` and `RingBufferCapacity` as arguments.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let ring_logger = RingBufferLogger::new(LevelFilter::Trace, Config::default(), RingBufferCapacity::Bytes(64 * 1024));
+    /// let buffer = ring_logger.buffer();
+    /// let _ = CombinedLogger::init(vec![
+    ///     FileLogger::new(LevelFilter::Warn, Config::default(), "my_rust_bin.log", None),
+    ///     ring_logger,
+    /// ]);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn new(
+        log_level: LevelFilter,
+        config: Config,
+        capacity: RingBufferCapacity,
+    ) -> Box<RingBufferLogger> {
+        let level = LevelHandle::new(log_level, Some(&config));
+        let lines = Lines {
+            lines: VecDeque::new(),
+            bytes: 0,
+            capacity,
+            spare: Vec::new(),
+        };
+        Box::new(RingBufferLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            buffer: RingBuffer(Arc::new(Mutex::new((lines, LoggerState::default())))),
+        })
+    }
+
+    /// Returns the lines kept by this logger, which stay accessible after it was moved.
+    pub fn buffer(&self) -> RingBuffer {
+        self.buffer.clone()
+    }
+}
+
+fn push_record(lines: &mut Lines, config: &Config, record: &Record<'_>) -> io::Result<()> {
+    let mut line = std::mem::take(&mut lines.spare);
+    line.clear();
+    if !format_record(config, record, &mut line)? {
+        lines.spare = line;
+    } else {
+        lines.push(line);
+    }
+    Ok(())
+}
+
+impl Log for RingBufferLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut buffer = self.buffer.lock();
+            let (lines, state) = &mut *buffer;
+            let result = state.log(&config, record, |record| {
+                push_record(lines, &config, record)
+            });
+            state.report(&config, "RingBufferLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let config = self.config.get();
+        let mut buffer = self.buffer.lock();
+        let (lines, state) = &mut *buffer;
+        let result = state.flush(|record| push_record(lines, &config, record));
+        state.report(&config, "RingBufferLogger", result);
+    }
+}
+
+impl SharedLogger for RingBufferLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.buffer.lock().1.stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
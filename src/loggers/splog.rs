@@ -1,22 +1,66 @@
-use super::logging::try_log;
-use crate::{Config, SharedLogger};
-use log::{
-    set_boxed_logger, set_max_level, Level, LevelFilter, Log, Metadata, Record, SetLoggerError,
+use super::logging::{
+    format_record, level_enabled, metadata_enabled, reset_color, set_color, LoggerState,
+    PreparedRecord, COLORS,
 };
-use std::io::{stderr, stdout};
-use std::sync::Mutex;
+use crate::{Config, ConfigHandle, LevelHandle, LoggerStats, SharedLogger, TermLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io::{stderr, stdout, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use termcolor2::{Ansi, ColorChoice};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+/// The stream a `SimpleLogger` writes to, see [`SimpleLogger::new_with_stream`].
+pub enum OutputStream {
+    /// Use Stderr for Errors and Stdout otherwise
+    #[default]
+    Mixed,
+    /// Only use Stdout
+    Stdout,
+    /// Only use Stderr, keeping the output of the program on stdout clean
+    Stderr,
+}
+
+impl OutputStream {
+    /// Returns true if records of `level` are written to stderr.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// fn stderr_levels(stream: OutputStream) -> Vec<Level> {
+    ///     Level::iter().filter(|&level| stream.uses_stderr(level)).collect()
+    /// }
+    ///
+    /// assert_eq!(stderr_levels(OutputStream::default()), [Level::Error]);
+    /// assert_eq!(stderr_levels(OutputStream::Stdout), Vec::<Level>::new());
+    /// assert_eq!(stderr_levels(OutputStream::Stderr), Level::iter().collect::<Vec<_>>());
+    /// ```
+    pub fn uses_stderr(self, level: Level) -> bool {
+        match self {
+            OutputStream::Mixed => level == Level::Error,
+            OutputStream::Stdout => false,
+            OutputStream::Stderr => true,
+        }
+    }
+}
 
 /// The SimpleLogger struct. Provides a very basic Logger implementation
+///
+/// Lines written to a terminal are shown in the color of their level, if the `Config`
+/// enables colors and the color environment variables do not disable them.
 pub struct SimpleLogger {
-    level: LevelFilter,
-    config: Config,
-    output_lock: Mutex<()>,
+    level: LevelHandle,
+    config: ConfigHandle,
+    stream: OutputStream,
+    /// Whether stdout and stderr are terminals
+    terminals: [bool; 2],
+    output_lock: Mutex<LoggerState>,
 }
 
 impl SimpleLogger {
     /// init function. Globally initializes the SimpleLogger as the one and only used log facility.
     ///
-    /// Takes the desired `Level` and `Config` as arguments. They cannot be changed later on.
+    /// Takes the desired `Level` and `Config` as arguments. The level can be changed later on
+    /// through the returned `LevelHandle`.
     /// Fails if another Logger was already initialized.
     ///
     /// # Examples
@@ -27,9 +71,12 @@ impl SimpleLogger {
     /// let _ = SimpleLogger::init(LevelFilter::Info, Config::default());
     /// # }
     /// ```
-    pub fn init(log_level: LevelFilter, config: Config) -> Result<(), SetLoggerError> {
-        set_max_level(log_level);
-        set_boxed_logger(SimpleLogger::new(log_level, config))
+    pub fn init(log_level: LevelFilter, config: Config) -> Result<LevelHandle, SetLoggerError> {
+        let logger = SimpleLogger::new(log_level, config);
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
     }
 
     /// allows to create a new logger, that can be independently used, no matter what is globally set.
@@ -49,51 +96,135 @@ impl SimpleLogger {
     /// ```
     #[must_use]
     pub fn new(log_level: LevelFilter, config: Config) -> Box<SimpleLogger> {
+        SimpleLogger::new_with_stream(log_level, config, OutputStream::default())
+    }
+
+    /// Same as [`SimpleLogger::new`], but writes to the given `stream`, e.g. only to
+    /// stderr for tools whose output goes to stdout.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let simple_logger = SimpleLogger::new_with_stream(LevelFilter::Info, Config::default(), OutputStream::Stderr);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn new_with_stream(
+        log_level: LevelFilter,
+        config: Config,
+        stream: OutputStream,
+    ) -> Box<SimpleLogger> {
+        let level = LevelHandle::new(log_level, Some(&config));
         Box::new(SimpleLogger {
-            level: log_level,
-            config,
-            output_lock: Mutex::new(()),
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            stream,
+            terminals: [stdout().is_terminal(), stderr().is_terminal()],
+            output_lock: Mutex::new(LoggerState::default()),
         })
     }
 }
 
+impl SimpleLogger {
+    fn try_log(&self, config: &Config, record: &Record<'_>) -> Result<(), std::io::Error> {
+        let to_stderr = self.stream.uses_stderr(record.level());
+        let colored = self.terminals[usize::from(to_stderr)] && colors_enabled(config);
+        if to_stderr {
+            write_record(config, record, &mut stderr().lock(), colored)
+        } else {
+            write_record(config, record, &mut stdout().lock(), colored)
+        }
+    }
+}
+
+/// Returns true if `config` enables colors and the color environment variables do
+/// not disable them.
+fn colors_enabled(config: &Config) -> bool {
+    COLORS
+        && config.enable_colors
+        && (!config.respect_color_env
+            || TermLogger::resolve_color_choice(ColorChoice::Auto, |name| std::env::var(name).ok())
+                != ColorChoice::Never)
+}
+
+/// Writes `record` to `writer`, in the color of its level if `colored` is set.
+fn write_record<W: Write>(
+    config: &Config,
+    record: &Record<'_>,
+    writer: &mut W,
+    colored: bool,
+) -> Result<(), std::io::Error> {
+    if !colored {
+        return format_record(config, record, writer).map(drop);
+    }
+
+    let mut line = Vec::new();
+    if !format_record(config, record, &mut line)? {
+        return Ok(());
+    }
+    let line_ending = config.line_ending.as_bytes();
+    let text = line.strip_suffix(line_ending).unwrap_or(&line);
+
+    let mut writer = Ansi::new(writer);
+    set_color(
+        &mut writer,
+        &config.level_style[record.level() as usize].color_spec(),
+    )?;
+    writer.write_all(text)?;
+    reset_color(&mut writer)?;
+    writer.write_all(&line[text.len()..])
+}
+
 impl Log for SimpleLogger {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level() <= self.level
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
     }
 
     fn log(&self, record: &Record<'_>) {
-        if self.enabled(record.metadata()) {
-            let _lock = self.output_lock.lock().unwrap();
-
-            match record.level() {
-                Level::Error => {
-                    let stderr = stderr();
-                    let mut stderr_lock = stderr.lock();
-                    let _ = try_log(&self.config, record, &mut stderr_lock);
-                }
-                _ => {
-                    let stdout = stdout();
-                    let mut stdout_lock = stdout.lock();
-                    let _ = try_log(&self.config, record, &mut stdout_lock);
-                }
-            }
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut state = self.output_lock.lock().unwrap();
+            let result = state.log(&config, record, |record| self.try_log(&config, record));
+            state.report(&config, "SimpleLogger", result);
         }
     }
 
     fn flush(&self) {
-        use std::io::Write;
-        let _ = stdout().flush();
+        let mut state = self.output_lock.lock().unwrap();
+        let config = self.config.get();
+        let result = state
+            .flush(|record| self.try_log(&config, record))
+            .and(stdout().flush())
+            .and(stderr().flush());
+        state.report(&config, "SimpleLogger", result);
     }
 }
 
 impl SharedLogger for SimpleLogger {
     fn level(&self) -> LevelFilter {
-        self.level
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
     }
 
-    fn config(&self) -> Option<&Config> {
-        Some(&self.config)
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.output_lock.lock().unwrap().stats.clone())
     }
 
     fn as_log(self: Box<Self>) -> Box<dyn Log> {
@@ -0,0 +1,102 @@
+use log::Level;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct Counters {
+    /// Written records, indexed by `Level as usize - 1`
+    written: [AtomicU64; 5],
+    skipped: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Counts the records a logger handled, returned by `SharedLogger::stats`.
+///
+/// The counters are updated while the logger runs, so the same `LoggerStats` can be
+/// read repeatedly, e.g. by a health endpoint. Handles are cheap to clone and can be
+/// sent to other threads.
+///
+/// Records below the level of the logger are not counted at all. Records of an enabled
+/// level are counted as written, as skipped if the filters, sampling or repeat
+/// suppression of the `Config` left them out, or as dropped if writing them failed.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # fn main() {
+/// let logger = TestLogger::new(
+///     LevelFilter::Info,
+///     ConfigBuilder::new().add_filter_ignore_str("noisy").build(),
+/// );
+/// let stats = logger.stats().unwrap();
+///
+/// logger.log(&Record::builder().level(Level::Warn).args(format_args!("low disk space")).build());
+/// logger.log(&Record::builder().level(Level::Info).args(format_args!("started")).build());
+/// logger.log(&Record::builder().level(Level::Info).target("noisy").args(format_args!("tick")).build());
+/// logger.log(&Record::builder().level(Level::Debug).args(format_args!("not enabled")).build());
+///
+/// assert_eq!(stats.written(Level::Warn), 1);
+/// assert_eq!(stats.written(Level::Info), 1);
+/// assert_eq!(stats.written(Level::Error), 0);
+/// assert_eq!(stats.total_written(), 2);
+/// assert_eq!(stats.skipped(), 1);
+/// assert_eq!(stats.dropped(), 0);
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct LoggerStats(Arc<Counters>);
+
+impl LoggerStats {
+    /// Returns the number of records written at `level`.
+    pub fn written(&self, level: Level) -> u64 {
+        self.0.written[level as usize - 1].load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of records written at any level.
+    pub fn total_written(&self) -> u64 {
+        self.0
+            .written
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Returns the number of records left out by the filters, sampling or repeat
+    /// suppression of the `Config`.
+    pub fn skipped(&self) -> u64 {
+        self.0.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of records that could not be written.
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_written(&self, level: Level) {
+        self.0.written[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_skipped(&self) {
+        self.0.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_dropped(&self) {
+        self.0.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for LoggerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut stats = f.debug_struct("LoggerStats");
+        for level in Level::iter() {
+            stats.field(level.as_str(), &self.written(level));
+        }
+        stats
+            .field("skipped", &self.skipped())
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
@@ -0,0 +1,356 @@
+//! Module providing the SyslogLogger Implementation
+
+use super::logging::{
+    hostname, level_enabled, level_in_range, mark_written, metadata_enabled, should_skip,
+    LoggerState, RecordOrigin,
+};
+use crate::config::FormatFlags;
+use crate::{Config, ConfigHandle, InitError, LevelHandle, LoggerStats, SharedLogger};
+use chrono::SecondsFormat;
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Syslog facility, describing which part of the system a message originates from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Facility {
+    Kern = 0,
+    #[default]
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    AuthPriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Options for the messages of a `SyslogLogger`.
+#[derive(Debug, Clone, Default)]
+pub struct SyslogOptions {
+    pub(crate) facility: Facility,
+    pub(crate) app_name: Option<String>,
+}
+
+impl SyslogOptions {
+    /// Creates the default options
+    pub fn new() -> SyslogOptions {
+        SyslogOptions::default()
+    }
+
+    /// Sets the facility of all messages. Default is `Facility::User`.
+    pub fn facility(&mut self, facility: Facility) -> &mut SyslogOptions {
+        self.facility = facility;
+        self
+    }
+
+    /// Sets the APP-NAME of all messages. Default is the file name of the executable.
+    pub fn app_name(&mut self, app_name: &str) -> &mut SyslogOptions {
+        self.app_name = Some(app_name.to_string());
+        self
+    }
+}
+
+enum Transport {
+    Unix(UnixDatagram),
+    Udp(UdpSocket),
+}
+
+/// The SyslogLogger struct. Provides a Logger implementation that writes RFC 5424 messages to syslog
+///
+/// Messages are sent to the local syslog daemon through `/dev/log`, or to a remote one
+/// over UDP. The `Config` filters apply, but the text layout does not, as syslog messages
+/// have a header of their own. Only the target is put in front of the message if
+/// `FormatFlags::TARGET` is enabled.
+pub struct SyslogLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    transport: Transport,
+    state: Mutex<LoggerState>,
+    facility: Facility,
+    hostname: String,
+    app_name: String,
+    pid: u32,
+}
+
+impl SyslogLogger {
+    /// init function. Globally initializes the SyslogLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level`, `Config` and `SyslogOptions` as arguments.
+    /// Fails if the syslog socket cannot be connected or another Logger was already initialized.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let mut options = SyslogOptions::new();
+    /// options.facility(Facility::Daemon).app_name("my_daemon");
+    /// let _ = SyslogLogger::init(LevelFilter::Info, Config::default(), &options);
+    /// # }
+    /// ```
+    pub fn init(
+        log_level: LevelFilter,
+        config: Config,
+        options: &SyslogOptions,
+    ) -> Result<LevelHandle, InitError> {
+        let logger = SyslogLogger::new(log_level, config, options)?;
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// no macros are provided for this case and you probably
+    /// dont want to use this function, but `init()`, if you dont want to build a `CombinedLogger`.
+    ///
+    /// Connects to the local syslog daemon through `/dev/log`, or `/var/run/syslog` on macOS.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// if let Ok(syslog_logger) = SyslogLogger::new(LevelFilter::Info, Config::default(), &SyslogOptions::new()) {
+    ///     let _ = CombinedLogger::init(vec![
+    ///         TermLogger::new(LevelFilter::Debug, Config::default(), TerminalMode::Mixed, ColorChoice::Auto),
+    ///         syslog_logger,
+    ///     ]);
+    /// }
+    /// # }
+    /// ```
+    pub fn new(
+        log_level: LevelFilter,
+        config: Config,
+        options: &SyslogOptions,
+    ) -> io::Result<Box<SyslogLogger>> {
+        match SyslogLogger::unix(log_level, config.clone(), "/dev/log", options) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                SyslogLogger::unix(log_level, config, "/var/run/syslog", options)
+            }
+            result => result,
+        }
+    }
+
+    /// Same as [`SyslogLogger::new`], but connects to the syslog socket at `path`.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::os::unix::net::UnixDatagram;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let path = std::env::temp_dir().join(format!("sp_log2-syslog-{}.sock", std::process::id()));
+    /// # let _ = std::fs::remove_file(&path);
+    /// let receiver = UnixDatagram::bind(&path).unwrap();
+    ///
+    /// let mut options = SyslogOptions::new();
+    /// options.facility(Facility::Local3).app_name("my_app");
+    /// let logger = SyslogLogger::unix(LevelFilter::Info, Config::default(), &path, &options).unwrap();
+    /// logger.log(&Record::builder().level(Level::Warn).target("db").args(format_args!("slow query")).build());
+    ///
+    /// let mut datagram = [0; 256];
+    /// let len = receiver.recv(&mut datagram).unwrap();
+    /// let message = String::from_utf8_lossy(&datagram[..len]);
+    /// // Local3 (19) * 8 + Warning (4)
+    /// assert!(message.starts_with("<156>1 "));
+    /// assert!(message.contains(" my_app "));
+    /// assert!(message.ends_with(" - - db: slow query"));
+    /// # drop(receiver);
+    /// # let _ = std::fs::remove_file(&path);
+    /// # }
+    /// ```
+    pub fn unix<P: AsRef<Path>>(
+        log_level: LevelFilter,
+        config: Config,
+        path: P,
+        options: &SyslogOptions,
+    ) -> io::Result<Box<SyslogLogger>> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(SyslogLogger::with_transport(
+            log_level,
+            config,
+            Transport::Unix(socket),
+            options,
+        ))
+    }
+
+    /// Same as [`SyslogLogger::new`], but sends the messages to a remote syslog daemon over UDP.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let syslog_logger = SyslogLogger::udp(LevelFilter::Info, Config::default(), "127.0.0.1:514", &SyslogOptions::new());
+    /// # }
+    /// ```
+    pub fn udp<A: ToSocketAddrs>(
+        log_level: LevelFilter,
+        config: Config,
+        addr: A,
+        options: &SyslogOptions,
+    ) -> io::Result<Box<SyslogLogger>> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        let local: SocketAddr = match addrs.first() {
+            Some(addr) if addr.is_ipv6() => ([0u16; 8], 0).into(),
+            _ => ([0, 0, 0, 0], 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(&addrs[..])?;
+        Ok(SyslogLogger::with_transport(
+            log_level,
+            config,
+            Transport::Udp(socket),
+            options,
+        ))
+    }
+
+    fn with_transport(
+        log_level: LevelFilter,
+        config: Config,
+        transport: Transport,
+        options: &SyslogOptions,
+    ) -> Box<SyslogLogger> {
+        let app_name = options.app_name.clone().unwrap_or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| {
+                    exe.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                })
+                .unwrap_or_else(|| "-".to_string())
+        });
+
+        let level = LevelHandle::new(log_level, Some(&config));
+        Box::new(SyslogLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            transport,
+            state: Mutex::new(LoggerState::default()),
+            facility: options.facility,
+            hostname: header_field(hostname(), 255),
+            app_name: header_field(&app_name, 48),
+            pid: std::process::id(),
+        })
+    }
+
+    fn send(&self, config: &Config, record: &Record<'_>) -> io::Result<()> {
+        if should_skip(config, record) || !level_in_range(config, record.level()) {
+            return Ok(());
+        }
+        mark_written();
+
+        let severity = match record.level() {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        };
+        let timestamp = RecordOrigin::current()
+            .time
+            .to_rfc3339_opts(SecondsFormat::Micros, false);
+        let target = if config.format.contains(FormatFlags::TARGET) {
+            format!("{}: ", record.target())
+        } else {
+            String::new()
+        };
+
+        let message = format!(
+            "<{}>1 {} {} {} {} - - {}{}",
+            self.facility as u8 * 8 + severity,
+            timestamp,
+            self.hostname,
+            self.app_name,
+            self.pid,
+            target,
+            record.args()
+        );
+        match &self.transport {
+            Transport::Unix(socket) => socket.send(message.as_bytes())?,
+            Transport::Udp(socket) => socket.send(message.as_bytes())?,
+        };
+        Ok(())
+    }
+}
+
+/// Makes `value` a valid header field: printable ASCII without spaces, at most `max_len` long.
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+impl Log for SyslogLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut state = self.state.lock().unwrap();
+            let result = state.log(&config, record, |record| self.send(&config, record));
+            state.report(&config, "SyslogLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let config = self.config.get();
+        let mut state = self.state.lock().unwrap();
+        let result = state.flush(|record| self.send(&config, record));
+        state.report(&config, "SyslogLogger", result);
+    }
+}
+
+impl SharedLogger for SyslogLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.state.lock().unwrap().stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
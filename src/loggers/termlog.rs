@@ -1,20 +1,278 @@
-use log::{
-    set_boxed_logger, set_max_level, Level, LevelFilter, Log, Metadata, Record, SetLoggerError,
-};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
 use std::default::Default;
-use std::io::{Error, Write};
-use std::sync::Mutex;
+use std::io::{self, Error, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use termcolor2::WriteColor;
 use termcolor2::{BufferedStandardStream, ColorChoice};
-use termcolor2::{ColorSpec, WriteColor};
 
 use super::logging::*;
 
-use crate::config::Format;
-use crate::{Config, SharedLogger, ThreadLogMode};
+use super::overflow::{lock_output, OverflowQueue};
+use crate::config::{FormatFlags, LogField, WrapMode};
+use crate::json::write_json;
+use crate::{
+    Config, ConfigBuilder, ConfigHandle, FlushGuard, LevelHandle, LoggerStats, SharedLogger,
+};
+
+#[cfg(windows)]
+mod console {
+    use std::ffi::c_void;
+
+    pub(super) const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    pub(super) const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+        fn GetConsoleScreenBufferInfo(console: *mut c_void, info: *mut ScreenBufferInfo) -> i32;
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ScreenBufferInfo {
+        size: [i16; 2],
+        cursor_position: [i16; 2],
+        attributes: u16,
+        window: [i16; 4],
+        maximum_window_size: [i16; 2],
+    }
+
+    /// Enables virtual terminal processing on the console of the standard `handle`, so it
+    /// interprets ANSI escape sequences.
+    ///
+    /// Returns false if the console does not support it. Handles that are not a console,
+    /// e.g. pipes, are left alone.
+    pub(super) fn enable_ansi(handle: u32) -> bool {
+        // SAFETY: the handle is only passed to the console functions, which fail for
+        // invalid and non-console handles
+        unsafe {
+            let console = GetStdHandle(handle);
+            let mut console_mode = 0;
+            if GetConsoleMode(console, &mut console_mode) == 0 {
+                return true;
+            }
+            console_mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(console, console_mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        }
+    }
+
+    /// Returns the width of the console window of the standard `handle`.
+    pub(super) fn width(handle: u32) -> Option<usize> {
+        let mut info = ScreenBufferInfo::default();
+        // SAFETY: `info` is a valid CONSOLE_SCREEN_BUFFER_INFO to write to
+        if unsafe { GetConsoleScreenBufferInfo(GetStdHandle(handle), &mut info) } == 0 {
+            return None;
+        }
+        let [left, _, right, _] = info.window;
+        usize::try_from(right - left + 1).ok()
+    }
+}
+
+#[cfg(unix)]
+mod winsize {
+    use std::os::raw::c_int;
+
+    /// Returns the width of the terminal of the file descriptor `fd`.
+    pub(super) fn width(fd: c_int) -> Option<usize> {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ writes a winsize struct to the pointer, and fails for
+        // descriptors that are not a terminal
+        if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } != 0 || size.ws_col == 0 {
+            return None;
+        }
+        Some(size.ws_col.into())
+    }
+}
+
+/// How long a looked up terminal width is used, before it is looked up again to follow resizes
+const TERMINAL_WIDTH_TTL: Duration = Duration::from_secs(1);
+
+/// The cached width of the terminal a stream writes to, for wrapping messages.
+struct TerminalWidth {
+    stderr: bool,
+    /// Whether this is stdout or stderr, rather than a stream handed to `with_streams`
+    standard: bool,
+    is_terminal: bool,
+    width: Option<usize>,
+    checked: Option<Instant>,
+}
+
+impl TerminalWidth {
+    fn new(stderr: bool) -> TerminalWidth {
+        let is_terminal = if stderr {
+            io::stderr().is_terminal()
+        } else {
+            io::stdout().is_terminal()
+        };
+        TerminalWidth {
+            stderr,
+            standard: true,
+            is_terminal,
+            width: None,
+            checked: None,
+        }
+    }
+
+    /// The width of a stream that is not a standard stream, which is only wrapped at a
+    /// fixed width.
+    fn none() -> TerminalWidth {
+        TerminalWidth {
+            stderr: false,
+            standard: false,
+            is_terminal: false,
+            width: None,
+            checked: None,
+        }
+    }
+
+    /// Returns the width messages are wrapped at with `wrap`, if any.
+    fn wrap_width(&mut self, wrap: WrapMode) -> Option<usize> {
+        match wrap {
+            WrapMode::Off => None,
+            WrapMode::Width(width) => (self.is_terminal || !self.standard).then_some(width),
+            WrapMode::Terminal if !self.is_terminal => None,
+            WrapMode::Terminal => {
+                if self
+                    .checked
+                    .is_none_or(|checked| checked.elapsed() >= TERMINAL_WIDTH_TTL)
+                {
+                    self.width = terminal_width(self.stderr);
+                    self.checked = Some(Instant::now());
+                }
+                self.width
+            }
+        }
+    }
+}
+
+/// Looks up the width of the terminal of stdout, or stderr if `stderr` is set.
+///
+/// Falls back to the `COLUMNS` environment variable on platforms where it cannot be looked up.
+fn terminal_width(stderr: bool) -> Option<usize> {
+    #[cfg(windows)]
+    let width = console::width(if stderr {
+        console::STD_ERROR_HANDLE
+    } else {
+        console::STD_OUTPUT_HANDLE
+    });
+    #[cfg(unix)]
+    let width = winsize::width(if stderr { 2 } else { 1 });
+    #[cfg(not(any(windows, unix)))]
+    let width = {
+        let _ = stderr;
+        None
+    };
+
+    width.or_else(|| {
+        std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+    })
+}
 
 struct OutputStreams {
-    err: BufferedStandardStream,
-    out: BufferedStandardStream,
+    err: TermStream,
+    out: TermStream,
+    state: LoggerState,
+}
+
+/// Consecutive failed writes to a closed stream after which it is no longer written to.
+const CLOSED_AFTER: u32 = 3;
+
+/// One of the streams of a `TermLogger`, which is turned off once it was closed, e.g. a
+/// pipe to `head` or a detached console, instead of failing the same write for every record.
+struct TermStream {
+    name: &'static str,
+    writer: Box<dyn WriteColor + Send>,
+    width: TerminalWidth,
+    failures: u32,
+    closed: bool,
+}
+
+impl TermStream {
+    fn new(
+        name: &'static str,
+        writer: Box<dyn WriteColor + Send>,
+        width: TerminalWidth,
+    ) -> TermStream {
+        TermStream {
+            name,
+            writer,
+            width,
+            failures: 0,
+            closed: false,
+        }
+    }
+
+    /// Runs `write` with the stream, unless it was turned off.
+    ///
+    /// The stream is turned off after [`CLOSED_AFTER`] consecutive writes failed because
+    /// it was closed, and the error handler of `config` is told once when that happens.
+    fn write<F>(&mut self, config: &Config, write: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut dyn WriteColor, &mut TerminalWidth) -> Result<(), Error>,
+    {
+        if self.closed {
+            return Err(Error::new(io::ErrorKind::BrokenPipe, StreamClosed));
+        }
+        let result = write(&mut *self.writer, &mut self.width);
+        match &result {
+            Err(error) if is_closed(error) => {
+                self.failures += 1;
+                if self.failures >= CLOSED_AFTER {
+                    self.closed = true;
+                    let error = Error::new(
+                        error.kind(),
+                        format!(
+                            "{} is closed, no longer writing to it: {}",
+                            self.name, error
+                        ),
+                    );
+                    notify_error(config, "TermLogger", &error);
+                }
+            }
+            _ => self.failures = 0,
+        }
+        result
+    }
+}
+
+/// Returns true if `error` means the other side closed the stream.
+fn is_closed(error: &Error) -> bool {
+    // ERROR_INVALID_HANDLE, e.g. after the console was detached
+    error.kind() == io::ErrorKind::BrokenPipe || (cfg!(windows) && error.raw_os_error() == Some(6))
+}
+
+/// The error of writes to a stream that was turned off, which is not reported again.
+#[derive(Debug)]
+struct StreamClosed;
+
+impl std::fmt::Display for StreamClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("stream is closed")
+    }
+}
+
+impl std::error::Error for StreamClosed {}
+
+/// Reports `result` to the error handler, unless it failed because a stream was turned off.
+fn report(state: &mut LoggerState, config: &Config, result: Result<(), Error>) {
+    match result {
+        Err(error)
+            if error
+                .get_ref()
+                .is_some_and(|error| error.is::<StreamClosed>()) => {}
+        result => state.report(config, "TermLogger", result),
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
@@ -26,21 +284,118 @@ pub enum TerminalMode {
     /// Use Stderr for Errors and Stdout otherwise
     #[default]
     Mixed,
+    /// Use Stderr for records at or above the given level and Stdout otherwise
+    MixedAt(Level),
+}
+
+impl TerminalMode {
+    /// Returns true if records of `level` are written to stderr in this mode.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// fn stderr_levels(mode: TerminalMode) -> Vec<Level> {
+    ///     Level::iter().filter(|&level| mode.uses_stderr(level)).collect()
+    /// }
+    ///
+    /// assert_eq!(stderr_levels(TerminalMode::Stdout), Vec::<Level>::new());
+    /// assert_eq!(stderr_levels(TerminalMode::Stderr), Level::iter().collect::<Vec<_>>());
+    /// assert_eq!(stderr_levels(TerminalMode::Mixed), [Level::Error]);
+    /// assert_eq!(stderr_levels(TerminalMode::MixedAt(Level::Error)), [Level::Error]);
+    /// assert_eq!(stderr_levels(TerminalMode::MixedAt(Level::Warn)), [Level::Error, Level::Warn]);
+    /// assert_eq!(
+    ///     stderr_levels(TerminalMode::MixedAt(Level::Trace)),
+    ///     Level::iter().collect::<Vec<_>>()
+    /// );
+    /// ```
+    pub fn uses_stderr(self, level: Level) -> bool {
+        match self {
+            TerminalMode::Stdout => false,
+            TerminalMode::Stderr => true,
+            TerminalMode::Mixed => level == Level::Error,
+            TerminalMode::MixedAt(threshold) => level <= threshold,
+        }
+    }
 }
 
 /// The TermLogger struct. Provides a stderr/out based Logger implementation
 ///
 /// Supports colored output
+///
+/// A stream that was closed, e.g. stdout piped to `head` or a detached console on Windows,
+/// is turned off after three writes in a row failed because of it, so that later records
+/// don't retry the failing write. The error handler of the `Config` is told once when
+/// that happens. The other stream, and the other loggers of a `CombinedLogger`, are still
+/// written to.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # use std::io::{self, Write};
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # use std::sync::{Arc, Mutex};
+/// struct ClosedPipe(Arc<AtomicUsize>);
+///
+/// impl Write for ClosedPipe {
+///     fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+///         self.0.fetch_add(1, Ordering::SeqCst);
+///         Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+///     }
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let attempts = Arc::new(AtomicUsize::new(0));
+/// let errors = Arc::new(Mutex::new(Vec::new()));
+/// let handler_errors = errors.clone();
+/// let config = ConfigBuilder::new()
+///     .set_format(FormatFlags::LEVEL)
+///     .set_error_handler(Arc::new(move |_: &str, error: &io::Error| {
+///         handler_errors.lock().unwrap().push(error.to_string());
+///     }))
+///     .build();
+/// let logger = TermLogger::with_streams(
+///     LevelFilter::Info,
+///     config,
+///     Box::new(termcolor2::NoColor::new(ClosedPipe(attempts.clone()))),
+///     Box::new(termcolor2::NoColor::new(Vec::new())),
+/// );
+///
+/// for i in 0..100 {
+///     logger.log(&Record::builder().level(Level::Info).args(format_args!("line {}", i)).build());
+/// }
+/// logger.log(&Record::builder().level(Level::Error).args(format_args!("failed")).build());
+///
+/// assert_eq!(attempts.load(Ordering::SeqCst), 3);
+/// assert_eq!(
+///     *errors.lock().unwrap(),
+///     ["broken pipe", "stdout is closed, no longer writing to it: broken pipe"]
+/// );
+/// let stats = logger.stats().unwrap();
+/// assert_eq!((stats.written(Level::Error), stats.dropped()), (1, 100));
+/// ```
 pub struct TermLogger {
-    level: LevelFilter,
-    config: Config,
+    level: LevelHandle,
+    config: ConfigHandle,
+    mode: TerminalMode,
     streams: Mutex<OutputStreams>,
+    output_hook: Option<OutputHook>,
+    overflow: OverflowQueue,
 }
 
+/// Wraps the terminal writes of a `TermLogger`, see [`TermLogger::set_output_hook`].
+///
+/// The hook gets a function writing one record, which it must call once.
+pub type OutputHook =
+    Box<dyn Fn(&mut dyn FnMut() -> io::Result<()>) -> io::Result<()> + Send + Sync>;
+
 impl TermLogger {
     /// init function. Globally initializes the TermLogger as the one and only used log facility.
     ///
-    /// Takes the desired `Level` and `Config` as arguments. They cannot be changed later on.
+    /// Takes the desired `Level` and `Config` as arguments. The level can be changed later on
+    /// through the returned `LevelHandle`.
     /// Fails if another Logger was already initialized
     ///
     /// # Examples
@@ -61,11 +416,109 @@ impl TermLogger {
         config: Config,
         mode: TerminalMode,
         color_choice: ColorChoice,
-    ) -> Result<(), SetLoggerError> {
+    ) -> Result<LevelHandle, SetLoggerError> {
         let logger = TermLogger::new(log_level, config, mode, color_choice);
-        set_max_level(log_level);
+        let handle = logger.level.clone();
         set_boxed_logger(logger)?;
-        Ok(())
+        handle.install();
+        Ok(handle)
+    }
+
+    /// Same as [`TermLogger::init`], but returns a [`FlushGuard`] that flushes the logger
+    /// when dropped. Hold it in `main`, so the last records are not lost on exit.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// fn main() {
+    ///     let _guard = TermLogger::init_with_guard(
+    ///         LevelFilter::Info,
+    ///         Config::default(),
+    ///         TerminalMode::Mixed,
+    ///         ColorChoice::Auto,
+    ///     );
+    /// }
+    /// ```
+    pub fn init_with_guard(
+        log_level: LevelFilter,
+        config: Config,
+        mode: TerminalMode,
+        color_choice: ColorChoice,
+    ) -> Result<FlushGuard, SetLoggerError> {
+        let logger = TermLogger::new(log_level, config, mode, color_choice);
+        let handle = logger.level.clone();
+        let guard = FlushGuard::install(logger)?;
+        handle.install();
+        Ok(guard)
+    }
+
+    /// Same as [`TermLogger::init`], but takes the levels from a `RUST_LOG`-style environment variable.
+    ///
+    /// The global level in the variable becomes the logger's level, the per-target levels are
+    /// added to the `Config` as level directives, see [`ConfigBuilder::parse_env_directives`].
+    /// If the variable is unset, empty or contains no global level, `default_level` is used.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    ///     TermLogger::init_from_env(
+    ///         "RUST_LOG",
+    ///         LevelFilter::Info,
+    ///         Config::default(),
+    ///         TerminalMode::Mixed,
+    ///         ColorChoice::Auto
+    ///     );
+    /// # }
+    /// ```
+    pub fn init_from_env(
+        env_var: &str,
+        default_level: LevelFilter,
+        config: Config,
+        mode: TerminalMode,
+        color_choice: ColorChoice,
+    ) -> Result<LevelHandle, SetLoggerError> {
+        let mut builder = ConfigBuilder::from(config);
+        let log_level = std::env::var(env_var)
+            .ok()
+            .and_then(|directives| builder.parse_env_directives(&directives))
+            .unwrap_or(default_level);
+
+        TermLogger::init(log_level, builder.build(), mode, color_choice)
+    }
+
+    /// Same as [`TermLogger::init`], but with separate `ColorChoice`s for stdout and stderr,
+    /// see [`TermLogger::new_with_choices`].
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    ///     TermLogger::init_with_choices(
+    ///         LevelFilter::Info,
+    ///         Config::default(),
+    ///         TerminalMode::Mixed,
+    ///         ColorChoice::Never,
+    ///         ColorChoice::Auto
+    ///     );
+    /// # }
+    /// ```
+    pub fn init_with_choices(
+        log_level: LevelFilter,
+        config: Config,
+        mode: TerminalMode,
+        stdout_choice: ColorChoice,
+        stderr_choice: ColorChoice,
+    ) -> Result<LevelHandle, SetLoggerError> {
+        let logger =
+            TermLogger::new_with_choices(log_level, config, mode, stdout_choice, stderr_choice);
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
     }
 
     /// allows to create a new logger, that can be independently used, no matter whats globally set.
@@ -75,6 +528,14 @@ impl TermLogger {
     ///
     /// Takes the desired `Level` and `Config` as arguments. They cannot be changed later on.
     ///
+    /// With `ColorChoice::Auto` the color environment variables are followed, see
+    /// [`TermLogger::resolve_color_choice`], unless disabled with
+    /// [`ConfigBuilder::set_respect_color_env`].
+    ///
+    /// On Windows, the console is switched to interpret ANSI escape sequences. If that fails,
+    /// e.g. on older Windows 10 builds, colors are disabled, unless `ColorChoice::AlwaysAnsi`
+    /// is passed.
+    ///
     /// Returns a `Box`ed TermLogger
     ///
     /// # Examples
@@ -97,39 +558,332 @@ impl TermLogger {
         mode: TerminalMode,
         color_choice: ColorChoice,
     ) -> Box<TermLogger> {
-        let streams = match mode {
-            TerminalMode::Stdout => OutputStreams {
-                err: BufferedStandardStream::stdout(color_choice),
-                out: BufferedStandardStream::stdout(color_choice),
-            },
-            TerminalMode::Stderr => OutputStreams {
-                err: BufferedStandardStream::stderr(color_choice),
-                out: BufferedStandardStream::stderr(color_choice),
-            },
-            TerminalMode::Mixed => OutputStreams {
-                err: BufferedStandardStream::stderr(color_choice),
-                out: BufferedStandardStream::stdout(color_choice),
-            },
+        TermLogger::new_with_choices(log_level, config, mode, color_choice, color_choice)
+    }
+
+    /// Same as [`TermLogger::new`], but with separate `ColorChoice`s for stdout and stderr,
+    /// e.g. to keep escape codes out of a piped stdout while coloring stderr.
+    ///
+    /// Only the choices of the streams used in `mode` matter.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let term_logger = TermLogger::new_with_choices(
+    ///     LevelFilter::Info,
+    ///     Config::default(),
+    ///     TerminalMode::Mixed,
+    ///     ColorChoice::Never,
+    ///     ColorChoice::Auto
+    /// );
+    /// # }
+    /// ```
+    ///
+    /// Each stream is colored on its own, as with these streams, where stdout drops the
+    /// colors like with `ColorChoice::Never`:
+    /// ```
+    /// # #[cfg(not(feature = "no-color"))]
+    /// # {
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io;
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone)]
+    /// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+    /// # impl io::Write for Buffer {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// # fn main() {
+    /// let out = Buffer(Arc::new(Mutex::new(Vec::new())));
+    /// let err = Buffer(Arc::new(Mutex::new(Vec::new())));
+    /// let config = ConfigBuilder::new().set_format(FormatFlags::LEVEL).build();
+    /// let logger = TermLogger::with_streams(
+    ///     LevelFilter::Info,
+    ///     config,
+    ///     Box::new(termcolor2::NoColor::new(out.clone())),
+    ///     Box::new(termcolor2::Ansi::new(err.clone())),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("started")).build());
+    /// logger.log(&Record::builder().level(Level::Error).args(format_args!("failed")).build());
+    ///
+    /// let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    /// let err = String::from_utf8(err.0.lock().unwrap().clone()).unwrap();
+    /// assert_eq!(out, " [INFO] started\n");
+    /// assert!(err.contains('\x1b') && err.contains("[ERROR]"), "{:?}", err);
+    /// # }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn new_with_choices(
+        log_level: LevelFilter,
+        mut config: Config,
+        mode: TerminalMode,
+        stdout_choice: ColorChoice,
+        stderr_choice: ColorChoice,
+    ) -> Box<TermLogger> {
+        let (streams, colored) = match mode {
+            TerminalMode::Stdout => {
+                let choice = stream_color_choice(&config, stdout_choice, false);
+                let streams = OutputStreams {
+                    err: TermStream::new(
+                        "stdout",
+                        Box::new(BufferedStandardStream::stdout(choice)),
+                        TerminalWidth::new(false),
+                    ),
+                    out: TermStream::new(
+                        "stdout",
+                        Box::new(BufferedStandardStream::stdout(choice)),
+                        TerminalWidth::new(false),
+                    ),
+                    state: LoggerState::default(),
+                };
+                (streams, choice != ColorChoice::Never)
+            }
+            TerminalMode::Stderr => {
+                let choice = stream_color_choice(&config, stderr_choice, true);
+                let streams = OutputStreams {
+                    err: TermStream::new(
+                        "stderr",
+                        Box::new(BufferedStandardStream::stderr(choice)),
+                        TerminalWidth::new(true),
+                    ),
+                    out: TermStream::new(
+                        "stderr",
+                        Box::new(BufferedStandardStream::stderr(choice)),
+                        TerminalWidth::new(true),
+                    ),
+                    state: LoggerState::default(),
+                };
+                (streams, choice != ColorChoice::Never)
+            }
+            TerminalMode::Mixed | TerminalMode::MixedAt(_) => {
+                let err_choice = stream_color_choice(&config, stderr_choice, true);
+                let out_choice = stream_color_choice(&config, stdout_choice, false);
+                let streams = OutputStreams {
+                    err: TermStream::new(
+                        "stderr",
+                        Box::new(BufferedStandardStream::stderr(err_choice)),
+                        TerminalWidth::new(true),
+                    ),
+                    out: TermStream::new(
+                        "stdout",
+                        Box::new(BufferedStandardStream::stdout(out_choice)),
+                        TerminalWidth::new(false),
+                    ),
+                    state: LoggerState::default(),
+                };
+                let colored = err_choice != ColorChoice::Never || out_choice != ColorChoice::Never;
+                (streams, colored)
+            }
         };
+        if !colored {
+            config.enable_colors = false;
+        }
+
+        TermLogger::from_streams(log_level, config, mode, streams)
+    }
 
+    /// Creates a `TermLogger` writing to the given streams instead of stdout and stderr,
+    /// e.g. a `termcolor2::Buffer` for a console widget, or to capture the colored output
+    /// in tests.
+    ///
+    /// Records are written to `err` or `out` like with `TerminalMode::Mixed`. Colors are
+    /// written if the `Config` enables them and the stream supports them, the color
+    /// choice and environment variables of the terminal do not apply. Messages are only
+    /// wrapped at a fixed [`WrapMode::Width`](crate::WrapMode::Width), not at a terminal width.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(not(feature = "no-color"))]
+    /// # {
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io::{self, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// # fn main() {
+    /// #[derive(Clone, Default)]
+    /// struct Shared(Arc<Mutex<Vec<u8>>>);
+    ///
+    /// impl Write for Shared {
+    ///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let (out, err) = (Shared::default(), Shared::default());
+    /// let config = ConfigBuilder::new()
+    ///     .set_format(FormatFlags::LEVEL)
+    ///     .set_level_color(Level::Error, Some(Color::Red))
+    ///     .build();
+    /// let logger = TermLogger::with_streams(
+    ///     LevelFilter::Info,
+    ///     config,
+    ///     Box::new(termcolor2::Ansi::new(out.clone())),
+    ///     Box::new(termcolor2::Ansi::new(err.clone())),
+    /// );
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("started")).build());
+    /// logger.log(&Record::builder().level(Level::Error).args(format_args!("failed")).build());
+    ///
+    /// let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    /// let err = String::from_utf8(err.0.lock().unwrap().clone()).unwrap();
+    /// assert!(out.contains("started") && !out.contains("failed"));
+    /// assert!(err.ends_with("\x1b[31m[ERROR]\x1b[0m failed\n"), "{:?}", err);
+    /// # }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_streams(
+        log_level: LevelFilter,
+        config: Config,
+        out: Box<dyn WriteColor + Send>,
+        err: Box<dyn WriteColor + Send>,
+    ) -> Box<TermLogger> {
+        let streams = OutputStreams {
+            err: TermStream::new("stderr", err, TerminalWidth::none()),
+            out: TermStream::new("stdout", out, TerminalWidth::none()),
+            state: LoggerState::default(),
+        };
+        TermLogger::from_streams(log_level, config, TerminalMode::Mixed, streams)
+    }
+
+    fn from_streams(
+        log_level: LevelFilter,
+        config: Config,
+        mode: TerminalMode,
+        streams: OutputStreams,
+    ) -> Box<TermLogger> {
+        let level = LevelHandle::new(log_level, Some(&config));
         Box::new(TermLogger {
-            level: log_level,
-            config,
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            mode,
+            overflow: OverflowQueue::new(streams.state.stats.clone()),
             streams: Mutex::new(streams),
+            output_hook: None,
         })
     }
 
+    /// Wraps the writing of every record with `hook`, e.g. to hide a progress bar while
+    /// the record is written, so the two don't get mixed up.
+    ///
+    /// The hook is called with a function writing the whole record, including its colors,
+    /// and flushing the stream. It runs while the streams of the logger are locked, so it
+    /// must not log itself.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use std::sync::Arc;
+    /// # fn main() {
+    /// let written = Arc::new(AtomicUsize::new(0));
+    /// let mut term_logger = TermLogger::new(
+    ///     LevelFilter::Info,
+    ///     Config::default(),
+    ///     TerminalMode::Stderr,
+    ///     ColorChoice::Never
+    /// );
+    /// let counter = written.clone();
+    /// // With indicatif, this would be `progress_bar.suspend(write)`
+    /// term_logger.set_output_hook(Box::new(move |write| {
+    ///     counter.fetch_add(1, Ordering::SeqCst);
+    ///     write()
+    /// }));
+    ///
+    /// term_logger.log(&Record::builder().level(Level::Info).args(format_args!("step done")).build());
+    /// assert_eq!(written.load(Ordering::SeqCst), 1);
+    /// # }
+    /// ```
+    pub fn set_output_hook(&mut self, hook: OutputHook) -> &mut TermLogger {
+        self.output_hook = Some(hook);
+        self
+    }
+
+    /// Resolves `ColorChoice::Auto` with the `NO_COLOR`, `CLICOLOR` and `CLICOLOR_FORCE`
+    /// conventions, looking the variables up with `var`. Other choices are returned as they are.
+    ///
+    /// A non-empty `NO_COLOR` disables colors, then a `CLICOLOR_FORCE` other than `0` forces
+    /// them, and a `CLICOLOR` of `0` disables them. Otherwise the choice stays `Auto`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use sp_log2::*;
+    /// fn resolve(choice: ColorChoice, env: &[(&str, &str)]) -> ColorChoice {
+    ///     TermLogger::resolve_color_choice(choice, |name| {
+    ///         env.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    ///     })
+    /// }
+    ///
+    /// assert_eq!(resolve(ColorChoice::Auto, &[]), ColorChoice::Auto);
+    /// assert_eq!(resolve(ColorChoice::Auto, &[("NO_COLOR", "1")]), ColorChoice::Never);
+    /// assert_eq!(resolve(ColorChoice::Auto, &[("NO_COLOR", "")]), ColorChoice::Auto);
+    /// assert_eq!(resolve(ColorChoice::Auto, &[("CLICOLOR_FORCE", "1")]), ColorChoice::Always);
+    /// assert_eq!(resolve(ColorChoice::Auto, &[("CLICOLOR_FORCE", "0")]), ColorChoice::Auto);
+    /// assert_eq!(resolve(ColorChoice::Auto, &[("CLICOLOR", "0")]), ColorChoice::Never);
+    /// assert_eq!(resolve(ColorChoice::Auto, &[("CLICOLOR", "1")]), ColorChoice::Auto);
+    /// assert_eq!(
+    ///     resolve(ColorChoice::Auto, &[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]),
+    ///     ColorChoice::Never
+    /// );
+    /// assert_eq!(
+    ///     resolve(ColorChoice::Auto, &[("CLICOLOR", "0"), ("CLICOLOR_FORCE", "1")]),
+    ///     ColorChoice::Always
+    /// );
+    ///
+    /// // Explicit choices win
+    /// assert_eq!(resolve(ColorChoice::Always, &[("NO_COLOR", "1")]), ColorChoice::Always);
+    /// assert_eq!(resolve(ColorChoice::Never, &[("CLICOLOR_FORCE", "1")]), ColorChoice::Never);
+    /// ```
+    pub fn resolve_color_choice<F>(color_choice: ColorChoice, var: F) -> ColorChoice
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if color_choice != ColorChoice::Auto {
+            return color_choice;
+        }
+
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            ColorChoice::Never
+        } else if var("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0") {
+            ColorChoice::Always
+        } else if var("CLICOLOR").is_some_and(|value| value == "0") {
+            ColorChoice::Never
+        } else {
+            ColorChoice::Auto
+        }
+    }
+
     fn try_log_term(
         &self,
+        config: &Config,
         record: &Record<'_>,
-        term_lock: &mut BufferedStandardStream,
+        term_lock: &mut dyn WriteColor,
+        width: &mut TerminalWidth,
     ) -> Result<(), Error> {
-        let color = self.config.level_color[record.level() as usize].clone();
+        let style = &config.level_style[record.level() as usize];
 
-        if record.level() > self.config.min_level || record.level() < self.config.max_level {
+        if should_skip(config, record) {
             return Ok(());
         }
 
+        if !level_in_range(config, record.level()) {
+            return Ok(());
+        }
+
+        if config.json_output {
+            return write_json(config, record, term_lock);
+        }
+
         let mut level = String::new();
         let mut time = String::new();
         let mut thread = String::new();
@@ -137,79 +891,105 @@ impl TermLogger {
         let mut location = String::new();
         let mut module = String::new();
 
-        if self.config.format & Format::Time != 0 {
-            time = write_time(&self.config)?;
+        if config.format.contains(FormatFlags::TIME) {
+            time = write_time(config)?;
         }
 
-        if self.config.format & Format::LevelFlag != 0 {
-            level = write_level(record, &self.config)?;
+        let hostname = write_hostname(config);
+        let uptime = write_uptime(config);
+        let context = write_context(&current_context(config));
+
+        if config.format.contains(FormatFlags::LEVEL) {
+            level = write_level(record, config)?;
         }
 
-        if self.config.format & Format::Thread != 0 {
-            thread = match self.config.thread_log_mode {
-                ThreadLogMode::IDs => write_thread_id(&self.config)?,
-                ThreadLogMode::Names | ThreadLogMode::Both => write_thread_name(&self.config)?,
-            }
+        if config.format.contains(FormatFlags::THREAD) {
+            thread = write_thread(config)?;
         }
 
-        if self.config.format & Format::Target != 0 {
-            target = write_target(record, &self.config)?;
+        if config.format.contains(FormatFlags::TARGET) {
+            target = write_target(record, config)?;
         }
 
-        if self.config.format & Format::FileLocation != 0 {
-            location = write_location(record)?;
+        if config.format.contains(FormatFlags::FILE_LOCATION) {
+            location = write_location(record, config)?;
         }
 
-        if self.config.format & Format::Module != 0 {
+        if config.format.contains(FormatFlags::MODULE) {
             module = write_module(record)?;
         }
 
-        let mut args = write_args(record, &self.config.line_ending)?;
-        args = args.trim_end().to_string();
-
-        if self.config.formatter.is_some() {
-            parse_and_format_log_term(
-                term_lock,
-                color,
-                &self.config,
-                &level,
-                &time,
-                &thread,
-                &target,
-                &location,
-                &module,
-                &args,
-            )?;
-        } else {
-            if !time.is_empty() {
-                write!(term_lock, "{}", time)?;
-            }
+        let args = write_args(record, config)?;
+        mark_written();
 
-            if !level.is_empty() {
-                if self.config.enable_colors {
-                    term_lock.set_color(ColorSpec::new().set_fg(color))?;
-                }
-                write!(term_lock, " [{}]", level)?;
-                if !self.config.enable_colors {
-                    term_lock.reset()?;
+        let colorize_line = COLORS && config.colorize_line && config.enable_colors;
+        if colorize_line {
+            set_color(term_lock, &style.color_spec())?;
+        }
+
+        for args in message_pieces(&args, config) {
+            let args = args.trim_end();
+            if config.formatter_for(record.level()).is_some() {
+                parse_and_format_log_term(
+                    term_lock,
+                    Some(style),
+                    config,
+                    record,
+                    &level,
+                    &time,
+                    &thread,
+                    &target,
+                    &location,
+                    &module,
+                    args,
+                )?;
+            } else {
+                let mut term_lock = ColumnWriter::new(&mut *term_lock);
+                let fields = TermFields {
+                    time: &time,
+                    uptime: &uptime,
+                    hostname,
+                    level: &level,
+                    thread: &thread,
+                    target: &target,
+                    location: &location,
+                    module: &module,
+                };
+                let (before, after) = config.fields_around_message();
+
+                for &field in before {
+                    fields.write(&mut term_lock, field, record, config, colorize_line)?;
                 }
-            }
 
-            if !thread.is_empty() {
-                write!(term_lock, " ({})", thread)?;
-            }
+                if !context.is_empty() {
+                    write!(term_lock, "{}{{{}}}", config.field_separator, context)?;
+                }
 
-            if !target.is_empty() {
-                write!(term_lock, " {}:", target)?;
-            }
+                write_message_separator(&mut term_lock, config)?;
+                let mut args = join_message_lines(args, config, term_lock.column()).into_owned();
+                if config.inline_tags {
+                    let base = colorize_line.then(|| style.color_spec());
+                    let colored = COLORS && config.enable_colors && term_lock.supports_color();
+                    args = render_inline_tags(&args, base.as_ref(), colored)?;
+                }
+                match width.wrap_width(config.wrap) {
+                    Some(width) => {
+                        let args = wrap_message(&args, width, term_lock.column());
+                        write!(term_lock, "{}", args)?;
+                    }
+                    None => write!(term_lock, "{}", args)?,
+                }
 
-            write!(term_lock, " {}", args)?;
+                for &field in after {
+                    fields.write(&mut term_lock, field, record, config, colorize_line)?;
+                }
 
-            if !location.is_empty() {
-                write!(term_lock, " [{}]", location)?;
+                writeln!(term_lock)?;
             }
+        }
 
-            writeln!(term_lock)?;
+        if colorize_line {
+            reset_color(term_lock)?;
         }
 
         // The log crate holds the logger as a `static mut`, which isn't dropped
@@ -220,48 +1000,221 @@ impl TermLogger {
         term_lock.flush()
     }
 
-    fn try_log(&self, record: &Record<'_>) -> Result<(), Error> {
-        if self.enabled(record.metadata()) {
-            if should_skip(&self.config, record) {
-                return Ok(());
+    fn try_log_term_stream(
+        &self,
+        config: &Config,
+        record: &Record<'_>,
+        err: &mut TermStream,
+        out: &mut TermStream,
+    ) -> Result<(), Error> {
+        let stream = if self.mode.uses_stderr(record.level()) {
+            err
+        } else {
+            out
+        };
+        stream.write(config, |term_lock, width| match &self.output_hook {
+            Some(hook) => hook(&mut || self.try_log_term(config, record, term_lock, width)),
+            None => self.try_log_term(config, record, term_lock, width),
+        })
+    }
+}
+
+/// Resolves the `ColorChoice` of stdout, or stderr if `stderr` is set, with the color
+/// environment variables and, on Windows, the console support of the stream.
+fn stream_color_choice(config: &Config, color_choice: ColorChoice, stderr: bool) -> ColorChoice {
+    if !COLORS {
+        return ColorChoice::Never;
+    }
+    let color_choice = if config.respect_color_env {
+        TermLogger::resolve_color_choice(color_choice, |name| std::env::var(name).ok())
+    } else {
+        color_choice
+    };
+    #[cfg(windows)]
+    let color_choice = {
+        let handle = if stderr {
+            console::STD_ERROR_HANDLE
+        } else {
+            console::STD_OUTPUT_HANDLE
+        };
+        match color_choice {
+            ColorChoice::Always | ColorChoice::Auto if !console::enable_ansi(handle) => {
+                ColorChoice::Never
             }
+            color_choice => color_choice,
+        }
+    };
+    #[cfg(not(windows))]
+    let _ = stderr;
+    color_choice
+}
 
-            let mut streams = self.streams.lock().unwrap();
+/// The rendered fields of a record in the default layout.
+struct TermFields<'a> {
+    time: &'a str,
+    uptime: &'a str,
+    hostname: &'a str,
+    level: &'a str,
+    thread: &'a str,
+    target: &'a str,
+    location: &'a str,
+    module: &'a str,
+}
 
-            if record.level() == Level::Error {
-                self.try_log_term(record, &mut streams.err)
-            } else {
-                self.try_log_term(record, &mut streams.out)
+impl TermFields<'_> {
+    /// Writes `field` with the space in front of it, except for the time at the start
+    /// of the line.
+    fn write<W: Write + WriteColor>(
+        &self,
+        term_lock: &mut ColumnWriter<W>,
+        field: LogField,
+        record: &Record<'_>,
+        config: &Config,
+        colorize_line: bool,
+    ) -> Result<(), Error> {
+        let separator = &config.field_separator;
+        match field {
+            LogField::Time => {
+                if !self.time.is_empty() {
+                    if term_lock.column() > 0 {
+                        write!(term_lock, "{}", separator)?;
+                    }
+                    write!(term_lock, "{}", self.time)?;
+                }
             }
-        } else {
-            Ok(())
+            LogField::Uptime => {
+                if !self.uptime.is_empty() {
+                    write!(term_lock, "{}{}", separator, self.uptime)?;
+                }
+            }
+            LogField::Hostname => {
+                if !self.hostname.is_empty() {
+                    write!(term_lock, "{}{}", separator, self.hostname)?;
+                }
+            }
+            LogField::Level => {
+                if !self.level.is_empty() {
+                    let icon = if config.use_level_icons {
+                        write_level_icon(record, config)
+                    } else {
+                        String::new()
+                    };
+                    write!(term_lock, "{}", separator)?;
+                    // Only the level itself is colored, unless the whole line is
+                    let colorize_level = COLORS && config.enable_colors && !colorize_line;
+                    if colorize_level {
+                        let style = &config.level_style[record.level() as usize];
+                        set_color(term_lock, &style.color_spec())?;
+                    }
+                    if icon.is_empty() {
+                        let (open, close) = &config.level_delimiters;
+                        write!(term_lock, "{}{}{}", open, self.level, close)?;
+                    } else {
+                        write!(term_lock, "{}", icon)?;
+                    }
+                    if colorize_level {
+                        reset_color(term_lock)?;
+                    }
+                }
+            }
+            LogField::Thread => write_process_thread(term_lock, config, self.thread)?,
+            LogField::Target => {
+                if !self.target.is_empty() {
+                    write!(
+                        term_lock,
+                        "{}{}{}",
+                        separator, self.target, config.target_suffix
+                    )?;
+                }
+            }
+            LogField::Module => {
+                if !self.module.is_empty() {
+                    write!(term_lock, "{}{}", separator, self.module)?;
+                }
+            }
+            LogField::Location => {
+                if !self.location.is_empty() {
+                    write!(term_lock, "{}[{}]", separator, self.location)?;
+                }
+            }
+            LogField::Message => {}
         }
+        Ok(())
     }
 }
 
 impl Log for TermLogger {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level() <= self.level
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
     }
 
     fn log(&self, record: &Record<'_>) {
-        let _ = self.try_log(record);
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let Some(mut streams) = lock_output(&self.streams, &config) else {
+                let to_stderr = self.mode.uses_stderr(record.level());
+                return self.overflow.push(&config, record, to_stderr);
+            };
+            let OutputStreams { err, out, state } = &mut *streams;
+
+            // Records queued while the lock was taken go first
+            let result = self.overflow.drain(|line, to_stderr| {
+                let stream = if to_stderr { &mut *err } else { &mut *out };
+                stream.write(&config, |writer, _| writer.write_all(line))
+            });
+            report(state, &config, result);
+
+            let result = state.log(&config, record, |record| {
+                self.try_log_term_stream(&config, record, err, out)
+            });
+            report(state, &config, result);
+        }
     }
 
     fn flush(&self) {
         let mut streams = self.streams.lock().unwrap();
-        let _ = streams.out.flush();
-        let _ = streams.err.flush();
+        let OutputStreams { err, out, state } = &mut *streams;
+        let config = self.config.get();
+        let result = self
+            .overflow
+            .drain(|line, to_stderr| {
+                let stream = if to_stderr { &mut *err } else { &mut *out };
+                stream.write(&config, |writer, _| writer.write_all(line))
+            })
+            .and_then(|()| {
+                state.flush(|record| self.try_log_term_stream(&config, record, err, out))
+            });
+        report(state, &config, result);
+        let result = out.write(&config, |writer, _| writer.flush());
+        report(state, &config, result);
+        let result = err.write(&config, |writer, _| writer.flush());
+        report(state, &config, result);
     }
 }
 
 impl SharedLogger for TermLogger {
     fn level(&self) -> LevelFilter {
-        self.level
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
     }
 
-    fn config(&self) -> Option<&Config> {
-        Some(&self.config)
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.streams.lock().unwrap().state.stats.clone())
     }
 
     fn as_log(self: Box<Self>) -> Box<dyn Log> {
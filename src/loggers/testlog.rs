@@ -0,0 +1,256 @@
+//! Module providing the TestLogger Implementation
+
+use super::logging::{format_record, level_enabled, metadata_enabled, LoggerState, PreparedRecord};
+use crate::{Config, ConfigHandle, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A record captured by a `TestLogger`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedRecord {
+    pub level: Level,
+    pub target: String,
+    /// The message of the record, without any formatting applied
+    pub message: String,
+    pub module_path: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    /// The record as rendered by the `Config`, without the trailing line ending
+    pub formatted: String,
+}
+
+/// The records captured by a `TestLogger`.
+///
+/// Cheap to clone, all clones share the same records. Obtained from `TestLogger::captured`
+/// or `TestLogger::init`, so records can still be inspected after the logger was moved
+/// into a `CombinedLogger` or installed globally.
+#[derive(Debug, Clone, Default)]
+pub struct CapturedRecords(Arc<Mutex<Vec<CapturedRecord>>>);
+
+impl CapturedRecords {
+    fn lock(&self) -> MutexGuard<'_, Vec<CapturedRecord>> {
+        // A failed assertion while holding the lock must not hide the records from other tests
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns a copy of all captured records.
+    pub fn records(&self) -> Vec<CapturedRecord> {
+        self.lock().clone()
+    }
+
+    /// Returns all captured records and clears them.
+    pub fn take_records(&self) -> Vec<CapturedRecord> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Returns true if a record with the given level contains `message`.
+    pub fn contains(&self, level: Level, message: &str) -> bool {
+        self.lock()
+            .iter()
+            .any(|record| record.level == level && record.message.contains(message))
+    }
+
+    /// Removes all captured records.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Returns the number of captured records.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no record was captured.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+}
+
+/// The TestLogger struct. Provides a Logger implementation that captures records in memory
+///
+/// Meant for unit tests that assert on what the code under test logged. The `Config`
+/// is applied like in every other logger, so filters and formatters can be tested as well.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # fn main() {
+/// let config = ConfigBuilder::new().add_filter_ignore_str("noisy").build();
+/// let logger = TestLogger::new(LevelFilter::Info, config);
+///
+/// for (target, level) in [("app", Level::Warn), ("noisy", Level::Warn), ("app", Level::Debug)] {
+///     logger.log(
+///         &Record::builder()
+///             .level(level)
+///             .target(target)
+///             .args(format_args!("disk almost full"))
+///             .build(),
+///     );
+/// }
+///
+/// assert!(logger.contains(Level::Warn, "almost full"));
+/// let records = logger.take_records();
+/// assert_eq!(records.len(), 1);
+/// assert_eq!(records[0].target, "app");
+/// assert!(logger.captured().is_empty());
+/// # }
+/// ```
+pub struct TestLogger {
+    level: LevelHandle,
+    config: ConfigHandle,
+    captured: CapturedRecords,
+    state: Mutex<LoggerState>,
+}
+
+impl TestLogger {
+    /// init function. Globally initializes the TestLogger as the one and only used log facility.
+    ///
+    /// Takes the desired `Level` and `Config` as arguments and returns the `CapturedRecords`
+    /// to inspect. Fails if another Logger was already initialized.
+    ///
+    /// As there is only one global logger per process, tests running in parallel share it.
+    /// Prefer `new()` and calling `Log::log` directly for isolated tests.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let captured = TestLogger::init(LevelFilter::Info, Config::default()).unwrap();
+    /// log::warn!("disk almost full");
+    /// assert!(captured.contains(Level::Warn, "almost full"));
+    /// # }
+    /// ```
+    pub fn init(log_level: LevelFilter, config: Config) -> Result<CapturedRecords, SetLoggerError> {
+        let logger = TestLogger::new(log_level, config);
+        let handle = logger.level.clone();
+        let captured = logger.captured();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(captured)
+    }
+
+    /// allows to create a new logger, that can be independently used, no matter what is globally set.
+    ///
+    /// Records can be passed to it by calling `Log::log` directly, or by putting it into a
+    /// `CombinedLogger`.
+    ///
+    /// Takes the desired `Level` and `Config` as arguments.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # fn main() {
+    /// let test_logger = TestLogger::new(LevelFilter::Info, Config::default());
+    /// let captured = test_logger.captured();
+    /// let _ = CombinedLogger::new(vec![
+    ///     TermLogger::new(LevelFilter::Warn, Config::default(), TerminalMode::Mixed, ColorChoice::Auto),
+    ///     test_logger,
+    /// ]);
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn new(log_level: LevelFilter, config: Config) -> Box<TestLogger> {
+        let level = LevelHandle::new(log_level, Some(&config));
+        Box::new(TestLogger {
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            captured: CapturedRecords::default(),
+            state: Mutex::new(LoggerState::default()),
+        })
+    }
+
+    /// Returns the records captured by this logger, which stay accessible after it was moved.
+    pub fn captured(&self) -> CapturedRecords {
+        self.captured.clone()
+    }
+
+    /// Returns all captured records and clears them.
+    pub fn take_records(&self) -> Vec<CapturedRecord> {
+        self.captured.take_records()
+    }
+
+    /// Returns true if a record with the given level contains `message`.
+    pub fn contains(&self, level: Level, message: &str) -> bool {
+        self.captured.contains(level, message)
+    }
+
+    /// Removes all captured records.
+    pub fn clear(&self) {
+        self.captured.clear();
+    }
+
+    fn capture(&self, config: &Config, record: &Record<'_>) -> Result<(), std::io::Error> {
+        let mut formatted = Vec::new();
+        if !format_record(config, record, &mut formatted)? {
+            return Ok(());
+        }
+
+        let formatted = String::from_utf8_lossy(&formatted);
+        self.captured.lock().push(CapturedRecord {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            module_path: record.module_path().map(str::to_string),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+            formatted: formatted.trim_end_matches(['\r', '\n']).to_string(),
+        });
+        Ok(())
+    }
+}
+
+impl Log for TestLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
+            let mut state = self.state.lock().unwrap();
+            let result = state.log(&config, record, |record| self.capture(&config, record));
+            state.report(&config, "TestLogger", result);
+        }
+    }
+
+    fn flush(&self) {
+        let mut state = self.state.lock().unwrap();
+        let config = self.config.get();
+        let result = state.flush(|record| self.capture(&config, record));
+        state.report(&config, "TestLogger", result);
+    }
+}
+
+impl SharedLogger for TestLogger {
+    fn level(&self) -> LevelFilter {
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.state.lock().unwrap().stats.clone())
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
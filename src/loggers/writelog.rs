@@ -1,36 +1,84 @@
 //! Module providing the WriteLogger Implementation
 
-use super::logging::try_log;
-use crate::{Config, SharedLogger};
-use log::{set_boxed_logger, set_max_level, LevelFilter, Log, Metadata, Record, SetLoggerError};
-use std::io::Write;
-use std::sync::Mutex;
+use super::logging::{format_record, level_enabled, metadata_enabled, LogOutput, PreparedRecord};
+use crate::banner::{write_banner, write_summary};
+use crate::{Config, ConfigHandle, FlushGuard, LevelHandle, LoggerStats, SharedLogger};
+use log::{set_boxed_logger, Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex, PoisonError};
 
 /// The WriteLogger struct. Provides a Logger implementation for structs implementing `Write`, e.g. File
 pub struct WriteLogger<W: Write + Send + 'static> {
-    level: LevelFilter,
-    config: Config,
-    writable: Mutex<W>,
+    level: LevelHandle,
+    config: ConfigHandle,
+    writable: Mutex<LogOutput<W>>,
+    flush_level: Option<Level>,
 }
 
 impl<W: Write + Send + 'static> WriteLogger<W> {
     /// init function. Globally initializes the WriteLogger as the one and only used log facility.
     ///
-    /// Takes the desired `Level`, `Config` and `Write` struct as arguments. They cannot be changed later on.
+    /// Takes the desired `Level`, `Config` and `Write` struct as arguments. The level can be changed
+    /// later on through the returned `LevelHandle`.
     /// Fails if another Logger was already initialized.
     ///
     /// # Examples
     /// ```
-    /// # extern crate simplelog;
-    /// # use simplelog::*;
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
     /// # use std::fs::File;
     /// # fn main() {
     /// let _ = WriteLogger::init(LevelFilter::Info, Config::default(), File::create("my_rust_bin.log").unwrap());
     /// # }
     /// ```
-    pub fn init(log_level: LevelFilter, config: Config, writable: W) -> Result<(), SetLoggerError> {
-        set_max_level(log_level);
-        set_boxed_logger(WriteLogger::new(log_level, config, writable))
+    pub fn init(
+        log_level: LevelFilter,
+        config: Config,
+        writable: W,
+    ) -> Result<LevelHandle, SetLoggerError> {
+        let logger = WriteLogger::new(log_level, config, writable);
+        let handle = logger.level.clone();
+        set_boxed_logger(logger)?;
+        handle.install();
+        Ok(handle)
+    }
+
+    /// Same as [`WriteLogger::init`], but returns a [`FlushGuard`] that flushes the logger,
+    /// and so the writer, when dropped. Hold it in `main`, e.g. for a writer wrapped in a
+    /// `BufWriter`, so the last records are not lost on exit.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::io::{self, BufWriter, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Buffer {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// let buffer = Buffer::default();
+    /// let config = ConfigBuilder::new().set_formatter(Some("[level] [message]")).build();
+    /// let guard = WriteLogger::init_with_guard(LevelFilter::Info, config, BufWriter::new(buffer.clone())).unwrap();
+    ///
+    /// log::info!("shutting down");
+    /// assert!(buffer.0.lock().unwrap().is_empty());
+    ///
+    /// drop(guard);
+    /// assert_eq!(String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(), "[INFO] shutting down\n");
+    /// ```
+    pub fn init_with_guard(
+        log_level: LevelFilter,
+        config: Config,
+        writable: W,
+    ) -> Result<FlushGuard, SetLoggerError> {
+        let logger = WriteLogger::new(log_level, config, writable);
+        let handle = logger.level.clone();
+        let guard = FlushGuard::install(logger)?;
+        handle.install();
+        Ok(guard)
     }
 
     /// allows to create a new logger, that can be independently used, no matter what is globally set.
@@ -42,8 +90,8 @@ impl<W: Write + Send + 'static> WriteLogger<W> {
     ///
     /// # Examples
     /// ```
-    /// # extern crate simplelog;
-    /// # use simplelog::*;
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
     /// # use std::fs::File;
     /// # fn main() {
     /// let file_logger = WriteLogger::new(LevelFilter::Info, Config::default(), File::create("my_rust_bin.log").unwrap());
@@ -51,38 +99,248 @@ impl<W: Write + Send + 'static> WriteLogger<W> {
     /// ```
     #[must_use]
     pub fn new(log_level: LevelFilter, config: Config, writable: W) -> Box<WriteLogger<W>> {
+        let level = LevelHandle::new(log_level, Some(&config));
+        let mut output = LogOutput::new(writable);
+        let result = write_banner(&config, &mut output.writer);
+        output.state.report(&config, "WriteLogger", result);
         Box::new(WriteLogger {
-            level: log_level,
-            config,
-            writable: Mutex::new(writable),
+            config: ConfigHandle::new(config, level.clone()),
+            level,
+            writable: Mutex::new(output),
+            flush_level: None,
         })
     }
+
+    /// Same as [`WriteLogger::new`], but wraps the writer in a `BufWriter` with the given
+    /// capacity, so records are written in large chunks instead of one or more writes each.
+    ///
+    /// The buffer is written out by `Log::flush`, when the logger is dropped, and after
+    /// every record at `Level::Error`, so errors are not stuck in the buffer if the process
+    /// crashes. The latter can be changed with [`WriteLogger::set_flush_level`]. Use
+    /// [`WriteLogger::init_with_guard`] or [`CombinedLogger::init_with_guard`](crate::CombinedLogger::init_with_guard)
+    /// to flush a global logger when `main` returns.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # use std::io::{self, Write};
+    /// # use std::sync::{Arc, Mutex};
+    /// # #[derive(Clone, Default)]
+    /// # struct Buffer(Arc<Mutex<Vec<u8>>>);
+    /// # impl Write for Buffer {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.lock().unwrap().write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// # fn main() {
+    /// let buffer = Buffer::default();
+    /// let written = || String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    /// let config = ConfigBuilder::new().set_formatter(Some("[level] [message]")).build();
+    /// let logger = WriteLogger::buffered(LevelFilter::Info, config, buffer.clone(), 8 * 1024);
+    ///
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("connecting")).build());
+    /// assert_eq!(written(), "");
+    ///
+    /// logger.log(&Record::builder().level(Level::Error).args(format_args!("connection lost")).build());
+    /// assert_eq!(written(), "[INFO] connecting\n[ERROR] connection lost\n");
+    ///
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("reconnecting")).build());
+    /// assert_eq!(written(), "[INFO] connecting\n[ERROR] connection lost\n");
+    ///
+    /// logger.flush();
+    /// assert_eq!(written(), "[INFO] connecting\n[ERROR] connection lost\n[INFO] reconnecting\n");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn buffered(
+        log_level: LevelFilter,
+        config: Config,
+        writable: W,
+        capacity: usize,
+    ) -> Box<WriteLogger<BufWriter<W>>> {
+        let mut logger = WriteLogger::new(
+            log_level,
+            config,
+            BufWriter::with_capacity(capacity, writable),
+        );
+        logger.set_flush_level(Some(Level::Error));
+        logger
+    }
+
+    /// Flushes the writer after every record at `level` or more severe. Default is `None`,
+    /// except for [`WriteLogger::buffered`], which flushes after `Level::Error` records.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::io::sink;
+    /// # fn main() {
+    /// let mut logger = WriteLogger::buffered(LevelFilter::Info, Config::default(), sink(), 64 * 1024);
+    /// logger.set_flush_level(Some(Level::Warn));
+    /// # }
+    /// ```
+    pub fn set_flush_level(&mut self, level: Option<Level>) -> &mut WriteLogger<W> {
+        self.flush_level = level;
+        self
+    }
+
+    /// Flushes the logger and returns the writer.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use log::{Log, Record};
+    /// # fn main() {
+    /// let config = ConfigBuilder::new().set_formatter(Some("[level] [message]")).build();
+    /// let logger = WriteLogger::new(LevelFilter::Info, config, Vec::new());
+    /// logger.log(&Record::builder().level(Level::Info).args(format_args!("in memory")).build());
+    ///
+    /// assert_eq!(logger.into_inner(), b"[INFO] in memory\n");
+    /// # }
+    /// ```
+    pub fn into_inner(self) -> W {
+        self.flush();
+        self.writable
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .writer
+    }
+}
+
+impl<W: Write + Send + 'static> WriteLogger<SharedWriter<W>> {
+    /// Same as [`WriteLogger::new`], but writes to a writer shared with the caller, who
+    /// can lock it to inspect the output while the logger is still in use.
+    ///
+    /// The logger locks the writer for every write and releases it in between, so a
+    /// record may be split over several writes. A thread holding the lock makes logging
+    /// threads wait until it is released, so do not log from a thread while it holds the
+    /// lock, which would deadlock.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate sp_log2;
+    /// # use sp_log2::*;
+    /// # use std::sync::{Arc, Mutex};
+    /// # fn main() {
+    /// let output = Arc::new(Mutex::new(Vec::new()));
+    /// let config = ConfigBuilder::new().set_formatter(Some("[level] [message]")).build();
+    /// let _ = CombinedLogger::init(vec![WriteLogger::new_shared(LevelFilter::Info, config, output.clone())]);
+    ///
+    /// log::warn!("disk almost full");
+    /// assert_eq!(*output.lock().unwrap(), b"[WARN] disk almost full\n");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn new_shared(
+        log_level: LevelFilter,
+        config: Config,
+        writable: Arc<Mutex<W>>,
+    ) -> Box<WriteLogger<SharedWriter<W>>> {
+        WriteLogger::new(log_level, config, SharedWriter(writable))
+    }
+}
+
+/// A writer shared between a `WriteLogger` and its creator, see [`WriteLogger::new_shared`].
+pub struct SharedWriter<W>(Arc<Mutex<W>>);
+
+impl<W> SharedWriter<W> {
+    /// Returns the shared writer.
+    pub fn get_ref(&self) -> &Arc<Mutex<W>> {
+        &self.0
+    }
+}
+
+impl<W: Write> Write for SharedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush()
+    }
 }
 
 impl<W: Write + Send + 'static> Log for WriteLogger<W> {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level() <= self.level
+        metadata_enabled(&self.config.get(), metadata, self.level.get_level())
     }
 
     fn log(&self, record: &Record<'_>) {
-        if self.enabled(record.metadata()) {
+        let config = self.config.get();
+        if level_enabled(&config, record.metadata(), self.level.get_level()) {
             let mut write_lock = self.writable.lock().unwrap();
-            let _ = try_log(&self.config, record, &mut *write_lock);
+            let LogOutput { writer, state } = &mut *write_lock;
+            let mut result = state.log(&config, record, |record| {
+                format_record(&config, record, writer).map(drop)
+            });
+            if self
+                .flush_level
+                .is_some_and(|flush_level| record.level() <= flush_level)
+            {
+                result = result.and(writer.flush());
+            }
+            state.report(&config, "WriteLogger", result);
         }
     }
 
     fn flush(&self) {
-        let _ = self.writable.lock().unwrap().flush();
+        let mut write_lock = self.writable.lock().unwrap();
+        let LogOutput { writer, state } = &mut *write_lock;
+        let config = self.config.get();
+        let result = state
+            .flush(|record| format_record(&config, record, writer).map(drop))
+            .and(writer.flush());
+        state.report(&config, "WriteLogger", result);
     }
 }
 
 impl<W: Write + Send + 'static> SharedLogger for WriteLogger<W> {
     fn level(&self) -> LevelFilter {
-        self.level
+        self.level.get_level()
+    }
+
+    fn config(&self) -> Option<Arc<Config>> {
+        Some(self.config.get())
+    }
+
+    fn try_log_prepared(&self, record: &PreparedRecord<'_>) {
+        record.with_fields(|| self.log(record.record()));
+    }
+
+    fn config_handle(&self) -> Option<ConfigHandle> {
+        Some(self.config.clone())
+    }
+
+    fn level_handle(&self) -> Option<LevelHandle> {
+        Some(self.level.clone())
+    }
+
+    fn stats(&self) -> Option<LoggerStats> {
+        Some(self.writable.lock().unwrap().state.stats.clone())
     }
 
-    fn config(&self) -> Option<&Config> {
-        Some(&self.config)
+    fn write_summary(&self) {
+        let mut write_lock = self.writable.lock().unwrap();
+        let LogOutput { writer, state } = &mut *write_lock;
+        let config = self.config.get();
+        let result = write_summary(&config, &state.stats, writer);
+        state.report(&config, "WriteLogger", result);
     }
 
     fn as_log(self: Box<Self>) -> Box<dyn Log> {
@@ -0,0 +1,80 @@
+use log::{Level, Metadata, Record};
+use std::panic;
+use std::thread;
+
+/// Installs a panic hook that logs panics through the installed logger.
+///
+/// The panic message, thread name and location are logged at `Level::Error` with the
+/// target `panic`, and the logger is flushed, before the previously installed hook runs,
+/// so by default the panic is still printed to stderr as well. If no logger accepting
+/// errors is installed, only the previous hook runs.
+///
+/// Call this once, after initializing the logger.
+///
+/// # Examples
+/// ```
+/// # extern crate sp_log2;
+/// # use sp_log2::*;
+/// # fn main() {
+/// let captured = TestLogger::init(LevelFilter::Info, Config::default()).unwrap();
+/// install_panic_hook();
+///
+/// let result = std::panic::catch_unwind(|| {
+///     panic!("out of cheese");
+/// });
+///
+/// assert!(result.is_err());
+/// assert!(captured.contains(Level::Error, "panicked at"));
+/// assert!(captured.contains(Level::Error, "out of cheese"));
+/// # }
+/// ```
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let logger = log::logger();
+        let metadata = Metadata::builder()
+            .level(Level::Error)
+            .target("panic")
+            .build();
+
+        if logger.enabled(&metadata) {
+            let payload = info.payload();
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.as_str()
+            } else {
+                "Box<dyn Any>"
+            };
+            let thread = thread::current();
+            let thread = thread.name().unwrap_or("<unnamed>");
+
+            match info.location() {
+                Some(location) => logger.log(
+                    &Record::builder()
+                        .metadata(metadata)
+                        .file(Some(location.file()))
+                        .line(Some(location.line()))
+                        .args(format_args!(
+                            "thread '{}' panicked at {}:{}:{}: {}",
+                            thread,
+                            location.file(),
+                            location.line(),
+                            location.column(),
+                            message
+                        ))
+                        .build(),
+                ),
+                None => logger.log(
+                    &Record::builder()
+                        .metadata(metadata)
+                        .args(format_args!("thread '{}' panicked: {}", thread, message))
+                        .build(),
+                ),
+            }
+            logger.flush();
+        }
+
+        previous(info);
+    }));
+}
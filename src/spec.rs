@@ -0,0 +1,284 @@
+//! A plain description of a `Config`, for loading it from a configuration file.
+
+use crate::config::{ConfigBuilder, ConfigError, FormatFlags, LineEnding};
+use crate::Config;
+use chrono::SecondsFormat;
+use log::{Level, LevelFilter};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+const FORMAT_NAMES: [(&str, FormatFlags); 10] = [
+    ("time", FormatFlags::TIME),
+    ("level", FormatFlags::LEVEL),
+    ("thread", FormatFlags::THREAD),
+    ("location", FormatFlags::FILE_LOCATION),
+    ("target", FormatFlags::TARGET),
+    ("module", FormatFlags::MODULE),
+    ("process_id", FormatFlags::PROCESS_ID),
+    ("hostname", FormatFlags::HOSTNAME),
+    ("uptime", FormatFlags::UPTIME),
+    ("context", FormatFlags::CONTEXT),
+];
+
+const LINE_ENDINGS: [(&str, LineEnding); 8] = [
+    ("lf", LineEnding::LF),
+    ("cr", LineEnding::CR),
+    ("crlf", LineEnding::Crlf),
+    ("vt", LineEnding::VT),
+    ("ff", LineEnding::FF),
+    ("nel", LineEnding::Nel),
+    ("ls", LineEnding::LS),
+    ("ps", LineEnding::PS),
+];
+
+/// A `Config` described with plain strings, as it is written in a configuration file.
+///
+/// Every field is optional, fields left at their default keep the value of
+/// `Config::default()`. Names are matched case-insensitively.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// # use std::collections::BTreeMap;
+/// let spec = ConfigSpec {
+///     format: Some(vec!["time".to_string(), "level".to_string(), "target".to_string()]),
+///     most_severe_level: Some("error".to_string()),
+///     least_severe_level: Some("info".to_string()),
+///     level_colors: BTreeMap::from([
+///         ("error".to_string(), "#f38ba8".to_string()),
+///         ("warn".to_string(), "yellow".to_string()),
+///     ]),
+///     time_format: Some("%H:%M".to_string()),
+///     time_offset: Some("utc".to_string()),
+///     filter_allow: vec!["myapp".to_string()],
+///     filter_ignore: vec!["myapp::noisy".to_string()],
+///     formatter: Some("[time] [level] [target]: [message]".to_string()),
+///     line_ending: Some("crlf".to_string()),
+/// };
+/// let config = spec.into_config().unwrap();
+///
+/// let logger = WriteLogger::new(LevelFilter::Trace, config, Vec::new());
+/// logger.log(&Record::builder().level(Level::Debug).target("myapp").args(format_args!("not in range")).build());
+/// logger.log(&Record::builder().level(Level::Warn).target("myapp::noisy").args(format_args!("ignored")).build());
+/// logger.log(&Record::builder().level(Level::Info).target("myapp::db").args(format_args!("connected")).build());
+///
+/// let output = String::from_utf8(logger.into_inner()).unwrap();
+/// assert_eq!(output.lines().count(), 1);
+/// assert!(output.ends_with("[INFO] myapp::db: connected\r\n"), "{:?}", output);
+///
+/// let err = ConfigSpec { least_severe_level: Some("verbose".to_string()), ..ConfigSpec::default() }
+///     .into_config()
+///     .unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "least_severe_level: invalid value 'verbose', expected one of: off, error, warn, info, debug, trace"
+/// );
+///
+/// let err = ConfigSpec {
+///     level_colors: BTreeMap::from([("info".to_string(), "#12345".to_string())]),
+///     ..ConfigSpec::default()
+/// }
+/// .into_config()
+/// .unwrap_err();
+/// assert!(err.to_string().starts_with("level_colors.info: invalid value '#12345', "));
+/// ```
+///
+/// With the `serde` feature a spec can be deserialized, fields that are missing keep
+/// their default:
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// # use sp_log2::*;
+/// # use std::collections::BTreeMap;
+/// let spec: ConfigSpec = serde_json::from_str(
+///     r#"{
+///         "format": ["level", "target"],
+///         "least_severe_level": "debug",
+///         "level_colors": { "error": "red" },
+///         "line_ending": "crlf"
+///     }"#,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     spec,
+///     ConfigSpec {
+///         format: Some(vec!["level".to_string(), "target".to_string()]),
+///         least_severe_level: Some("debug".to_string()),
+///         level_colors: BTreeMap::from([("error".to_string(), "red".to_string())]),
+///         line_ending: Some("crlf".to_string()),
+///         ..ConfigSpec::default()
+///     }
+/// );
+/// assert!(spec.into_config().is_ok());
+///
+/// let spec: ConfigSpec = serde_json::from_str("{}").unwrap();
+/// assert_eq!(spec, ConfigSpec::default());
+///
+/// let spec: ConfigSpec = serde_json::from_str(r#"{ "most_severe_level": "fatal" }"#).unwrap();
+/// assert_eq!(
+///     spec.into_config().unwrap_err().to_string(),
+///     "most_severe_level: invalid value 'fatal', expected one of: off, error, warn, info, debug, trace"
+/// );
+///
+/// let spec: ConfigSpec = serde_json::from_str(r#"{ "level_colors": { "warn": "reddish" } }"#).unwrap();
+/// assert!(spec
+///     .into_config()
+///     .unwrap_err()
+///     .to_string()
+///     .starts_with("level_colors.warn: invalid value 'reddish', "));
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default))]
+pub struct ConfigSpec {
+    /// The parts of the default layout, see `FormatFlags`: `time`, `level`, `thread`,
+    /// `location`, `target`, `module`, `process_id`, `hostname`, `uptime` and `context`
+    pub format: Option<Vec<String>>,
+    /// The most severe level that is logged, e.g. `error`
+    pub most_severe_level: Option<String>,
+    /// The least severe level that is logged, e.g. `trace`
+    pub least_severe_level: Option<String>,
+    /// Colors of the levels by level name, in the syntax of `ConfigBuilder::set_level_color_str`,
+    /// e.g. `#f38ba8`
+    pub level_colors: BTreeMap<String, String>,
+    /// `rfc2822`, `rfc3339`, `rfc3339_secs`, `rfc3339_millis`, `rfc3339_micros`, `unix`,
+    /// `unix_millis` or a custom format like `%H:%M:%S`
+    pub time_format: Option<String>,
+    /// `utc` or `local`
+    pub time_offset: Option<String>,
+    /// Target prefixes that are logged
+    pub filter_allow: Vec<String>,
+    /// Target prefixes that are not logged
+    pub filter_ignore: Vec<String>,
+    /// The formatter, see `ConfigBuilder::set_formatter`
+    pub formatter: Option<String>,
+    /// `lf`, `cr`, `crlf`, `vt`, `ff`, `nel`, `ls` or `ps`
+    pub line_ending: Option<String>,
+}
+
+impl ConfigSpec {
+    /// Builds the `Config` described by this spec.
+    ///
+    /// Fails with `ConfigError::InvalidValue` naming the field if a value cannot be
+    /// parsed, and like [`ConfigBuilder::try_build`] if the result does not validate.
+    pub fn into_config(self) -> Result<Config, ConfigError> {
+        let mut builder = ConfigBuilder::new();
+
+        if let Some(format) = &self.format {
+            let mut flags = FormatFlags::empty();
+            for name in format {
+                flags |= lookup("format", name, &FORMAT_NAMES)?;
+            }
+            builder.set_format(flags);
+        }
+
+        let default = Config::default();
+        builder.set_level_range(
+            parse_level_filter("most_severe_level", self.most_severe_level.as_deref())?
+                .unwrap_or(default.most_severe),
+            parse_level_filter("least_severe_level", self.least_severe_level.as_deref())?
+                .unwrap_or(default.least_severe),
+        );
+
+        for (level, color) in &self.level_colors {
+            let field = format!("level_colors.{}", level);
+            let level = Level::from_str(level).map_err(|_| ConfigError::InvalidValue {
+                field: "level_colors".to_string(),
+                value: level.clone(),
+                reason: "expected one of: error, warn, info, debug, trace".to_string(),
+            })?;
+            builder
+                .set_level_color_str(level, color)
+                .map_err(|err| ConfigError::InvalidValue {
+                    field,
+                    value: color.clone(),
+                    reason: err.to_string(),
+                })?;
+        }
+
+        if let Some(time_format) = &self.time_format {
+            match time_format.to_ascii_lowercase().as_str() {
+                "rfc2822" => builder.set_time_format_rfc2822(),
+                "rfc3339" => builder.set_time_format_rfc3339(),
+                "rfc3339_secs" => {
+                    builder.set_time_format_rfc3339_with_precision(SecondsFormat::Secs)
+                }
+                "rfc3339_millis" => {
+                    builder.set_time_format_rfc3339_with_precision(SecondsFormat::Millis)
+                }
+                "rfc3339_micros" => {
+                    builder.set_time_format_rfc3339_with_precision(SecondsFormat::Micros)
+                }
+                "unix" => builder.set_time_format_unix(),
+                "unix_millis" => builder.set_time_format_unix_millis(),
+                _ => builder.set_time_format_custom(time_format),
+            };
+        }
+
+        if let Some(time_offset) = &self.time_offset {
+            match time_offset.to_ascii_lowercase().as_str() {
+                "utc" => builder.set_time_offset_utc(),
+                "local" => builder.set_time_offset_to_local(),
+                _ => {
+                    return Err(ConfigError::InvalidValue {
+                        field: "time_offset".to_string(),
+                        value: time_offset.clone(),
+                        reason: "expected one of: utc, local".to_string(),
+                    })
+                }
+            };
+        }
+
+        for filter in self.filter_allow {
+            builder.add_filter_allow(filter);
+        }
+        for filter in self.filter_ignore {
+            builder.add_filter_ignore(filter);
+        }
+
+        if let Some(formatter) = &self.formatter {
+            builder.set_formatter(Some(formatter));
+        }
+
+        if let Some(line_ending) = &self.line_ending {
+            builder.set_line_ending(lookup("line_ending", line_ending, &LINE_ENDINGS)?);
+        }
+
+        builder.try_build()
+    }
+}
+
+fn parse_level_filter(
+    field: &str,
+    value: Option<&str>,
+) -> Result<Option<LevelFilter>, ConfigError> {
+    value
+        .map(|value| {
+            LevelFilter::from_str(value).map_err(|_| ConfigError::InvalidValue {
+                field: field.to_string(),
+                value: value.to_string(),
+                reason: "expected one of: off, error, warn, info, debug, trace".to_string(),
+            })
+        })
+        .transpose()
+}
+
+fn lookup<T: Clone>(field: &str, value: &str, names: &[(&str, T)]) -> Result<T, ConfigError> {
+    names
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, item)| item.clone())
+        .ok_or_else(|| ConfigError::InvalidValue {
+            field: field.to_string(),
+            value: value.to_string(),
+            reason: format!(
+                "expected one of: {}",
+                names
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        })
+}
@@ -0,0 +1,114 @@
+//! Temporarily silencing every logger of this crate, see [`suspend`].
+
+use log::Level;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of live guards, for the fast path when logging is not suspended.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of live guards by the most severe level they suspend, `0` for all levels.
+static SUSPENDED: [AtomicUsize; 6] = [const { AtomicUsize::new(0) }; 6];
+
+/// Suspends logging of all levels until the returned guard is dropped.
+///
+/// This applies to every logger of this crate, in all threads. Guards can be nested,
+/// logging resumes once the last one is dropped. Records logged in the meantime are
+/// discarded, they are not written later.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// let logger = TestLogger::new(LevelFilter::Info, Config::default());
+/// let log = |message: &str| {
+///     logger.log(&Record::builder().level(Level::Error).args(format_args!("{}", message)).build());
+/// };
+///
+/// log("before");
+/// {
+///     let _outer = sp_log2::suspend();
+///     log("retrying");
+///     {
+///         let _inner = sp_log2::suspend();
+///         log("retrying again");
+///     }
+///     log("still retrying");
+/// }
+/// log("after");
+///
+/// let messages: Vec<String> = logger.take_records().into_iter().map(|r| r.message).collect();
+/// assert_eq!(messages, ["before", "after"]);
+/// ```
+pub fn suspend() -> SuspendGuard {
+    SuspendGuard::new(0)
+}
+
+/// Suspends logging of the levels less severe than `level` until the returned guard is
+/// dropped, so e.g. `suspend_below(Level::Error)` still lets errors through.
+///
+/// Like [`suspend`], this applies to every logger of this crate and guards can be nested.
+///
+/// # Examples
+/// ```
+/// # use sp_log2::*;
+/// # use log::{Log, Record};
+/// let logger = TestLogger::new(LevelFilter::Info, Config::default());
+/// let log = |level: Level| {
+///     logger.log(&Record::builder().level(level).args(format_args!("attempt failed")).build());
+/// };
+///
+/// let guard = sp_log2::suspend_below(Level::Warn);
+/// for level in [Level::Error, Level::Warn, Level::Info] {
+///     log(level);
+/// }
+///
+/// // Guards are `Send`, so they can be held across `.await` points
+/// std::thread::spawn(move || drop(guard)).join().unwrap();
+/// log(Level::Info);
+///
+/// let levels: Vec<Level> = logger.take_records().iter().map(|r| r.level).collect();
+/// assert_eq!(levels, [Level::Error, Level::Warn, Level::Info]);
+/// ```
+pub fn suspend_below(level: Level) -> SuspendGuard {
+    SuspendGuard::new(level as usize)
+}
+
+/// Returns true if records of `level` are currently suspended.
+#[inline(always)]
+pub(crate) fn is_suspended(level: Level) -> bool {
+    ACTIVE.load(Ordering::Relaxed) != 0
+        && SUSPENDED[..level as usize]
+            .iter()
+            .any(|count| count.load(Ordering::Relaxed) != 0)
+}
+
+/// Resumes logging when dropped, returned by [`suspend`] and [`suspend_below`].
+#[must_use = "logging resumes when the guard is dropped"]
+pub struct SuspendGuard {
+    level: usize,
+}
+
+impl SuspendGuard {
+    fn new(level: usize) -> SuspendGuard {
+        SUSPENDED[level].fetch_add(1, Ordering::Relaxed);
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+        SuspendGuard { level }
+    }
+}
+
+impl Drop for SuspendGuard {
+    fn drop(&mut self) {
+        SUSPENDED[self.level].fetch_sub(1, Ordering::Relaxed);
+        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for SuspendGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = Level::iter().nth(self.level.wrapping_sub(1));
+        f.debug_struct("SuspendGuard")
+            .field("below", &level)
+            .finish()
+    }
+}